@@ -0,0 +1,160 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::time::Duration;
+
+use crate::{OutputFormat, SensorData, SensorKind};
+
+pub const AGGREGATE_CSV_HEADER: &str = "window_start,source,count,metric,min,avg,max\n";
+
+/// Сводка одной величины за окно
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSummary {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+}
+
+impl MetricSummary {
+    pub fn avg(&self, count: u64) -> f64 {
+        self.sum / count as f64
+    }
+}
+
+/// min/avg/max всех величин одного сервера за окно [start, start + window)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub start: DateTime<Utc>,
+    pub kind: SensorKind,
+    pub count: u64,
+    pub metrics: Vec<MetricSummary>,
+}
+
+impl Aggregate {
+    fn source(&self) -> &'static str {
+        match self.kind {
+            SensorKind::TempPressure => "S1",
+            SensorKind::Accelerometer => "S2",
+        }
+    }
+
+    pub fn format_text(&self) -> String {
+        let mut line = format!("{} [{}] n={}", self.start.format("%Y-%m-%d %H:%M:%S%.3f"), self.source(), self.count);
+        for m in &self.metrics {
+            line.push_str(&format!(" {}={:.2}/{:.2}/{:.2}", m.name, m.min, m.avg(self.count), m.max));
+        }
+        line.push('\n');
+        line
+    }
+
+    /// По строке на величину
+    pub fn format_csv(&self) -> String {
+        let start = self.start.to_rfc3339_opts(SecondsFormat::Micros, true);
+        self.metrics
+            .iter()
+            .map(|m| {
+                format!(
+                    "{},{},{},{},{},{},{}\n",
+                    start, self.source(), self.count, m.name, m.min, m.avg(self.count), m.max
+                )
+            })
+            .collect()
+    }
+
+    /// Двоичный формат агрегатов не поддерживает: Config::validate это запрещает
+    pub fn encode(&self, format: OutputFormat) -> Vec<u8> {
+        match format {
+            OutputFormat::Csv => self.format_csv().into_bytes(),
+            OutputFormat::Text | OutputFormat::Binary => self.format_text().into_bytes(),
+        }
+    }
+}
+
+/// Копит отсчёты одного сервера и отдаёт сводку, когда приходит отсчёт из следующего окна.
+/// Окна выровнены по началу эпохи; запоздавший отсчёт из прошлого окна учитывается в текущем
+pub struct Aggregator {
+    window_us: i64,
+    current: Option<(i64, Aggregate)>,
+}
+
+impl Aggregator {
+    pub fn new(window: Duration) -> Self {
+        Aggregator { window_us: (window.as_micros() as i64).max(1), current: None }
+    }
+
+    pub fn push(&mut self, data: &SensorData) -> Option<Aggregate> {
+        let index = data.timestamp().timestamp_micros().div_euclid(self.window_us);
+        let finished = match &self.current {
+            Some((current, _)) if index > *current => self.current.take().map(|(_, a)| a),
+            _ => None,
+        };
+
+        let (_, aggregate) = self.current.get_or_insert_with(|| {
+            let start = DateTime::from_timestamp_micros(index * self.window_us).unwrap_or_default();
+            (index, Aggregate { start, kind: data.kind(), count: 0, metrics: Vec::new() })
+        });
+        aggregate.count += 1;
+        for (name, value) in data.values() {
+            match aggregate.metrics.iter_mut().find(|m| m.name == name) {
+                Some(m) => {
+                    m.min = m.min.min(value);
+                    m.max = m.max.max(value);
+                    m.sum += value;
+                }
+                None => aggregate.metrics.push(MetricSummary { name, min: value, max: value, sum: value }),
+            }
+        }
+        finished
+    }
+
+    /// Незавершённое окно, например при остановке
+    pub fn flush(&mut self) -> Option<Aggregate> {
+        self.current.take().map(|(_, a)| a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(millis: i64, temperature: f32, pressure: i16) -> SensorData {
+        let timestamp = DateTime::from_timestamp_millis(millis).unwrap();
+        SensorData::TempPressure { timestamp, temperature, pressure }
+    }
+
+    #[test]
+    fn test_aggregate_per_window() {
+        let mut aggregator = Aggregator::new(Duration::from_secs(1));
+        assert!(aggregator.push(&temp(1_000, 20.0, 1000)).is_none());
+        assert!(aggregator.push(&temp(1_400, 22.0, 1004)).is_none());
+        // Запоздавший отсчёт попадает в текущее окно
+        assert!(aggregator.push(&temp(900, 21.0, 1002)).is_none());
+
+        let done = aggregator.push(&temp(2_100, 30.0, 990)).unwrap();
+        assert_eq!(done.start, DateTime::from_timestamp(1, 0).unwrap());
+        assert_eq!(done.count, 3);
+        assert_eq!(done.metrics[0], MetricSummary { name: "temperature", min: 20.0, max: 22.0, sum: 63.0 });
+        assert_eq!(done.metrics[1].avg(done.count), 1002.0);
+        assert_eq!(
+            done.format_text(),
+            "1970-01-01 00:00:01.000 [S1] n=3 temperature=20.00/21.00/22.00 pressure=1000.00/1002.00/1004.00\n"
+        );
+
+        let last = aggregator.flush().unwrap();
+        assert_eq!(last.count, 1);
+        assert!(aggregator.flush().is_none());
+    }
+
+    #[test]
+    fn test_aggregate_csv() {
+        let mut aggregator = Aggregator::new(Duration::from_millis(500));
+        let timestamp = DateTime::from_timestamp_millis(1_700_000_000_250).unwrap();
+        aggregator.push(&SensorData::Accelerometer { timestamp, x: 1, y: -2, z: 3 });
+        let csv = aggregator.flush().unwrap().format_csv();
+        assert_eq!(
+            csv,
+            "2023-11-14T22:13:20.000000Z,S2,1,x,1,1,1\n\
+             2023-11-14T22:13:20.000000Z,S2,1,y,-2,-2,-2\n\
+             2023-11-14T22:13:20.000000Z,S2,1,z,3,3,3\n"
+        );
+    }
+}
@@ -0,0 +1,531 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+use crate::units::Units;
+use crate::{Config, SampleHook, SensorData, SensorKind, ServerConfig};
+
+/// Сколько уведомлений может ждать отправки
+const QUEUE_CAPACITY: usize = 1000;
+/// Период проверки правил no_data_secs
+const SILENCE_CHECK_MS: u64 = 1000;
+/// Предел ожидания webhook и команды
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+fn default_min_interval() -> u64 {
+    60
+}
+
+fn default_stderr() -> bool {
+    true
+}
+
+/// Правило оповещения: порог поля (above/below) или тишина сервера (no_data_secs)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub name: String,
+    /// Имя сервера; без него правило действует на все серверы, у которых есть поле
+    #[serde(default)]
+    pub server: Option<String>,
+    /// temperature, pressure, x, y, z или accel — модуль вектора ускорения.
+    /// Значение берётся после пересчёта units сервера
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    #[serde(default)]
+    pub no_data_secs: Option<u64>,
+    /// Не чаще одного уведомления на сервер за столько секунд; остальные срабатывания считаются подавленными
+    #[serde(default = "default_min_interval")]
+    pub min_interval_secs: u64,
+    /// Строка в stderr на каждое уведомление
+    #[serde(default = "default_stderr")]
+    pub stderr: bool,
+    /// POST с JSON уведомления (нужна сборка с feature "webhook")
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Команда оболочки; подробности в переменных окружения ALERT_*
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+/// Есть ли поле у датчика такого типа
+fn has_field(kind: SensorKind, field: &str) -> bool {
+    match field {
+        "accel" => kind == SensorKind::Accelerometer,
+        _ => kind.required_fields()[1..].contains(&field),
+    }
+}
+
+impl AlertRule {
+    fn applies_to(&self, server: &ServerConfig) -> bool {
+        self.server.as_ref().is_none_or(|name| *name == server.name)
+            && self.field.as_ref().is_none_or(|field| has_field(server.kind, field))
+    }
+
+    pub fn validate(&self, servers: &[ServerConfig]) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("name: must not be empty".into());
+        }
+        if let Some(name) = &self.server {
+            if !servers.iter().any(|server| server.name == *name) {
+                return Err(format!("server: unknown server '{}'", name));
+            }
+        }
+        match (&self.field, self.no_data_secs) {
+            (Some(_), Some(_)) | (None, None) => return Err("either field or no_data_secs must be set".into()),
+            (Some(field), None) => {
+                if !["temperature", "pressure", "x", "y", "z", "accel"].contains(&field.as_str()) {
+                    return Err(format!("field: unknown field '{}'", field));
+                }
+                if self.above.is_none() && self.below.is_none() {
+                    return Err("field: requires above or below".into());
+                }
+                if [self.above, self.below].iter().flatten().any(|limit| !limit.is_finite()) {
+                    return Err("above and below must be finite numbers".into());
+                }
+                if !servers.iter().any(|server| self.applies_to(server)) {
+                    return Err(format!("field: no server has the field '{}'", field));
+                }
+            }
+            (None, Some(secs)) => {
+                if self.above.is_some() || self.below.is_some() {
+                    return Err("above and below require field".into());
+                }
+                if secs == 0 {
+                    return Err("no_data_secs: must be greater than 0".into());
+                }
+            }
+        }
+        if let Some(url) = &self.webhook {
+            if cfg!(not(feature = "webhook")) {
+                return Err("webhook: the logger was built without the `webhook` feature".into());
+            }
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("webhook: expected an http:// or https:// URL, got '{}'", url));
+            }
+        }
+        if self.command.as_ref().is_some_and(|command| command.trim().is_empty()) {
+            return Err("command: must not be empty".into());
+        }
+        Ok(())
+    }
+}
+
+/// Уведомление; в таком виде уходит в webhook
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub server: String,
+    pub message: String,
+    pub value: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+    /// Срабатывания, подавленные ограничением частоты с прошлого уведомления
+    pub suppressed: u64,
+    /// Правило целиком: по нему выбираются каналы доставки, даже если конфигурацию уже перечитали
+    #[serde(skip)]
+    source: Arc<AlertRule>,
+}
+
+/// Значение поля в единицах выходного файла
+fn value(data: &SensorData, field: &str, units: &Units) -> Option<f64> {
+    let values: Vec<(&str, f64)> = data
+        .values()
+        .into_iter()
+        .map(|(name, raw)| (name, units.get(name).map_or(raw, |c| c.apply(raw))))
+        .collect();
+    match field {
+        "accel" if data.kind() == SensorKind::Accelerometer => Some(values.iter().map(|(_, v)| v * v).sum::<f64>().sqrt()),
+        _ => values.iter().find(|(name, _)| *name == field).map(|(_, v)| *v),
+    }
+}
+
+#[derive(Default)]
+struct Limit {
+    last: Option<Instant>,
+    suppressed: u64,
+}
+
+/// Проверка правил с ограничением частоты по паре (имя правила, сервер). Время передаётся явно
+pub struct AlertEngine {
+    rules: Vec<Arc<AlertRule>>,
+    servers: Vec<ServerConfig>,
+    last_seen: HashMap<String, Instant>,
+    limits: HashMap<(String, String), Limit>,
+}
+
+impl AlertEngine {
+    /// Отсчёт времени тишины начинается с запуска
+    pub fn new(config: &Config, now: Instant) -> Self {
+        AlertEngine {
+            rules: config.alerts.iter().cloned().map(Arc::new).collect(),
+            servers: config.servers.clone(),
+            last_seen: config.servers.iter().map(|server| (server.name.clone(), now)).collect(),
+            limits: HashMap::new(),
+        }
+    }
+
+    fn fire(&mut self, index: usize, server: &str, message: String, value: Option<f64>, now: Instant) -> Option<Alert> {
+        let rule = Arc::clone(&self.rules[index]);
+        let limit = self.limits.entry((rule.name.clone(), server.to_string())).or_default();
+        if limit.last.is_some_and(|last| now.duration_since(last) < Duration::from_secs(rule.min_interval_secs)) {
+            limit.suppressed += 1;
+            return None;
+        }
+        let suppressed = std::mem::take(&mut limit.suppressed);
+        limit.last = Some(now);
+        Some(Alert {
+            rule: rule.name.clone(),
+            server: server.to_string(),
+            message,
+            value,
+            timestamp: Utc::now(),
+            suppressed,
+            source: rule,
+        })
+    }
+
+    /// Новые правила после перечитывания конфигурации. Ограничение частоты и счёт
+    /// подавленных сохраняются для правил с прежними именами
+    pub fn reload(&mut self, rules: &[AlertRule]) {
+        self.rules = rules.iter().cloned().map(Arc::new).collect();
+        self.limits.retain(|(name, _), _| rules.iter().any(|rule| rule.name == *name));
+    }
+
+    pub fn sample(&mut self, server: &str, data: &SensorData, now: Instant) -> Vec<Alert> {
+        self.last_seen.insert(server.to_string(), now);
+        let Some(config) = self.servers.iter().find(|s| s.name == server) else {
+            return Vec::new();
+        };
+        let mut triggered = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some(field) = rule.field.as_deref().filter(|_| rule.applies_to(config)) else {
+                continue;
+            };
+            let Some(value) = value(data, field, &config.units) else {
+                continue;
+            };
+            let message = match (rule.above, rule.below) {
+                (Some(above), _) if value > above => format!("{} {:.2} above {}", field, value, above),
+                (_, Some(below)) if value < below => format!("{} {:.2} below {}", field, value, below),
+                _ => continue,
+            };
+            triggered.push((index, message, value));
+        }
+        triggered
+            .into_iter()
+            .filter_map(|(index, message, value)| self.fire(index, server, message, Some(value), now))
+            .collect()
+    }
+
+    /// Правила no_data_secs для серверов, от которых давно нет отсчётов
+    pub fn silence(&mut self, now: Instant) -> Vec<Alert> {
+        let mut triggered = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some(secs) = rule.no_data_secs else {
+                continue;
+            };
+            for server in self.servers.iter().filter(|server| rule.applies_to(server)) {
+                let silent = self.last_seen.get(&server.name).map_or(Duration::ZERO, |seen| now.duration_since(*seen));
+                if silent >= Duration::from_secs(secs) {
+                    triggered.push((index, server.name.clone(), format!("no data for {} s", silent.as_secs())));
+                }
+            }
+        }
+        triggered
+            .into_iter()
+            .filter_map(|(index, server, message)| self.fire(index, &server, message, None, now))
+            .collect()
+    }
+}
+
+/// Выполняет команду оболочки с подробностями уведомления в окружении
+async fn run_command(command: &str, alert: &Alert) -> Result<(), String> {
+    #[cfg(unix)]
+    let mut process = tokio::process::Command::new("sh");
+    #[cfg(unix)]
+    process.arg("-c");
+    #[cfg(not(unix))]
+    let mut process = tokio::process::Command::new("cmd");
+    #[cfg(not(unix))]
+    process.arg("/C");
+    process
+        .arg(command)
+        .env("ALERT_RULE", &alert.rule)
+        .env("ALERT_SERVER", &alert.server)
+        .env("ALERT_MESSAGE", &alert.message)
+        .env("ALERT_VALUE", alert.value.map(|v| v.to_string()).unwrap_or_default())
+        .env("ALERT_TIMESTAMP", alert.timestamp.to_rfc3339())
+        .env("ALERT_SUPPRESSED", alert.suppressed.to_string())
+        .kill_on_drop(true);
+    let status = tokio::time::timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS), process.status())
+        .await
+        .map_err(|_| format!("timed out after {} s", DELIVERY_TIMEOUT_SECS))?
+        .map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("exited with {}", status));
+    }
+    Ok(())
+}
+
+struct Delivery {
+    #[cfg(feature = "webhook")]
+    client: reqwest::Client,
+}
+
+impl Delivery {
+    #[cfg(feature = "webhook")]
+    async fn post(&self, url: &str, alert: &Alert) -> Result<(), String> {
+        let response = self.client.post(url).json(alert).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, alert: &Alert) {
+        let rule = &alert.source;
+        warn!(rule = %alert.rule, server = %alert.server, suppressed = alert.suppressed, "Alert: {}", alert.message);
+        if rule.stderr {
+            eprintln!("[ALERT] {} {} {}: {}", alert.timestamp.format("%Y-%m-%d %H:%M:%S"), alert.rule, alert.server, alert.message);
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(url) = &rule.webhook {
+            if let Err(e) = self.post(url, alert).await {
+                error!(rule = %alert.rule, error = %e, "Alert webhook failed");
+            }
+        }
+        if let Some(command) = &rule.command {
+            if let Err(e) = run_command(command, alert).await {
+                error!(rule = %alert.rule, error = %e, "Alert command failed");
+            }
+        }
+    }
+}
+
+/// Проверка правил на каждом отсчёте и отправка уведомлений отдельной задачей
+pub struct Alerter {
+    engine: Mutex<AlertEngine>,
+    sender: mpsc::Sender<Alert>,
+}
+
+impl Alerter {
+    /// Запускает задачу отправки; после отмены shutdown она отправляет остаток очереди и завершается.
+    /// Нужен работающий tokio runtime
+    pub fn start(
+        config: &Config,
+        shutdown: CancellationToken,
+    ) -> Result<(Arc<Self>, JoinHandle<()>), Box<dyn std::error::Error + Send + Sync>> {
+        let delivery = Delivery {
+            #[cfg(feature = "webhook")]
+            client: reqwest::Client::builder().timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS)).build()?,
+        };
+        let (sender, mut receiver) = mpsc::channel::<Alert>(QUEUE_CAPACITY);
+        let alerter = Arc::new(Alerter { engine: Mutex::new(AlertEngine::new(config, Instant::now())), sender });
+        let engine = Arc::clone(&alerter);
+
+        let handle = tokio::spawn(async move {
+            let mut timer = tokio::time::interval(Duration::from_millis(SILENCE_CHECK_MS));
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    alert = receiver.recv() => match alert {
+                        Some(alert) => delivery.deliver(&alert).await,
+                        None => break,
+                    },
+                    _ = timer.tick() => {
+                        let alerts = engine.engine.lock().map(|mut e| e.silence(Instant::now())).unwrap_or_default();
+                        for alert in &alerts {
+                            delivery.deliver(alert).await;
+                        }
+                    }
+                }
+            }
+            while let Ok(alert) = receiver.try_recv() {
+                delivery.deliver(&alert).await;
+            }
+        });
+        Ok((alerter, handle))
+    }
+
+    pub fn observe(&self, server: &str, data: &SensorData) {
+        let Ok(mut engine) = self.engine.lock() else {
+            return;
+        };
+        for alert in engine.sample(server, data, Instant::now()) {
+            if self.sender.try_send(alert).is_err() {
+                warn!(server, "Alert queue is full, dropping alerts");
+            }
+        }
+    }
+
+    pub fn reload(&self, rules: &[AlertRule]) {
+        if let Ok(mut engine) = self.engine.lock() {
+            engine.reload(rules);
+        }
+    }
+
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| self.observe(server, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Conversion;
+
+    fn rules(toml: &str) -> Config {
+        let mut config = Config::default();
+        config.servers[1].units.insert("x".into(), Conversion::new(0.001, 0.0, "g"));
+        config.servers[1].units.insert("y".into(), Conversion::new(0.001, 0.0, "g"));
+        config.servers[1].units.insert("z".into(), Conversion::new(0.001, 0.0, "g"));
+        #[derive(Deserialize)]
+        struct Rules {
+            alerts: Vec<AlertRule>,
+        }
+        config.alerts = toml::from_str::<Rules>(toml).unwrap().alerts;
+        for rule in &config.alerts {
+            rule.validate(&config.servers).unwrap();
+        }
+        config
+    }
+
+    #[test]
+    fn test_thresholds_rate_limited() {
+        let config = rules(concat!(
+            "[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\nabove = 35.0\nmin_interval_secs = 10\n",
+            "[[alerts]]\nname = \"shock\"\nfield = \"accel\"\nabove = 2.0\nmin_interval_secs = 0\n",
+            "[[alerts]]\nname = \"silent\"\nserver = \"Server2\"\nno_data_secs = 30\n",
+        ));
+        let start = Instant::now();
+        let mut engine = AlertEngine::new(&config, start);
+        let timestamp = Utc::now();
+        let hot = SensorData::TempPressure { timestamp, temperature: 36.5, pressure: 1013 };
+
+        let alerts = engine.sample("Server1", &hot, start);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].rule.as_str(), alerts[0].message.as_str()), ("hot", "temperature 36.50 above 35"));
+        // Повторы в пределах min_interval_secs подавляются и учитываются в следующем уведомлении
+        assert!(engine.sample("Server1", &hot, start + Duration::from_secs(5)).is_empty());
+        assert!(engine.sample("Server1", &hot, start + Duration::from_secs(9)).is_empty());
+        let alerts = engine.sample("Server1", &hot, start + Duration::from_secs(10));
+        assert_eq!(alerts[0].suppressed, 2);
+        let cool = SensorData::TempPressure { timestamp, temperature: 20.0, pressure: 1013 };
+        assert!(engine.sample("Server1", &cool, start + Duration::from_secs(30)).is_empty());
+
+        // Модуль ускорения в g после пересчёта units
+        let calm = SensorData::Accelerometer { timestamp, x: 0, y: 0, z: 1000 };
+        assert!(engine.sample("Server2", &calm, start).is_empty());
+        let shock = SensorData::Accelerometer { timestamp, x: 1200, y: -1600, z: 1000 };
+        let alerts = engine.sample("Server2", &shock, start + Duration::from_secs(1));
+        assert_eq!(alerts[0].message, "accel 2.24 above 2");
+
+        assert!(engine.silence(start + Duration::from_secs(30)).is_empty());
+        let alerts = engine.silence(start + Duration::from_secs(31));
+        assert_eq!((alerts[0].server.as_str(), alerts[0].message.as_str()), ("Server2", "no data for 30 s"));
+    }
+
+    #[test]
+    fn test_reload_rules() {
+        let config = rules("[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\nabove = 35.0\nmin_interval_secs = 60\n");
+        let start = Instant::now();
+        let mut engine = AlertEngine::new(&config, start);
+        let warm = SensorData::TempPressure { timestamp: Utc::now(), temperature: 30.0, pressure: 1013 };
+        assert!(engine.sample("Server1", &warm, start).is_empty());
+
+        // Новый порог действует сразу, ограничение частоты правила с тем же именем сохраняется
+        let lowered = rules("[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\nabove = 25.0\nmin_interval_secs = 60\n");
+        engine.reload(&lowered.alerts);
+        let alerts = engine.sample("Server1", &warm, start + Duration::from_secs(1));
+        assert_eq!(alerts[0].message, "temperature 30.00 above 25");
+        assert_eq!(alerts[0].source.above, Some(25.0));
+        assert!(engine.sample("Server1", &warm, start + Duration::from_secs(2)).is_empty());
+        engine.reload(&lowered.alerts);
+        assert!(engine.sample("Server1", &warm, start + Duration::from_secs(3)).is_empty());
+
+        engine.reload(&[]);
+        assert!(engine.sample("Server1", &warm, start + Duration::from_secs(120)).is_empty());
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        let config = Config::default();
+        let rule = |toml: &str| {
+            let rule: AlertRule = toml::from_str(&format!("name = \"r\"\n{}", toml)).unwrap();
+            rule.validate(&config.servers)
+        };
+        assert!(rule("field = \"x\"\nbelow = -5.0\n").is_ok());
+        assert_eq!(rule("field = \"x\"\n").unwrap_err(), "field: requires above or below");
+        assert_eq!(rule("field = \"x\"\nabove = 1.0\nno_data_secs = 5\n").unwrap_err(), "either field or no_data_secs must be set");
+        assert_eq!(rule("server = \"Lab\"\nno_data_secs = 5\n").unwrap_err(), "server: unknown server 'Lab'");
+        assert_eq!(
+            rule("server = \"Server1\"\nfield = \"accel\"\nabove = 1.0\n").unwrap_err(),
+            "field: no server has the field 'accel'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_receives_alert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alert.txt");
+        let mut config = rules("[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\nabove = 35.0\nstderr = false\n");
+        config.alerts[0].command = Some(format!("echo \"$ALERT_RULE $ALERT_SERVER $ALERT_MESSAGE\" > {}", path.display()));
+        let shutdown = CancellationToken::new();
+        let (alerter, handle) = Alerter::start(&config, shutdown.clone()).unwrap();
+
+        alerter.hook()("Server1", &SensorData::TempPressure { timestamp: Utc::now(), temperature: 40.0, pressure: 1000 });
+        shutdown.cancel();
+        handle.await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hot Server1 temperature 40.00 above 35\n");
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn test_webhook_posts_json() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:19038").unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        });
+        let mut config = rules("[[alerts]]\nname = \"quiet\"\nno_data_secs = 1\nstderr = false\n");
+        config.alerts[0].webhook = Some("http://127.0.0.1:19038/alert".to_string());
+        let shutdown = CancellationToken::new();
+        let (_alerter, handle) = Alerter::start(&config, shutdown.clone()).unwrap();
+
+        let body = tokio::task::spawn_blocking(move || server.join().unwrap()).await.unwrap();
+        shutdown.cancel();
+        handle.await.unwrap();
+        assert_eq!(body["rule"], "quiet");
+        assert_eq!(body["message"], "no data for 1 s");
+        assert!(body["value"].is_null());
+    }
+}
@@ -0,0 +1,86 @@
+use clap::Parser;
+use std::process;
+use std::time::Duration;
+use network_logger::mock::{Faults, MockConfig, MockServer, DEFAULT_ACK};
+use network_logger::SensorKind;
+use tracing::info;
+
+/// Имитатор серверов датчиков для проверки всей цепочки без сети
+#[derive(Debug, Parser)]
+#[command(name = "mock-server", version, about = "Serves synthetic Server1/Server2 frames for offline testing")]
+struct Args {
+    /// Address for temperature/pressure frames (Server1)
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:5123")]
+    server1: String,
+
+    /// Address for accelerometer frames (Server2)
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:5124")]
+    server2: String,
+
+    /// Serve only one sensor type
+    #[arg(long, value_enum)]
+    only: Option<SensorKind>,
+
+    /// Expected auth key
+    #[arg(long, value_name = "KEY")]
+    key: Option<String>,
+
+    /// Reply to a valid key
+    #[arg(long, value_name = "TEXT", default_value = DEFAULT_ACK)]
+    ack: String,
+
+    /// Command that requests one frame
+    #[arg(long, value_name = "TEXT", default_value = "get")]
+    request: String,
+
+    /// Corrupt the checksum of every Nth frame of a connection
+    #[arg(long, value_name = "N")]
+    bad_checksum_every: Option<u32>,
+
+    /// Pause before every Nth frame
+    #[arg(long, value_name = "N")]
+    stall_every: Option<u32>,
+
+    /// Length of the pause; the default is longer than the logger's read timeout
+    #[arg(long, value_name = "MS", default_value_t = 6000)]
+    stall_ms: u64,
+
+    /// Close the connection instead of answering every Nth request
+    #[arg(long, value_name = "N")]
+    disconnect_every: Option<u32>,
+}
+
+fn main() {
+    let args = Args::parse();
+    tracing_subscriber::fmt().with_target(false).init();
+
+    let faults = Faults {
+        bad_checksum_every: args.bad_checksum_every,
+        stall_every: args.stall_every,
+        stall: Duration::from_millis(args.stall_ms),
+        disconnect_every: args.disconnect_every,
+    };
+    let mut servers = Vec::new();
+    for (kind, addr) in [(SensorKind::TempPressure, &args.server1), (SensorKind::Accelerometer, &args.server2)] {
+        if args.only.is_some_and(|only| only != kind) {
+            continue;
+        }
+        let mut config = MockConfig::new(kind);
+        config.faults = faults.clone();
+        config.ack = args.ack.clone();
+        config.request = args.request.clone();
+        if let Some(key) = &args.key {
+            config.auth_key = key.clone();
+        }
+        let server = MockServer::start(addr, config).unwrap_or_else(|e| {
+            eprintln!("[ERROR] Cannot listen on {}: {}", addr, e);
+            process::exit(1);
+        });
+        info!(server = kind.server_name(), address = %server.local_addr(), "Listening");
+        servers.push(server);
+    }
+
+    for server in servers {
+        server.wait();
+    }
+}
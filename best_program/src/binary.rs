@@ -0,0 +1,163 @@
+use chrono::DateTime;
+use std::io::{self, Read};
+
+use crate::{calculate_checksum, SensorData};
+
+/// Заголовок файла: сигнатура и версия формата
+pub const BINARY_MAGIC: &[u8; 4] = b"SNL1";
+
+pub const TAG_TEMP_PRESSURE: u8 = 1;
+pub const TAG_ACCELEROMETER: u8 = 2;
+
+/// Запись: [длина u16 LE][тег][timestamp i64 LE][поля датчика][контрольная сумма].
+/// Длина считает всё после себя, сумма берётся по тегу и данным
+pub fn encode_record(data: &SensorData) -> Vec<u8> {
+    let mut body = Vec::with_capacity(24);
+    match data {
+        SensorData::TempPressure { timestamp, temperature, pressure } => {
+            body.push(TAG_TEMP_PRESSURE);
+            body.extend_from_slice(&timestamp.timestamp_micros().to_le_bytes());
+            body.extend_from_slice(&temperature.to_le_bytes());
+            body.extend_from_slice(&pressure.to_le_bytes());
+        }
+        SensorData::Accelerometer { timestamp, x, y, z } => {
+            body.push(TAG_ACCELEROMETER);
+            body.extend_from_slice(&timestamp.timestamp_micros().to_le_bytes());
+            body.extend_from_slice(&x.to_le_bytes());
+            body.extend_from_slice(&y.to_le_bytes());
+            body.extend_from_slice(&z.to_le_bytes());
+        }
+    }
+    let checksum = calculate_checksum(&body);
+
+    let mut record = Vec::with_capacity(body.len() + 3);
+    record.extend_from_slice(&((body.len() + 1) as u16).to_le_bytes());
+    record.extend_from_slice(&body);
+    record.push(checksum);
+    record
+}
+
+/// Разбирает запись без префикса длины
+pub fn decode_record(record: &[u8]) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+    let (&checksum, body) = record.split_last().ok_or("Empty record")?;
+    if calculate_checksum(body) != checksum {
+        return Err("Checksum mismatch in binary record".into());
+    }
+    let (&tag, payload) = body.split_first().ok_or("Empty record")?;
+    let expected = match tag {
+        TAG_TEMP_PRESSURE => 8 + 4 + 2,
+        TAG_ACCELEROMETER => 8 + 4 + 4 + 4,
+        _ => return Err(format!("Unknown record tag {}", tag).into()),
+    };
+    if payload.len() != expected {
+        return Err(format!("Record tag {}: expected {} bytes, got {}", tag, expected, payload.len()).into());
+    }
+
+    let micros = i64::from_le_bytes(payload[0..8].try_into()?);
+    let timestamp = DateTime::from_timestamp_micros(micros).ok_or("Invalid timestamp")?;
+    Ok(match tag {
+        TAG_TEMP_PRESSURE => SensorData::TempPressure {
+            timestamp,
+            temperature: f32::from_le_bytes(payload[8..12].try_into()?),
+            pressure: i16::from_le_bytes(payload[12..14].try_into()?),
+        },
+        _ => SensorData::Accelerometer {
+            timestamp,
+            x: i32::from_le_bytes(payload[8..12].try_into()?),
+            y: i32::from_le_bytes(payload[12..16].try_into()?),
+            z: i32::from_le_bytes(payload[16..20].try_into()?),
+        },
+    })
+}
+
+/// Последовательно читает записи из файла, записанного в формате binary
+pub struct BinaryReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> BinaryReader<R> {
+    /// Проверяет сигнатуру в начале потока
+    pub fn new(mut reader: R) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != BINARY_MAGIC {
+            return Err("Not a sensor binary file".into());
+        }
+        Ok(BinaryReader { reader })
+    }
+
+    /// Следующая запись; Ok(None) в конце файла. Обрезанная запись — ошибка
+    pub fn read_record(&mut self) -> Result<Option<SensorData>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut len = [0u8; 2];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut record = vec![0u8; u16::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut record)
+            .map_err(|e| format!("Truncated record: {}", e))?;
+        decode_record(&record).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for BinaryReader<R> {
+    type Item = Result<SensorData, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn samples() -> Vec<SensorData> {
+        let timestamp = DateTime::from_timestamp_micros(1700000000123456).unwrap();
+        vec![
+            SensorData::TempPressure { timestamp, temperature: -12.25, pressure: 1013 },
+            SensorData::Accelerometer { timestamp, x: 100, y: -200, z: i32::MAX },
+        ]
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut file = BINARY_MAGIC.to_vec();
+        for data in samples() {
+            file.extend(encode_record(&data));
+        }
+        let decoded: Vec<SensorData> = BinaryReader::new(Cursor::new(file))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", samples()));
+    }
+
+    #[test]
+    fn test_binary_record_is_compact() {
+        let [temp, accel] = <[SensorData; 2]>::try_from(samples()).unwrap();
+        assert_eq!(encode_record(&temp).len(), 18);
+        assert_eq!(encode_record(&accel).len(), 24);
+        assert!(crate::format_data(&temp).len() >= 3 * encode_record(&temp).len());
+    }
+
+    #[test]
+    fn test_binary_reader_errors() {
+        assert!(BinaryReader::new(Cursor::new(b"XXXX".to_vec())).is_err());
+
+        let mut record = encode_record(&samples()[0]);
+        let last = record.len() - 1;
+        record[last] ^= 0xFF;
+        let mut file = BINARY_MAGIC.to_vec();
+        file.extend(record);
+        let mut reader = BinaryReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.next().unwrap().unwrap_err().to_string().contains("Checksum"));
+
+        let mut file = BINARY_MAGIC.to_vec();
+        file.extend(&encode_record(&samples()[1])[..10]);
+        let mut reader = BinaryReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.next().unwrap().unwrap_err().to_string().contains("Truncated"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{format_data_with, Config, Connection, SensorData, ServerConfig, ServerStats};
+
+/// Подключение, авторизация и один кадр. Кадр, принятый только после поиска начала
+/// (resync), считается ошибкой: контрольная сумма первого не сошлась
+pub async fn check_server(server: &ServerConfig, config: &Config) -> Result<(SensorData, Duration), String> {
+    let stats = ServerStats::new();
+    let started = Instant::now();
+    let mut connection = Connection::open(server, &stats, config).await.map_err(|e| format!("connect: {}", e))?;
+    let data = connection.fetch(server, &stats, config).await.map_err(|e| format!("fetch: {}", e))?;
+    if stats.checksum_errors.load(Ordering::Relaxed) > 0 {
+        return Err("fetch: checksum mismatch".to_string());
+    }
+    Ok((data, started.elapsed()))
+}
+
+/// Строка отчёта на сервер: PASS со временем и полученным отсчётом или FAIL с причиной
+pub fn report(servers: &[ServerConfig], results: &[Result<(SensorData, Duration), String>]) -> String {
+    let name_width = servers.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    let address_width = servers.iter().map(|s| s.address.len()).max().unwrap_or(0);
+    let mut lines = String::new();
+    for (server, result) in servers.iter().zip(results) {
+        let outcome = match result {
+            Ok((data, elapsed)) => format!(
+                "PASS  {:.1} ms  {}",
+                elapsed.as_secs_f64() * 1000.0,
+                format_data_with(data, &server.units).trim_end()
+            ),
+            Err(e) => format!("FAIL  {}", e),
+        };
+        lines.push_str(&format!(
+            "{:<name_width$}  {:<address_width$}  {}\n",
+            server.name, server.address, outcome
+        ));
+    }
+    lines
+}
+
+/// Проверяет все серверы одновременно и печатает отчёт в порядке конфигурации; true — все прошли
+pub async fn run(config: Arc<Config>) -> bool {
+    let tasks: Vec<_> = (0..config.servers.len())
+        .map(|index| {
+            let config = config.clone();
+            tokio::spawn(async move { check_server(&config.servers[index], &config).await })
+        })
+        .collect();
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.unwrap_or_else(|e| Err(e.to_string())));
+    }
+    print!("{}", report(&config.servers, &results));
+    results.iter().all(Result::is_ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Faults, MockConfig, MockServer};
+    use crate::SensorKind;
+
+    #[tokio::test]
+    async fn test_check_report() {
+        let good = MockServer::start("127.0.0.1:19035", MockConfig::new(SensorKind::TempPressure)).unwrap();
+        let mut corrupt = MockConfig::new(SensorKind::Accelerometer);
+        corrupt.faults = Faults { bad_checksum_every: Some(1), ..Faults::default() };
+        let corrupt = MockServer::start("127.0.0.1:19036", corrupt).unwrap();
+        let config = Arc::new(Config {
+            servers: vec![
+                ServerConfig::new("Server1", "127.0.0.1:19035", SensorKind::TempPressure),
+                ServerConfig::new("Server2", "127.0.0.1:19036", SensorKind::Accelerometer),
+                // Порт, на котором никто не слушает
+                ServerConfig::new("Lab", "127.0.0.1:19037", SensorKind::Accelerometer),
+            ],
+            ..Config::default()
+        });
+
+        let mut outcomes = Vec::new();
+        for server in &config.servers {
+            outcomes.push(check_server(server, &config).await);
+        }
+        assert!(outcomes[0].is_ok());
+        assert!(outcomes[1].as_ref().unwrap_err().starts_with("fetch:"));
+        assert!(outcomes[2].as_ref().unwrap_err().starts_with("connect:"));
+
+        let text = report(&config.servers, &outcomes);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("Server1  127.0.0.1:19035  PASS"));
+        assert!(lines[0].contains("[S1] temperature="));
+        assert!(lines[1].starts_with("Server2  127.0.0.1:19036  FAIL  fetch:"));
+        assert!(lines[2].starts_with("Lab      127.0.0.1:19037  FAIL  connect:"));
+        assert!(!run(config).await);
+        good.stop();
+        corrupt.stop();
+    }
+}
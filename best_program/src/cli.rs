@@ -0,0 +1,371 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::DEFAULT_CONFIG_FILE;
+use crate::daemon::{DEFAULT_LOG_FILE, DEFAULT_PID_FILE};
+use crate::logging::LogFormat;
+use crate::output::DayBoundary;
+use crate::{Config, FsyncPolicy, OutputFormat};
+
+/// Аргументы командной строки. Заданные значения перекрывают config.toml
+#[derive(Debug, Parser)]
+#[command(name = "network_logger", version, about = "Collects sensor packets from the TCP servers in the config, over one or more connections each")]
+pub struct Cli {
+    /// Path to the TOML config file
+    #[arg(short, long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Output file for collected data
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Write each sensor type to its own file next to the output (temp_pressure.txt, accel.txt)
+    #[arg(long)]
+    pub split_output: bool,
+
+    /// Start a new dated output file at midnight, local time or UTC
+    #[arg(long, value_enum, value_name = "ZONE")]
+    pub split_daily: Option<DayBoundary>,
+
+    /// Output file format
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write min/avg/max over windows of this many milliseconds instead of every sample
+    #[arg(long, value_name = "MS")]
+    pub aggregate: Option<u64>,
+
+    /// Also store samples in this SQLite database (requires the `sqlite` feature)
+    #[arg(long, value_name = "FILE")]
+    pub sqlite: Option<String>,
+
+    /// Also store samples in this Parquet file (requires the `parquet` feature)
+    #[arg(long, value_name = "FILE")]
+    pub parquet: Option<String>,
+
+    /// Serve the HTTP API on this address (requires the `http` feature)
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<String>,
+
+    /// When to fsync the output file
+    #[arg(long, value_enum)]
+    pub fsync: Option<FsyncPolicy>,
+
+    /// Also print every sample to stdout
+    #[arg(long)]
+    pub stdout: bool,
+
+    /// Server address; the Nth occurrence replaces the address of the Nth configured server
+    #[arg(short, long, value_name = "ADDR", action = clap::ArgAction::Append)]
+    pub server: Vec<String>,
+
+    /// Replay a capture file (text, CSV or binary) through the sinks instead of connecting to the servers
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Replay speed relative to the recorded intervals; 0 replays without pauses
+    #[arg(long, value_name = "X", default_value_t = 1.0, value_parser = parse_speed)]
+    pub speed: f64,
+
+    /// Stop after this long instead of waiting for Ctrl+C: seconds, or e.g. 90s, 15m, 2h, 1h30m, 1d
+    #[arg(short, long, value_name = "TIME", value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+
+    /// Stop after writing this many samples (all servers together)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_samples: Option<u64>,
+
+    /// Connect to each server, fetch one frame, print PASS/FAIL per server and exit (1 if any failed)
+    #[arg(long)]
+    pub check: bool,
+
+    /// Show a live dashboard instead of log lines; the log goes to a file (requires the `tui` feature)
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Detach from the terminal, write a pid file and send the log to a file
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Pid file (default with --daemon: network_logger.pid)
+    #[arg(long, value_name = "FILE")]
+    pub pid_file: Option<String>,
+
+    /// Write the log to this file instead of stdout (default with --daemon: network_logger.log)
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<String>,
+
+    /// Write connection failures, checksum errors, resyncs and gaps to this file as JSON lines
+    #[arg(long, value_name = "FILE")]
+    pub event_log: Option<String>,
+
+    /// Log filter, e.g. `debug` or `network_logger=trace`
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log output format
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Seconds between statistics reports
+    #[arg(long, value_name = "SECS")]
+    pub stats_interval: Option<u64>,
+
+    /// Also write the statistics as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    pub stats_file: Option<String>,
+
+    /// Keep the counter totals in this file across restarts
+    #[arg(long, value_name = "FILE")]
+    pub state_file: Option<String>,
+
+    /// Start the counters from zero instead of the totals in the state file
+    #[arg(long)]
+    pub fresh: bool,
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed >= 0.0 => Ok(speed),
+        _ => Err("expected a non-negative number".to_string()),
+    }
+}
+
+/// Число без единицы — секунды; иначе пары число+единица (s, m, h, d): 2h, 1h30m
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || "expected seconds or a duration such as 90s, 15m, 2h or 1h30m".to_string();
+    if let Ok(secs) = value.parse::<u64>() {
+        return if secs > 0 { Ok(Duration::from_secs(secs)) } else { Err("must be greater than 0".to_string()) };
+    }
+    let mut total = 0u64;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86_400,
+            _ => return Err(invalid()),
+        };
+        total = number.checked_mul(unit).and_then(|secs| total.checked_add(secs)).ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    if total == 0 {
+        return Err("must be greater than 0".to_string());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+impl Cli {
+    /// Загружает конфигурацию и применяет поверх неё аргументы.
+    /// Явно указанный --config обязан существовать, файл по умолчанию — нет
+    pub fn load_config(&self) -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+        let config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::load_or_default(DEFAULT_CONFIG_FILE.as_ref())?,
+        };
+        self.apply(config)
+    }
+
+    pub fn apply(&self, mut config: Config) -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+        if self.server.len() > config.servers.len() {
+            return Err(format!(
+                "--server: given {} times but only {} servers are configured",
+                self.server.len(),
+                config.servers.len()
+            ).into());
+        }
+        for (server, address) in config.servers.iter_mut().zip(&self.server) {
+            server.address = address.clone();
+        }
+        if let Some(output) = &self.output {
+            config.output = output.clone();
+        }
+        if self.split_output {
+            config.split_output = true;
+        }
+        if let Some(boundary) = self.split_daily {
+            config.split_daily = Some(boundary);
+        }
+        if let Some(window) = self.aggregate {
+            config.aggregate_window_ms = Some(window);
+        }
+        if let Some(path) = &self.sqlite {
+            config.sqlite_path = Some(path.clone());
+        }
+        if let Some(path) = &self.parquet {
+            config.parquet_path = Some(path.clone());
+        }
+        if let Some(addr) = &self.http {
+            config.http_listen = Some(addr.clone());
+        }
+        if let Some(format) = self.format {
+            config.format = format;
+        }
+        if let Some(policy) = self.fsync {
+            config.fsync = policy;
+        }
+        if self.stdout {
+            config.stdout = true;
+        }
+        if let Some(level) = &self.log_level {
+            config.log_level = level.clone();
+        }
+        if let Some(format) = self.log_format {
+            config.log_format = format;
+        }
+        if let Some(secs) = self.stats_interval {
+            config.stats_interval_secs = secs;
+        }
+        if let Some(path) = &self.stats_file {
+            config.stats_file = Some(path.clone());
+        }
+        if let Some(path) = &self.state_file {
+            config.state_file = Some(path.clone());
+        }
+        // Воспроизведённые отсчёты не должны попадать в итоги сбора
+        if self.replay.is_some() {
+            config.state_file = None;
+        }
+        if let Some(path) = &self.pid_file {
+            config.pid_file = Some(path.clone());
+        }
+        if let Some(path) = &self.log_file {
+            config.log_file = Some(path.clone());
+        }
+        if let Some(path) = &self.event_log {
+            config.event_log = Some(path.clone());
+        }
+        if self.check && (self.daemon || self.tui || self.replay.is_some()) {
+            return Err("--check: cannot be combined with --daemon, --tui or --replay".into());
+        }
+        if self.tui {
+            if cfg!(not(feature = "tui")) {
+                return Err("--tui: the logger was built without the `tui` feature".into());
+            }
+            if self.daemon || config.stdout {
+                return Err("--tui: cannot be combined with --daemon or --stdout".into());
+            }
+            if config.history_size == 0 {
+                return Err("--tui: the charts need history_size greater than 0".into());
+            }
+            config.log_file.get_or_insert_with(|| DEFAULT_LOG_FILE.to_string());
+        }
+        if self.daemon {
+            if config.stdout {
+                return Err("--stdout: no terminal to print to with --daemon".into());
+            }
+            config.pid_file.get_or_insert_with(|| DEFAULT_PID_FILE.to_string());
+            config.log_file.get_or_insert_with(|| DEFAULT_LOG_FILE.to_string());
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let cli = Cli::parse_from([
+            "network_logger",
+            "--output", "out.txt",
+            "--server", "127.0.0.1:7001",
+            "--server", "127.0.0.1:7002",
+            "--stats-interval", "3",
+            "--stats-file", "stats.json",
+            "--event-log", "events.log",
+            "--duration", "60",
+            "--format", "csv",
+            "--log-level", "debug",
+            "--log-format", "json",
+            "--fsync", "flush",
+            "--split-output",
+        ]);
+        let config = cli.apply(Config::default()).unwrap();
+        assert_eq!(config.output, "out.txt");
+        assert_eq!(config.servers[0].address, "127.0.0.1:7001");
+        assert_eq!(config.servers[1].address, "127.0.0.1:7002");
+        assert_eq!(config.stats_interval_secs, 3);
+        assert_eq!(config.stats_file.as_deref(), Some("stats.json"));
+        assert_eq!(config.event_log.as_deref(), Some("events.log"));
+        assert_eq!(config.format, OutputFormat::Csv);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.fsync, FsyncPolicy::Flush);
+        assert!(config.split_output);
+        assert_eq!(cli.duration(), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_cli_daemon_defaults() {
+        let cli = Cli::parse_from(["network_logger", "--daemon"]);
+        let config = cli.apply(Config::default()).unwrap();
+        assert_eq!(config.pid_file.as_deref(), Some(DEFAULT_PID_FILE));
+        assert_eq!(config.log_file.as_deref(), Some(DEFAULT_LOG_FILE));
+
+        let cli = Cli::parse_from(["network_logger", "--daemon", "--pid-file", "/run/logger.pid"]);
+        let config = cli.apply(Config { log_file: Some("lab.log".to_string()), ..Config::default() }).unwrap();
+        assert_eq!(config.pid_file.as_deref(), Some("/run/logger.pid"));
+        assert_eq!(config.log_file.as_deref(), Some("lab.log"));
+    }
+
+    #[test]
+    fn test_cli_without_args_keeps_config() {
+        let cli = Cli::parse_from(["network_logger"]);
+        assert_eq!(cli.apply(Config::default()).unwrap(), Config::default());
+        assert_eq!(cli.duration(), None);
+    }
+
+    #[test]
+    fn test_cli_rejects_invalid_values() {
+        let cli = Cli::parse_from(["network_logger", "--server", "nowhere"]);
+        assert!(cli.apply(Config::default()).unwrap_err().to_string().contains("servers[0].address"));
+
+        let cli = Cli::parse_from(["network_logger", "--stats-interval", "0"]);
+        assert!(cli.apply(Config::default()).is_err());
+
+        let cli = Cli::parse_from(["network_logger", "-s", "127.0.0.1:1", "-s", "127.0.0.1:2", "-s", "127.0.0.1:3"]);
+        assert!(cli.apply(Config::default()).is_err());
+
+        let cli = Cli::parse_from(["network_logger", "--config", "/nonexistent/config.toml"]);
+        assert!(cli.load_config().is_err());
+
+        let cli = Cli::parse_from(["network_logger", "--tui", "--daemon"]);
+        assert!(cli.apply(Config::default()).unwrap_err().to_string().contains("--tui"));
+
+        let cli = Cli::parse_from(["network_logger", "--check", "--daemon"]);
+        assert!(cli.apply(Config::default()).unwrap_err().to_string().contains("--check"));
+
+        let cli = Cli::parse_from(["network_logger", "--daemon", "--stdout"]);
+        assert!(cli.apply(Config::default()).unwrap_err().to_string().contains("--daemon"));
+
+        assert!(Cli::try_parse_from(["network_logger", "--speed", "-2"]).is_err());
+        assert!(Cli::try_parse_from(["network_logger", "--max-samples", "0"]).is_err());
+        assert_eq!(Cli::parse_from(["network_logger", "--speed", "0"]).speed, 0.0);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("60"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86_400)));
+        for invalid in ["", "0", "0m", "h", "2x", "2ч", "1.5h", "2h30", "-5"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+        let cli = Cli::parse_from(["network_logger", "--duration", "2h", "--max-samples", "1000"]);
+        assert_eq!(cli.duration(), Some(Duration::from_secs(7200)));
+        assert_eq!(cli.max_samples, Some(1000));
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+
+/// Вес нового замера в сглаженном смещении: сглаживает сетевую задержку
+const SMOOTHING: f64 = 0.1;
+
+/// Что изменилось после очередного замера
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkewEvent {
+    /// Смещение превысило порог (мс)
+    Exceeded(i64),
+    /// Смещение вернулось в пределы порога (мс)
+    Recovered(i64),
+    /// С прошлого предупреждения смещение ушло больше чем на порог (старое, новое, мс)
+    Drifted(i64, i64),
+}
+
+/// Смещение часов сервера относительно локальных: время в кадре минус время приёма.
+/// Положительное — часы сервера спешат
+pub struct SkewTracker {
+    threshold_ms: i64,
+    smoothed_ms: Option<f64>,
+    reference_ms: Option<i64>,
+    exceeded: bool,
+}
+
+impl SkewTracker {
+    pub fn new(threshold_ms: u64) -> Self {
+        SkewTracker { threshold_ms: threshold_ms as i64, smoothed_ms: None, reference_ms: None, exceeded: false }
+    }
+
+    /// Сглаженное смещение в миллисекундах
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.smoothed_ms.map(|v| v.round() as i64)
+    }
+
+    pub fn observe(&mut self, server_time: DateTime<Utc>, local_time: DateTime<Utc>) -> Option<SkewEvent> {
+        let sample = (server_time - local_time).num_microseconds().unwrap_or(i64::MAX) as f64 / 1000.0;
+        let smoothed = match self.smoothed_ms {
+            Some(prev) => prev + SMOOTHING * (sample - prev),
+            None => sample,
+        };
+        self.smoothed_ms = Some(smoothed);
+        let offset = smoothed.round() as i64;
+
+        let over = offset.abs() > self.threshold_ms;
+        if over != self.exceeded {
+            self.exceeded = over;
+            self.reference_ms = Some(offset);
+            return Some(if over { SkewEvent::Exceeded(offset) } else { SkewEvent::Recovered(offset) });
+        }
+        let reference = *self.reference_ms.get_or_insert(offset);
+        if (offset - reference).abs() > self.threshold_ms {
+            self.reference_ms = Some(offset);
+            return Some(SkewEvent::Drifted(reference, offset));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_skew_exceeded_and_recovered() {
+        let mut tracker = SkewTracker::new(1000);
+        let local = DateTime::from_timestamp(1700000000, 0).unwrap();
+
+        assert_eq!(tracker.observe(local + TimeDelta::milliseconds(200), local), None);
+        assert_eq!(tracker.offset_ms(), Some(200));
+
+        // Сглаживание: одиночный скачок не поднимает тревогу
+        assert_eq!(tracker.observe(local + TimeDelta::milliseconds(3000), local), None);
+        assert_eq!(tracker.offset_ms(), Some(480));
+
+        let mut event = None;
+        for _ in 0..20 {
+            event = event.or(tracker.observe(local + TimeDelta::milliseconds(3000), local));
+        }
+        assert!(matches!(event, Some(SkewEvent::Exceeded(ms)) if ms > 1000));
+
+        let events: Vec<_> = (0..40).filter_map(|_| tracker.observe(local, local)).collect();
+        assert!(matches!(events.last(), Some(SkewEvent::Recovered(ms)) if *ms <= 1000));
+    }
+
+    #[test]
+    fn test_skew_drift() {
+        let mut tracker = SkewTracker::new(1000);
+        let local = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let ahead = |ms| local + TimeDelta::milliseconds(ms);
+
+        assert_eq!(tracker.observe(ahead(10_000), local), Some(SkewEvent::Exceeded(10_000)));
+        assert_eq!(tracker.observe(ahead(10_000), local), None);
+        // Часы сервера продолжают уходить вперёд
+        assert_eq!(tracker.observe(ahead(30_000), local), Some(SkewEvent::Drifted(10_000, 12_000)));
+    }
+}
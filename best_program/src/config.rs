@@ -0,0 +1,783 @@
+use serde::Deserialize;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::aggregate::AGGREGATE_CSV_HEADER;
+use crate::alert::AlertRule;
+use crate::logging::{self, LogFormat};
+use crate::moving_average::{self, AverageWindows, CSV_AVERAGES_HEADER};
+use crate::output::{self, DayBoundary};
+use crate::protocol::ProtocolSpec;
+use crate::units::{self, Units};
+use crate::validation::ValidationConfig;
+use crate::write_queue::QueueFullPolicy;
+use crate::{FsyncPolicy, OutputFormat, PacketSchema, SensorKind, Transport, KEY};
+use crate::{
+    FLUSH_INTERVAL_SECS, FSYNC_INTERVAL_SECS, GAP_THRESHOLD_MS, MAX_CLOCK_SKEW_MS, READ_TIMEOUT_MS, REQUEST_DELAY_MS, SHUTDOWN_TIMEOUT_MS,
+    STATS_INTERVAL_SECS, WATCHDOG_TIMEOUT_MS, WRITE_TIMEOUT_MS,
+};
+
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+pub const DEFAULT_SERVER1: &str = "95.163.237.76:5123";
+pub const DEFAULT_SERVER2: &str = "95.163.237.76:5124";
+pub const DEFAULT_OUTPUT_FILE: &str = "sensor_data.txt";
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+pub const DEFAULT_PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+pub const DEFAULT_WRITE_QUEUE_SIZE: usize = 10_000;
+
+/// Пакеты больше этого размера считаются ошибкой конфигурации
+const MAX_PACKET_SIZE: usize = 4096;
+/// Предел параллельных соединений с одним сервером
+const MAX_CONNECTIONS: usize = 16;
+
+/// Один сервер датчиков: имя для логов и статистики, адрес, тип пакетов
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub name: String,
+    pub address: String,
+    pub kind: SensorKind,
+    /// Размер кадра; по умолчанию — минимальный для раскладки
+    #[serde(default)]
+    pub packet_size: Option<usize>,
+    /// Своя раскладка кадра вместо стандартной для kind
+    #[serde(default)]
+    pub fields: Option<PacketSchema>,
+    /// tcp (по умолчанию) или udp
+    #[serde(default)]
+    pub transport: Transport,
+    /// Шифрование TCP-соединения (нужна сборка с feature "tls")
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Правила правдоподобия отсчётов; без них принимается всё
+    #[serde(default)]
+    pub validation: Option<ValidationConfig>,
+    /// Период опроса: запросы начинаются не чаще; 0 — без пауз. По умолчанию 1 мс
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    /// Ключ, ожидаемый ответ и команда запроса; по умолчанию — общий auth_key и "get"
+    #[serde(default)]
+    pub protocol: ProtocolSpec,
+    /// Свой выходной файл вместо общего output
+    #[serde(default)]
+    pub output: Option<String>,
+    /// Сколько соединений одновременно запрашивают кадры; по умолчанию одно
+    #[serde(default)]
+    pub connections: Option<usize>,
+    /// Пересчёт полей в физические единицы для текстового и CSV вывода
+    #[serde(default)]
+    pub units: Units,
+    /// Скользящие средние: поле -> окно в отсчётах; пишутся рядом с сырыми значениями
+    #[serde(default)]
+    pub moving_average: AverageWindows,
+}
+
+/// Сертификаты TLS для одного сервера; пути к файлам PEM
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Корневые сертификаты; без них — общедоступные корни webpki
+    pub ca_file: Option<String>,
+    /// Имя в сертификате сервера; по умолчанию — IP из address
+    pub server_name: Option<String>,
+    /// Клиентский сертификат и ключ для взаимной аутентификации
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn new(name: &str, address: &str, kind: SensorKind) -> Self {
+        ServerConfig {
+            name: name.to_string(),
+            address: address.to_string(),
+            kind,
+            packet_size: None,
+            fields: None,
+            transport: Transport::Tcp,
+            tls: None,
+            validation: None,
+            poll_interval_ms: None,
+            protocol: ProtocolSpec::default(),
+            output: None,
+            connections: None,
+            units: Units::new(),
+            moving_average: AverageWindows::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &PacketSchema {
+        self.fields.as_ref().unwrap_or(self.kind.schema())
+    }
+
+    pub fn packet_size(&self) -> usize {
+        self.packet_size.unwrap_or(self.schema().min_size())
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.unwrap_or(REQUEST_DELAY_MS))
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.unwrap_or(1)
+    }
+}
+
+/// Публикация отсчётов в MQTT (нужна сборка с feature "mqtt")
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    /// Темы имеют вид <topic_prefix>/<server>/<metric>
+    pub topic_prefix: String,
+    /// 0 — не более одного раза, 1 — хотя бы один, 2 — ровно один
+    pub qos: u8,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Пауза перед повторным подключением к брокеру
+    pub reconnect_delay_ms: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "network_logger".to_string(),
+            topic_prefix: "sensors".to_string(),
+            qos: 0,
+            username: None,
+            password: None,
+            reconnect_delay_ms: 1000,
+        }
+    }
+}
+
+/// Запись отсчётов в InfluxDB (нужна сборка с feature "influx")
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfluxConfig {
+    /// Адрес записи вместе с параметрами: /write?db=... (v1) или /api/v2/write?org=...&bucket=...
+    pub url: String,
+    /// Передаётся как "Authorization: Token <token>"
+    pub token: Option<String>,
+    /// Пакет отправляется, когда набралось столько строк или прошло flush_interval_ms
+    pub batch_size: usize,
+    pub flush_interval_ms: u64,
+    /// Повторы после первой неудачной отправки; пауза удваивается от retry_delay_ms
+    pub max_retries: u32,
+    pub retry_delay_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        InfluxConfig {
+            url: "http://localhost:8086/write?db=sensors".to_string(),
+            token: None,
+            batch_size: 500,
+            flush_interval_ms: 1000,
+            max_retries: 5,
+            retry_delay_ms: 500,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Настройки логгера. Каждое поле необязательно: отсутствующие берутся из Config::default()
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub servers: Vec<ServerConfig>,
+    pub auth_key: String,
+    pub output: String,
+    /// Писать серверы без своего output в отдельные файлы по типу датчика рядом с output
+    pub split_output: bool,
+    /// Закрывать файлы в полночь (local или utc) и продолжать в файле новой даты; None — без деления по дням
+    pub split_daily: Option<DayBoundary>,
+    pub format: OutputFormat,
+    /// Окно агрегации: вместо каждого отсчёта в файл пишутся min/avg/max за окно; None — без агрегации
+    pub aggregate_window_ms: Option<u64>,
+    /// Размер файла, после которого он закрывается и начинается новый; None — без ротации
+    pub rotate_max_bytes: Option<u64>,
+    /// Сжимать закрытые файлы gzip в фоновом потоке
+    pub compress_rotated: bool,
+    /// База SQLite для копии отсчётов (нужна сборка с feature "sqlite")
+    pub sqlite_path: Option<String>,
+    /// Файл Parquet для копии отсчётов (нужна сборка с feature "parquet")
+    pub parquet_path: Option<String>,
+    /// Сколько отсчётов в одной группе строк Parquet
+    pub parquet_row_group_size: usize,
+    pub mqtt: Option<MqttConfig>,
+    pub influx: Option<InfluxConfig>,
+    /// Сколько последних отсчётов каждого сервера держать в памяти; 0 — не хранить
+    pub history_size: usize,
+    /// Адрес HTTP API (/latest, /stats, /health); нужна сборка с feature "http"
+    pub http_listen: Option<String>,
+    /// Дублировать отсчёты в stdout в формате выходного файла
+    pub stdout: bool,
+    /// Фильтр сообщений в синтаксисе tracing: "info", "debug", "network_logger=trace"
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Писать сообщения в этот файл вместо stdout
+    pub log_file: Option<String>,
+    /// Сетевые события (сбои подключения, ошибки контрольной суммы, resync, пропуски) в этот файл, JSON по строке
+    pub event_log: Option<String>,
+    /// Файл с pid запущенного логгера; удаляется при выходе
+    pub pid_file: Option<String>,
+    pub read_timeout_ms: u64,
+    pub write_timeout_ms: u64,
+    /// Порог расхождения часов сервера с локальными для предупреждения
+    pub max_clock_skew_ms: u64,
+    /// Перерыв между соседними отсчётами сервера, после которого он считается пропуском данных
+    pub gap_threshold_ms: u64,
+    pub stats_interval_secs: u64,
+    /// JSON со счётчиками, перезаписывается каждые stats_interval_secs и при остановке
+    pub stats_file: Option<String>,
+    /// Итоги счётчиков: читаются при запуске, пишутся каждые stats_interval_secs и при остановке
+    pub state_file: Option<String>,
+    pub flush_interval_secs: u64,
+    /// never, flush (после каждого сброса) или interval (каждые fsync_interval_secs)
+    pub fsync: FsyncPolicy,
+    pub fsync_interval_secs: u64,
+    /// Сколько записей может ждать потока записи в каждый выходной файл
+    pub write_queue_size: usize,
+    /// block (ждать места) или drop (отбросить отсчёт), когда очередь заполнена
+    pub write_queue_full: QueueFullPolicy,
+    /// Сколько ждать завершения задач после Ctrl+C, прежде чем прервать их и выйти
+    pub shutdown_timeout_ms: u64,
+    /// Worker без единого завершённого шага дольше этого срока перезапускается; 0 — без сторожа
+    pub watchdog_timeout_ms: u64,
+    /// Правила оповещений: пороги полей и тишина серверов
+    pub alerts: Vec<AlertRule>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            servers: vec![
+                ServerConfig::new("Server1", DEFAULT_SERVER1, SensorKind::TempPressure),
+                ServerConfig::new("Server2", DEFAULT_SERVER2, SensorKind::Accelerometer),
+            ],
+            auth_key: String::from_utf8_lossy(KEY).into_owned(),
+            output: DEFAULT_OUTPUT_FILE.to_string(),
+            split_output: false,
+            split_daily: None,
+            format: OutputFormat::Text,
+            aggregate_window_ms: None,
+            rotate_max_bytes: None,
+            compress_rotated: true,
+            sqlite_path: None,
+            parquet_path: None,
+            parquet_row_group_size: DEFAULT_PARQUET_ROW_GROUP_SIZE,
+            mqtt: None,
+            influx: None,
+            history_size: DEFAULT_HISTORY_SIZE,
+            http_listen: None,
+            stdout: false,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            log_file: None,
+            event_log: None,
+            pid_file: None,
+            read_timeout_ms: READ_TIMEOUT_MS,
+            write_timeout_ms: WRITE_TIMEOUT_MS,
+            max_clock_skew_ms: MAX_CLOCK_SKEW_MS,
+            gap_threshold_ms: GAP_THRESHOLD_MS,
+            stats_interval_secs: STATS_INTERVAL_SECS,
+            stats_file: None,
+            state_file: None,
+            flush_interval_secs: FLUSH_INTERVAL_SECS,
+            fsync: FsyncPolicy::Never,
+            fsync_interval_secs: FSYNC_INTERVAL_SECS,
+            write_queue_size: DEFAULT_WRITE_QUEUE_SIZE,
+            write_queue_full: QueueFullPolicy::Block,
+            shutdown_timeout_ms: SHUTDOWN_TIMEOUT_MS,
+            watchdog_timeout_ms: WATCHDOG_TIMEOUT_MS,
+            alerts: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let config: Config = toml::from_str(text).map_err(|e| format!("Invalid config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+        Self::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Файл конфигурации необязателен: без него используются значения по умолчанию
+    pub fn load_or_default(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn aggregate_window(&self) -> Option<Duration> {
+        self.aggregate_window_ms.map(Duration::from_millis)
+    }
+
+    /// Заголовок выходного файла с учётом режима агрегации
+    pub fn header(&self) -> Option<&'static [u8]> {
+        match (self.aggregate_window_ms, self.format) {
+            (Some(_), OutputFormat::Csv) => Some(AGGREGATE_CSV_HEADER.as_bytes()),
+            (Some(_), _) => None,
+            (None, OutputFormat::Csv) if self.has_moving_averages() => Some(CSV_AVERAGES_HEADER.as_bytes()),
+            (None, format) => format.header(),
+        }
+    }
+
+    /// Хотя бы один сервер пишет скользящие средние
+    pub fn has_moving_averages(&self) -> bool {
+        self.servers.iter().any(|server| !server.moving_average.is_empty())
+    }
+
+    /// Файл отсчётов сервера: его output, при split_output — temp_pressure.<расширение output>
+    /// или accel.<...> в каталоге output, иначе общий output
+    pub fn server_output(&self, server: &ServerConfig) -> PathBuf {
+        if let Some(path) = &server.output {
+            return PathBuf::from(path);
+        }
+        let output = Path::new(&self.output);
+        if !self.split_output {
+            return output.to_path_buf();
+        }
+        let stem = match server.kind {
+            SensorKind::TempPressure => "temp_pressure",
+            SensorKind::Accelerometer => "accel",
+        };
+        match output.extension() {
+            Some(ext) => output.with_file_name(format!("{}.{}", stem, ext.to_string_lossy())),
+            None => output.with_file_name(stem),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.servers.is_empty() {
+            return Err("servers: at least one server is required".into());
+        }
+        for (i, server) in self.servers.iter().enumerate() {
+            if server.name.is_empty() {
+                return Err(format!("servers[{}].name: must not be empty", i).into());
+            }
+            if self.servers[..i].iter().any(|s| s.name == server.name) {
+                return Err(format!("servers[{}].name: duplicate name '{}'", i, server.name).into());
+            }
+            if server.address.parse::<SocketAddr>().is_err() {
+                return Err(format!("servers[{}].address: invalid socket address '{}'", i, server.address).into());
+            }
+            let schema = server.schema();
+            schema
+                .validate(server.kind.required_fields())
+                .map_err(|e| format!("servers[{}].fields: {}", i, e))?;
+            let size = server.packet_size();
+            if size < schema.min_size() || size > MAX_PACKET_SIZE {
+                return Err(format!(
+                    "servers[{}].packet_size: must be between {} and {}, got {}",
+                    i, schema.min_size(), MAX_PACKET_SIZE, size
+                ).into());
+            }
+            if !(1..=MAX_CONNECTIONS).contains(&server.connections()) {
+                return Err(format!("servers[{}].connections: must be between 1 and {}", i, MAX_CONNECTIONS).into());
+            }
+            if let Some(path) = &server.output {
+                if path.is_empty() {
+                    return Err(format!("servers[{}].output: must not be empty", i).into());
+                }
+                output::check_template(path).map_err(|e| format!("servers[{}].output: {}", i, e))?;
+            }
+            if !server.units.is_empty() && self.format == OutputFormat::Binary {
+                return Err(format!("servers[{}].units: not supported with the binary format", i).into());
+            }
+            units::validate(&server.units, server.kind).map_err(|e| format!("servers[{}].units: {}", i, e))?;
+            if !server.moving_average.is_empty() {
+                if self.format == OutputFormat::Binary {
+                    return Err(format!("servers[{}].moving_average: not supported with the binary format", i).into());
+                }
+                if self.aggregate_window_ms.is_some() {
+                    return Err(format!("servers[{}].moving_average: not supported with aggregate_window_ms", i).into());
+                }
+            }
+            moving_average::validate(&server.moving_average, server.kind)
+                .map_err(|e| format!("servers[{}].moving_average: {}", i, e))?;
+            if let Some(rules) = &server.validation {
+                rules.validate(server.kind).map_err(|e| format!("servers[{}].validation: {}", i, e))?;
+            }
+            server
+                .protocol
+                .validate(server.transport)
+                .map_err(|e| format!("servers[{}].protocol: {}", i, e))?;
+            if self.watchdog_timeout().is_some_and(|limit| server.poll_interval().saturating_add(self.read_timeout()) >= limit) {
+                return Err(format!(
+                    "servers[{}].poll_interval_ms: plus read_timeout_ms must stay below watchdog_timeout_ms",
+                    i
+                ).into());
+            }
+            if let Some(tls) = &server.tls {
+                if cfg!(not(feature = "tls")) {
+                    return Err(format!("servers[{}].tls: the logger was built without the `tls` feature", i).into());
+                }
+                if server.transport == Transport::Udp {
+                    return Err(format!("servers[{}].tls: not supported with the udp transport", i).into());
+                }
+                if tls.client_cert.is_some() != tls.client_key.is_some() {
+                    return Err(format!("servers[{}].tls: client_cert and client_key must be set together", i).into());
+                }
+            }
+        }
+        if self.auth_key.is_empty() {
+            return Err("auth_key: must not be empty".into());
+        }
+        if self.output.is_empty() {
+            return Err("output: must not be empty".into());
+        }
+        output::check_template(&self.output).map_err(|e| format!("output: {}", e))?;
+        if cfg!(not(feature = "sqlite")) && self.sqlite_path.is_some() {
+            return Err("sqlite_path: the logger was built without the `sqlite` feature".into());
+        }
+        if cfg!(not(feature = "parquet")) && self.parquet_path.is_some() {
+            return Err("parquet_path: the logger was built without the `parquet` feature".into());
+        }
+        if self.parquet_row_group_size == 0 {
+            return Err("parquet_row_group_size: must be greater than 0".into());
+        }
+        if let Some(mqtt) = &self.mqtt {
+            if cfg!(not(feature = "mqtt")) {
+                return Err("mqtt: the logger was built without the `mqtt` feature".into());
+            }
+            if mqtt.host.is_empty() || mqtt.client_id.is_empty() || mqtt.topic_prefix.is_empty() {
+                return Err("mqtt: host, client_id and topic_prefix must not be empty".into());
+            }
+            if mqtt.qos > 2 {
+                return Err(format!("mqtt.qos: must be 0, 1 or 2, got {}", mqtt.qos).into());
+            }
+            if mqtt.username.is_none() && mqtt.password.is_some() {
+                return Err("mqtt.password: requires mqtt.username".into());
+            }
+            if mqtt.reconnect_delay_ms == 0 {
+                return Err("mqtt.reconnect_delay_ms: must be greater than 0".into());
+            }
+        }
+        if let Some(influx) = &self.influx {
+            if cfg!(not(feature = "influx")) {
+                return Err("influx: the logger was built without the `influx` feature".into());
+            }
+            if !influx.url.starts_with("http://") && !influx.url.starts_with("https://") {
+                return Err(format!("influx.url: expected an http:// or https:// URL, got '{}'", influx.url).into());
+            }
+            for (name, value) in [
+                ("influx.batch_size", influx.batch_size as u64),
+                ("influx.flush_interval_ms", influx.flush_interval_ms),
+                ("influx.retry_delay_ms", influx.retry_delay_ms),
+                ("influx.timeout_ms", influx.timeout_ms),
+            ] {
+                if value == 0 {
+                    return Err(format!("{}: must be greater than 0", name).into());
+                }
+            }
+        }
+        if let Some(addr) = &self.http_listen {
+            if cfg!(not(feature = "http")) {
+                return Err("http_listen: the logger was built without the `http` feature".into());
+            }
+            if addr.parse::<SocketAddr>().is_err() {
+                return Err(format!("http_listen: invalid socket address '{}'", addr).into());
+            }
+        }
+        for (i, rule) in self.alerts.iter().enumerate() {
+            rule.validate(&self.servers).map_err(|e| format!("alerts[{}]: {}", i, e))?;
+            if self.alerts[..i].iter().any(|r| r.name == rule.name) {
+                return Err(format!("alerts[{}].name: duplicate name '{}'", i, rule.name).into());
+            }
+        }
+        logging::parse_filter(&self.log_level)?;
+        if let Some(window) = self.aggregate_window_ms {
+            if window == 0 {
+                return Err("aggregate_window_ms: must be greater than 0".into());
+            }
+            if self.format == OutputFormat::Binary {
+                return Err("aggregate_window_ms: not supported with the binary format".into());
+            }
+        }
+        if self.log_file.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err("log_file: must not be empty".into());
+        }
+        if self.event_log.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err("event_log: must not be empty".into());
+        }
+        if self.pid_file.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err("pid_file: must not be empty".into());
+        }
+        if self.stats_file.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err("stats_file: must not be empty".into());
+        }
+        if self.state_file.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err("state_file: must not be empty".into());
+        }
+        if self.stdout && self.format == OutputFormat::Binary {
+            return Err("stdout: not supported with the binary format".into());
+        }
+        if self.watchdog_timeout_ms != 0 && self.watchdog_timeout_ms <= self.read_timeout_ms {
+            return Err("watchdog_timeout_ms: must be greater than read_timeout_ms (or 0 to disable)".into());
+        }
+        if self.write_queue_size == 0 {
+            return Err("write_queue_size: must be greater than 0".into());
+        }
+        if self.rotate_max_bytes == Some(0) {
+            return Err("rotate_max_bytes: must be greater than 0".into());
+        }
+        for (name, value) in [
+            ("read_timeout_ms", self.read_timeout_ms),
+            ("write_timeout_ms", self.write_timeout_ms),
+            ("max_clock_skew_ms", self.max_clock_skew_ms),
+            ("gap_threshold_ms", self.gap_threshold_ms),
+            ("stats_interval_secs", self.stats_interval_secs),
+            ("flush_interval_secs", self.flush_interval_secs),
+            ("fsync_interval_secs", self.fsync_interval_secs),
+            ("shutdown_timeout_ms", self.shutdown_timeout_ms),
+        ] {
+            if value == 0 {
+                return Err(format!("{}: must be greater than 0", name).into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms)
+    }
+
+    pub fn gap_threshold(&self) -> Duration {
+        Duration::from_millis(self.gap_threshold_ms)
+    }
+
+    pub fn watchdog_timeout(&self) -> Option<Duration> {
+        (self.watchdog_timeout_ms > 0).then(|| Duration::from_millis(self.watchdog_timeout_ms))
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(self.shutdown_timeout_ms)
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        Duration::from_millis(self.write_timeout_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Conversion, SERVER1_PACKET_SIZE, SERVER2_PACKET_SIZE};
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = Config::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.servers.len(), 2);
+        assert_eq!(config.servers[0].packet_size(), SERVER1_PACKET_SIZE);
+        assert_eq!(config.servers[1].packet_size(), SERVER2_PACKET_SIZE);
+        assert_eq!(config.auth_key.as_bytes(), KEY);
+    }
+
+    #[test]
+    fn test_partial_config_keeps_defaults() {
+        let config = Config::from_toml(
+            "output = \"out.txt\"\nread_timeout_ms = 100\n",
+        )
+        .unwrap();
+        assert_eq!(config.output, "out.txt");
+        assert_eq!(config.read_timeout(), Duration::from_millis(100));
+        assert_eq!(config.servers, Config::default().servers);
+    }
+
+    #[test]
+    fn test_three_servers() {
+        let config = Config::from_toml(concat!(
+            "[[servers]]\nname = \"Lab\"\naddress = \"127.0.0.1:9000\"\nkind = \"temp_pressure\"\n",
+            "[[servers]]\nname = \"Arm\"\naddress = \"127.0.0.1:9001\"\nkind = \"accelerometer\"\npacket_size = 24\n",
+            "[[servers]]\nname = \"Roof\"\naddress = \"127.0.0.1:9002\"\nkind = \"temp_pressure\"\ntransport = \"udp\"\n",
+        ))
+        .unwrap();
+        assert_eq!(config.servers.len(), 3);
+        assert_eq!(config.servers[1].kind, SensorKind::Accelerometer);
+        assert_eq!(config.servers[1].packet_size(), 24);
+        assert_eq!(config.servers[2].name, "Roof");
+        assert_eq!(config.servers[2].packet_size(), SERVER1_PACKET_SIZE);
+        assert_eq!(config.servers[0].transport, Transport::Tcp);
+        assert_eq!(config.servers[2].transport, Transport::Udp);
+        assert_eq!(config.servers[0].protocol, ProtocolSpec::default());
+    }
+
+    #[test]
+    fn test_mixed_protocols() {
+        let config = Config::from_toml(concat!(
+            "[[servers]]\nname = \"Old\"\naddress = \"127.0.0.1:9000\"\nkind = \"temp_pressure\"\n",
+            "[[servers]]\nname = \"New\"\naddress = \"127.0.0.1:9001\"\nkind = \"accelerometer\"\n",
+            "protocol = { auth_key = \"secret\", ack = \"OK\", request = \"read\\n\" }\n",
+        ))
+        .unwrap();
+        assert_eq!(config.servers[0].protocol.key(&config), KEY);
+        assert_eq!(config.servers[1].protocol.key(&config), b"secret");
+        assert_eq!(config.servers[1].protocol.request(), b"read\n");
+        assert_eq!(config.servers[1].protocol.ack.as_deref(), Some("OK"));
+    }
+
+    #[test]
+    fn test_poll_interval() {
+        let config = Config::from_toml(concat!(
+            "[[servers]]\nname = \"Fast\"\naddress = \"127.0.0.1:9000\"\nkind = \"temp_pressure\"\npoll_interval_ms = 0\n",
+            "[[servers]]\nname = \"Slow\"\naddress = \"127.0.0.1:9001\"\nkind = \"accelerometer\"\npoll_interval_ms = 1000\n",
+            "[[servers]]\nname = \"Default\"\naddress = \"127.0.0.1:9002\"\nkind = \"accelerometer\"\n",
+        ))
+        .unwrap();
+        assert_eq!(config.servers[0].poll_interval(), Duration::ZERO);
+        assert_eq!(config.servers[1].poll_interval(), Duration::from_secs(1));
+        assert_eq!(config.servers[2].poll_interval(), Duration::from_millis(REQUEST_DELAY_MS));
+    }
+
+    #[test]
+    fn test_units() {
+        let config = Config::from_toml(concat!(
+            "[[servers]]\nname = \"Arm\"\naddress = \"127.0.0.1:9000\"\nkind = \"accelerometer\"\n",
+            "[servers.units]\nx = { scale = 0.001, unit = \"g\", decimals = 3 }\nz = { offset = -1000 }\n",
+        ))
+        .unwrap();
+        let units = &config.servers[0].units;
+        assert_eq!(units["x"], Conversion { decimals: Some(3), ..Conversion::new(0.001, 0.0, "g") });
+        assert_eq!(units["z"].apply(1981.0), 981.0);
+        assert!(!units.contains_key("y"));
+    }
+
+    #[test]
+    fn test_config_validation_errors() {
+        let server = |name: &str, address: &str, extra: &str| {
+            format!("[[servers]]\nname = \"{}\"\naddress = \"{}\"\nkind = \"accelerometer\"\n{}", name, address, extra)
+        };
+
+        let err = Config::from_toml(&server("A", "nowhere", "")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].address"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "packet_size = 10\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].packet_size"));
+
+        let err = Config::from_toml(&(server("A", "127.0.0.1:1", "") + &server("A", "127.0.0.1:2", ""))).unwrap_err();
+        assert!(err.to_string().contains("servers[1].name"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "fields = [{ name = \"timestamp\", offset = 0, type = \"u64\" }]\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].fields: missing field 'x'"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "transport = \"udp\"\ntls = {}\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].tls"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "tls = { client_cert = \"client.pem\" }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].tls"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "connections = 0\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].connections"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "units = { pressure = { scale = 0.75 } }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].units: unknown field 'pressure'"));
+
+        let err = Config::from_toml(&(String::from("format = \"binary\"\n") + &server("A", "127.0.0.1:1", "units = { x = { scale = 0.001 } }\n"))).unwrap_err();
+        assert!(err.to_string().contains("servers[0].units: not supported with the binary format"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "moving_average = { x = 0 }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].moving_average: x: window must be between 1 and"));
+
+        let err = Config::from_toml(&(String::from("aggregate_window_ms = 1000\n") + &server("A", "127.0.0.1:1", "moving_average = { x = 10 }\n"))).unwrap_err();
+        assert!(err.to_string().contains("servers[0].moving_average: not supported with aggregate_window_ms"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "output = \"arm_%Q.txt\"\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].output: invalid time format"));
+
+        let err = Config::from_toml("[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\n").unwrap_err();
+        assert!(err.to_string().contains("alerts[0]: field: requires above or below"));
+
+        let rule = "[[alerts]]\nname = \"quiet\"\nno_data_secs = 30\n";
+        let err = Config::from_toml(&rule.repeat(2)).unwrap_err();
+        assert!(err.to_string().contains("alerts[1].name: duplicate name 'quiet'"));
+
+        let err = Config::from_toml("parquet_row_group_size = 0\n").unwrap_err();
+        assert!(err.to_string().contains("parquet_row_group_size"));
+
+        let err = Config::from_toml("[mqtt]\nqos = 3\n").unwrap_err();
+        assert!(err.to_string().contains("mqtt"));
+
+        let err = Config::from_toml("[influx]\nurl = \"localhost:8086\"\n").unwrap_err();
+        assert!(err.to_string().contains("influx"));
+
+        let err = Config::from_toml("http_listen = \"localhost\"\n").unwrap_err();
+        assert!(err.to_string().contains("http_listen"));
+
+        let err = Config::from_toml("format = \"binary\"\naggregate_window_ms = 1000\n").unwrap_err();
+        assert!(err.to_string().contains("aggregate_window_ms"));
+
+        let err = Config::from_toml("format = \"binary\"\nstdout = true\n").unwrap_err();
+        assert!(err.to_string().contains("stdout"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "protocol = { request = \"\" }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].protocol: request"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "transport = \"udp\"\nprotocol = { auth_key = \"k\" }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].protocol"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "validation = { temperature = [0.0, 50.0] }\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].validation"));
+
+        let err = Config::from_toml("servers = []\n").unwrap_err();
+        assert!(err.to_string().contains("servers"));
+
+        let err = Config::from_toml("read_timeout_ms = 0\n").unwrap_err();
+        assert!(err.to_string().contains("read_timeout_ms"));
+
+        let err = Config::from_toml("watchdog_timeout_ms = 1000\n").unwrap_err();
+        assert!(err.to_string().contains("watchdog_timeout_ms"));
+
+        let err = Config::from_toml(&server("A", "127.0.0.1:1", "poll_interval_ms = 28000\n")).unwrap_err();
+        assert!(err.to_string().contains("servers[0].poll_interval_ms"));
+
+        let err = Config::from_toml("fsync = \"interval\"\nfsync_interval_secs = 0\n").unwrap_err();
+        assert!(err.to_string().contains("fsync_interval_secs"));
+
+        let err = Config::from_toml("fsync = \"always\"\n").unwrap_err();
+        assert!(err.to_string().contains("fsync"));
+
+        let err = Config::from_toml("rotate_max_bytes = 0\n").unwrap_err();
+        assert!(err.to_string().contains("rotate_max_bytes"));
+
+        let err = Config::from_toml("state_file = \"\"\n").unwrap_err();
+        assert!(err.to_string().contains("state_file"));
+
+        let err = Config::from_toml("write_queue_size = 0\n").unwrap_err();
+        assert!(err.to_string().contains("write_queue_size"));
+
+        let err = Config::from_toml("write_queue_full = \"wait\"\n").unwrap_err();
+        assert!(err.to_string().contains("write_queue_full"));
+
+        let err = Config::from_toml("log_level = \"network_logger=loud\"\n").unwrap_err();
+        assert!(err.to_string().contains("log_level"));
+
+        let err = Config::from_toml("format = \"xml\"\n").unwrap_err();
+        assert!(err.to_string().contains("format"));
+
+        let err = Config::from_toml("unknown = 1\n").unwrap_err();
+        assert!(err.to_string().contains("unknown"));
+    }
+
+    #[test]
+    fn test_load_or_default_missing_file() {
+        let config = Config::load_or_default(Path::new("/nonexistent/config.toml")).unwrap();
+        assert_eq!(config, Config::default());
+    }
+}
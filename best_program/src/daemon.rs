@@ -0,0 +1,127 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+
+use crate::Config;
+
+pub const DEFAULT_PID_FILE: &str = "network_logger.pid";
+pub const DEFAULT_LOG_FILE: &str = "network_logger.log";
+
+/// Отсоединяется от терминала: fork, setsid, stdout и stderr дописываются в log_file,
+/// pid записывается в заблокированный pid_file. Рабочий каталог не меняется.
+/// Вызывать до запуска tokio: в дочернем процессе остаётся только текущий поток
+#[cfg(unix)]
+pub fn detach(pid_file: &Path, log_file: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(|e| format!("Cannot open log file {}: {}", log_file.display(), e))?;
+    daemonize::Daemonize::new()
+        .pid_file(pid_file)
+        .working_directory(std::env::current_dir()?)
+        .umask(0o022)
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .start()
+        .map_err(|e| format!("Cannot start daemon: {}", e).into())
+}
+
+#[cfg(not(unix))]
+pub fn detach(_pid_file: &Path, _log_file: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    Err("--daemon is only supported on Unix".into())
+}
+
+/// Pid текущего процесса для работы без --daemon (например, под systemd)
+pub fn write_pid_file(path: &Path) -> io::Result<()> {
+    fs::write(path, format!("{}\n", process::id()))
+}
+
+/// Удаляет pid-файл при выходе; отсутствие файла не ошибка
+pub fn remove_pid_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Pid-файл, записанный этим процессом; его удаляет exit или drop PidFileGuard
+static PID_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Pid-файл процесса: pid_file из конфигурации, а с --daemon без него — DEFAULT_PID_FILE
+pub fn pid_file_path(config: &Config, daemon: bool) -> Option<PathBuf> {
+    match &config.pid_file {
+        Some(path) => Some(PathBuf::from(path)),
+        None => daemon.then(|| PathBuf::from(DEFAULT_PID_FILE)),
+    }
+}
+
+/// Удаляет pid-файл при выходе из main, в том числе по панике
+pub struct PidFileGuard;
+
+impl Drop for PidFileGuard {
+    fn drop(&mut self) {
+        remove_registered();
+    }
+}
+
+/// Запоминает записанный pid-файл, чтобы его удалили guard и exit
+pub fn register_pid_file(path: PathBuf) -> PidFileGuard {
+    *PID_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+    PidFileGuard
+}
+
+/// process::exit с удалением pid-файла: деструкторы при process::exit не вызываются
+pub fn exit(code: i32) -> ! {
+    remove_registered();
+    process::exit(code)
+}
+
+fn remove_registered() {
+    let path = PID_FILE.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(path) = path {
+        if let Err(e) = remove_pid_file(&path) {
+            eprintln!("[ERROR] Cannot remove pid file {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logger.pid");
+        write_pid_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), process::id().to_string());
+        remove_pid_file(&path).unwrap();
+        assert!(!path.exists());
+        remove_pid_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_daemon_uses_default_pid_file() {
+        let config = Config::default();
+        assert_eq!(pid_file_path(&config, true), Some(PathBuf::from(DEFAULT_PID_FILE)));
+        assert_eq!(pid_file_path(&config, false), None);
+
+        let config = Config { pid_file: Some("run/logger.pid".to_string()), ..Config::default() };
+        assert_eq!(pid_file_path(&config, true), Some(PathBuf::from("run/logger.pid")));
+        assert_eq!(pid_file_path(&config, false), Some(PathBuf::from("run/logger.pid")));
+    }
+
+    #[test]
+    fn test_guard_removes_registered_pid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logger.pid");
+        write_pid_file(&path).unwrap();
+        let guard = register_pid_file(path.clone());
+        assert!(path.exists());
+        drop(guard);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::{SampleHook, SensorData};
+
+/// Последние отсчёты каждого сервера в кольцевом буфере фиксированного размера:
+/// при заполнении новый отсчёт вытесняет самый старый
+pub struct SampleHistory {
+    capacity: usize,
+    servers: Mutex<BTreeMap<String, VecDeque<SensorData>>>,
+}
+
+impl SampleHistory {
+    pub fn new(capacity: usize) -> Self {
+        SampleHistory { capacity, servers: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&self, server: &str, data: &SensorData) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Ok(mut servers) = self.servers.lock() else {
+            return;
+        };
+        let samples = match servers.get_mut(server) {
+            Some(samples) => samples,
+            None => servers.entry(server.to_string()).or_insert_with(|| VecDeque::with_capacity(self.capacity)),
+        };
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(data.clone());
+    }
+
+    pub fn latest(&self, server: &str) -> Option<SensorData> {
+        self.servers.lock().ok()?.get(server)?.back().cloned()
+    }
+
+    /// Отсчёты с from <= время <= to в порядке поступления; None — граница не задана
+    pub fn range(
+        &self,
+        server: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Vec<SensorData> {
+        let Ok(servers) = self.servers.lock() else {
+            return Vec::new();
+        };
+        let Some(samples) = servers.get(server) else {
+            return Vec::new();
+        };
+        samples
+            .iter()
+            .filter(|data| from.is_none_or(|from| data.timestamp() >= from))
+            .filter(|data| to.is_none_or(|to| data.timestamp() <= to))
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self, server: &str) -> usize {
+        self.servers.lock().map(|s| s.get(server).map_or(0, VecDeque::len)).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self, server: &str) -> bool {
+        self.len(server) == 0
+    }
+
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| self.push(server, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(secs: i64) -> SensorData {
+        let timestamp = DateTime::from_timestamp(secs, 0).unwrap();
+        SensorData::Accelerometer { timestamp, x: secs as i32, y: 0, z: 0 }
+    }
+
+    #[test]
+    fn test_history_evicts_oldest() {
+        let history = SampleHistory::new(3);
+        for secs in 1..=5 {
+            history.push("Server2", &sample(secs));
+        }
+        history.push("Server1", &sample(100));
+
+        assert_eq!(history.len("Server2"), 3);
+        assert_eq!(history.len("Server1"), 1);
+        assert!(history.is_empty("Unknown"));
+        let xs: Vec<_> = history
+            .range("Server2", None, None)
+            .iter()
+            .map(|d| d.timestamp().timestamp())
+            .collect();
+        assert_eq!(xs, [3, 4, 5]);
+        assert_eq!(history.latest("Server2").unwrap().timestamp().timestamp(), 5);
+        assert!(history.latest("Unknown").is_none());
+    }
+
+    #[test]
+    fn test_history_range_query() {
+        let history = Arc::new(SampleHistory::new(10));
+        let hook = history.clone().hook();
+        for secs in [10, 20, 30, 40] {
+            hook("Server2", &sample(secs));
+        }
+        let at = |secs| Some(DateTime::from_timestamp(secs, 0).unwrap());
+
+        assert_eq!(history.range("Server2", at(20), at(30)).len(), 2);
+        assert_eq!(history.range("Server2", at(25), None).len(), 2);
+        assert_eq!(history.range("Server2", None, at(10)).len(), 1);
+        assert!(history.range("Server2", at(50), None).is_empty());
+
+        let disabled = SampleHistory::new(0);
+        disabled.push("Server2", &sample(1));
+        assert!(disabled.is_empty("Server2"));
+    }
+}
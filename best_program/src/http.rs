@@ -0,0 +1,177 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{SampleHistory, SensorData, ServerStats};
+
+/// Состояние сборщика для HTTP API: недавние отсчёты и счётчики каждого сервера
+pub struct ApiState {
+    history: Arc<SampleHistory>,
+    stats: Vec<(String, Arc<ServerStats>)>,
+    started: Instant,
+}
+
+impl ApiState {
+    pub fn new(stats: Vec<(String, Arc<ServerStats>)>, history: Arc<SampleHistory>) -> Self {
+        ApiState { history, stats, started: Instant::now() }
+    }
+}
+
+#[derive(Deserialize)]
+struct TimeRange {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    uptime_secs: u64,
+    servers: usize,
+}
+
+/// Сервер без отсчётов отдаётся как null
+async fn latest(State(state): State<Arc<ApiState>>) -> Json<BTreeMap<String, Option<SensorData>>> {
+    Json(
+        state
+            .stats
+            .iter()
+            .map(|(name, _)| (name.clone(), state.history.latest(name)))
+            .collect(),
+    )
+}
+
+/// /history/{server}?from=...&to=... — время в RFC 3339, обе границы необязательны
+async fn history(
+    State(state): State<Arc<ApiState>>,
+    Path(server): Path<String>,
+    Query(range): Query<TimeRange>,
+) -> Result<Json<Vec<SensorData>>, StatusCode> {
+    if !state.stats.iter().any(|(name, _)| *name == server) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(state.history.range(&server, range.from, range.to)))
+}
+
+async fn stats(State(state): State<Arc<ApiState>>) -> Json<serde_json::Value> {
+    let stats: BTreeMap<&str, &ServerStats> = state.stats.iter().map(|(name, s)| (name.as_str(), s.as_ref())).collect();
+    Json(serde_json::to_value(stats).unwrap_or_default())
+}
+
+async fn health(State(state): State<Arc<ApiState>>) -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        uptime_secs: state.started.elapsed().as_secs(),
+        servers: state.stats.len(),
+    })
+}
+
+pub fn router(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/latest", get(latest))
+        .route("/history/{server}", get(history))
+        .route("/stats", get(stats))
+        .route("/health", get(health))
+        .with_state(state)
+}
+
+/// Занимает адрес сразу, чтобы ошибка была видна при запуске, и обслуживает запросы до отмены shutdown
+pub async fn start(
+    addr: &str,
+    state: Arc<ApiState>,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>, Box<dyn std::error::Error + Send + Sync>> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Cannot listen for HTTP on {}: {}", addr, e))?;
+    Ok(tokio::spawn(async move {
+        let result = axum::serve(listener, router(state))
+            .with_graceful_shutdown(async move { shutdown.cancelled().await })
+            .await;
+        if let Err(e) = result {
+            tracing::error!(error = %e, "HTTP API stopped");
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::sync::atomic::Ordering;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn get(port: u16, path: &str) -> (String, serde_json::Value) {
+        let (status, body) = get_raw(port, path).await;
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    async fn get_raw(port: u16, path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_api_endpoints() {
+        let port = 19026;
+        let server1 = Arc::new(ServerStats::new());
+        server1.packets_received.store(5, Ordering::Relaxed);
+        let history = Arc::new(SampleHistory::new(10));
+        let state = Arc::new(ApiState::new(
+            vec![
+                ("Server1".to_string(), server1),
+                ("Server2".to_string(), Arc::new(ServerStats::new())),
+            ],
+            history.clone(),
+        ));
+        for secs in [1699999990, 1700000000] {
+            let timestamp = DateTime::from_timestamp(secs, 0).unwrap();
+            history.push("Server1", &SensorData::TempPressure { timestamp, temperature: 23.1, pressure: 1013 });
+        }
+
+        let shutdown = CancellationToken::new();
+        let handle = start(&format!("127.0.0.1:{}", port), state, shutdown.clone()).await.unwrap();
+
+        let (status, body) = get(port, "/latest").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["Server1"]["kind"], "temp_pressure");
+        assert_eq!(body["Server1"]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(body["Server1"]["temperature"].to_string(), "23.1");
+        assert_eq!(body["Server1"]["pressure"], 1013);
+        assert!(body["Server2"].is_null());
+
+        let (_, body) = get(port, "/history/Server1?from=2023-11-14T22:13:15Z").await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (_, body) = get(port, "/history/Server1").await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        let (status, _) = get_raw(port, "/history/Nowhere").await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+
+        let (_, body) = get(port, "/stats").await;
+        assert_eq!(body["Server1"]["packets_received"], 5);
+        assert_eq!(body["Server2"]["checksum_errors"], 0);
+        assert_eq!(body["Server2"]["latency"]["count"], 0);
+
+        let (_, body) = get(port, "/health").await;
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["servers"], 2);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+    }
+}
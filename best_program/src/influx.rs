@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{InfluxConfig, SampleHook, SensorData};
+
+/// Сколько строк может ждать отправки, пока сервер недоступен
+const QUEUE_CAPACITY: usize = 10_000;
+/// Предел удвоения паузы между повторами
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// Экранирование значения тега: запятые, пробелы и "=" предваряются обратной косой чертой
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Строка line protocol для одного отсчёта: измерение по типу датчика, тег server, время в наносекундах
+pub fn line(server: &str, data: &SensorData) -> String {
+    let fields = match data {
+        SensorData::TempPressure { temperature, pressure, .. } => {
+            format!("temp_pressure,server={} temperature={},pressure={}i", escape_tag(server), temperature, pressure)
+        }
+        SensorData::Accelerometer { x, y, z, .. } => {
+            format!("accelerometer,server={} x={}i,y={}i,z={}i", escape_tag(server), x, y, z)
+        }
+    };
+    format!("{} {}", fields, data.timestamp().timestamp_micros() * 1000)
+}
+
+/// Почему отправка не удалась: повтор имеет смысл только при сбое сети и ответах 5xx/429
+enum Failure {
+    Retry(String),
+    Rejected(String),
+}
+
+struct Writer {
+    client: reqwest::Client,
+    config: InfluxConfig,
+}
+
+impl Writer {
+    async fn post(&self, body: &str) -> Result<(), Failure> {
+        let mut request = self.client.post(&self.config.url).body(body.to_string());
+        if let Some(token) = &self.config.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        let response = request.send().await.map_err(|e| Failure::Retry(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        let message = format!("HTTP {}: {}", status, text.trim());
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Err(Failure::Retry(message))
+        } else {
+            Err(Failure::Rejected(message))
+        }
+    }
+
+    /// Отправка с повторами и удвоением паузы. После отмены shutdown — не больше одной попытки
+    async fn deliver(&self, body: &str, shutdown: &CancellationToken) -> Result<(), String> {
+        let mut delay = Duration::from_millis(self.config.retry_delay_ms);
+        let mut attempt = 0;
+        loop {
+            let error = match self.post(body).await {
+                Ok(()) => return Ok(()),
+                Err(Failure::Rejected(e)) => return Err(e),
+                Err(Failure::Retry(e)) => e,
+            };
+            if attempt >= self.config.max_retries || shutdown.is_cancelled() {
+                return Err(error);
+            }
+            attempt += 1;
+            tracing::warn!(attempt, delay_ms = delay.as_millis() as u64, error = %error, "InfluxDB write failed, retrying");
+            tokio::select! {
+                _ = shutdown.cancelled() => {}
+                _ = tokio::time::sleep(delay) => {}
+            }
+            delay = (delay * 2).min(Duration::from_millis(MAX_RETRY_DELAY_MS));
+        }
+    }
+}
+
+/// Пакетная запись отсчётов в InfluxDB. Отправкой занимается отдельная задача;
+/// пока идут повторы, новые строки копятся в очереди, при переполнении отбрасываются
+pub struct InfluxSink {
+    sender: mpsc::Sender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl InfluxSink {
+    /// Запускает задачу отправки; после отмены shutdown она отправляет остаток очереди и завершается.
+    /// Нужен работающий tokio runtime
+    pub fn start(
+        config: &InfluxConfig,
+        shutdown: CancellationToken,
+    ) -> Result<(Self, JoinHandle<()>), Box<dyn std::error::Error + Send + Sync>> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        let writer = Writer { client, config: config.clone() };
+        let (sender, mut receiver) = mpsc::channel::<String>(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_in_task = Arc::clone(&dropped);
+
+        let handle = tokio::spawn(async move {
+            let mut timer = tokio::time::interval(Duration::from_millis(writer.config.flush_interval_ms));
+            timer.tick().await;
+            let mut batch: Vec<String> = Vec::new();
+            loop {
+                let stopping = tokio::select! {
+                    _ = shutdown.cancelled() => true,
+                    line = receiver.recv() => match line {
+                        Some(line) => {
+                            batch.push(line);
+                            if batch.len() < writer.config.batch_size {
+                                continue;
+                            }
+                            false
+                        }
+                        None => true,
+                    },
+                    _ = timer.tick() => false,
+                };
+                if stopping {
+                    while let Ok(line) = receiver.try_recv() {
+                        batch.push(line);
+                    }
+                }
+                for chunk in batch.chunks(writer.config.batch_size) {
+                    if let Err(e) = writer.deliver(&chunk.join("\n"), &shutdown).await {
+                        dropped_in_task.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                        tracing::error!(lines = chunk.len(), error = %e, "InfluxDB batch dropped");
+                    }
+                }
+                batch.clear();
+                if stopping {
+                    break;
+                }
+            }
+        });
+        Ok((InfluxSink { sender, dropped }, handle))
+    }
+
+    /// Ставит строку в очередь; при переполненной очереди отсчёт отбрасывается
+    pub fn push(&self, server: &str, data: &SensorData) {
+        if self.sender.try_send(line(server, data)).is_err() && self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+            tracing::warn!(server, "InfluxDB queue is full, dropping samples");
+        }
+    }
+
+    /// Отсчёты, не попавшие в InfluxDB: из-за переполнения очереди или исчерпанных повторов
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| self.push(server, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    #[test]
+    fn test_line_protocol() {
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        assert_eq!(
+            line("Server1", &SensorData::TempPressure { timestamp, temperature: 23.5, pressure: 1013 }),
+            "temp_pressure,server=Server1 temperature=23.5,pressure=1013i 1700000000000000000"
+        );
+        assert_eq!(
+            line("Lab arm,2", &SensorData::Accelerometer { timestamp, x: 1, y: -2, z: 3 }),
+            "accelerometer,server=Lab\\ arm\\,2 x=1i,y=-2i,z=3i 1700000000000000000"
+        );
+    }
+
+    /// HTTP-сервер, отвечающий по очереди заданными статусами; возвращает тела запросов
+    fn influx_server(port: u16, statuses: Vec<u16>) -> Arc<Mutex<Vec<String>>> {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&bodies);
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        thread::spawn(move || {
+            for status in statuses {
+                let Ok((stream, _)) = listener.accept() else { return };
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                received.lock().unwrap().push(String::from_utf8(body).unwrap());
+                let response = format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        bodies
+    }
+
+    #[tokio::test]
+    async fn test_batches_retried_after_server_error() {
+        let bodies = influx_server(19032, vec![503, 204, 204]);
+        let config = InfluxConfig {
+            url: "http://127.0.0.1:19032/write?db=test".to_string(),
+            batch_size: 2,
+            retry_delay_ms: 10,
+            ..InfluxConfig::default()
+        };
+        let shutdown = CancellationToken::new();
+        let (sink, handle) = InfluxSink::start(&config, shutdown.clone()).unwrap();
+        let sink = Arc::new(sink);
+        let hook = sink.clone().hook();
+
+        for i in 0..3 {
+            let timestamp = DateTime::from_timestamp(1700000000 + i, 0).unwrap();
+            hook("Server1", &SensorData::TempPressure { timestamp, temperature: 20.0, pressure: 1000 });
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // Третий отсчёт ждёт неполный пакет до остановки
+        shutdown.cancel();
+        handle.await.unwrap();
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(bodies[1].lines().count(), 2);
+        assert!(bodies[2].ends_with(" 1700000002000000000"));
+        assert_eq!(sink.dropped(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 2^SUB_BITS поддиапазонов на каждую степень двойки: погрешность квантиля не больше 1/8
+const SUB_BITS: u32 = 3;
+const SUB: usize = 1 << SUB_BITS;
+const BUCKETS: usize = (64 - SUB_BITS as usize + 1) * SUB;
+
+/// Номер корзины для значения в микросекундах; значения меньше SUB считаются точно
+fn bucket(us: u64) -> usize {
+    if us < SUB as u64 {
+        return us as usize;
+    }
+    let exp = 63 - us.leading_zeros();
+    let sub = (us >> (exp - SUB_BITS)) as usize & (SUB - 1);
+    (exp - SUB_BITS + 1) as usize * SUB + sub
+}
+
+/// Наибольшее значение, попадающее в корзину
+fn bucket_upper(index: usize) -> u64 {
+    if index < SUB {
+        return index as u64;
+    }
+    let shift = (index / SUB - 1) as u32;
+    let lower = ((SUB + index % SUB) as u64) << shift;
+    lower + ((1u64 << shift) - 1)
+}
+
+/// Квантили задержки в миллисекундах
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Гистограмма задержек запрос→ответ с логарифмическими корзинами; запись без блокировок
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, elapsed: Duration) {
+        let us = elapsed.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[bucket(us)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_us.load(Ordering::Relaxed))
+    }
+
+    /// Значение, не меньше которого q-я доля замеров; None — замеров нет
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Some(Duration::from_micros(bucket_upper(index)).min(self.max()));
+            }
+        }
+        Some(self.max())
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let ms = |q| self.quantile(q).unwrap_or_default().as_secs_f64() * 1000.0;
+        LatencySummary {
+            count: self.count(),
+            p50_ms: ms(0.50),
+            p95_ms: ms(0.95),
+            p99_ms: ms(0.99),
+            max_ms: self.max().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+impl fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
+impl Serialize for LatencyHistogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.summary().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_cover_their_values() {
+        for us in [0, 1, 7, 8, 9, 15, 16, 17, 1000, 4_500_000, u64::MAX / 3, u64::MAX] {
+            let index = bucket(us);
+            assert!(index < BUCKETS);
+            assert!(bucket_upper(index) >= us, "{} -> {}", us, index);
+            assert!(index == 0 || bucket_upper(index - 1) < us, "{} -> {}", us, index);
+        }
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        let within = |q: f64, expected_ms: f64| {
+            let value = histogram.quantile(q).unwrap().as_secs_f64() * 1000.0;
+            assert!(value >= expected_ms && value <= expected_ms * 1.125, "q{} = {}", q, value);
+        };
+        within(0.50, 50.0);
+        within(0.95, 95.0);
+        within(0.99, 99.0);
+
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.max_ms, 100.0);
+        assert!(summary.p99_ms <= summary.max_ms);
+    }
+}
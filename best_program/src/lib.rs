@@ -0,0 +1,2776 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::sync::watch;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use std::net::SocketAddr;
+use logging::EVENTS;
+
+pub mod aggregate;
+pub mod alert;
+pub mod binary;
+pub mod check;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod daemon;
+pub mod history;
+#[cfg(feature = "influx")]
+pub mod influx;
+pub mod latency;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod logging;
+pub mod mock;
+pub mod moving_average;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod output;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod protocol;
+pub mod reload;
+pub mod replay;
+pub mod rotation;
+pub mod schema;
+pub mod sequence;
+pub mod sink;
+pub mod snapshot;
+pub mod state;
+pub mod transport;
+pub mod units;
+pub mod validation;
+pub mod watchdog;
+pub mod write_queue;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+pub use cli::Cli;
+pub use config::{Config, InfluxConfig, MqttConfig, ServerConfig, TlsConfig};
+pub use history::SampleHistory;
+pub use protocol::{KeepaliveSpec, ProtocolSpec};
+pub use schema::PacketSchema;
+pub use sink::{DataSink, FanOut, FileSink};
+pub use transport::{Connection, Transport};
+pub use units::{Conversion, Units};
+
+pub const KEY: &[u8] = b"isu_pt";
+pub const GET_CMD: &[u8] = b"get";
+
+pub const SERVER1_PACKET_SIZE: usize = 15; // 8 + 4 + 2 + 1
+pub const SERVER2_PACKET_SIZE: usize = 21; // 8 + 4 + 4 + 4 + 1
+
+// Значения по умолчанию для Config
+pub const READ_TIMEOUT_MS: u64 = 4500;
+pub const WRITE_TIMEOUT_MS: u64 = 2000;
+pub const MAX_CLOCK_SKEW_MS: u64 = 5000;
+pub const GAP_THRESHOLD_MS: u64 = 2000;
+pub const SHUTDOWN_TIMEOUT_MS: u64 = 3000;
+pub const WATCHDOG_TIMEOUT_MS: u64 = 30000;
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;    
+/// Период опроса сервера по умолчанию
+const REQUEST_DELAY_MS: u64 = 1;
+/// Без успешного отсчёта дольше этого (плюс два периода опроса) соединение считается зависшим
+const STALL_TIMEOUT_SECS: u64 = 5;
+// Ресинхронизация: сколько кадров можно просмотреть и сколько ждать очередной байт
+const RESYNC_MAX_FRAMES: usize = 4;
+const RESYNC_POLL_MS: u64 = 50;
+const MIN_RECONNECT_DELAY_MS: u64 = 20;
+const CONNECT_TIMEOUT_SECS: u64 = 5;
+const AUTH_TIMEOUT_SECS: u64 = 3;
+#[allow(dead_code)]
+const MAX_RECONNECT_DELAY_MS: u64 = 1000;
+pub const STATS_INTERVAL_SECS: u64 = 10;
+pub const FLUSH_INTERVAL_SECS: u64 = 5;
+pub const FSYNC_INTERVAL_SECS: u64 = 30;
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SensorData {
+    TempPressure {
+        timestamp: DateTime<Utc>,
+        temperature: f32,
+        pressure: i16,
+    },
+    Accelerometer {
+        timestamp: DateTime<Utc>,
+        x: i32,
+        y: i32,
+        z: i32,
+    },
+}
+
+impl SensorData {
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            SensorData::TempPressure { timestamp, .. } | SensorData::Accelerometer { timestamp, .. } => *timestamp,
+        }
+    }
+
+    pub fn kind(&self) -> SensorKind {
+        match self {
+            SensorData::TempPressure { .. } => SensorKind::TempPressure,
+            SensorData::Accelerometer { .. } => SensorKind::Accelerometer,
+        }
+    }
+
+    /// Измеренные величины по именам полей кадра
+    pub fn values(&self) -> Vec<(&'static str, f64)> {
+        match self {
+            SensorData::TempPressure { temperature, pressure, .. } => {
+                vec![("temperature", *temperature as f64), ("pressure", *pressure as f64)]
+            }
+            SensorData::Accelerometer { x, y, z, .. } => {
+                vec![("x", *x as f64), ("y", *y as f64), ("z", *z as f64)]
+            }
+        }
+    }
+}
+
+/// Тип датчика за сервером: определяет размер и разбор пакета
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum SensorKind {
+    TempPressure,
+    Accelerometer,
+}
+
+impl SensorKind {
+    pub fn packet_size(self) -> usize {
+        match self {
+            SensorKind::TempPressure => SERVER1_PACKET_SIZE,
+            SensorKind::Accelerometer => SERVER2_PACKET_SIZE,
+        }
+    }
+
+    /// Раскладка кадра по умолчанию для этого типа датчика
+    pub fn schema(self) -> &'static PacketSchema {
+        match self {
+            SensorKind::TempPressure => PacketSchema::temp_pressure(),
+            SensorKind::Accelerometer => PacketSchema::accelerometer(),
+        }
+    }
+
+    /// Поля, без которых из кадра не собрать SensorData
+    pub fn required_fields(self) -> &'static [&'static str] {
+        match self {
+            SensorKind::TempPressure => &["timestamp", "temperature", "pressure"],
+            SensorKind::Accelerometer => &["timestamp", "x", "y", "z"],
+        }
+    }
+
+    pub fn server_name(self) -> &'static str {
+        match self {
+            SensorKind::TempPressure => "Server1",
+            SensorKind::Accelerometer => "Server2",
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ServerStats {
+    pub packets_received: AtomicU64,
+    pub checksum_errors: AtomicU64,
+    pub timeout_errors: AtomicU64,
+    pub connection_errors: AtomicU64,
+    pub reconnections: AtomicU64,
+    pub sync_resets: AtomicU64,
+    pub resyncs: AtomicU64,
+    /// Отсчёты, не прошедшие правила правдоподобия (отброшенные или помеченные)
+    pub rejected_samples: AtomicU64,
+    /// Сглаженное смещение часов сервера относительно локальных, мс
+    pub clock_offset_ms: AtomicI64,
+    /// Повторы кадра с тем же временем (пропущены)
+    pub duplicates: AtomicU64,
+    /// Кадры со временем раньше предыдущего (пропущены)
+    pub out_of_order: AtomicU64,
+    /// Пропуски данных дольше gap_threshold_ms и их суммарная длительность
+    pub gaps: AtomicU64,
+    pub gap_time_ms: AtomicU64,
+    /// Перезапуски зависшего worker сторожем
+    pub watchdog_restarts: AtomicU64,
+    /// Проверки keepalive без верного ответа; после каждой соединение открывается заново
+    pub keepalive_failures: AtomicU64,
+    /// Время (Unix, мс) последнего завершившегося шага worker: отсчёта, ошибки или попытки подключения
+    pub last_activity_ms: AtomicI64,
+    /// Отсчёты, ждавшие места в заполненной очереди к потоку записи (write_queue_full = "block")
+    pub write_queue_waits: AtomicU64,
+    /// Отсчёты, отброшенные из-за заполненной очереди (write_queue_full = "drop")
+    pub write_queue_drops: AtomicU64,
+    /// Задержка от отправки "get" до получения кадра целиком
+    pub latency: latency::LatencyHistogram,
+    /// Период опроса из перечитанной конфигурации; None — из ServerConfig
+    #[serde(skip)]
+    pub poll_interval: Mutex<Option<Duration>>,
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Отметка для сторожа: worker не завис
+    pub fn touch(&self) {
+        self.last_activity_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn poll_interval(&self, server: &ServerConfig) -> Duration {
+        self.poll_interval.lock().ok().and_then(|interval| *interval).unwrap_or_else(|| server.poll_interval())
+    }
+
+    pub fn set_poll_interval(&self, interval: Duration) {
+        if let Ok(mut current) = self.poll_interval.lock() {
+            *current = Some(interval);
+        }
+    }
+
+    /// Накопительные счётчики по именам. Смещение часов, время активности и задержки сюда не входят
+    pub fn counters(&self) -> [(&'static str, &AtomicU64); 16] {
+        [
+            ("packets_received", &self.packets_received),
+            ("checksum_errors", &self.checksum_errors),
+            ("timeout_errors", &self.timeout_errors),
+            ("connection_errors", &self.connection_errors),
+            ("reconnections", &self.reconnections),
+            ("sync_resets", &self.sync_resets),
+            ("resyncs", &self.resyncs),
+            ("rejected_samples", &self.rejected_samples),
+            ("duplicates", &self.duplicates),
+            ("out_of_order", &self.out_of_order),
+            ("gaps", &self.gaps),
+            ("gap_time_ms", &self.gap_time_ms),
+            ("watchdog_restarts", &self.watchdog_restarts),
+            ("keepalive_failures", &self.keepalive_failures),
+            ("write_queue_waits", &self.write_queue_waits),
+            ("write_queue_drops", &self.write_queue_drops),
+        ]
+    }
+
+    /// Краткая строка для периодической статистики
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ok, {} csum_err, {} reconn, {} sync, {} resync, {} rejected, {} dup, {} ooo",
+            self.packets_received.load(Ordering::Relaxed),
+            self.checksum_errors.load(Ordering::Relaxed),
+            self.reconnections.load(Ordering::Relaxed),
+            self.sync_resets.load(Ordering::Relaxed),
+            self.resyncs.load(Ordering::Relaxed),
+            self.rejected_samples.load(Ordering::Relaxed),
+            self.duplicates.load(Ordering::Relaxed),
+            self.out_of_order.load(Ordering::Relaxed),
+        )
+    }
+}
+
+pub fn calculate_checksum(data: &[u8]) -> u8 {
+    let sum: u32 = data.iter().map(|&b| b as u32).sum();
+    (sum % 256) as u8
+}
+
+pub fn verify_checksum(data: &[u8], checksum: u8) -> bool {
+    calculate_checksum(data) == checksum
+}
+
+/// Создание TCP соединения с оптимальными настройками
+pub(crate) async fn create_optimized_socket(
+    addr: &str,
+) -> Result<TcpStream, Box<dyn std::error::Error + Send + Sync>> {
+    let socket_addr: SocketAddr = addr.parse()?;
+    
+    let socket = if socket_addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+    
+    socket.set_keepalive(true)?;
+    socket.set_nodelay(true)?;
+    socket.set_recv_buffer_size(65536)?;
+    socket.set_send_buffer_size(65536)?;
+    
+    let stream = timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS), socket.connect(socket_addr))
+        .await
+        .map_err(|_| format!("Connect timeout: {}", addr))??;
+    
+    Ok(stream)
+}
+
+/// Запись с ограничением по времени вместо таймаута сокета
+async fn write_with_timeout<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    data: &[u8],
+    limit: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match timeout(limit, stream.write_all(data)).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Write timeout").into()),
+    }
+}
+
+#[allow(dead_code)]
+async fn drain_input_buffer(stream: &mut TcpStream) -> usize {
+    let mut total_drained = 0;
+    let mut buf = [0u8; 512];
+    let mut attempts = 0;
+    
+    while attempts < 3 {
+        match timeout(Duration::from_millis(30), stream.read(&mut buf)).await {
+            Ok(Ok(0)) => break,
+            Ok(Ok(n)) => {
+                total_drained += n;
+                attempts = 0;
+                if total_drained > 2048 {
+                    break;
+                }
+            }
+            _ => {
+                attempts += 1;
+            }
+        }
+    }
+    
+    total_drained
+}
+
+pub async fn connect_and_auth(
+    server: &ServerConfig, 
+    stats: &ServerStats,
+    config: &Config,
+) -> Result<TcpStream, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stream = create_optimized_socket(&server.address).await?;
+    authenticate(&mut stream, server, stats, config).await?;
+    Ok(stream)
+}
+
+/// Отправка ключа и ожидание ответа по server.protocol; одинаково для открытого TCP и TLS
+pub(crate) async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    server: &ServerConfig,
+    stats: &ServerStats,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    write_with_timeout(stream, server.protocol.key(config), config.write_timeout()).await?;
+    
+    let mut auth_buf = [0u8; 64];
+    let total = match timeout(Duration::from_secs(AUTH_TIMEOUT_SECS), stream.read(&mut auth_buf)).await {
+        Ok(Ok(n)) if n > 0 => {
+            // Ответ может прийти несколькими сегментами
+            match timeout(Duration::from_millis(30), stream.read(&mut auth_buf[n..])).await {
+                Ok(Ok(more)) => n + more,
+                _ => n,
+            }
+        }
+        Ok(Err(e)) => {
+            stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(e.into());
+        }
+        _ => 0,
+    };
+    
+    if total == 0 {
+        stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+        return Err("No auth response received".into());
+    }
+    if let Err(e) = server.protocol.check_ack(&auth_buf[..total]) {
+        stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+        return Err(e.into());
+    }
+    
+    info!(target: EVENTS, address = %server.address, auth_bytes = total, "Connected");
+    
+    Ok(())
+}
+
+async fn read_exact_reliable<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut [u8],
+    limit: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let target_len = buf.len();
+    match timeout(limit, stream.read_exact(buf)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Err("Connection closed by server".into())
+        }
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => {
+            let message = format!("Read timeout: expected {} bytes", target_len);
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, message).into())
+        }
+    }
+}
+
+/// Ошибка ввода-вывода данного вида: таймаут чтения или записи, несовпадение контрольной суммы
+fn is_io_error(e: &(dyn std::error::Error + Send + Sync + 'static), kind: std::io::ErrorKind) -> bool {
+    e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == kind)
+}
+
+/// Разбор пакета целиком по раскладке типа датчика по умолчанию.
+/// Пакет может быть длиннее минимального (ServerConfig::packet_size) - лишние байты не разбираются.
+pub fn parse_packet(
+    kind: SensorKind,
+    packet: &[u8],
+) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+    parse_with_schema(kind, kind.schema(), packet)
+}
+
+/// Разбор пакета по произвольной раскладке; имена полей должны совпадать с kind.required_fields()
+pub fn parse_with_schema(
+    kind: SensorKind,
+    schema: &PacketSchema,
+    packet: &[u8],
+) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+    let frame = schema.decode(packet)?;
+    let int = |name: &str| -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        frame
+            .get(name)
+            .ok_or_else(|| format!("Missing field '{}'", name))?
+            .as_i64()
+            .ok_or_else(|| format!("Field '{}' is not an integer", name).into())
+    };
+
+    let timestamp = DateTime::from_timestamp_micros(int("timestamp")?)
+        .ok_or("Invalid timestamp")?;
+
+    match kind {
+        SensorKind::TempPressure => {
+            let temperature = frame.get("temperature").ok_or("Missing field 'temperature'")?.as_f64() as f32;
+            let pressure = i16::try_from(int("pressure")?).map_err(|_| "Field 'pressure' out of range")?;
+            
+            Ok(SensorData::TempPressure {
+                timestamp,
+                temperature,
+                pressure,
+            })
+        }
+        SensorKind::Accelerometer => {
+            let axis = |name: &str| -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
+                i32::try_from(int(name)?).map_err(|_| format!("Field '{}' out of range", name).into())
+            };
+            
+            Ok(SensorData::Accelerometer {
+                timestamp,
+                x: axis("x")?,
+                y: axis("y")?,
+                z: axis("z")?,
+            })
+        }
+    }
+}
+
+pub(crate) async fn fetch_packet<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    server: &ServerConfig,
+    stats: &ServerStats,
+    config: &Config,
+) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+    let started = Instant::now();
+    write_with_timeout(stream, server.protocol.request(), config.write_timeout()).await?;
+    
+    let mut packet = vec![0u8; server.packet_size()];
+    read_exact_reliable(stream, &mut packet, config.read_timeout()).await?;
+    stats.latency.record(started.elapsed());
+    
+    let data = match parse_with_schema(server.kind, server.schema(), &packet) {
+        Ok(data) => data,
+        Err(e) if is_io_error(&*e, std::io::ErrorKind::InvalidData) => {
+            stats.checksum_errors.fetch_add(1, Ordering::Relaxed);
+            let data = resync(stream, server, packet).await.map_err(|resync_err| {
+                let message = format!("{} (resync failed: {})", e, resync_err);
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+            })?;
+            stats.resyncs.fetch_add(1, Ordering::Relaxed);
+            info!(target: EVENTS, error = %e, "Resynchronized after checksum error");
+            data
+        }
+        Err(e) => return Err(e),
+    };
+    
+    stats.packets_received.fetch_add(1, Ordering::Relaxed);
+    
+    Ok(data)
+}
+
+/// Проверка соединения в паузе опроса: команда keepalive и её ответ или, если команды нет,
+/// запрос кадра. Кадр проверяется по контрольной сумме и отбрасывается
+pub(crate) async fn probe<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    server: &ServerConfig,
+    keepalive: &protocol::KeepaliveSpec,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match (&keepalive.command, &keepalive.response) {
+        (Some(command), Some(response)) => {
+            write_with_timeout(stream, command.as_bytes(), config.write_timeout()).await?;
+            let mut reply = vec![0u8; response.len()];
+            read_exact_reliable(stream, &mut reply, config.read_timeout()).await?;
+            if reply != response.as_bytes() {
+                return Err(format!("Unexpected keepalive response: {:?}", String::from_utf8_lossy(&reply)).into());
+            }
+        }
+        _ => {
+            write_with_timeout(stream, server.protocol.request(), config.write_timeout()).await?;
+            let mut packet = vec![0u8; server.packet_size()];
+            read_exact_reliable(stream, &mut packet, config.read_timeout()).await?;
+            parse_with_schema(server.kind, server.schema(), &packet)?;
+        }
+    }
+    Ok(())
+}
+
+/// Время кадра правдоподобно, если оно не раньше 2000 года и не позже чем через сутки
+fn is_plausible_timestamp(timestamp: DateTime<Utc>) -> bool {
+    const Y2000_SECS: i64 = 946_684_800;
+    timestamp.timestamp() >= Y2000_SECS && timestamp <= Utc::now() + chrono::Duration::days(1)
+}
+
+/// Сдвигает окно по одному байту, пока кадр не сойдётся по контрольной сумме
+/// и не даст правдоподобное время. Если байтов в сокете нет, запрашивает следующий кадр.
+async fn resync<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    server: &ServerConfig,
+    mut window: Vec<u8>,
+) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+    let poll = Duration::from_millis(RESYNC_POLL_MS);
+    let mut requests = 0;
+    let mut byte = [0u8; 1];
+
+    for _ in 0..window.len() * RESYNC_MAX_FRAMES {
+        window.remove(0);
+        loop {
+            match read_exact_reliable(stream, &mut byte, poll).await {
+                Ok(()) => break,
+                Err(e) if is_io_error(&*e, std::io::ErrorKind::TimedOut) && requests < RESYNC_MAX_FRAMES => {
+                    stream.write_all(server.protocol.request()).await?;
+                    requests += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        window.push(byte[0]);
+
+        if let Ok(data) = parse_with_schema(server.kind, server.schema(), &window) {
+            if is_plausible_timestamp(data.timestamp()) {
+                return Ok(data);
+            }
+        }
+    }
+    Err("no valid frame found".into())
+}
+
+/// Подключение к одному серверу датчиков: авторизация и запрос пакетов по одному
+pub struct SensorClient {
+    connection: Connection,
+    server: ServerConfig,
+    stats: Arc<ServerStats>,
+    config: Config,
+}
+
+impl SensorClient {
+    pub async fn connect(
+        server: &str,
+        kind: SensorKind,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let server = ServerConfig::new(kind.server_name(), server, kind);
+        Self::connect_with(server, Config::default(), Arc::new(ServerStats::new())).await
+    }
+
+    /// Подключение с заданными настройками; статистика может быть общей между переподключениями
+    pub async fn connect_with(
+        server: ServerConfig,
+        config: Config,
+        stats: Arc<ServerStats>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let connection = Connection::open(&server, &stats, &config)
+            .instrument(info_span!("client", server = %server.name))
+            .await?;
+        Ok(SensorClient { connection, server, stats, config })
+    }
+
+    pub async fn fetch(&mut self) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+        self.connection.fetch(&self.server, &self.stats, &self.config).await
+    }
+
+    pub fn kind(&self) -> SensorKind {
+        self.server.kind
+    }
+
+    pub fn server(&self) -> &ServerConfig {
+        &self.server
+    }
+
+    pub fn stats(&self) -> &Arc<ServerStats> {
+        &self.stats
+    }
+}
+
+pub fn format_data(data: &SensorData) -> String {
+    format_data_with(data, &Units::new())
+}
+
+/// Текстовая строка с пересчётом полей в физические единицы
+pub fn format_data_with(data: &SensorData, units: &Units) -> String {
+    let field = |name: &str, raw: f64| {
+        let (value, unit) = units::field(units, name, raw);
+        format!("{}={}{}", name, value, unit)
+    };
+    match data {
+        SensorData::TempPressure { timestamp, temperature, pressure } => {
+            format!(
+                "{} [S1] {} {}\n",
+                timestamp.format("%Y-%m-%d %H:%M:%S"),
+                field("temperature", *temperature as f64),
+                field("pressure", *pressure as f64)
+            )
+        }
+        SensorData::Accelerometer { timestamp, x, y, z } => {
+            format!(
+                "{} [S2] {} {} {}\n",
+                timestamp.format("%Y-%m-%d %H:%M:%S"),
+                field("x", *x as f64), field("y", *y as f64), field("z", *z as f64)
+            )
+        }
+    }
+}
+
+pub const CSV_HEADER: &str = "timestamp,source,temperature,pressure,x,y,z\n";
+
+/// Строка CSV: у каждого датчика заполнены только свои столбцы
+pub fn format_csv(data: &SensorData) -> String {
+    format_csv_with(data, &Units::new())
+}
+
+/// Строка CSV с пересчётом полей; единицы в CSV не подписываются
+pub fn format_csv_with(data: &SensorData, units: &Units) -> String {
+    let field = |name: &str, raw: f64| units::field(units, name, raw).0;
+    match data {
+        SensorData::TempPressure { timestamp, temperature, pressure } => {
+            format!(
+                "{},S1,{},{},,,\n",
+                timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                field("temperature", *temperature as f64),
+                field("pressure", *pressure as f64)
+            )
+        }
+        SensorData::Accelerometer { timestamp, x, y, z } => {
+            format!(
+                "{},S2,,,{},{},{}\n",
+                timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                field("x", *x as f64), field("y", *y as f64), field("z", *z as f64)
+            )
+        }
+    }
+}
+
+/// Формат выходного файла
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Csv,
+    Binary,
+}
+
+impl OutputFormat {
+    pub fn encode(self, data: &SensorData) -> Vec<u8> {
+        self.encode_with(data, &Units::new())
+    }
+
+    /// Двоичный формат всегда хранит сырые значения: Config::validate запрещает ему пересчёт
+    pub fn encode_with(self, data: &SensorData, units: &Units) -> Vec<u8> {
+        match self {
+            OutputFormat::Text => format_data_with(data, units).into_bytes(),
+            OutputFormat::Csv => format_csv_with(data, units).into_bytes(),
+            OutputFormat::Binary => binary::encode_record(data),
+        }
+    }
+
+    /// Заголовок пишется только в пустой файл
+    pub fn header(self) -> Option<&'static [u8]> {
+        match self {
+            OutputFormat::Text => None,
+            OutputFormat::Csv => Some(CSV_HEADER.as_bytes()),
+            OutputFormat::Binary => Some(binary::BINARY_MAGIC),
+        }
+    }
+}
+
+/// Когда выходной файл записывается на диск (fsync); сброс буфера в ОС — по flush_interval_secs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// Только при выходе и ротации; при отключении питания теряется то, что ОС не успела записать
+    #[default]
+    Never,
+    /// После каждого сброса буфера
+    Flush,
+    /// Каждые fsync_interval_secs
+    Interval,
+}
+
+/// Общий для всех серверов получатель отсчётов (имя сервера, данные); к worker подключается через sink::HookSink
+pub type SampleHook = Arc<dyn Fn(&str, &SensorData) + Send + Sync>;
+
+/// Срок без принятых кадров, после которого соединение считается зависшим
+fn stall_limit(poll_interval: Duration) -> Duration {
+    Duration::from_secs(STALL_TIMEOUT_SECS).saturating_add(poll_interval.saturating_mul(2))
+}
+
+/// Место кадра в последовательности сервера с учётом в статистике; false — повтор или кадр
+/// из прошлого, он не записывается
+fn check_sequence(sequence: &mut sequence::SequenceTracker, data: &SensorData, stats: &ServerStats) -> bool {
+    match sequence.check(data.timestamp()) {
+        sequence::Sequence::Next => true,
+        sequence::Sequence::Gap(gap) => {
+            let duration_ms = gap.duration().num_milliseconds().max(0) as u64;
+            stats.gaps.fetch_add(1, Ordering::Relaxed);
+            stats.gap_time_ms.fetch_add(duration_ms, Ordering::Relaxed);
+            warn!(target: EVENTS, start = %gap.start, end = %gap.end, duration_ms, "Gap in data");
+            true
+        }
+        sequence::Sequence::Duplicate => {
+            stats.duplicates.fetch_add(1, Ordering::Relaxed);
+            debug!(timestamp = %data.timestamp(), "Duplicate frame skipped");
+            false
+        }
+        sequence::Sequence::OutOfOrder => {
+            stats.out_of_order.fetch_add(1, Ordering::Relaxed);
+            warn!(timestamp = %data.timestamp(), last = ?sequence.last(), "Out-of-order frame skipped");
+            false
+        }
+    }
+}
+
+async fn data_collection_loop(
+    connection: &mut Connection,
+    server: &ServerConfig,
+    sink: &mut dyn DataSink,
+    mut sequence: Option<&mut sequence::SequenceTracker>,
+    stats: &Arc<ServerStats>,
+    shutdown: &CancellationToken,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut consecutive_errors = 0u32;
+    let mut last_success = Instant::now();
+    let mut validator = server.validation.as_ref().map(validation::Validator::new);
+    let mut skew = clock::SkewTracker::new(config.max_clock_skew_ms);
+    let mut next_request = Instant::now();
+    
+    loop {
+        // Период опроса мог смениться при перечитывании конфигурации
+        let poll_interval = stats.poll_interval(server);
+        // Проверка в начале итерации: пропущенные повторы и кадры не по порядку её не обходят
+        if last_success.elapsed() > stall_limit(poll_interval) {
+            return Err("Stalled".into());
+        }
+        
+        // В паузе дольше интервала keepalive соединение проверяется, чтобы не ждать срока stall_limit
+        loop {
+            let pause = next_request.saturating_duration_since(Instant::now());
+            if pause.is_zero() {
+                break;
+            }
+            let keepalive = server.protocol.keepalive.as_ref().filter(|k| k.interval() < pause);
+            tokio::select! {
+                _ = shutdown.cancelled() => return Ok(()),
+                _ = tokio::time::sleep(keepalive.map_or(pause, |k| k.interval())) => {}
+            }
+            let Some(keepalive) = keepalive else {
+                continue;
+            };
+            let probed = tokio::select! {
+                _ = shutdown.cancelled() => return Ok(()),
+                probed = connection.probe(server, keepalive, config) => probed,
+            };
+            if let Err(e) = probed {
+                stats.keepalive_failures.fetch_add(1, Ordering::Relaxed);
+                return Err(format!("Keepalive failed: {}", e).into());
+            }
+            stats.touch();
+        }
+        next_request = Instant::now() + poll_interval;
+        
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            result = connection.fetch(server, stats, config) => result,
+        };
+        stats.touch();
+        
+        match result {
+            Ok(data) => {
+                consecutive_errors = 0;
+                
+                // Без трекера порядок проверяет collect_in_parallel после сведения соединений
+                if let Some(sequence) = sequence.as_deref_mut() {
+                    if !check_sequence(sequence, &data, stats) {
+                        continue;
+                    }
+                }
+                // Сервер, повторяющий один и тот же кадр, живым не считается
+                last_success = Instant::now();
+                
+                match skew.observe(data.timestamp(), Utc::now()) {
+                    Some(clock::SkewEvent::Exceeded(offset_ms)) => warn!(offset_ms, "Server clock skew exceeds threshold"),
+                    Some(clock::SkewEvent::Recovered(offset_ms)) => info!(offset_ms, "Server clock skew back within threshold"),
+                    Some(clock::SkewEvent::Drifted(from_ms, to_ms)) => warn!(from_ms, to_ms, "Server clock is drifting"),
+                    None => {}
+                }
+                if let Some(offset) = skew.offset_ms() {
+                    stats.clock_offset_ms.store(offset, Ordering::Relaxed);
+                }
+                
+                if let Some(validator) = &mut validator {
+                    if let Err(reason) = validator.check(&data) {
+                        stats.rejected_samples.fetch_add(1, Ordering::Relaxed);
+                        if validator.action() == validation::RejectAction::Drop {
+                            debug!(%reason, "Sample dropped");
+                            continue;
+                        }
+                        warn!(%reason, "Implausible sample");
+                    }
+                }
+                
+                if let Err(e) = sink.write(&data) {
+                    error!(error = %e, "Write error");
+                }
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                let error_msg = e.to_string();
+                debug!(target: EVENTS, error = %error_msg, consecutive_errors, "Fetch failed");
+                
+                if is_io_error(&*e, std::io::ErrorKind::InvalidData) {
+                    stats.sync_resets.fetch_add(1, Ordering::Relaxed);
+                    return Err("Stream desync".into());
+                }
+                
+                // Таймауты чтения и записи; WouldBlock — истёкший таймаут сокета на Unix
+                if is_io_error(&*e, std::io::ErrorKind::TimedOut) || is_io_error(&*e, std::io::ErrorKind::WouldBlock) {
+                    stats.timeout_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                
+                if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                    return Err(format!("Too many errors: {}", consecutive_errors).into());
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Сбор с одного сервера до отмены shutdown: подключение, чтение, переподключение при ошибках
+pub async fn worker_task(
+    server: &ServerConfig,
+    mut sink: impl DataSink,
+    stats: Arc<ServerStats>,
+    shutdown: CancellationToken,
+    config: &Config,
+) {
+    let span = info_span!("worker", server = %server.name);
+    async {
+        info!("Worker started");
+        stats.touch();
+
+        let connections = server.connections();
+        if connections > 1 {
+            collect_in_parallel(server, connections, &mut sink, &stats, &shutdown, config).await;
+        } else {
+            let mut sequence = sequence::SequenceTracker::new(config.gap_threshold());
+            connection_loop(server, &mut sink, Some(&mut sequence), &stats, &shutdown, config).await;
+        }
+        
+        if let Err(e) = sink.finish() {
+            error!(error = %e, "Write error");
+        }
+        info!("Worker finished");
+    }
+    .instrument(span)
+    .await
+}
+
+/// Подключение, сбор и переподключение одного соединения до отмены shutdown.
+/// Трекер живёт дольше соединения, чтобы ловить повторы после переподключения
+async fn connection_loop(
+    server: &ServerConfig,
+    sink: &mut dyn DataSink,
+    mut sequence: Option<&mut sequence::SequenceTracker>,
+    stats: &Arc<ServerStats>,
+    shutdown: &CancellationToken,
+    config: &Config,
+) {
+    while !shutdown.is_cancelled() {
+        let connected = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            connected = Connection::open(server, stats, config) => connected,
+        };
+        stats.touch();
+        match connected {
+            Ok(mut connection) => {
+                let reconnects = stats.reconnections.load(Ordering::Relaxed);
+                if reconnects > 0 {
+                    info!(target: EVENTS, reconnects, "Reconnected");
+                }
+                
+                match data_collection_loop(&mut connection, server, sink, sequence.as_deref_mut(), stats, shutdown, config).await {
+                    Ok(_) => {
+                        info!("Loop ended gracefully");
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(target: EVENTS, error = %e, "Collection interrupted, reconnecting");
+                        stats.reconnections.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            Err(e) => {
+                error!(target: EVENTS, error = %e, "Connect failed");
+                stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+                stats.reconnections.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        
+        tokio::select! {
+            _ = shutdown.cancelled() => {}
+            _ = tokio::time::sleep(Duration::from_millis(MIN_RECONNECT_DELAY_MS)) => {}
+        }
+    }
+}
+
+/// Несколько соединений с одним сервером. Счётчики у них общие (ServerStats сервера). Соединения
+/// начинают опрос со сдвигом на долю периода, так что запросы чередуются. Порядок и повторы
+/// проверяются одним трекером после сведения кадров, в приёмники worker попадают только принятые.
+/// Если сведённые кадры перестали приниматься, все соединения переподключаются.
+/// При отмене задачи worker соединения прерываются вместе с ней
+async fn collect_in_parallel(
+    server: &ServerConfig,
+    connections: usize,
+    sink: &mut dyn DataSink,
+    stats: &Arc<ServerStats>,
+    shutdown: &CancellationToken,
+    config: &Config,
+) {
+    let server = Arc::new(server.clone());
+    let config = Arc::new(config.clone());
+    let mut sequence = sequence::SequenceTracker::new(config.gap_threshold());
+    while !shutdown.is_cancelled() {
+        let restart = shutdown.child_token();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = tokio::task::JoinSet::new();
+        for connection in 1..=connections {
+            let offset = stats.poll_interval(&server).mul_f64((connection - 1) as f64 / connections as f64);
+            let mut channel = sink::ChannelSink::new(sender.clone());
+            let (server, config, stats, restart) = (server.clone(), config.clone(), stats.clone(), restart.clone());
+            tasks.spawn(
+                async move {
+                    tokio::select! {
+                        _ = restart.cancelled() => return,
+                        _ = tokio::time::sleep(offset) => {}
+                    }
+                    connection_loop(&server, &mut channel, None, &stats, &restart, &config).await
+                }
+                .instrument(info_span!("connection", connection)),
+            );
+        }
+        drop(sender);
+        
+        // Канал закрывается, когда завершились все соединения
+        let mut last_success = Instant::now();
+        while let Some(data) = receiver.recv().await {
+            if check_sequence(&mut sequence, &data, stats) {
+                last_success = Instant::now();
+                if let Err(e) = sink.write(&data) {
+                    error!(error = %e, "Write error");
+                }
+            } else if !restart.is_cancelled() && last_success.elapsed() > stall_limit(stats.poll_interval(&server)) {
+                warn!(target: EVENTS, "Stalled, reconnecting all connections");
+                stats.reconnections.fetch_add(1, Ordering::Relaxed);
+                restart.cancel();
+            }
+        }
+        while tasks.join_next().await.is_some() {}
+        
+        tokio::select! {
+            _ = shutdown.cancelled() => {}
+            _ = tokio::time::sleep(Duration::from_millis(MIN_RECONNECT_DELAY_MS)) => {}
+        }
+    }
+}
+
+/// Периодический сброс буферов выходных файлов, ротация и вывод статистики; при отмене делает последний сброс.
+/// Настройки ротации берутся из текущей конфигурации на каждом сбросе, остальные — при запуске.
+/// Последний снимок stats_file пишет вызывающий после остановки задач: эту задачу могут прервать по сроку
+pub async fn stats_and_flush_task(
+    outputs: Vec<output::OutputFile>,
+    stats: Vec<(String, Arc<ServerStats>)>,
+    stats_file: Option<Arc<Mutex<snapshot::StatsFile>>>,
+    shutdown: CancellationToken,
+    current: watch::Receiver<Arc<Config>>,
+) {
+    let config = Arc::clone(&current.borrow());
+    let mut flush_timer = tokio::time::interval(Duration::from_secs(config.flush_interval_secs));
+    let mut stats_timer = tokio::time::interval(Duration::from_secs(config.stats_interval_secs));
+    let mut fsync_timer = tokio::time::interval(Duration::from_secs(config.fsync_interval_secs));
+    // Первый тик interval срабатывает сразу
+    flush_timer.tick().await;
+    stats_timer.tick().await;
+    fsync_timer.tick().await;
+    let mut compressors = Vec::new();
+    let mut midnight = config.split_daily.map(|boundary| boundary.next_midnight(Utc::now()));
+    let midnight_timer = tokio::time::sleep_until(until(midnight));
+    tokio::pin!(midnight_timer);
+    
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = flush_timer.tick() => {
+                for output in &outputs {
+                    if config.fsync == FsyncPolicy::Flush {
+                        if let Err(e) = sync_output(&output.writer) {
+                            error!(file = %output.current().display(), error = %e, "Fsync failed");
+                        }
+                    } else if let Ok(mut w) = output.writer.lock() {
+                        let _ = w.flush();
+                    }
+                    let rotation = Arc::clone(&current.borrow());
+                    match rotation::rotate_if_needed(output, &rotation) {
+                        Ok(Some(rotated)) => {
+                            info!(file = %rotated.display(), "Rotated output");
+                            if rotation.compress_rotated {
+                                compressors.push(compress_in_background(rotated));
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!(file = %output.current().display(), error = %e, "Rotation failed"),
+                    }
+                }
+            }
+            _ = &mut midnight_timer, if midnight.is_some() => {
+                // Таймер идёт по монотонным часам; если системные ещё не дошли до полуночи, он взводится заново
+                let now = Utc::now();
+                if midnight.is_some_and(|at| now >= at) {
+                    let rotation = Arc::clone(&current.borrow());
+                    for output in &outputs {
+                        match rotation::split_day(output, &rotation, now) {
+                            Ok(Some(closed)) => {
+                                info!(file = %closed.display(), next = %output.current().display(), "Started the file for the new day");
+                                if rotation.compress_rotated {
+                                    compressors.push(compress_in_background(closed));
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => error!(file = %output.current().display(), error = %e, "Daily split failed"),
+                        }
+                    }
+                    midnight = config.split_daily.map(|boundary| boundary.next_midnight(now));
+                }
+                midnight_timer.as_mut().reset(until(midnight));
+            }
+            _ = fsync_timer.tick(), if config.fsync == FsyncPolicy::Interval => {
+                for output in &outputs {
+                    if let Err(e) = sync_output(&output.writer) {
+                        error!(file = %output.current().display(), error = %e, "Fsync failed");
+                    }
+                }
+            }
+            _ = stats_timer.tick() => {
+                for (name, s) in &stats {
+                    let latency = s.latency.summary();
+                    info!(
+                        server = %name,
+                        packets = s.packets_received.load(Ordering::Relaxed),
+                        checksum_errors = s.checksum_errors.load(Ordering::Relaxed),
+                        reconnections = s.reconnections.load(Ordering::Relaxed),
+                        sync_resets = s.sync_resets.load(Ordering::Relaxed),
+                        resyncs = s.resyncs.load(Ordering::Relaxed),
+                        rejected = s.rejected_samples.load(Ordering::Relaxed),
+                        clock_offset_ms = s.clock_offset_ms.load(Ordering::Relaxed),
+                        duplicates = s.duplicates.load(Ordering::Relaxed),
+                        out_of_order = s.out_of_order.load(Ordering::Relaxed),
+                        gaps = s.gaps.load(Ordering::Relaxed),
+                        gap_time_ms = s.gap_time_ms.load(Ordering::Relaxed),
+                        watchdog_restarts = s.watchdog_restarts.load(Ordering::Relaxed),
+                        keepalive_failures = s.keepalive_failures.load(Ordering::Relaxed),
+                        write_queue_waits = s.write_queue_waits.load(Ordering::Relaxed),
+                        write_queue_drops = s.write_queue_drops.load(Ordering::Relaxed),
+                        latency_p50_ms = latency.p50_ms,
+                        latency_p95_ms = latency.p95_ms,
+                        latency_p99_ms = latency.p99_ms,
+                        latency_max_ms = latency.max_ms,
+                        "Stats"
+                    );
+                }
+                if let Some(Ok(mut file)) = stats_file.as_ref().map(|file| file.lock()) {
+                    if let Err(e) = file.write(&stats, true) {
+                        error!(error = %e, "Writing the stats file failed");
+                    }
+                }
+                if let Some(path) = &config.state_file {
+                    if let Err(e) = state::save(path.as_ref(), &stats) {
+                        error!(error = %e, "Writing the state file failed");
+                    }
+                }
+            }
+        }
+    }
+    
+    for output in &outputs {
+        if let Ok(mut w) = output.writer.lock() {
+            let _ = w.flush();
+        }
+    }
+    for handle in compressors {
+        let _ = handle.await;
+    }
+}
+
+/// Момент tokio для времени по системным часам; без времени — далёкое будущее
+fn until(at: Option<DateTime<Utc>>) -> tokio::time::Instant {
+    let wait = at.map_or(Duration::from_secs(86400 * 365), |at| (at - Utc::now()).to_std().unwrap_or_default());
+    tokio::time::Instant::now() + wait
+}
+
+fn compress_in_background(path: std::path::PathBuf) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn_blocking(move || match rotation::compress_file(&path) {
+        Ok(gz) => info!(file = %gz.display(), "Compressed rotated file"),
+        Err(e) => error!(file = %path.display(), error = %e, "Compression failed"),
+    })
+}
+
+/// Сброс буфера и запись файла на диск
+pub fn sync_output(writer: &Mutex<BufWriter<std::fs::File>>) -> std::io::Result<()> {
+    let mut w = writer.lock().map_err(|_| std::io::Error::other("Writer lock poisoned"))?;
+    w.flush()?;
+    w.get_ref().sync_data()
+}
+
+/// Ждёт задачи (имя, handle) до общего срока; не успевшие прерываются, возвращаются их имена
+pub async fn join_with_deadline(
+    handles: Vec<(&'static str, tokio::task::JoinHandle<()>)>,
+    limit: Duration,
+) -> Vec<&'static str> {
+    let deadline = tokio::time::Instant::now() + limit;
+    let mut aborted = Vec::new();
+    for (name, mut handle) in handles {
+        match tokio::time::timeout_at(deadline, &mut handle).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!(task = name, error = %e, "Task failed"),
+            Err(_) => {
+                handle.abort();
+                aborted.push(name);
+            }
+        }
+    }
+    aborted
+}
+
+// ==================== TESTS ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    // ============ CHECKSUM TESTS ============
+    
+    #[test]
+    fn test_calculate_checksum() {
+        let data = vec![1, 2, 3, 4, 5];
+        assert_eq!(calculate_checksum(&data), 15);
+        
+        let data2 = vec![255, 255];
+        assert_eq!(calculate_checksum(&data2), (510 % 256) as u8);
+    }
+
+    #[test]
+    fn test_calculate_checksum_empty() {
+        let data: Vec<u8> = vec![];
+        assert_eq!(calculate_checksum(&data), 0);
+    }
+
+    #[test]
+    fn test_calculate_checksum_large() {
+        let data = vec![255u8; 1000];
+        let expected = ((255u32 * 1000) % 256) as u8;
+        assert_eq!(calculate_checksum(&data), expected);
+    }
+
+    #[test]
+    fn test_verify_checksum_valid() {
+        let data = vec![1, 2, 3, 4, 5];
+        let checksum = calculate_checksum(&data);
+        assert!(verify_checksum(&data, checksum));
+    }
+
+    #[test]
+    fn test_verify_checksum_invalid() {
+        let data = vec![1, 2, 3, 4, 5];
+        assert!(!verify_checksum(&data, 99));
+    }
+
+    #[test]
+    fn test_verify_checksum_empty() {
+        let data: Vec<u8> = vec![];
+        assert!(verify_checksum(&data, 0));
+    }
+
+    // ============ SENSOR DATA TESTS ============
+
+    #[test]
+    fn test_format_data_temp_pressure() {
+        let timestamp = DateTime::from_timestamp_micros(1700000000000000).unwrap();
+        let data = SensorData::TempPressure {
+            timestamp,
+            temperature: 25.5,
+            pressure: 1013,
+        };
+        
+        let formatted = format_data(&data);
+        assert!(formatted.contains("[S1]"));
+        assert!(formatted.contains("temperature=25.50C"));
+        assert!(formatted.contains("pressure=1013"));
+        assert!(formatted.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_format_data_accelerometer() {
+        let timestamp = DateTime::from_timestamp_micros(1700000000000000).unwrap();
+        let data = SensorData::Accelerometer {
+            timestamp,
+            x: 100,
+            y: -200,
+            z: 300,
+        };
+        
+        let formatted = format_data(&data);
+        assert!(formatted.contains("[S2]"));
+        assert!(formatted.contains("x=100"));
+        assert!(formatted.contains("y=-200"));
+        assert!(formatted.contains("z=300"));
+        assert!(formatted.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let timestamp = DateTime::from_timestamp_micros(1700000000000000).unwrap();
+        let temp = SensorData::TempPressure { timestamp, temperature: 25.5, pressure: 1013 };
+        let accel = SensorData::Accelerometer { timestamp, x: 100, y: -200, z: 300 };
+
+        assert_eq!(format_csv(&temp), "2023-11-14T22:13:20.000000Z,S1,25.50,1013,,,\n");
+        assert_eq!(format_csv(&accel), "2023-11-14T22:13:20.000000Z,S2,,,100,-200,300\n");
+        assert_eq!(CSV_HEADER.split(',').count(), format_csv(&temp).split(',').count());
+        assert_eq!(OutputFormat::Csv.header(), Some(CSV_HEADER.as_bytes()));
+        assert_eq!(OutputFormat::Text.header(), None);
+        assert_eq!(OutputFormat::Text.encode(&temp), format_data(&temp).into_bytes());
+    }
+
+    #[test]
+    fn test_sensor_data_clone() {
+        let timestamp = DateTime::from_timestamp_micros(1000000).unwrap();
+        let data = SensorData::TempPressure {
+            timestamp,
+            temperature: 25.5,
+            pressure: 1013,
+        };
+        
+        let cloned = data.clone();
+        match (data, cloned) {
+            (SensorData::TempPressure { temperature: t1, pressure: p1, .. }, 
+             SensorData::TempPressure { temperature: t2, pressure: p2, .. }) => {
+                assert_eq!(t1, t2);
+                assert_eq!(p1, p2);
+            }
+            _ => panic!("Clone mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_sensor_data_clone_accelerometer() {
+        let timestamp = DateTime::from_timestamp_micros(1000000).unwrap();
+        let data = SensorData::Accelerometer {
+            timestamp,
+            x: 1,
+            y: 2,
+            z: 3,
+        };
+        
+        let cloned = data.clone();
+        match (data, cloned) {
+            (SensorData::Accelerometer { x: x1, y: y1, z: z1, .. }, 
+             SensorData::Accelerometer { x: x2, y: y2, z: z2, .. }) => {
+                assert_eq!(x1, x2);
+                assert_eq!(y1, y2);
+                assert_eq!(z1, z2);
+            }
+            _ => panic!("Clone mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_sensor_data_debug() {
+        let timestamp = DateTime::from_timestamp_micros(1000000).unwrap();
+        let data = SensorData::TempPressure {
+            timestamp,
+            temperature: 25.5,
+            pressure: 1013,
+        };
+        let debug_str = format!("{:?}", data);
+        assert!(debug_str.contains("TempPressure"));
+        
+        let data2 = SensorData::Accelerometer {
+            timestamp,
+            x: 1, y: 2, z: 3,
+        };
+        let debug_str2 = format!("{:?}", data2);
+        assert!(debug_str2.contains("Accelerometer"));
+    }
+
+    // ============ SERVER STATS TESTS ============
+
+    #[test]
+    fn test_server_stats_new() {
+        let stats = ServerStats::new();
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.checksum_errors.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.timeout_errors.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.reconnections.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.sync_resets.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_server_stats_all_fields() {
+        let stats = ServerStats::new();
+        
+        stats.packets_received.fetch_add(10, Ordering::Relaxed);
+        stats.checksum_errors.fetch_add(2, Ordering::Relaxed);
+        stats.timeout_errors.fetch_add(3, Ordering::Relaxed);
+        stats.connection_errors.fetch_add(4, Ordering::Relaxed);
+        stats.reconnections.fetch_add(5, Ordering::Relaxed);
+        stats.sync_resets.fetch_add(1, Ordering::Relaxed);
+        
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 10);
+        assert_eq!(stats.checksum_errors.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.timeout_errors.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 4);
+        assert_eq!(stats.reconnections.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.sync_resets.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_server_stats_debug() {
+        let stats = ServerStats::new();
+        let debug_str = format!("{:?}", stats);
+        assert!(debug_str.contains("ServerStats"));
+    }
+
+    #[test]
+    fn test_server_stats_default() {
+        let stats: ServerStats = Default::default();
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 0);
+    }
+
+    // ============ SOCKET TESTS ============
+
+    #[tokio::test]
+    async fn test_create_optimized_socket_invalid_address() {
+        let result = create_optimized_socket("invalid_address").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_optimized_socket_connection_refused() {
+        let result = create_optimized_socket("127.0.0.1:59999").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_optimized_socket_success() {
+        let port = 19001;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            let _ = listener.accept();
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let result = create_optimized_socket(&format!("127.0.0.1:{}", port)).await;
+        assert!(result.is_ok());
+    }
+
+    // ============ DRAIN BUFFER TESTS ============
+
+    #[tokio::test]
+    async fn test_drain_input_buffer_empty() {
+        let port = 19002;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            let _ = listener.accept();
+            thread::sleep(Duration::from_millis(200));
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        let drained = drain_input_buffer(&mut stream).await;
+        assert_eq!(drained, 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_input_buffer_with_data() {
+        let port = 19003;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"Hello, World!");
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let drained = drain_input_buffer(&mut stream).await;
+        assert!(drained > 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_input_buffer_large_data() {
+        let port = 19004;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let data = vec![0u8; 3000];
+                let _ = stream.write_all(&data);
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let drained = drain_input_buffer(&mut stream).await;
+        assert!(drained > 0);
+        assert!(drained <= 2048 + 512);  // Should stop at limit
+    }
+
+    // ============ CONNECT AND AUTH TESTS ============
+
+    #[tokio::test]
+    async fn test_connect_and_auth_success() {
+        let port = 19005;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = ServerStats::new();
+        let result = connect_and_auth(&ServerConfig::new("Server1", &format!("127.0.0.1:{}", port), SensorKind::TempPressure), &stats, &Config::default()).await;
+        assert!(result.is_ok());
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_auth_connection_refused() {
+        let stats = ServerStats::new();
+        let result = connect_and_auth(&ServerConfig::new("Server1", "127.0.0.1:59998", SensorKind::TempPressure), &stats, &Config::default()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_auth_no_response() {
+        let port = 19006;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut buf);
+                // Don't send response
+                thread::sleep(Duration::from_secs(5));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = ServerStats::new();
+        let result = connect_and_auth(&ServerConfig::new("Server1", &format!("127.0.0.1:{}", port), SensorKind::TempPressure), &stats, &Config::default()).await;
+        assert!(result.is_err());
+        assert!(stats.connection_errors.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_custom_protocol() {
+        let mut mock = mock::MockConfig::new(SensorKind::Accelerometer);
+        mock.auth_key = "lab_key".to_string();
+        mock.ack = "WELCOME\n".to_string();
+        mock.request = "next\n".to_string();
+        let _server = mock::MockServer::start("127.0.0.1:19031", mock).unwrap();
+
+        let mut server = ServerConfig::new("Lab", "127.0.0.1:19031", SensorKind::Accelerometer);
+        server.protocol = ProtocolSpec {
+            auth_key: Some("lab_key".to_string()),
+            ack: Some("WELCOME".to_string()),
+            request: "next\n".to_string(),
+            keepalive: None,
+        };
+        let mut client = SensorClient::connect_with(server.clone(), Config::default(), Arc::new(ServerStats::new())).await.unwrap();
+        assert!(client.fetch().await.is_ok());
+
+        // Сервер отвечает на ключ, но не тем, что ожидается
+        server.protocol.ack = Some("AUTH_OK".to_string());
+        let stats = ServerStats::new();
+        let err = connect_and_auth(&server, &stats, &Config::default()).await.unwrap_err();
+        assert!(err.to_string().contains("WELCOME"));
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 1);
+    }
+
+    // ============ READ EXACT RELIABLE TESTS ============
+
+    #[tokio::test]
+    async fn test_read_exact_reliable_success() {
+        let port = 19007;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"Hello");
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        let mut buf = [0u8; 5];
+        let result = read_exact_reliable(&mut stream, &mut buf, Duration::from_millis(READ_TIMEOUT_MS)).await;
+        assert!(result.is_ok());
+        assert_eq!(&buf, b"Hello");
+    }
+
+    #[tokio::test]
+    async fn test_read_exact_reliable_connection_closed() {
+        let port = 19008;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((stream, _)) = listener.accept() {
+                drop(stream);  // Close immediately
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut buf = [0u8; 10];
+        let result = read_exact_reliable(&mut stream, &mut buf, Duration::from_millis(READ_TIMEOUT_MS)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_exact_reliable_partial_then_complete() {
+        let port = 19009;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"Hel");
+                thread::sleep(Duration::from_millis(50));
+                let _ = stream.write_all(b"lo");
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        let mut buf = [0u8; 5];
+        let result = read_exact_reliable(&mut stream, &mut buf, Duration::from_millis(READ_TIMEOUT_MS)).await;
+        assert!(result.is_ok());
+        assert_eq!(&buf, b"Hello");
+    }
+
+    // ============ FETCH DATA TESTS ============
+
+    fn mock_server_with_valid_data(port: u16, is_server1: bool) {
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let timestamp = DateTime::from_timestamp_micros(1700000000000000).unwrap();
+                    let data = if is_server1 {
+                        SensorData::TempPressure { timestamp, temperature: 23.5, pressure: 1013 }
+                    } else {
+                        SensorData::Accelerometer { timestamp, x: 100, y: -200, z: 300 }
+                    };
+                    let _ = stream.write_all(&mock::encode_frame(&data));
+                }
+                
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_server1_valid() {
+        let port = 19010;
+        mock_server_with_valid_data(port, true);
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let stats = ServerStats::new();
+        let result = fetch_packet(&mut stream, &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure), &stats, &Config::default()).await;
+        
+        assert!(result.is_ok());
+        if let Ok(SensorData::TempPressure { temperature, pressure, .. }) = result {
+            assert_eq!(temperature, 23.5);
+            assert_eq!(pressure, 1013);
+        }
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.latency.count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_server2_valid() {
+        let port = 19011;
+        mock_server_with_valid_data(port, false);
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let stats = ServerStats::new();
+        let result = fetch_packet(&mut stream, &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::Accelerometer), &stats, &Config::default()).await;
+        
+        assert!(result.is_ok());
+        if let Ok(SensorData::Accelerometer { x, y, z, .. }) = result {
+            assert_eq!(x, 100);
+            assert_eq!(y, -200);
+            assert_eq!(z, 300);
+        }
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_server1_checksum_error() {
+        let port = 19012;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let mut data = vec![0u8; 14];
+                    data.push(255);  // Wrong checksum
+                    let _ = stream.write_all(&data);
+                }
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let stats = ServerStats::new();
+        let result = fetch_packet(&mut stream, &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure), &stats, &Config::default()).await;
+        
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Checksum"));
+        assert_eq!(stats.checksum_errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_server2_checksum_error() {
+        let port = 19013;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let mut data = vec![0u8; 20];
+                    data.push(255);  // Wrong checksum
+                    let _ = stream.write_all(&data);
+                }
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let stats = ServerStats::new();
+        let result = fetch_packet(&mut stream, &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::Accelerometer), &stats, &Config::default()).await;
+        
+        assert!(result.is_err());
+        assert_eq!(stats.checksum_errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_server1_invalid_timestamp() {
+        let port = 19014;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let mut data = Vec::new();
+                    // Invalid timestamp (too large)
+                    data.extend_from_slice(&i64::MAX.to_be_bytes());
+                    let temperature: f32 = 23.5;
+                    let pressure: i16 = 1013;
+                    data.extend_from_slice(&temperature.to_be_bytes());
+                    data.extend_from_slice(&pressure.to_be_bytes());
+                    let checksum = calculate_checksum(&data);
+                    data.push(checksum);
+                    let _ = stream.write_all(&data);
+                }
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let stats = ServerStats::new();
+        let result = fetch_packet(&mut stream, &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure), &stats, &Config::default()).await;
+        
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("timestamp"));
+    }
+
+    // ============ DATA COLLECTION LOOP TESTS ============
+
+    #[tokio::test]
+    async fn test_data_collection_graceful_stop() {
+        let port = 19015;
+        let shutdown = CancellationToken::new();
+        
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let timestamp: u64 = 1700000000000000;
+                    let temperature: f32 = 22.5;
+                    let pressure: i16 = 1010;
+                    
+                    let mut data = Vec::new();
+                    data.extend_from_slice(&timestamp.to_be_bytes());
+                    data.extend_from_slice(&temperature.to_be_bytes());
+                    data.extend_from_slice(&pressure.to_be_bytes());
+                    let checksum = calculate_checksum(&data);
+                    data.push(checksum);
+                    
+                    let _ = stream.write_all(&data);
+                }
+                
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            shutdown_clone.cancel();
+        });
+        
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure),
+            &mut FileSink::new(writer, &Config::default()),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(result.is_ok());
+        assert!(stats.packets_received.load(Ordering::Relaxed) >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_loop_server2() {
+        let port = 19016;
+        let shutdown = CancellationToken::new();
+        
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let timestamp: u64 = 1700000000000000;
+                    let x: i32 = 100;
+                    let y: i32 = 200;
+                    let z: i32 = 300;
+                    
+                    let mut data = Vec::new();
+                    data.extend_from_slice(&timestamp.to_be_bytes());
+                    data.extend_from_slice(&x.to_be_bytes());
+                    data.extend_from_slice(&y.to_be_bytes());
+                    data.extend_from_slice(&z.to_be_bytes());
+                    let checksum = calculate_checksum(&data);
+                    data.push(checksum);
+                    
+                    let _ = stream.write_all(&data);
+                }
+                
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            shutdown_clone.cancel();
+        });
+        
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::Accelerometer),
+            &mut FileSink::new(writer, &Config::default()),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(result.is_ok());
+        assert!(stats.packets_received.load(Ordering::Relaxed) >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_checksum_desync() {
+        let port = 19017;
+        let shutdown = CancellationToken::new();
+        
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    // Send data with wrong checksum
+                    let mut data = vec![0u8; 14];
+                    data.push(255);
+                    let _ = stream.write_all(&data);
+                }
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure),
+            &mut FileSink::new(writer, &Config::default()),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("desync"));
+        assert_eq!(stats.sync_resets.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_repeated_frame_stalls() {
+        let port = 19039;
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                // На каждый запрос один и тот же кадр
+                let timestamp = DateTime::from_timestamp_micros(1700000000000000).unwrap();
+                let frame = mock::encode_frame(&SensorData::TempPressure { timestamp, temperature: 23.5, pressure: 1013 });
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                while stream.read_exact(&mut cmd_buf).is_ok() && stream.write_all(&frame).is_ok() {}
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = Arc::new(ServerStats::new());
+        let server = ServerConfig {
+            poll_interval_ms: Some(50),
+            ..ServerConfig::new("TestServer", &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        };
+        let config = Config::default();
+        let mut connection = Connection::Tcp(connect_and_auth(&server, &stats, &config).await.unwrap());
+        let mut sequence = sequence::SequenceTracker::new(config.gap_threshold());
+        let shutdown = CancellationToken::new();
+        let mut sink = FanOut::new();
+        let collection = data_collection_loop(&mut connection, &server, &mut sink, Some(&mut sequence), &stats, &shutdown, &config);
+        
+        let result = tokio::time::timeout(Duration::from_secs(STALL_TIMEOUT_SECS + 5), collection).await;
+        assert_eq!(result.expect("repeated frames must not keep the connection alive").unwrap_err().to_string(), "Stalled");
+        assert!(stats.duplicates.load(Ordering::Relaxed) > 10);
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), stats.duplicates.load(Ordering::Relaxed) + 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_counts_read_timeouts() {
+        let port = 19041;
+        // Сервер принимает запросы, но не отвечает на них
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                while stream.read_exact(&mut cmd_buf).is_ok() {}
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = Arc::new(ServerStats::new());
+        let server = ServerConfig {
+            poll_interval_ms: Some(10),
+            ..ServerConfig::new("TestServer", &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        };
+        let config = Config { read_timeout_ms: 50, ..Config::default() };
+        let mut connection = Connection::Tcp(connect_and_auth(&server, &stats, &config).await.unwrap());
+        let result = data_collection_loop(
+            &mut connection,
+            &server,
+            &mut FanOut::new(),
+            Some(&mut sequence::SequenceTracker::new(config.gap_threshold())),
+            &stats,
+            &CancellationToken::new(),
+            &config,
+        ).await;
+        
+        assert!(result.unwrap_err().to_string().starts_with("Too many errors"));
+        assert_eq!(stats.timeout_errors.load(Ordering::Relaxed), MAX_CONSECUTIVE_ERRORS as u64);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_drops_implausible_samples() {
+        let port = 19027;
+        mock_server_with_valid_data(port, true);
+        
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        let server = ServerConfig {
+            validation: Some(validation::ValidationConfig { temperature: Some([30.0, 40.0]), ..Default::default() }),
+            ..ServerConfig::new("TestServer", &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        };
+        let stream = connect_and_auth(&server, &stats, &Config::default()).await.unwrap();
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            shutdown_clone.cancel();
+        });
+        
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &server,
+            &mut FileSink::new(writer.clone(), &Config::default()),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(result.is_ok());
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.rejected_samples.load(Ordering::Relaxed), 1);
+        writer.lock().unwrap().flush().unwrap();
+        assert_eq!(temp_file.as_file().metadata().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_respects_poll_interval() {
+        let mock = mock::MockServer::start("127.0.0.1:19030", mock::MockConfig::new(SensorKind::TempPressure)).unwrap();
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        let server = ServerConfig {
+            poll_interval_ms: Some(100),
+            ..ServerConfig::new("TestServer", "127.0.0.1:19030", SensorKind::TempPressure)
+        };
+        let stream = connect_and_auth(&server, &stats, &Config::default()).await.unwrap();
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(450));
+            shutdown_clone.cancel();
+        });
+        
+        let started = Instant::now();
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &server,
+            &mut FanOut::new(),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        // Запросы в 0, 100, 200, 300 и 400 мс; отмена прерывает ожидание пятого периода
+        assert!(result.is_ok());
+        assert!(started.elapsed() < Duration::from_millis(550));
+        let packets = stats.packets_received.load(Ordering::Relaxed);
+        assert!((4..=5).contains(&packets), "{} packets", packets);
+        mock.stop();
+    }
+
+    #[tokio::test]
+    async fn test_worker_with_parallel_connections() {
+        let mock = mock::MockServer::start("127.0.0.1:19033", mock::MockConfig::new(SensorKind::Accelerometer)).unwrap();
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        let server = ServerConfig {
+            poll_interval_ms: Some(100),
+            connections: Some(3),
+            ..ServerConfig::new("TestServer", "127.0.0.1:19033", SensorKind::Accelerometer)
+        };
+        let config = Config::default();
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(450));
+            shutdown_clone.cancel();
+        });
+        worker_task(&server, FileSink::new(writer, &config), stats.clone(), shutdown, &config).await;
+        
+        // Каждое из трёх соединений успевает 4-5 запросов; все отсчёты в одном приёмнике
+        let packets = stats.packets_received.load(Ordering::Relaxed);
+        assert!((10..=15).contains(&packets), "{} packets", packets);
+        assert_eq!(stats.out_of_order.load(Ordering::Relaxed), 0);
+        let lines = std::fs::read_to_string(temp_file.path()).unwrap().lines().count() as u64;
+        assert_eq!(lines, packets);
+        mock.stop();
+    }
+
+    #[tokio::test]
+    async fn test_parallel_connections_share_sequence() {
+        let port = 19040;
+        let frames = 20u64;
+        // Соединения делят последовательность 1..=frames с шагом 1,5 с (меньше порога пропуска, но
+        // каждое соединение по отдельности видит пропуски): каждый запрос получает следующий кадр,
+        // после последнего оба соединения повторяют его
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            let served = Arc::new(AtomicU64::new(0));
+            for stream in listener.incoming().take(2) {
+                let (mut stream, served) = (stream.unwrap(), served.clone());
+                thread::spawn(move || {
+                    let mut auth_buf = vec![0u8; KEY.len()];
+                    let _ = stream.read_exact(&mut auth_buf);
+                    let _ = stream.write_all(b"AUTH_OK\n");
+                    let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                    while stream.read_exact(&mut cmd_buf).is_ok() {
+                        let n = (served.fetch_add(1, Ordering::Relaxed) + 1).min(frames);
+                        let timestamp = DateTime::from_timestamp_micros(1700000000000000 + n as i64 * 1_500_000).unwrap();
+                        let sample = mock::synthetic_sample(SensorKind::Accelerometer, n, timestamp);
+                        if stream.write_all(&mock::encode_frame(&sample)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        let server = ServerConfig {
+            poll_interval_ms: Some(40),
+            connections: Some(2),
+            ..ServerConfig::new("TestServer", &format!("127.0.0.1:{}", port), SensorKind::Accelerometer)
+        };
+        let config = Config::default();
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(600));
+            shutdown_clone.cancel();
+        });
+        worker_task(&server, FileSink::new(writer, &config), stats.clone(), shutdown, &config).await;
+        
+        let packets = stats.packets_received.load(Ordering::Relaxed);
+        assert!(packets > frames, "{} packets", packets);
+        assert_eq!(stats.gaps.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.out_of_order.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.duplicates.load(Ordering::Relaxed), packets - frames);
+        let output = std::fs::read_to_string(temp_file.path()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len() as u64, frames);
+        assert_eq!(lines.iter().collect::<std::collections::HashSet<_>>().len(), lines.len());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_detects_silent_connection() {
+        let port = 19034;
+        
+        // Сервер отвечает на один запрос и одну проверку, потом молчит, не закрывая соединение
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    let sample = mock::synthetic_sample(SensorKind::TempPressure, 0, Utc::now());
+                    let _ = stream.write_all(&mock::encode_frame(&sample));
+                }
+                let mut ping = [0u8; 5];
+                if stream.read_exact(&mut ping).is_ok() && &ping == b"ping\n" {
+                    let _ = stream.write_all(b"PONG\n");
+                }
+                thread::sleep(Duration::from_secs(2));
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        let mut server = ServerConfig {
+            poll_interval_ms: Some(10_000),
+            ..ServerConfig::new("TestServer", &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        };
+        server.protocol.keepalive = Some(KeepaliveSpec {
+            interval_ms: 100,
+            command: Some("ping\n".to_string()),
+            response: Some("PONG\n".to_string()),
+        });
+        let config = Config { read_timeout_ms: 200, ..Config::default() };
+        let stream = connect_and_auth(&server, &stats, &config).await.unwrap();
+        
+        let started = Instant::now();
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &server,
+            &mut FanOut::new(),
+            Some(&mut sequence::SequenceTracker::new(config.gap_threshold())),
+            &stats,
+            &shutdown,
+            &config,
+        ).await;
+        
+        // Первая проверка проходит, вторая не получает ответа за read_timeout_ms
+        assert!(result.unwrap_err().to_string().contains("Keepalive failed"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.keepalive_failures.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_resyncs_after_shifted_frame() {
+        let port = 19021;
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                let mut cmd_buf = vec![0u8; GET_CMD.len()];
+                if stream.read_exact(&mut cmd_buf).is_ok() {
+                    // Три лишних байта перед кадром сдвигают окно чтения
+                    let mut data = vec![0xAA, 0xBB, 0xCC];
+                    let mut frame = Vec::new();
+                    frame.extend_from_slice(&1700000000000000u64.to_be_bytes());
+                    frame.extend_from_slice(&19.5f32.to_be_bytes());
+                    frame.extend_from_slice(&990i16.to_be_bytes());
+                    frame.push(calculate_checksum(&frame));
+                    data.extend_from_slice(&frame);
+                    let _ = stream.write_all(&data);
+                }
+                thread::sleep(Duration::from_millis(500));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 8];
+        stream.read_exact(&mut auth_buf).await.unwrap();
+        
+        let stats = ServerStats::new();
+        let server = ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure);
+        match fetch_packet(&mut stream, &server, &stats, &Config::default()).await {
+            Ok(SensorData::TempPressure { temperature, pressure, .. }) => {
+                assert_eq!(temperature, 19.5);
+                assert_eq!(pressure, 990);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(stats.checksum_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.resyncs.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_consecutive_errors() {
+        let port = 19018;
+        let shutdown = CancellationToken::new();
+        
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            if let Ok((mut stream, _)) = listener.accept() {
+                stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+                
+                let mut auth_buf = vec![0u8; KEY.len()];
+                let _ = stream.read_exact(&mut auth_buf);
+                let _ = stream.write_all(b"AUTH_OK\n");
+                
+                // Don't send data - will cause consecutive timeouts
+                thread::sleep(Duration::from_secs(10));
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await.unwrap();
+        
+        stream.write_all(KEY).await.unwrap();
+        let mut auth_buf = [0u8; 16];
+        assert!(stream.read(&mut auth_buf).await.unwrap() > 0);
+        
+        let result = data_collection_loop(
+            &mut Connection::Tcp(stream),
+            &ServerConfig::new("TestServer", "127.0.0.1:0", SensorKind::TempPressure),
+            &mut FileSink::new(writer, &Config::default()),
+            Some(&mut sequence::SequenceTracker::new(Config::default().gap_threshold())),
+            &stats,
+            &shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(result.is_err());
+    }
+
+    // ============ WORKER TASK TESTS ============
+
+    #[tokio::test]
+    async fn test_worker_task_connection_refused() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            shutdown_clone.cancel();
+        });
+        
+        worker_task(
+            &ServerConfig::new("Server1", "127.0.0.1:59997", SensorKind::TempPressure),
+            FileSink::new(writer, &Config::default()),
+            stats.clone(),
+            shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(stats.connection_errors.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_task_with_reconnect() {
+        let port = 19019;
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        
+        thread::spawn(move || {
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap();
+            for _ in 0..2 {
+                if let Ok((mut stream, _)) = listener.accept() {
+                    let mut buf = vec![0u8; KEY.len()];
+                    let _ = stream.read_exact(&mut buf);
+                    let _ = stream.write_all(b"AUTH_OK\n");
+                    thread::sleep(Duration::from_millis(50));
+                    drop(stream);
+                }
+            }
+        });
+        
+        thread::sleep(Duration::from_millis(50));
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            shutdown_clone.cancel();
+        });
+        
+        worker_task(
+            &ServerConfig::new("Server1", &format!("127.0.0.1:{}", port), SensorKind::TempPressure),
+            FileSink::new(writer, &Config::default()),
+            stats.clone(),
+            shutdown,
+            &Config::default(),
+        ).await;
+        
+        assert!(stats.reconnections.load(Ordering::Relaxed) > 0);
+    }
+
+    // ============ STATS AND FLUSH TASK TESTS ============
+
+    #[tokio::test]
+    async fn test_stats_and_flush_task() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats1 = Arc::new(ServerStats::new());
+        let stats2 = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+        
+        stats1.packets_received.store(100, Ordering::Relaxed);
+        stats2.packets_received.store(200, Ordering::Relaxed);
+        
+        {
+            let mut w = writer.lock().unwrap();
+            w.write_all(b"test data\n").unwrap();
+        }
+        
+        let shutdown_clone = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(600));
+            shutdown_clone.cancel();
+        });
+        
+        let dir = tempfile::tempdir().unwrap();
+        let stats_path = dir.path().join("stats.json");
+        let stats_file = Arc::new(Mutex::new(snapshot::StatsFile::new(&stats_path)));
+        let output = output::OutputFile {
+            template: temp_file.path().to_path_buf(),
+            path: Arc::new(Mutex::new(temp_file.path().to_path_buf())),
+            writer: writer.clone(),
+        };
+        stats_and_flush_task(
+            vec![output],
+            vec![("Server1".to_string(), stats1), ("Server2".to_string(), stats2)],
+            Some(stats_file),
+            shutdown,
+            watch::channel(Arc::new(Config::default())).1,
+        ).await;
+        
+        // Verify file was flushed
+        let metadata = temp_file.as_file().metadata().unwrap();
+        assert!(metadata.len() > 0);
+        
+        // Интервал статистики не истёк, а последний снимок пишет вызывающий
+        assert!(!stats_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_final_stats_snapshot_after_aborted_task() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = vec![("Server1".to_string(), Arc::new(ServerStats::new()))];
+        let dir = tempfile::tempdir().unwrap();
+        let stats_path = dir.path().join("stats.json");
+        let stats_file = Arc::new(Mutex::new(snapshot::StatsFile::new(&stats_path)));
+        let config = Config { stats_interval_secs: 1, ..Config::default() };
+        let output = output::OutputFile {
+            template: temp_file.path().to_path_buf(),
+            path: Arc::new(Mutex::new(temp_file.path().to_path_buf())),
+            writer,
+        };
+        
+        // Задача статистики не дожидается отмены и прерывается по сроку остановки
+        let task = tokio::spawn({
+            let (stats, stats_file) = (stats.clone(), stats_file.clone());
+            async move {
+                stats_and_flush_task(vec![output], stats, Some(stats_file), CancellationToken::new(), watch::channel(Arc::new(config)).1).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        let running: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+        assert_eq!(running["running"], true);
+        stats[0].1.packets_received.store(42, Ordering::Relaxed);
+        assert_eq!(join_with_deadline(vec![("stats", task)], Duration::from_millis(50)).await, vec!["stats"]);
+        
+        // Как в main после остановки задач
+        stats_file.lock().unwrap().write(&stats, false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+        assert_eq!(json["running"], false);
+        assert_eq!(json["servers"]["Server1"]["packets_received"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_periodic_fsync() {
+        // Для interval сброс буфера реже срока fsync: данные попадают в файл только через fsync
+        for (fsync, flush_interval_secs) in [(FsyncPolicy::Flush, 1), (FsyncPolicy::Interval, 60)] {
+            let temp_file = NamedTempFile::new().unwrap();
+            let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+            writer.lock().unwrap().write_all(b"test data\n").unwrap();
+            let shutdown = CancellationToken::new();
+            let config = Config { fsync, flush_interval_secs, fsync_interval_secs: 1, ..Config::default() };
+            
+            let output = output::OutputFile {
+            template: temp_file.path().to_path_buf(),
+            path: Arc::new(Mutex::new(temp_file.path().to_path_buf())),
+            writer: writer.clone(),
+        };
+            let task = tokio::spawn({
+                let shutdown = shutdown.clone();
+                async move { stats_and_flush_task(vec![output], Vec::new(), None, shutdown, watch::channel(Arc::new(config)).1).await }
+            });
+            // Данные на диске ещё до остановки
+            tokio::time::sleep(Duration::from_millis(1300)).await;
+            assert_eq!(temp_file.as_file().metadata().unwrap().len(), 10, "{:?}", fsync);
+            shutdown.cancel();
+            task.await.unwrap();
+        }
+    }
+
+    // ============ SHUTDOWN TESTS ============
+
+    #[tokio::test]
+    async fn test_join_with_deadline_aborts_stuck_tasks() {
+        let quick = tokio::spawn(async {});
+        let stuck = tokio::spawn(tokio::time::sleep(Duration::from_secs(60)));
+        let started = Instant::now();
+        let aborted = join_with_deadline(vec![("quick", quick), ("stuck", stuck)], Duration::from_millis(100)).await;
+        assert_eq!(aborted, ["stuck"]);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let quick = tokio::spawn(async {});
+        assert!(join_with_deadline(vec![("quick", quick)], Duration::from_millis(100)).await.is_empty());
+    }
+
+    #[test]
+    fn test_sync_output() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Mutex::new(BufWriter::new(temp_file.reopen().unwrap()));
+        writer.lock().unwrap().write_all(b"pending\n").unwrap();
+        sync_output(&writer).unwrap();
+        assert_eq!(std::fs::read_to_string(temp_file.path()).unwrap(), "pending\n");
+    }
+
+    // ============ FILE WRITING TESTS ============
+
+    #[test]
+    fn test_file_writing() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        
+        let test_data = "2024-01-01 12:00:00 [S1] temperature=25.00C pressure=1013\n";
+        {
+            let mut w = writer.lock().unwrap();
+            w.write_all(test_data.as_bytes()).unwrap();
+            w.flush().unwrap();
+        }
+        
+        let metadata = temp_file.as_file().metadata().unwrap();
+        assert!(metadata.len() > 0);
+    }
+
+    #[test]
+    fn test_file_writing_multiple() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        
+        for i in 0..10 {
+            let line = format!("Line {}\n", i);
+            let mut w = writer.lock().unwrap();
+            w.write_all(line.as_bytes()).unwrap();
+        }
+        
+        {
+            let mut w = writer.lock().unwrap();
+            w.flush().unwrap();
+        }
+        
+        let metadata = temp_file.as_file().metadata().unwrap();
+        assert!(metadata.len() > 50);
+    }
+
+    // ============ ATOMIC OPERATIONS TESTS ============
+
+    #[test]
+    fn test_atomic_operations() {
+        let running = AtomicBool::new(true);
+        assert!(running.load(Ordering::SeqCst));
+        
+        running.store(false, Ordering::SeqCst);
+        assert!(!running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_atomic_u64_operations() {
+        let counter = AtomicU64::new(0);
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+        
+        counter.fetch_add(5, Ordering::Relaxed);
+        assert_eq!(counter.load(Ordering::Relaxed), 5);
+        
+        counter.store(100, Ordering::Relaxed);
+        assert_eq!(counter.load(Ordering::Relaxed), 100);
+    }
+
+    // ============ EDGE CASES ============
+
+    #[test]
+    fn test_extreme_temperature_values() {
+        let timestamp = DateTime::from_timestamp_micros(1000000).unwrap();
+        
+        let cold = SensorData::TempPressure {
+            timestamp,
+            temperature: -273.15,
+            pressure: 0,
+        };
+        let formatted = format_data(&cold);
+        assert!(formatted.contains("-273.15"));
+        
+        let hot = SensorData::TempPressure {
+            timestamp,
+            temperature: 1000.0,
+            pressure: i16::MAX,
+        };
+        let formatted = format_data(&hot);
+        assert!(formatted.contains("1000.00"));
+    }
+
+    #[test]
+    fn test_extreme_accelerometer_values() {
+        let timestamp = DateTime::from_timestamp_micros(1000000).unwrap();
+        
+        let data = SensorData::Accelerometer {
+            timestamp,
+            x: i32::MAX,
+            y: i32::MIN,
+            z: 0,
+        };
+        let formatted = format_data(&data);
+        assert!(formatted.contains(&i32::MAX.to_string()));
+        assert!(formatted.contains(&i32::MIN.to_string()));
+    }
+
+    // ============ PACKET PARSING AND CLIENT TESTS ============
+
+    #[test]
+    fn test_parse_packet() {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&1700000000000000u64.to_be_bytes());
+        packet.extend_from_slice(&21.25f32.to_be_bytes());
+        packet.extend_from_slice(&1005i16.to_be_bytes());
+        packet.push(calculate_checksum(&packet));
+
+        match parse_packet(SensorKind::TempPressure, &packet) {
+            Ok(SensorData::TempPressure { temperature, pressure, .. }) => {
+                assert_eq!(temperature, 21.25);
+                assert_eq!(pressure, 1005);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let err = parse_packet(SensorKind::Accelerometer, &packet).unwrap_err();
+        assert!(err.to_string().contains("packet size"));
+
+        let last = packet.len() - 1;
+        packet[last] = packet[last].wrapping_add(1);
+        let err = parse_packet(SensorKind::TempPressure, &packet).unwrap_err();
+        assert!(err.to_string().contains("Checksum"));
+    }
+
+    #[test]
+    fn test_parse_with_custom_schema() {
+        use crate::schema::{Endian, FieldSpec, FieldType};
+
+        // Little-endian акселерометр с 16-битными осями и временем в конце кадра
+        let little = |name: &str, offset, ty| FieldSpec { endian: Endian::Little, ..FieldSpec::new(name, offset, ty) };
+        let schema = PacketSchema::new(vec![
+            little("x", 0, FieldType::I16),
+            little("y", 2, FieldType::I16),
+            little("z", 4, FieldType::I16),
+            little("timestamp", 6, FieldType::I64),
+        ]);
+        let mut packet = Vec::new();
+        for v in [-1i16, 2, 300] {
+            packet.extend_from_slice(&v.to_le_bytes());
+        }
+        packet.extend_from_slice(&1700000000000000i64.to_le_bytes());
+        packet.push(calculate_checksum(&packet));
+
+        match parse_with_schema(SensorKind::Accelerometer, &schema, &packet) {
+            Ok(SensorData::Accelerometer { timestamp, x, y, z }) => {
+                assert_eq!((x, y, z), (-1, 2, 300));
+                assert_eq!(timestamp.timestamp(), 1700000000);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let err = parse_with_schema(SensorKind::TempPressure, &schema, &packet).unwrap_err();
+        assert!(err.to_string().contains("Missing field 'temperature'"));
+    }
+
+    #[tokio::test]
+    async fn test_sensor_client_fetch() {
+        let port = 19020;
+        mock_server_with_valid_data(port, false);
+
+        let mut client = SensorClient::connect(&format!("127.0.0.1:{}", port), SensorKind::Accelerometer).await.unwrap();
+        assert_eq!(client.kind(), SensorKind::Accelerometer);
+
+        match client.fetch().await {
+            Ok(SensorData::Accelerometer { x, y, z, .. }) => {
+                assert_eq!((x, y, z), (100, -200, 300));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(client.stats().packets_received.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sensor_client_connection_refused() {
+        let result = SensorClient::connect("127.0.0.1:59996", SensorKind::TempPressure).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Deserialize;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::filter::{filter_fn, FilterExt};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+use crate::Config;
+
+/// Цель сетевых событий (сбои подключения, ошибки контрольной суммы, resync, пропуски).
+/// С event_log они пишутся только в этот файл
+pub const EVENTS: &str = "network_logger::events";
+
+/// Замена фильтра основного лога на работающем subscriber (log_level при перечитывании конфигурации)
+pub type LogHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Формат диагностических сообщений
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Проверка строки уровня: "info", "debug", "network_logger=trace,warn" и т.п.
+pub fn parse_filter(level: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(level).map_err(|e| format!("log_level: {}", e))
+}
+
+fn open_append(path: &str, field: &str) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: cannot open {}: {}", field, path, e))
+}
+
+/// Subscriber по настройкам: основной лог и, с event_log, файл сетевых событий в JSON
+/// по строке на событие, со span worker (имя сервера) и connection
+pub fn subscriber(
+    config: &Config,
+) -> Result<(Box<dyn Subscriber + Send + Sync>, LogHandle), Box<dyn std::error::Error + Send + Sync>> {
+    let (filter, handle) = reload::Layer::new(parse_filter(&config.log_level)?);
+    let writer = match &config.log_file {
+        Some(path) => BoxMakeWriter::new(Mutex::new(open_append(path, "log_file")?)),
+        None => BoxMakeWriter::new(io::stdout),
+    };
+    let main = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_ansi(config.log_file.is_none())
+        .with_writer(writer);
+    let main = match config.log_format {
+        LogFormat::Text => main.boxed(),
+        LogFormat::Json => main.json().boxed(),
+    };
+    let separate = config.event_log.is_some();
+    let main = main.with_filter(filter.and(filter_fn(move |meta| !separate || meta.target() != EVENTS)));
+
+    let events = match &config.event_log {
+        Some(path) => Some(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(false)
+                .with_writer(Mutex::new(open_append(path, "event_log")?))
+                .with_filter(filter_fn(|meta| meta.is_span() || meta.target() == EVENTS)),
+        ),
+        None => None,
+    };
+    Ok((Box::new(tracing_subscriber::registry().with(main).with(events)), handle))
+}
+
+/// Устанавливает глобальный subscriber; повторный вызов возвращает ошибку.
+/// С log_file сообщения дописываются в файл без цветовых кодов
+pub fn init(config: &Config) -> Result<LogHandle, Box<dyn std::error::Error + Send + Sync>> {
+    let (subscriber, handle) = subscriber(config)?;
+    subscriber.try_init()?;
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_events_go_to_event_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("logger.log");
+        let events = dir.path().join("events.log");
+        let config = Config {
+            log_file: Some(log.to_string_lossy().into_owned()),
+            event_log: Some(events.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+
+        let (subscriber, handle) = subscriber(&config).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            let _worker = tracing::info_span!("worker", server = "Server1").entered();
+            tracing::info!("Worker started");
+            tracing::error!(target: EVENTS, error = "refused", "Connect failed");
+            // Отладочные события попадают в файл событий и при log_level = info
+            tracing::debug!(target: EVENTS, error = "timeout", "Fetch failed");
+            tracing::debug!("Before reload");
+            handle.reload(parse_filter("debug").unwrap()).unwrap();
+            tracing::debug!("After reload");
+        });
+
+        let main = fs::read_to_string(&log).unwrap();
+        assert!(main.contains("Worker started"));
+        assert!(!main.contains("Connect failed"));
+        assert!(!main.contains("Before reload"));
+        assert!(main.contains("After reload"));
+        let lines: Vec<serde_json::Value> =
+            fs::read_to_string(&events).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "ERROR");
+        assert_eq!(lines[0]["fields"]["message"], "Connect failed");
+        assert_eq!(lines[0]["fields"]["error"], "refused");
+        assert_eq!(lines[0]["spans"][0]["server"], "Server1");
+        assert!(lines[0]["timestamp"].is_string());
+        assert_eq!(lines[1]["fields"]["message"], "Fetch failed");
+    }
+}
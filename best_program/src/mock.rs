@@ -0,0 +1,271 @@
+use chrono::{DateTime, Utc};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::{calculate_checksum, SensorData, SensorKind, GET_CMD, KEY};
+
+/// Ответ на верный ключ по умолчанию
+pub const DEFAULT_ACK: &str = "AUTH_OK\n";
+
+/// Как часто потоки сервера проверяют флаг остановки
+const POLL_MS: u64 = 100;
+
+/// Ошибки, вносимые в поток: Some(n) — каждый n-й кадр соединения, Some(0) — никогда
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    /// Кадр с неверной контрольной суммой
+    pub bad_checksum_every: Option<u32>,
+    /// Пауза перед ответом длиной stall
+    pub stall_every: Option<u32>,
+    pub stall: Duration,
+    /// Разрыв соединения вместо ответа
+    pub disconnect_every: Option<u32>,
+}
+
+impl Faults {
+    fn hit(every: Option<u32>, frame: u64) -> bool {
+        matches!(every, Some(n) if frame.is_multiple_of(n as u64))
+    }
+}
+
+/// Что отдаёт сервер-имитатор
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    pub kind: SensorKind,
+    pub auth_key: String,
+    /// Ответ на верный ключ
+    pub ack: String,
+    /// Команда, на которую отдаётся кадр
+    pub request: String,
+    pub faults: Faults,
+}
+
+impl MockConfig {
+    pub fn new(kind: SensorKind) -> Self {
+        MockConfig {
+            kind,
+            auth_key: String::from_utf8_lossy(KEY).into_owned(),
+            ack: DEFAULT_ACK.to_string(),
+            request: String::from_utf8_lossy(GET_CMD).into_owned(),
+            faults: Faults::default(),
+        }
+    }
+}
+
+/// Плавно меняющиеся показания для n-го кадра
+pub fn synthetic_sample(kind: SensorKind, n: u64, timestamp: DateTime<Utc>) -> SensorData {
+    let phase = n as f64 / 50.0;
+    match kind {
+        SensorKind::TempPressure => SensorData::TempPressure {
+            timestamp,
+            temperature: (21.0 + 3.0 * phase.sin()) as f32,
+            pressure: (1013.0 + 8.0 * (phase / 3.0).cos()) as i16,
+        },
+        SensorKind::Accelerometer => SensorData::Accelerometer {
+            timestamp,
+            x: (1000.0 * phase.sin()) as i32,
+            y: (1000.0 * phase.cos()) as i32,
+            z: 9810 + (50.0 * (phase * 7.0).sin()) as i32,
+        },
+    }
+}
+
+/// Кадр в раскладке по умолчанию: поля big-endian, последний байт — контрольная сумма
+pub fn encode_frame(data: &SensorData) -> Vec<u8> {
+    let mut frame = (data.timestamp().timestamp_micros() as u64).to_be_bytes().to_vec();
+    match *data {
+        SensorData::TempPressure { temperature, pressure, .. } => {
+            frame.extend_from_slice(&temperature.to_be_bytes());
+            frame.extend_from_slice(&pressure.to_be_bytes());
+        }
+        SensorData::Accelerometer { x, y, z, .. } => {
+            for axis in [x, y, z] {
+                frame.extend_from_slice(&axis.to_be_bytes());
+            }
+        }
+    }
+    frame.push(calculate_checksum(&frame));
+    frame
+}
+
+/// Сервер-имитатор: проверка ключа и ответ синтетическим кадром на каждую команду запроса.
+/// Каждое соединение обслуживается своим потоком
+pub struct MockServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start(addr: &str, config: MockConfig) -> io::Result<Self> {
+        if config.request.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "request command must not be empty"));
+        }
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let config = Arc::new(config);
+
+        let stopping = stop.clone();
+        let handle = thread::spawn(move || {
+            while !stopping.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        let config = config.clone();
+                        let stopping = stopping.clone();
+                        thread::spawn(move || {
+                            info!(%peer, "Client connected");
+                            match serve(stream, &config, &stopping) {
+                                Ok(()) => info!(%peer, "Client disconnected"),
+                                Err(e) => warn!(%peer, error = %e, "Connection failed"),
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(10)),
+                    Err(e) => warn!(error = %e, "Accept failed"),
+                }
+            }
+        });
+        Ok(MockServer { addr, stop, handle: Some(handle) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Блокирует до остановки сервера
+    pub fn wait(mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Перестаёт принимать соединения; открытые закрываются в течение POLL_MS
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Читает buf целиком; false — клиент закрыл соединение или сервер останавливается
+fn read_full(stream: &mut TcpStream, buf: &mut [u8], stop: &AtomicBool) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        match stream.read(&mut buf[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => filled += n,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Пауза, прерываемая остановкой сервера
+fn stall(duration: Duration, stop: &AtomicBool) {
+    let step = Duration::from_millis(POLL_MS);
+    let mut left = duration;
+    while !left.is_zero() && !stop.load(Ordering::Relaxed) {
+        let nap = left.min(step);
+        thread::sleep(nap);
+        left -= nap;
+    }
+}
+
+fn serve(mut stream: TcpStream, config: &MockConfig, stop: &AtomicBool) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_millis(POLL_MS)))?;
+
+    let mut key = vec![0u8; config.auth_key.len()];
+    if !read_full(&mut stream, &mut key, stop)? {
+        return Ok(());
+    }
+    if key != config.auth_key.as_bytes() {
+        stream.write_all(b"AUTH_FAIL\n")?;
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "wrong auth key"));
+    }
+    stream.write_all(config.ack.as_bytes())?;
+
+    let faults = &config.faults;
+    let mut command = vec![0u8; config.request.len()];
+    let mut frames = 0u64;
+    while read_full(&mut stream, &mut command, stop)? {
+        if command != config.request.as_bytes() {
+            debug!(?command, "Unknown command ignored");
+            continue;
+        }
+        frames += 1;
+        if Faults::hit(faults.disconnect_every, frames) {
+            debug!(frames, "Injected disconnect");
+            return Ok(());
+        }
+        if Faults::hit(faults.stall_every, frames) {
+            debug!(frames, stall_ms = faults.stall.as_millis() as u64, "Injected stall");
+            stall(faults.stall, stop);
+        }
+        let mut frame = encode_frame(&synthetic_sample(config.kind, frames, Utc::now()));
+        if Faults::hit(faults.bad_checksum_every, frames) {
+            debug!(frames, "Injected bad checksum");
+            if let Some(checksum) = frame.last_mut() {
+                *checksum = checksum.wrapping_add(1);
+            }
+        }
+        stream.write_all(&frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_packet, SensorClient};
+
+    #[test]
+    fn test_encode_frame_round_trip() {
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        for kind in [SensorKind::TempPressure, SensorKind::Accelerometer] {
+            let data = synthetic_sample(kind, 7, timestamp);
+            let frame = encode_frame(&data);
+            assert_eq!(frame.len(), kind.packet_size());
+            assert_eq!(format!("{:?}", parse_packet(kind, &frame).unwrap()), format!("{:?}", data));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_injects_faults() {
+        let mut config = MockConfig::new(SensorKind::TempPressure);
+        config.faults = Faults { bad_checksum_every: Some(2), disconnect_every: Some(4), ..Faults::default() };
+        let server = MockServer::start("127.0.0.1:19028", config).unwrap();
+
+        let address = server.local_addr().to_string();
+        let mut client = SensorClient::connect(&address, SensorKind::TempPressure).await.unwrap();
+        assert!(client.fetch().await.is_ok());
+        // Второй кадр испорчен: клиент ресинхронизируется на третьем
+        assert!(client.fetch().await.is_ok());
+        assert_eq!(client.stats().checksum_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(client.stats().resyncs.load(Ordering::Relaxed), 1);
+        // На четвёртый запрос сервер рвёт соединение
+        let err = client.fetch().await.unwrap_err();
+        assert!(err.to_string().contains("closed"));
+
+        server.stop();
+    }
+}
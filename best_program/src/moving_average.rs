@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::units::{self, Units};
+use crate::{SensorData, SensorKind};
+
+/// Наибольшее окно скользящего среднего, в отсчётах
+pub const MAX_WINDOW: usize = 10_000;
+/// Поля, для которых в CSV есть столбцы *_avg, по порядку столбцов
+pub const AVERAGE_FIELDS: [&str; 5] = ["temperature", "pressure", "x", "y", "z"];
+/// Заголовок CSV, когда хотя бы один сервер считает скользящие средние
+pub const CSV_AVERAGES_HEADER: &str =
+    "timestamp,source,temperature,pressure,x,y,z,temperature_avg,pressure_avg,x_avg,y_avg,z_avg\n";
+
+/// Окна по полям: имя поля -> число последних отсчётов в среднем
+pub type AverageWindows = BTreeMap<String, usize>;
+
+pub fn validate(windows: &AverageWindows, kind: SensorKind) -> Result<(), String> {
+    let fields = &kind.required_fields()[1..];
+    for (field, &window) in windows {
+        if !fields.contains(&field.as_str()) {
+            return Err(format!("unknown field '{}', expected one of: {}", field, fields.join(", ")));
+        }
+        if !(1..=MAX_WINDOW).contains(&window) {
+            return Err(format!("{}: window must be between 1 and {}", field, MAX_WINDOW));
+        }
+    }
+    Ok(())
+}
+
+struct Window {
+    field: String,
+    size: usize,
+    values: VecDeque<f64>,
+}
+
+/// Скользящие средние одного сервера. Пока окно не заполнено, среднее берётся
+/// по уже пришедшим отсчётам
+pub struct MovingAverages {
+    windows: Vec<Window>,
+}
+
+impl MovingAverages {
+    pub fn new(windows: &AverageWindows) -> Self {
+        let windows = windows
+            .iter()
+            .map(|(field, &size)| Window { field: field.clone(), size, values: VecDeque::with_capacity(size) })
+            .collect();
+        MovingAverages { windows }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Добавляет отсчёт и возвращает средние настроенных полей в порядке полей отсчёта.
+    /// Сумма пересчитывается по окну целиком, чтобы не копить ошибку округления
+    pub fn push(&mut self, data: &SensorData) -> Vec<(&'static str, f64)> {
+        let mut averages = Vec::new();
+        for (name, value) in data.values() {
+            let Some(window) = self.windows.iter_mut().find(|w| w.field == name) else {
+                continue;
+            };
+            if window.values.len() == window.size {
+                window.values.pop_front();
+            }
+            window.values.push_back(value);
+            averages.push((name, window.values.iter().sum::<f64>() / window.values.len() as f64));
+        }
+        averages
+    }
+}
+
+/// Дописывает средние к текстовой строке: " x_avg=13.20"
+pub fn append_text(line: &mut String, averages: &[(&str, f64)], units: &Units) {
+    if averages.is_empty() {
+        return;
+    }
+    let newline = line.ends_with('\n');
+    if newline {
+        line.pop();
+    }
+    for (name, value) in averages {
+        let (value, unit) = units::average(units, name, *value);
+        line.push_str(&format!(" {}_avg={}{}", name, value, unit));
+    }
+    if newline {
+        line.push('\n');
+    }
+}
+
+/// Дописывает столбцы *_avg к строке CSV; у полей без среднего столбец пустой
+pub fn append_csv(line: &mut String, averages: &[(&str, f64)], units: &Units) {
+    let newline = line.ends_with('\n');
+    if newline {
+        line.pop();
+    }
+    for field in AVERAGE_FIELDS {
+        line.push(',');
+        if let Some((name, value)) = averages.iter().find(|(name, _)| *name == field) {
+            line.push_str(&units::average(units, name, *value).0);
+        }
+    }
+    if newline {
+        line.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format_csv, format_data_with, Conversion};
+    use chrono::DateTime;
+
+    #[test]
+    fn test_moving_average_columns() {
+        let mut windows = AverageWindows::new();
+        windows.insert("x".into(), 3);
+        windows.insert("z".into(), 2);
+        assert!(validate(&windows, SensorKind::Accelerometer).is_ok());
+        assert!(validate(&windows, SensorKind::TempPressure).unwrap_err().starts_with("unknown field 'x'"));
+        let mut averages = MovingAverages::new(&windows);
+
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let sample = |x, z| SensorData::Accelerometer { timestamp, x, y: 0, z };
+        assert_eq!(averages.push(&sample(10, 1000)), [("x", 10.0), ("z", 1000.0)]);
+        averages.push(&sample(20, 2000));
+        averages.push(&sample(30, 3000));
+        // Окно x — три последних отсчёта, z — два
+        let last = sample(70, 5000);
+        let values = averages.push(&last);
+        assert_eq!(values, [("x", 40.0), ("z", 4000.0)]);
+
+        let mut units = Units::new();
+        units.insert("z".into(), Conversion::new(0.001, 0.0, "g"));
+        let mut line = format_data_with(&last, &units);
+        append_text(&mut line, &values, &units);
+        assert_eq!(line, "2023-11-14 22:13:20 [S2] x=70 y=0 z=5.00g x_avg=40.00 z_avg=4.00g\n");
+        let mut line = format_csv(&last);
+        append_csv(&mut line, &values, &Units::new());
+        assert_eq!(line, "2023-11-14T22:13:20.000000Z,S2,,,70,0,5000,,,40.00,,4000.00\n");
+        assert_eq!(line.split(',').count(), CSV_AVERAGES_HEADER.split(',').count());
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::SecondsFormat;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{MqttConfig, SampleHook, SensorData};
+
+/// Сколько сообщений может ждать отправки, пока брокер недоступен
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Темы и тела сообщений для одного отсчёта: по сообщению на величину
+pub fn messages(prefix: &str, server: &str, data: &SensorData) -> Vec<(String, String)> {
+    let timestamp = data.timestamp().to_rfc3339_opts(SecondsFormat::Micros, true);
+    let values = match data {
+        SensorData::TempPressure { temperature, pressure, .. } => vec![
+            ("temperature", format!("{:.2}", temperature)),
+            ("pressure", pressure.to_string()),
+        ],
+        SensorData::Accelerometer { x, y, z, .. } => vec![
+            ("x", x.to_string()),
+            ("y", y.to_string()),
+            ("z", z.to_string()),
+        ],
+    };
+    values
+        .into_iter()
+        .map(|(metric, value)| {
+            (
+                format!("{}/{}/{}", prefix, server, metric),
+                format!("{{\"timestamp\":\"{}\",\"value\":{}}}", timestamp, value),
+            )
+        })
+        .collect()
+}
+
+/// Публикация отсчётов в MQTT. Соединение с брокером обслуживает отдельная задача,
+/// которая переподключается после обрыва; пока брокер недоступен, сообщения копятся в очереди
+pub struct MqttSink {
+    client: AsyncClient,
+    qos: QoS,
+    topic_prefix: String,
+    dropped: Arc<AtomicU64>,
+}
+
+impl MqttSink {
+    /// Запускает обслуживание соединения; нужен работающий tokio runtime
+    pub fn start(config: &MqttConfig) -> Self {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+        let (client, mut eventloop) = AsyncClient::new(options, QUEUE_CAPACITY);
+
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_in_loop = Arc::clone(&dropped);
+        let reconnect_delay = Duration::from_millis(config.reconnect_delay_ms);
+        let broker = format!("{}:{}", config.host, config.port);
+        tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        let dropped = dropped_in_loop.swap(0, Ordering::Relaxed);
+                        tracing::info!(broker = %broker, dropped, "MQTT connected");
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!(broker = %broker, error = %e, "MQTT connection failed, retrying");
+                        tokio::time::sleep(reconnect_delay).await;
+                    }
+                }
+            }
+        });
+
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        };
+        MqttSink { client, qos, topic_prefix: config.topic_prefix.clone(), dropped }
+    }
+
+    /// Ставит сообщения в очередь; при переполненной очереди отсчёт отбрасывается
+    pub fn publish(&self, server: &str, data: &SensorData) {
+        for (topic, payload) in messages(&self.topic_prefix, server, data) {
+            if self.client.try_publish(topic, self.qos, false, payload).is_err()
+                && self.dropped.fetch_add(1, Ordering::Relaxed) == 0
+            {
+                tracing::warn!(server, "MQTT queue is full, dropping samples until the broker is back");
+            }
+        }
+    }
+
+    /// Сообщения, отброшенные с последнего подключения к брокеру
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| self.publish(server, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_messages_per_metric() {
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let data = SensorData::TempPressure { timestamp, temperature: 23.5, pressure: 1013 };
+
+        assert_eq!(
+            messages("sensors", "Server1", &data),
+            vec![
+                (
+                    "sensors/Server1/temperature".to_string(),
+                    "{\"timestamp\":\"2023-11-14T22:13:20.000000Z\",\"value\":23.50}".to_string(),
+                ),
+                (
+                    "sensors/Server1/pressure".to_string(),
+                    "{\"timestamp\":\"2023-11-14T22:13:20.000000Z\",\"value\":1013}".to_string(),
+                ),
+            ]
+        );
+
+        let data = SensorData::Accelerometer { timestamp, x: 1, y: -2, z: 3 };
+        let topics: Vec<_> = messages("lab", "Arm", &data).into_iter().map(|(topic, _)| topic).collect();
+        assert_eq!(topics, ["lab/Arm/x", "lab/Arm/y", "lab/Arm/z"]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_without_broker_does_not_block() {
+        let config = MqttConfig { port: 1, reconnect_delay_ms: 10, ..MqttConfig::default() };
+        let sink = Arc::new(MqttSink::start(&config));
+        let hook = sink.clone().hook();
+
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let data = SensorData::Accelerometer { timestamp, x: 1, y: 2, z: 3 };
+        for _ in 0..QUEUE_CAPACITY {
+            hook("Server2", &data);
+        }
+        // 3 сообщения на отсчёт: очередь переполнена, лишние отброшены
+        assert!(sink.dropped() > 0);
+    }
+}
@@ -0,0 +1,235 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::rotation::gz_path;
+use crate::Config;
+
+/// Путь с полями strftime (%Y, %m, %d, %H, %M, %S...) раскрывается при открытии каждого файла
+pub fn is_template(path: &Path) -> bool {
+    path.to_string_lossy().contains('%')
+}
+
+/// Ошибка для неизвестного поля strftime, иначе панику дал бы format при открытии файла
+pub fn check_template(path: &str) -> Result<(), String> {
+    if StrftimeItems::new(path).any(|item| item == Item::Error) {
+        return Err(format!("invalid time format in '{}' (write %% for a literal %)", path));
+    }
+    Ok(())
+}
+
+/// Граница суток для split_daily
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DayBoundary {
+    /// Полночь по часовому поясу компьютера
+    Local,
+    Utc,
+}
+
+/// Начало следующих суток в поясе tz. Если полночи нет (переход на летнее время), сутки начинаются с первого существующего часа
+fn next_midnight_in<Tz: TimeZone>(tz: &Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.with_timezone(tz).date_naive();
+    let tomorrow = today.succ_opt().unwrap_or(today).and_time(NaiveTime::MIN);
+    (0..=3)
+        .find_map(|hours| tz.from_local_datetime(&(tomorrow + TimeDelta::hours(hours))).earliest())
+        .map_or(now + TimeDelta::days(1), |start| start.with_timezone(&Utc))
+}
+
+impl DayBoundary {
+    pub fn today(self, now: DateTime<Utc>) -> NaiveDate {
+        match self {
+            DayBoundary::Local => now.with_timezone(&Local).date_naive(),
+            DayBoundary::Utc => now.date_naive(),
+        }
+    }
+
+    pub fn next_midnight(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            DayBoundary::Local => next_midnight_in(&Local, now),
+            DayBoundary::Utc => next_midnight_in(&Utc, now),
+        }
+    }
+}
+
+/// Файл дня для обычного пути: sensor_data.txt -> sensor_data_2024-01-01.txt
+pub fn dated_path(path: &Path, date: NaiveDate) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    path.with_file_name(format!("{}_{}{}", stem, date.format("%Y-%m-%d"), ext))
+}
+
+/// Путь нового файла: шаблон раскрывается (в местном времени при split_daily = "local", иначе в UTC),
+/// обычный путь при split_daily получает дату, без него остаётся как есть
+pub fn next_path(template: &Path, config: &Config, now: DateTime<Utc>) -> PathBuf {
+    match (is_template(template), config.split_daily) {
+        (true, Some(DayBoundary::Local)) => expand_template(template, now.with_timezone(&Local)),
+        (true, _) => expand_template(template, now),
+        (false, Some(boundary)) => dated_path(template, boundary.today(now)),
+        (false, None) => template.to_path_buf(),
+    }
+}
+
+/// Раскрывает шаблон по времени now. Если такой файл (или его .gz) уже есть,
+/// добавляет счётчик: sensor_20240101.txt -> sensor_20240101-1.txt
+pub fn expand_template<Tz: TimeZone>(template: &Path, now: DateTime<Tz>) -> PathBuf
+where
+    Tz::Offset: Display,
+{
+    let path = PathBuf::from(now.format(&template.to_string_lossy()).to_string());
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.clone();
+    let mut n = 1;
+    while candidate.exists() || gz_path(&candidate).exists() {
+        candidate = path.with_file_name(format!("{}-{}{}", stem, n, ext));
+        n += 1;
+    }
+    candidate
+}
+
+/// Открывает файл для дописывания; в пустой файл сразу пишется заголовок формата
+pub fn open_writer(path: &Path, config: &Config) -> io::Result<BufWriter<File>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let empty = file.metadata()?.len() == 0;
+    let mut writer = BufWriter::with_capacity(65536, file);
+    if let (true, Some(header)) = (empty, config.header()) {
+        writer.write_all(header)?;
+        writer.flush()?;
+    }
+    Ok(writer)
+}
+
+/// Выходной файл: общий для всех серверов или свой у сервера
+#[derive(Clone)]
+pub struct OutputFile {
+    /// Путь из конфигурации, возможно шаблон
+    pub template: PathBuf,
+    /// Файл, в который идёт запись сейчас; для шаблона меняется при ротации
+    pub path: Arc<Mutex<PathBuf>>,
+    pub writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl OutputFile {
+    /// Шаблон раскрывается в новый файл (каталоги создаются), обычный путь (или файл дня) дописывается
+    pub fn open(template: &Path, config: &Config) -> io::Result<Self> {
+        let path = next_path(template, config, Utc::now());
+        let writer = open_new(&path, config)
+            .map_err(|e| io::Error::new(e.kind(), format!("Cannot open output file {}: {}", path.display(), e)))?;
+        Ok(OutputFile {
+            template: template.to_path_buf(),
+            path: Arc::new(Mutex::new(path)),
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    pub fn current(&self) -> PathBuf {
+        self.path.lock().map(|path| path.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+}
+
+/// open_writer, но с созданием недостающего каталога (шаблон может раскрыться в новый)
+pub fn open_new(path: &Path, config: &Config) -> io::Result<BufWriter<File>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    open_writer(path, config)
+}
+
+/// Открывает выходные файлы всех серверов; серверы с одинаковым путём делят один файл.
+/// Возвращает файлы и номер файла для каждого сервера
+pub fn open_outputs(config: &Config) -> io::Result<(Vec<OutputFile>, Vec<usize>)> {
+    let mut outputs: Vec<OutputFile> = Vec::new();
+    let mut indices = Vec::with_capacity(config.servers.len());
+    for server in &config.servers {
+        let path = config.server_output(server);
+        let index = match outputs.iter().position(|output| output.template == path) {
+            Some(index) => index,
+            None => {
+                outputs.push(OutputFile::open(&path, config)?);
+                outputs.len() - 1
+            }
+        };
+        indices.push(index);
+    }
+    Ok((outputs, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutputFormat, SensorKind, ServerConfig};
+    use std::fs;
+
+    #[test]
+    fn test_split_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut own = ServerConfig::new("Server3", "127.0.0.1:5125", SensorKind::TempPressure);
+        own.output = Some(dir.path().join("lab.csv").to_string_lossy().into_owned());
+        let config = Config {
+            output: dir.path().join("data.csv").to_string_lossy().into_owned(),
+            format: OutputFormat::Csv,
+            split_output: true,
+            servers: vec![
+                ServerConfig::new("Server1", "127.0.0.1:5123", SensorKind::TempPressure),
+                ServerConfig::new("Server2", "127.0.0.1:5124", SensorKind::Accelerometer),
+                own,
+                ServerConfig::new("Server4", "127.0.0.1:5126", SensorKind::Accelerometer),
+            ],
+            ..Config::default()
+        };
+
+        let (outputs, indices) = open_outputs(&config).unwrap();
+        let names: Vec<_> = outputs.iter().map(|o| o.current().file_name().unwrap().to_str().unwrap().to_string()).collect();
+        assert_eq!(names, ["temp_pressure.csv", "accel.csv", "lab.csv"]);
+        assert_eq!(indices, [0, 1, 2, 1]);
+        assert!(!dir.path().join("data.csv").exists());
+        assert_eq!(fs::read_to_string(outputs[0].current()).unwrap(), crate::CSV_HEADER);
+
+        // Заголовок не повторяется при дописывании в существующий файл
+        drop(outputs);
+        open_outputs(&config).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("accel.csv")).unwrap(), crate::CSV_HEADER);
+    }
+
+    #[test]
+    fn test_output_template() {
+        assert!(check_template("sensor_%Y%m%d_%H%M%S.txt").is_ok());
+        assert!(check_template("100%%.txt").is_ok());
+        assert!(check_template("sensor_%Q.txt").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("%Y/sensor_%Y%m%d_%H%M%S.txt");
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let first = expand_template(&template, now);
+        assert_eq!(first, dir.path().join("2023/sensor_20231114_221320.txt"));
+
+        let config = Config { output: template.to_string_lossy().into_owned(), ..Config::default() };
+        let output = OutputFile::open(&template, &config).unwrap();
+        assert!(output.current().exists());
+        // Тот же шаблон в ту же секунду не дописывает существующий файл
+        fs::create_dir_all(first.parent().unwrap()).unwrap();
+        fs::write(&first, b"earlier run").unwrap();
+        assert_eq!(expand_template(&template, now), dir.path().join("2023/sensor_20231114_221320-1.txt"));
+        assert_eq!(fs::read(&first).unwrap(), b"earlier run");
+    }
+
+    #[test]
+    fn test_daily_paths() {
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        assert_eq!(DayBoundary::Utc.next_midnight(now), DateTime::from_timestamp(1700006400, 0).unwrap());
+        // В UTC+3 уже 15 ноября, следующая полночь — 16-го по местному времени
+        let moscow = chrono::FixedOffset::east_opt(3 * 3600).unwrap();
+        assert_eq!(next_midnight_in(&moscow, now).to_rfc3339(), "2023-11-15T21:00:00+00:00");
+
+        let config = Config { split_daily: Some(DayBoundary::Utc), ..Config::default() };
+        assert_eq!(next_path(Path::new("logs/data.csv"), &config, now), Path::new("logs/data_2023-11-14.csv"));
+        assert_eq!(next_path(Path::new("data"), &config, now), Path::new("data_2023-11-14"));
+        assert_eq!(next_path(Path::new("data.csv"), &Config::default(), now), Path::new("data.csv"));
+    }
+}
@@ -0,0 +1,205 @@
+use ::parquet::basic::Compression;
+use ::parquet::data_type::{ByteArray, ByteArrayType, DataType, FloatType, Int32Type, Int64Type};
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use ::parquet::schema::parser::parse_message_type;
+use chrono::Utc;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::rotation::rotated_path;
+use crate::{SampleHook, SensorData};
+
+/// Одна таблица для обоих датчиков, как в CSV: поля чужого типа пустые (null)
+const SCHEMA: &str = "
+    message sample {
+        REQUIRED INT64 timestamp_us (TIMESTAMP(MICROS, true));
+        REQUIRED BINARY server (STRING);
+        OPTIONAL FLOAT temperature;
+        OPTIONAL INT32 pressure (INTEGER(16, true));
+        OPTIONAL INT32 x;
+        OPTIONAL INT32 y;
+        OPTIONAL INT32 z;
+    }
+";
+
+/// Отсчёты, ещё не записанные группой строк; по вектору на столбец
+#[derive(Default)]
+struct Rows {
+    timestamp_us: Vec<i64>,
+    server: Vec<ByteArray>,
+    temperature: Vec<Option<f32>>,
+    pressure: Vec<Option<i32>>,
+    x: Vec<Option<i32>>,
+    y: Vec<Option<i32>>,
+    z: Vec<Option<i32>>,
+}
+
+impl Rows {
+    fn push(&mut self, server: &str, data: &SensorData) {
+        self.timestamp_us.push(data.timestamp().timestamp_micros());
+        self.server.push(ByteArray::from(server));
+        let (temperature, pressure, axes) = match *data {
+            SensorData::TempPressure { temperature, pressure, .. } => (Some(temperature), Some(pressure as i32), None),
+            SensorData::Accelerometer { x, y, z, .. } => (None, None, Some([x, y, z])),
+        };
+        self.temperature.push(temperature);
+        self.pressure.push(pressure);
+        self.x.push(axes.map(|a| a[0]));
+        self.y.push(axes.map(|a| a[1]));
+        self.z.push(axes.map(|a| a[2]));
+    }
+
+    fn len(&self) -> usize {
+        self.timestamp_us.len()
+    }
+}
+
+fn write_required<T: DataType>(column: &mut SerializedColumnWriter<'_>, values: &[T::T]) -> ::parquet::errors::Result<()> {
+    column.typed::<T>().write_batch(values, None, None)?;
+    Ok(())
+}
+
+/// Null записывается нулевым уровнем определения, в values попадают только заданные значения
+fn write_optional<T: DataType>(column: &mut SerializedColumnWriter<'_>, values: &[Option<T::T>]) -> ::parquet::errors::Result<()> {
+    let present: Vec<T::T> = values.iter().flatten().cloned().collect();
+    let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+    column.typed::<T>().write_batch(&present, Some(&levels), None)?;
+    Ok(())
+}
+
+struct State {
+    /// None после close
+    writer: Option<SerializedFileWriter<File>>,
+    rows: Rows,
+}
+
+impl State {
+    fn write_row_group(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        let rows = std::mem::take(&mut self.rows);
+        if rows.len() == 0 {
+            return Ok(());
+        }
+        let mut group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = group.next_column()? {
+            match index {
+                0 => write_required::<Int64Type>(&mut column, &rows.timestamp_us)?,
+                1 => write_required::<ByteArrayType>(&mut column, &rows.server)?,
+                2 => write_optional::<FloatType>(&mut column, &rows.temperature)?,
+                3 => write_optional::<Int32Type>(&mut column, &rows.pressure)?,
+                4 => write_optional::<Int32Type>(&mut column, &rows.x)?,
+                5 => write_optional::<Int32Type>(&mut column, &rows.y)?,
+                _ => write_optional::<Int32Type>(&mut column, &rows.z)?,
+            }
+            column.close()?;
+            index += 1;
+        }
+        group.close()?;
+        Ok(())
+    }
+}
+
+/// Запись отсчётов в Parquet (сжатие Snappy): группа строк на каждые row_group_size отсчётов.
+/// Файл читаем только после close, который дописывает метаданные в конец
+pub struct ParquetSink {
+    state: Mutex<State>,
+    row_group_size: usize,
+}
+
+impl ParquetSink {
+    /// Существующий файл не дописывается (формат этого не позволяет), а переименовывается как при ротации
+    pub fn create(path: &Path, row_group_size: usize) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if path.exists() {
+            let previous = rotated_path(path, Utc::now());
+            fs::rename(path, &previous)?;
+            tracing::info!(file = %previous.display(), "Previous Parquet file moved aside");
+        }
+        let file = File::create(path).map_err(|e| format!("Cannot create Parquet file {}: {}", path.display(), e))?;
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let writer = SerializedFileWriter::new(file, Arc::new(parse_message_type(SCHEMA)?), Arc::new(properties))?;
+        Ok(ParquetSink {
+            state: Mutex::new(State { writer: Some(writer), rows: Rows::default() }),
+            row_group_size,
+        })
+    }
+
+    pub fn append(&self, server: &str, data: &SensorData) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut state = self.state.lock().map_err(|_| "Parquet writer lock poisoned")?;
+        if state.writer.is_none() {
+            return Err("Parquet file already closed".into());
+        }
+        state.rows.push(server, data);
+        if state.rows.len() >= self.row_group_size {
+            state.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Записывает оставшиеся отсчёты и метаданные файла; повторный вызов ничего не делает
+    pub fn close(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut state = self.state.lock().map_err(|_| "Parquet writer lock poisoned")?;
+        if state.writer.is_none() {
+            return Ok(());
+        }
+        state.write_row_group()?;
+        if let Some(writer) = state.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+
+    /// Обработчик для worker_task; ошибки записи печатаются и не останавливают сбор
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| {
+            if let Err(e) = self.append(server, data) {
+                tracing::error!(server, error = %e, "Parquet write failed");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::RowAccessor;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_parquet_sink_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.parquet");
+        fs::write(&path, b"old capture").unwrap();
+        let sink = Arc::new(ParquetSink::create(&path, 2).unwrap());
+        let hook = sink.clone().hook();
+
+        for i in 0..3 {
+            let timestamp = DateTime::from_timestamp_micros(1_000_000 * i).unwrap();
+            hook("Server1", &SensorData::TempPressure { timestamp, temperature: 20.5, pressure: 1000 });
+        }
+        let timestamp = DateTime::from_timestamp_micros(1_500_000).unwrap();
+        hook("Server2", &SensorData::Accelerometer { timestamp, x: 1, y: -2, z: 3 });
+        sink.close().unwrap();
+        sink.close().unwrap();
+        assert!(sink.append("Server1", &SensorData::Accelerometer { timestamp, x: 0, y: 0, z: 0 }).is_err());
+
+        // Старый файл сохранён рядом
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 4);
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+        assert_eq!(rows[0].get_string(1).unwrap(), "Server1");
+        assert_eq!(rows[0].get_float(2).unwrap(), 20.5);
+        assert!(rows[0].get_int(4).is_err());
+        assert_eq!(rows[3].get_timestamp_micros(0).unwrap(), 1_500_000);
+        assert_eq!(rows[3].get_int(5).unwrap(), -2);
+        assert!(rows[3].get_float(2).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::{Config, Transport, GET_CMD};
+
+/// Проверка соединения в паузах опроса
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepaliveSpec {
+    /// Проверка идёт, пока до следующего запроса дольше этого срока
+    pub interval_ms: u64,
+    /// Команда проверки; без неё запрашивается кадр, который отбрасывается
+    pub command: Option<String>,
+    /// Ответ на command целиком
+    pub response: Option<String>,
+}
+
+impl Default for KeepaliveSpec {
+    fn default() -> Self {
+        KeepaliveSpec { interval_ms: 1000, command: None, response: None }
+    }
+}
+
+impl KeepaliveSpec {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("keepalive.interval_ms: must be greater than 0".into());
+        }
+        match (&self.command, &self.response) {
+            (Some(command), Some(response)) if command.is_empty() || response.is_empty() => {
+                Err("keepalive: command and response must not be empty".into())
+            }
+            (Some(_), None) | (None, Some(_)) => Err("keepalive: command and response go together".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Рукопожатие и команда запроса кадра для одного сервера
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtocolSpec {
+    /// Ключ авторизации; по умолчанию — общий auth_key
+    pub auth_key: Option<String>,
+    /// Начало ответа на ключ, означающее успех; без него годится любой непустой ответ
+    pub ack: Option<String>,
+    /// Команда, в ответ на которую сервер присылает один кадр
+    pub request: String,
+    /// Проверка соединения в долгих паузах опроса; без неё — только запросы кадров
+    pub keepalive: Option<KeepaliveSpec>,
+}
+
+impl Default for ProtocolSpec {
+    fn default() -> Self {
+        ProtocolSpec {
+            auth_key: None,
+            ack: None,
+            request: String::from_utf8_lossy(GET_CMD).into_owned(),
+            keepalive: None,
+        }
+    }
+}
+
+impl ProtocolSpec {
+    pub fn key<'a>(&'a self, config: &'a Config) -> &'a [u8] {
+        self.auth_key.as_deref().unwrap_or(&config.auth_key).as_bytes()
+    }
+
+    pub fn request(&self) -> &[u8] {
+        self.request.as_bytes()
+    }
+
+    /// Проверка ответа сервера на ключ
+    pub fn check_ack(&self, response: &[u8]) -> Result<(), String> {
+        match &self.ack {
+            Some(ack) if !response.starts_with(ack.as_bytes()) => {
+                Err(format!("Unexpected auth response: {:?}", String::from_utf8_lossy(response)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate(&self, transport: Transport) -> Result<(), String> {
+        if self.request.is_empty() {
+            return Err("request: must not be empty".into());
+        }
+        if self.auth_key.as_ref().is_some_and(|key| key.is_empty()) {
+            return Err("auth_key: must not be empty".into());
+        }
+        if self.ack.as_ref().is_some_and(|ack| ack.is_empty()) {
+            return Err("ack: must not be empty".into());
+        }
+        if transport == Transport::Udp && (self.auth_key.is_some() || self.ack.is_some() || self.keepalive.is_some()) {
+            return Err("auth_key, ack and keepalive do not apply to the udp transport".into());
+        }
+        if let Some(keepalive) = &self.keepalive {
+            keepalive.validate()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_defaults_and_ack() {
+        let config = Config { auth_key: "shared".to_string(), ..Config::default() };
+        let default = ProtocolSpec::default();
+        assert_eq!(default.key(&config), b"shared");
+        assert_eq!(default.request(), GET_CMD);
+        assert!(default.check_ack(b"anything").is_ok());
+
+        let spec = ProtocolSpec {
+            auth_key: Some("own".into()),
+            ack: Some("AUTH_OK".into()),
+            request: "next\n".into(),
+            keepalive: None,
+        };
+        assert_eq!(spec.key(&config), b"own");
+        assert!(spec.check_ack(b"AUTH_OK\n").is_ok());
+        assert!(spec.check_ack(b"AUTH_FAIL\n").unwrap_err().contains("AUTH_FAIL"));
+        assert!(spec.validate(Transport::Tcp).is_ok());
+        assert!(spec.validate(Transport::Udp).is_err());
+
+        let keepalive = |command: Option<&str>, response: Option<&str>| ProtocolSpec {
+            keepalive: Some(KeepaliveSpec {
+                command: command.map(String::from),
+                response: response.map(String::from),
+                ..KeepaliveSpec::default()
+            }),
+            ..ProtocolSpec::default()
+        };
+        assert!(keepalive(None, None).validate(Transport::Tcp).is_ok());
+        assert!(keepalive(Some("ping\n"), Some("PONG\n")).validate(Transport::Tcp).is_ok());
+        assert!(keepalive(Some("ping\n"), None).validate(Transport::Tcp).is_err());
+        assert!(keepalive(None, None).validate(Transport::Udp).is_err());
+    }
+}
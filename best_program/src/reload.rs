@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::alert::Alerter;
+use crate::logging::{self, LogHandle};
+use crate::{Config, ServerStats};
+
+/// Переносит в текущую конфигурацию то, что меняется без переподключения: log_level, правила
+/// оповещений, настройки ротации и poll_interval_ms серверов с прежними именами.
+/// Второе значение — true, если в новой конфигурации есть и другие изменения (нужен перезапуск)
+pub fn merge(current: &Config, loaded: &Config) -> (Config, bool) {
+    let mut merged = current.clone();
+    merged.log_level = loaded.log_level.clone();
+    merged.alerts = loaded.alerts.clone();
+    merged.rotate_max_bytes = loaded.rotate_max_bytes;
+    merged.compress_rotated = loaded.compress_rotated;
+    for server in &mut merged.servers {
+        if let Some(new) = loaded.servers.iter().find(|s| s.name == server.name) {
+            server.poll_interval_ms = new.poll_interval_ms;
+        }
+    }
+    let restart = merged != *loaded;
+    (merged, restart)
+}
+
+/// Применяет перечитанную конфигурацию к работающему логгеру
+pub struct Reloader {
+    config: watch::Sender<Arc<Config>>,
+    /// Статистика серверов в порядке config.servers
+    stats: Vec<Arc<ServerStats>>,
+    log: Option<LogHandle>,
+    alerter: Option<Arc<Alerter>>,
+}
+
+impl Reloader {
+    pub fn new(config: Arc<Config>, stats: &[(String, Arc<ServerStats>)]) -> Self {
+        Reloader {
+            config: watch::Sender::new(config),
+            stats: stats.iter().map(|(_, stats)| Arc::clone(stats)).collect(),
+            log: None,
+            alerter: None,
+        }
+    }
+
+    pub fn with_log(mut self, log: LogHandle) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub fn with_alerter(mut self, alerter: Arc<Alerter>) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Текущая конфигурация; меняется после каждого успешного перечитывания
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.config.subscribe()
+    }
+
+    /// Ошибка проверки оставляет прежние настройки без изменений
+    pub fn apply(&self, loaded: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let current = Arc::clone(&self.config.borrow());
+        let (merged, restart) = merge(&current, loaded);
+        merged.validate()?;
+        if let Some(log) = &self.log {
+            if merged.log_level != current.log_level {
+                log.reload(logging::parse_filter(&merged.log_level)?)?;
+            }
+        }
+        for (server, stats) in merged.servers.iter().zip(&self.stats) {
+            stats.set_poll_interval(server.poll_interval());
+        }
+        if let Some(alerter) = &self.alerter {
+            alerter.reload(&merged.alerts);
+        }
+        info!(
+            log_level = %merged.log_level,
+            alerts = merged.alerts.len(),
+            rotate_max_bytes = ?merged.rotate_max_bytes,
+            "Configuration reloaded"
+        );
+        if restart {
+            warn!("Other configuration changes take effect after a restart");
+        }
+        self.config.send_replace(Arc::new(merged));
+        Ok(())
+    }
+}
+
+/// Запросы перечитывания: SIGHUP
+#[cfg(unix)]
+fn listen() -> std::io::Result<mpsc::Receiver<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            // Запрос, пришедший во время перечитывания, не теряется, а повторы схлопываются
+            let _ = sender.try_send(());
+            if sender.is_closed() {
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Запросы перечитывания: строка "reload" в консоли
+#[cfg(not(unix))]
+fn listen() -> std::io::Result<mpsc::Receiver<()>> {
+    let (sender, receiver) = mpsc::channel(1);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim() == "reload" {
+                let _ = sender.try_send(());
+            }
+            if sender.is_closed() {
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Перечитывает конфигурацию через load по каждому запросу до отмены shutdown
+pub async fn run<F>(reloader: Reloader, load: F, shutdown: CancellationToken)
+where
+    F: Fn() -> Result<Config, Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut requests = match listen() {
+        Ok(requests) => requests,
+        Err(e) => {
+            error!(error = %e, "Cannot listen for reload requests");
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            request = requests.recv() => {
+                if request.is_none() {
+                    break;
+                }
+                info!("Reloading configuration");
+                if let Err(e) = load().and_then(|config| reloader.apply(&config)) {
+                    error!(error = %e, "Reload failed, keeping the current configuration");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FsyncPolicy, SensorKind, ServerConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn test_reload_applies_runtime_settings() {
+        let current = Arc::new(Config::default());
+        let stats: Vec<_> = current.servers.iter().map(|s| (s.name.clone(), Arc::new(ServerStats::new()))).collect();
+        let reloader = Reloader::new(Arc::clone(&current), &stats);
+        let mut watched = reloader.subscribe();
+
+        let mut loaded = Config::from_toml(concat!(
+            "log_level = \"debug\"\n",
+            "rotate_max_bytes = 1048576\n",
+            "[[alerts]]\nname = \"hot\"\nfield = \"temperature\"\nabove = 35.0\n",
+        ))
+        .unwrap();
+        loaded.servers[1].poll_interval_ms = Some(250);
+        let (merged, restart) = merge(&current, &loaded);
+        assert!(!restart);
+        assert_eq!(merged, loaded);
+
+        reloader.apply(&loaded).unwrap();
+        assert!(watched.has_changed().unwrap());
+        let applied = Arc::clone(&watched.borrow_and_update());
+        assert_eq!(applied.rotate_max_bytes, Some(1048576));
+        assert_eq!(applied.alerts[0].name, "hot");
+        assert_eq!(stats[1].1.poll_interval(&current.servers[1]), Duration::from_millis(250));
+        assert_eq!(stats[0].1.poll_interval(&current.servers[0]), current.servers[0].poll_interval());
+
+        // Адреса, формат и состав серверов на ходу не меняются
+        loaded.fsync = FsyncPolicy::Flush;
+        loaded.servers[0].address = "127.0.0.1:6000".to_string();
+        loaded.servers.push(ServerConfig::new("Lab", "127.0.0.1:6001", SensorKind::Accelerometer));
+        loaded.log_level = "warn".to_string();
+        let (merged, restart) = merge(&applied, &loaded);
+        assert!(restart);
+        assert_eq!((merged.log_level.as_str(), merged.servers.len()), ("warn", 2));
+        assert_eq!(merged.servers[0].address, current.servers[0].address);
+        assert_eq!(merged.fsync, current.fsync);
+
+        // Неверное значение отклоняется, прежние настройки остаются
+        loaded.log_level = "info".to_string();
+        loaded.rotate_max_bytes = Some(0);
+        assert!(reloader.apply(&loaded).is_err());
+        assert!(!watched.has_changed().unwrap());
+        assert_eq!(watched.borrow().log_level, "debug");
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+use crate::binary::{BinaryReader, BINARY_MAGIC};
+use crate::moving_average::CSV_AVERAGES_HEADER;
+use crate::{DataSink, SensorData, SensorKind, ServerStats, CSV_HEADER};
+
+type Record = Result<SensorData, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Итог воспроизведения
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub replayed: u64,
+    /// Нечитаемые строки и отсчёты без сервера подходящего типа
+    pub skipped: u64,
+}
+
+/// Разбор строки текстового формата (обратное к format_data); время в файле с точностью до секунды
+pub fn parse_text_record(line: &str) -> Record {
+    let time = line.get(..19).ok_or("Line too short")?;
+    let timestamp = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")?.and_utc();
+    let mut parts = line[19..].split_whitespace();
+    let source = parts.next().ok_or("Missing source")?;
+    let mut field = |name: &str| -> Result<&str, Box<dyn std::error::Error + Send + Sync>> {
+        parts
+            .next()
+            .and_then(|part| part.strip_prefix(name)?.strip_prefix('='))
+            .ok_or_else(|| format!("Missing field '{}'", name).into())
+    };
+    match source {
+        "[S1]" => Ok(SensorData::TempPressure {
+            timestamp,
+            temperature: field("temperature")?.trim_end_matches('C').parse()?,
+            pressure: field("pressure")?.parse()?,
+        }),
+        "[S2]" => Ok(SensorData::Accelerometer {
+            timestamp,
+            x: field("x")?.parse()?,
+            y: field("y")?.parse()?,
+            z: field("z")?.parse()?,
+        }),
+        _ => Err(format!("Unknown source {}", source).into()),
+    }
+}
+
+/// Разбор строки CSV (обратное к format_csv); столбцы скользящих средних пропускаются
+pub fn parse_csv_record(line: &str) -> Record {
+    let columns: Vec<&str> = line.split(',').collect();
+    let raw = match columns.len() {
+        12 => &columns[..7],
+        _ => &columns[..],
+    };
+    let [time, source, temperature, pressure, x, y, z] = raw[..] else {
+        return Err(format!("Expected 7 or 12 columns, got {}", columns.len()).into());
+    };
+    let timestamp = DateTime::parse_from_rfc3339(time)?.with_timezone(&Utc);
+    match source {
+        "S1" => Ok(SensorData::TempPressure { timestamp, temperature: temperature.parse()?, pressure: pressure.parse()? }),
+        "S2" => Ok(SensorData::Accelerometer { timestamp, x: x.parse()?, y: y.parse()?, z: z.parse()? }),
+        _ => Err(format!("Unknown source {}", source).into()),
+    }
+}
+
+/// Открывает файл, записанный логгером: формат определяется по сигнатуре binary
+/// и по виду каждой строки (текст или CSV). Файлы с агрегатами не воспроизводятся
+pub fn open_capture(path: &Path) -> Result<Box<dyn Iterator<Item = Record> + Send>, Box<dyn std::error::Error + Send + Sync>> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+        return Ok(Box::new(BinaryReader::new(reader)?));
+    }
+    let headers = [CSV_HEADER.trim_end(), CSV_AVERAGES_HEADER.trim_end()];
+    Ok(Box::new(reader.lines().enumerate().filter_map(move |(index, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if line.is_empty() || headers.contains(&line.as_str()) {
+            return None;
+        }
+        let record = if line.contains(" [S") { parse_text_record(&line) } else { parse_csv_record(&line) };
+        Some(record.map_err(|e| format!("line {}: {}", index + 1, e).into()))
+    })))
+}
+
+/// Передаёт отсчёты в приёмники первого сервера того же типа. speed — множитель скорости
+/// относительно исходных интервалов между отсчётами, 0 — без пауз
+pub async fn replay<S: DataSink>(
+    records: impl Iterator<Item = Record>,
+    speed: f64,
+    targets: &mut [(SensorKind, S, Arc<ServerStats>)],
+    shutdown: &CancellationToken,
+) -> ReplaySummary {
+    let mut summary = ReplaySummary::default();
+    let mut previous: Option<DateTime<Utc>> = None;
+
+    for record in records {
+        if shutdown.is_cancelled() {
+            break;
+        }
+        let data = match record {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(error = %e, "Unreadable record skipped");
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        let Some((_, sink, stats)) = targets.iter_mut().find(|(kind, _, _)| *kind == data.kind()) else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        if let (Some(previous), true) = (previous, speed > 0.0) {
+            let pause = (data.timestamp() - previous).to_std().unwrap_or_default().div_f64(speed);
+            if pause > Duration::ZERO {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(pause) => {}
+                }
+            }
+        }
+        previous = Some(data.timestamp());
+
+        stats.packets_received.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = sink.write(&data) {
+            error!(error = %e, "Write error");
+        }
+        summary.replayed += 1;
+    }
+
+    for (_, sink, _) in targets.iter_mut() {
+        if let Err(e) = sink.finish() {
+            error!(error = %e, "Write error");
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{binary, format_csv, format_data, FileSink};
+    use std::io::{BufWriter, Write};
+    use std::sync::Mutex;
+    use std::time::Instant;
+    use tempfile::NamedTempFile;
+
+    fn samples() -> Vec<SensorData> {
+        let at = |secs| DateTime::from_timestamp(secs, 0).unwrap();
+        vec![
+            SensorData::TempPressure { timestamp: at(1700000000), temperature: 23.5, pressure: 1013 },
+            SensorData::Accelerometer { timestamp: at(1700000000), x: 100, y: -200, z: 300 },
+            SensorData::TempPressure { timestamp: at(1700000001), temperature: -4.25, pressure: 998 },
+        ]
+    }
+
+    fn capture(content: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    fn read_all(file: &NamedTempFile) -> Vec<String> {
+        open_capture(file.path()).unwrap().map(|r| format!("{:?}", r.unwrap())).collect()
+    }
+
+    #[test]
+    fn test_open_capture_in_every_format() {
+        let expected: Vec<String> = samples().iter().map(|d| format!("{:?}", d)).collect();
+
+        let text: String = samples().iter().map(format_data).collect();
+        assert_eq!(read_all(&capture(text.as_bytes())), expected);
+
+        let csv: String = CSV_HEADER.to_string() + &samples().iter().map(format_csv).collect::<String>();
+        assert_eq!(read_all(&capture(csv.as_bytes())), expected);
+
+        let averaged: String = CSV_AVERAGES_HEADER.to_string()
+            + &samples().iter().map(|d| format_csv(d).replace('\n', ",,,,,\n")).collect::<String>();
+        assert_eq!(read_all(&capture(averaged.as_bytes())), expected);
+
+        let mut bin = BINARY_MAGIC.to_vec();
+        samples().iter().for_each(|d| bin.extend(binary::encode_record(d)));
+        assert_eq!(read_all(&capture(&bin)), expected);
+
+        let broken = capture(b"2023-11-14 22:13:20 [S1] n=3 temperature=1/2/3\n");
+        let err = open_capture(broken.path()).unwrap().next().unwrap().unwrap_err();
+        assert!(err.to_string().starts_with("line 1:"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_paced_into_sinks() {
+        let output = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(output.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        let mut targets = [(SensorKind::TempPressure, FileSink::new(writer, &Default::default()), stats.clone())];
+        let records = samples().into_iter().map(Ok).chain([Err("bad line".into())]);
+
+        let started = Instant::now();
+        let summary = replay(records, 10.0, &mut targets, &CancellationToken::new()).await;
+
+        // Одна секунда между отсчётами при ускорении в 10 раз
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(summary, ReplaySummary { replayed: 2, skipped: 2 });
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 2);
+        let content = std::fs::read_to_string(output.path()).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.ends_with("[S1] temperature=-4.25C pressure=998\n"));
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::output::{is_template, next_path, open_new, open_writer, OutputFile};
+use crate::{Config, FsyncPolicy};
+
+/// Имя закрытого файла: sensor_data.txt -> sensor_data.20240101-120000.txt.
+/// При совпадении имён добавляется счётчик
+pub fn rotated_path(path: &Path, now: DateTime<Utc>) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let stamp = now.format("%Y%m%d-%H%M%S");
+
+    let mut candidate = path.with_file_name(format!("{}.{}{}", stem, stamp, ext));
+    let mut n = 1;
+    while candidate.exists() || gz_path(&candidate).exists() {
+        candidate = path.with_file_name(format!("{}.{}-{}{}", stem, stamp, n, ext));
+        n += 1;
+    }
+    candidate
+}
+
+pub(crate) fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Если файл вырос до config.rotate_max_bytes, закрывает его и продолжает запись в новый:
+/// обычный путь переименовывается с отметкой времени, шаблон раскрывается заново.
+/// Если fsync включён, закрываемый файл сначала записывается на диск. Возвращает путь закрытого файла
+pub fn rotate_if_needed(output: &OutputFile, config: &Config) -> io::Result<Option<PathBuf>> {
+    let Some(limit) = config.rotate_max_bytes else {
+        return Ok(None);
+    };
+    let mut w = output.writer.lock().map_err(|_| io::Error::other("Writer lock poisoned"))?;
+    w.flush()?;
+    if w.get_ref().metadata()?.len() < limit {
+        return Ok(None);
+    }
+    if config.fsync != FsyncPolicy::Never {
+        w.get_ref().sync_data()?;
+    }
+
+    let mut path = output.path.lock().map_err(|_| io::Error::other("Output path lock poisoned"))?;
+    if is_template(&output.template) {
+        let next = next_path(&output.template, config, Utc::now());
+        *w = open_new(&next, config)?;
+        let closed = std::mem::replace(&mut *path, next);
+        return Ok(Some(closed));
+    }
+    let rotated = rotated_path(&path, Utc::now());
+    fs::rename(&*path, &rotated)?;
+    *w = open_writer(&path, config)?;
+    Ok(Some(rotated))
+}
+
+/// Полночь при split_daily: закрывает файл и продолжает запись в файле новой даты
+/// (шаблон раскрывается заново). Возвращает путь закрытого файла; None — файл этой даты уже открыт
+pub fn split_day(output: &OutputFile, config: &Config, now: DateTime<Utc>) -> io::Result<Option<PathBuf>> {
+    let mut w = output.writer.lock().map_err(|_| io::Error::other("Writer lock poisoned"))?;
+    let mut path = output.path.lock().map_err(|_| io::Error::other("Output path lock poisoned"))?;
+    let next = next_path(&output.template, config, now);
+    if next == *path {
+        return Ok(None);
+    }
+    w.flush()?;
+    if config.fsync != FsyncPolicy::Never {
+        w.get_ref().sync_data()?;
+    }
+    *w = open_new(&next, config)?;
+    Ok(Some(std::mem::replace(&mut *path, next)))
+}
+
+/// Сжимает файл в <путь>.gz и удаляет исходный только после успешной записи архива
+pub fn compress_file(path: &Path) -> io::Result<PathBuf> {
+    let gz = gz_path(path);
+    let mut tmp = gz.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+    fs::rename(&tmp, &gz)?;
+    fs::remove_file(path)?;
+    Ok(gz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{dated_path, DayBoundary};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_rotated_path() {
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let path = Path::new("/nonexistent/sensor_data.txt");
+        assert_eq!(rotated_path(path, now), Path::new("/nonexistent/sensor_data.20231114-221320.txt"));
+    }
+
+    #[test]
+    fn test_rotate_and_compress() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("data.csv");
+        let config = Config {
+            output: output.to_string_lossy().into_owned(),
+            format: crate::OutputFormat::Csv,
+            rotate_max_bytes: Some(10),
+            ..Config::default()
+        };
+
+        let raw = fs::OpenOptions::new().create(true).append(true).open(&output).unwrap();
+        let file = OutputFile {
+            template: output.clone(),
+            path: Arc::new(Mutex::new(output.clone())),
+            writer: Arc::new(Mutex::new(BufWriter::new(raw))),
+        };
+        file.writer.lock().unwrap().write_all(b"short").unwrap();
+        assert!(rotate_if_needed(&file, &config).unwrap().is_none());
+
+        file.writer.lock().unwrap().write_all(b" and now long enough").unwrap();
+        let rotated = rotate_if_needed(&file, &config).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), "short and now long enough");
+        assert_eq!(fs::read_to_string(&output).unwrap(), crate::CSV_HEADER);
+
+        let gz = compress_file(&rotated).unwrap();
+        assert!(!rotated.exists());
+        let mut text = String::new();
+        GzDecoder::new(File::open(gz).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "short and now long enough");
+    }
+
+    #[test]
+    fn test_split_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("data.csv");
+        let config = Config {
+            output: output.to_string_lossy().into_owned(),
+            format: crate::OutputFormat::Csv,
+            split_daily: Some(DayBoundary::Utc),
+            ..Config::default()
+        };
+        let file = OutputFile::open(&output, &config).unwrap();
+        let today = file.current();
+        assert_eq!(today, dated_path(&output, Utc::now().date_naive()));
+        file.writer.lock().unwrap().write_all(b"today\n").unwrap();
+
+        assert!(split_day(&file, &config, Utc::now()).unwrap().is_none());
+        let tomorrow = Utc::now() + chrono::TimeDelta::days(1);
+        assert_eq!(split_day(&file, &config, tomorrow).unwrap(), Some(today.clone()));
+        assert_eq!(file.current(), dated_path(&output, tomorrow.date_naive()));
+        assert_eq!(fs::read_to_string(&today).unwrap(), format!("{}today\n", crate::CSV_HEADER));
+        assert_eq!(fs::read_to_string(file.current()).unwrap(), crate::CSV_HEADER);
+    }
+
+    #[test]
+    fn test_rotate_template_opens_next_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("data_%Y%m%d_%H%M%S.txt");
+        let config = Config {
+            output: template.to_string_lossy().into_owned(),
+            rotate_max_bytes: Some(10),
+            ..Config::default()
+        };
+        let file = OutputFile::open(&template, &config).unwrap();
+        let first = file.current();
+        file.writer.lock().unwrap().write_all(b"long enough to rotate").unwrap();
+
+        // Закрытый файл остаётся под своим именем, следующий получает новое
+        assert_eq!(rotate_if_needed(&file, &config).unwrap(), Some(first.clone()));
+        assert_ne!(file.current(), first);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "long enough to rotate");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,276 @@
+use serde::Deserialize;
+use std::io;
+use std::sync::OnceLock;
+
+use crate::calculate_checksum;
+
+/// Тип поля в кадре
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl FieldType {
+    pub fn size(self) -> usize {
+        match self {
+            FieldType::U8 | FieldType::I8 => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => 4,
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+/// Описание одного поля: имя, смещение от начала кадра, тип и порядок байт
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSpec {
+    pub name: String,
+    pub offset: usize,
+    #[serde(rename = "type")]
+    pub ty: FieldType,
+    #[serde(default)]
+    pub endian: Endian,
+}
+
+impl FieldSpec {
+    pub fn new(name: &str, offset: usize, ty: FieldType) -> Self {
+        FieldSpec { name: name.to_string(), offset, ty, endian: Endian::Big }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+impl Value {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(v) => v as f64,
+            Value::UInt(v) => v as f64,
+            Value::Float(v) => v,
+        }
+    }
+
+    /// Целое значение; для дробных полей — None
+    pub fn as_i64(self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(v),
+            Value::UInt(v) => i64::try_from(v).ok(),
+            Value::Float(_) => None,
+        }
+    }
+}
+
+/// Разобранный кадр: значения в порядке описания полей
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    values: Vec<(String, Value)>,
+}
+
+impl Frame {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+
+    pub fn fields(&self) -> &[(String, Value)] {
+        &self.values
+    }
+}
+
+/// Раскладка кадра. Контрольная сумма всегда в последнем байте пакета
+/// и считается по всем байтам перед ней
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct PacketSchema {
+    pub fields: Vec<FieldSpec>,
+}
+
+impl PacketSchema {
+    pub fn new(fields: Vec<FieldSpec>) -> Self {
+        PacketSchema { fields }
+    }
+
+    /// Кадр Server1: timestamp u64, temperature f32, pressure i16
+    pub fn temp_pressure() -> &'static PacketSchema {
+        static SCHEMA: OnceLock<PacketSchema> = OnceLock::new();
+        SCHEMA.get_or_init(|| {
+            PacketSchema::new(vec![
+                FieldSpec::new("timestamp", 0, FieldType::U64),
+                FieldSpec::new("temperature", 8, FieldType::F32),
+                FieldSpec::new("pressure", 12, FieldType::I16),
+            ])
+        })
+    }
+
+    /// Кадр Server2: timestamp u64, x/y/z i32
+    pub fn accelerometer() -> &'static PacketSchema {
+        static SCHEMA: OnceLock<PacketSchema> = OnceLock::new();
+        SCHEMA.get_or_init(|| {
+            PacketSchema::new(vec![
+                FieldSpec::new("timestamp", 0, FieldType::U64),
+                FieldSpec::new("x", 8, FieldType::I32),
+                FieldSpec::new("y", 12, FieldType::I32),
+                FieldSpec::new("z", 16, FieldType::I32),
+            ])
+        })
+    }
+
+    /// Минимальный размер пакета: конец последнего поля плюс байт контрольной суммы
+    pub fn min_size(&self) -> usize {
+        self.fields.iter().map(|f| f.offset + f.ty.size()).max().unwrap_or(0) + 1
+    }
+
+    pub fn validate(&self, required: &[&str]) -> Result<(), String> {
+        for (i, field) in self.fields.iter().enumerate() {
+            if self.fields[..i].iter().any(|f| f.name == field.name) {
+                return Err(format!("duplicate field '{}'", field.name));
+            }
+        }
+        for name in required {
+            if !self.fields.iter().any(|f| f.name == *name) {
+                return Err(format!("missing field '{}'", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Несовпадение контрольной суммы - io::ErrorKind::InvalidData, по нему запускается ресинхронизация
+    pub fn decode(&self, packet: &[u8]) -> Result<Frame, Box<dyn std::error::Error + Send + Sync>> {
+        let min_size = self.min_size();
+        if packet.len() < min_size {
+            return Err(format!("Invalid packet size: expected at least {}, got {}",
+                               min_size, packet.len()).into());
+        }
+
+        let (data, checksum) = packet.split_at(packet.len() - 1);
+        let checksum = checksum[0];
+        let calculated = calculate_checksum(data);
+        if calculated != checksum {
+            let message = format!("Checksum mismatch: calculated {}, received {}", calculated, checksum);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        }
+
+        let values = self
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), read_field(data, field)))
+            .collect();
+        Ok(Frame { values })
+    }
+}
+
+fn read_field(data: &[u8], field: &FieldSpec) -> Value {
+    let mut bytes = [0u8; 8];
+    let size = field.ty.size();
+    bytes[..size].copy_from_slice(&data[field.offset..field.offset + size]);
+    if field.endian == Endian::Big {
+        bytes[..size].reverse();
+    }
+    // bytes[..size] теперь little-endian
+    macro_rules! le {
+        ($t:ty) => {
+            <$t>::from_le_bytes(bytes[..size].try_into().unwrap())
+        };
+    }
+    match field.ty {
+        FieldType::U8 => Value::UInt(le!(u8) as u64),
+        FieldType::I8 => Value::Int(le!(i8) as i64),
+        FieldType::U16 => Value::UInt(le!(u16) as u64),
+        FieldType::I16 => Value::Int(le!(i16) as i64),
+        FieldType::U32 => Value::UInt(le!(u32) as u64),
+        FieldType::I32 => Value::Int(le!(i32) as i64),
+        FieldType::U64 => Value::UInt(le!(u64)),
+        FieldType::I64 => Value::Int(le!(i64)),
+        FieldType::F32 => Value::Float(le!(f32) as f64),
+        FieldType::F64 => Value::Float(le!(f64)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SERVER1_PACKET_SIZE, SERVER2_PACKET_SIZE};
+
+    fn with_checksum(mut data: Vec<u8>) -> Vec<u8> {
+        data.push(calculate_checksum(&data));
+        data
+    }
+
+    #[test]
+    fn test_builtin_schema_sizes() {
+        assert_eq!(PacketSchema::temp_pressure().min_size(), SERVER1_PACKET_SIZE);
+        assert_eq!(PacketSchema::accelerometer().min_size(), SERVER2_PACKET_SIZE);
+    }
+
+    #[test]
+    fn test_decode_mixed_endianness() {
+        let schema = PacketSchema::new(vec![
+            FieldSpec::new("id", 0, FieldType::U8),
+            FieldSpec { endian: Endian::Little, ..FieldSpec::new("level", 1, FieldType::I16) },
+            FieldSpec::new("ratio", 3, FieldType::F64),
+        ]);
+        let mut data = vec![7];
+        data.extend_from_slice(&(-300i16).to_le_bytes());
+        data.extend_from_slice(&0.25f64.to_be_bytes());
+        let frame = schema.decode(&with_checksum(data)).unwrap();
+
+        assert_eq!(frame.get("id"), Some(Value::UInt(7)));
+        assert_eq!(frame.get("level"), Some(Value::Int(-300)));
+        assert_eq!(frame.get("ratio"), Some(Value::Float(0.25)));
+        assert_eq!(frame.get("missing"), None);
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let schema = PacketSchema::accelerometer();
+        assert!(schema.decode(&[0; 5]).unwrap_err().to_string().contains("Invalid packet size"));
+        let err = schema.decode(&[1; 21]).unwrap_err();
+        assert!(err.to_string().contains("Checksum"));
+        assert_eq!(err.downcast_ref::<io::Error>().map(io::Error::kind), Some(io::ErrorKind::InvalidData));
+
+        assert!(schema.validate(&["timestamp", "x"]).is_ok());
+        assert!(schema.validate(&["temperature"]).unwrap_err().contains("missing"));
+        let duplicated = PacketSchema::new(vec![
+            FieldSpec::new("x", 0, FieldType::U8),
+            FieldSpec::new("x", 1, FieldType::U8),
+        ]);
+        assert!(duplicated.validate(&[]).unwrap_err().contains("duplicate"));
+    }
+
+    #[test]
+    fn test_schema_from_toml() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            fields: PacketSchema,
+        }
+        let wrapper: Wrapper = toml::from_str(
+            "fields = [\n  { name = \"timestamp\", offset = 0, type = \"u64\" },\n  { name = \"x\", offset = 8, type = \"i16\", endian = \"little\" },\n]\n",
+        )
+        .unwrap();
+        assert_eq!(wrapper.fields.fields[1].ty, FieldType::I16);
+        assert_eq!(wrapper.fields.fields[1].endian, Endian::Little);
+        assert_eq!(wrapper.fields.min_size(), 11);
+    }
+}
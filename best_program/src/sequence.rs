@@ -0,0 +1,117 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::time::Duration;
+
+/// Сколько кадров подряд из прошлого считать переводом часов сервера назад
+const CLOCK_RESET_AFTER: u32 = 5;
+
+/// Место отсчёта относительно предыдущего принятого
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    Next,
+    /// Следующий, но после перерыва дольше порога
+    Gap(Gap),
+    /// Время совпадает с предыдущим: сервер повторил кадр
+    Duplicate,
+    /// Время раньше предыдущего
+    OutOfOrder,
+}
+
+/// Перерыв между соседними принятыми отсчётами
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Gap {
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+}
+
+/// Последнее принятое время сервера; живёт дольше соединения, чтобы ловить повторы после переподключения
+#[derive(Debug)]
+pub struct SequenceTracker {
+    gap_threshold: TimeDelta,
+    last: Option<DateTime<Utc>>,
+    backwards: u32,
+}
+
+impl SequenceTracker {
+    pub fn new(gap_threshold: Duration) -> Self {
+        SequenceTracker {
+            gap_threshold: TimeDelta::from_std(gap_threshold).unwrap_or(TimeDelta::MAX),
+            last: None,
+            backwards: 0,
+        }
+    }
+
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        self.last
+    }
+
+    /// Принимает Next и Gap. Если кадры из прошлого идут подряд, часы сервера
+    /// переведены назад, и новое время принимается за точку отсчёта
+    pub fn check(&mut self, timestamp: DateTime<Utc>) -> Sequence {
+        let verdict = match self.last {
+            Some(last) if timestamp == last => Sequence::Duplicate,
+            Some(last) if timestamp < last && self.backwards + 1 < CLOCK_RESET_AFTER => {
+                self.backwards += 1;
+                return Sequence::OutOfOrder;
+            }
+            Some(last) if timestamp - last > self.gap_threshold => Sequence::Gap(Gap { start: last, end: timestamp }),
+            _ => Sequence::Next,
+        };
+        if verdict != Sequence::Duplicate {
+            self.last = Some(timestamp);
+        }
+        self.backwards = 0;
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_duplicates_and_out_of_order() {
+        let mut tracker = SequenceTracker::new(Duration::from_secs(60));
+        assert_eq!(tracker.check(at(10)), Sequence::Next);
+        assert_eq!(tracker.check(at(10)), Sequence::Duplicate);
+        assert_eq!(tracker.check(at(11)), Sequence::Next);
+        assert_eq!(tracker.check(at(9)), Sequence::OutOfOrder);
+        assert_eq!(tracker.check(at(11)), Sequence::Duplicate);
+        assert_eq!(tracker.check(at(12)), Sequence::Next);
+        assert_eq!(tracker.last(), Some(at(12)));
+    }
+
+    #[test]
+    fn test_clock_set_back_is_accepted() {
+        let mut tracker = SequenceTracker::new(Duration::from_secs(60));
+        tracker.check(at(1000));
+        for secs in 1..CLOCK_RESET_AFTER as i64 {
+            assert_eq!(tracker.check(at(secs)), Sequence::OutOfOrder);
+        }
+        assert_eq!(tracker.check(at(100)), Sequence::Next);
+        assert_eq!(tracker.check(at(101)), Sequence::Next);
+        assert_eq!(tracker.last(), Some(at(101)));
+    }
+
+    #[test]
+    fn test_gap_over_threshold() {
+        let mut tracker = SequenceTracker::new(Duration::from_secs(60));
+        assert_eq!(tracker.check(at(0)), Sequence::Next);
+        assert_eq!(tracker.check(at(60)), Sequence::Next);
+        let Sequence::Gap(gap) = tracker.check(at(200)) else {
+            panic!("gap expected");
+        };
+        assert_eq!((gap.start, gap.end), (at(60), at(200)));
+        assert_eq!(gap.duration().num_seconds(), 140);
+        assert_eq!(tracker.check(at(201)), Sequence::Next);
+    }
+}
@@ -0,0 +1,424 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::aggregate::Aggregator;
+use crate::moving_average::{self, AverageWindows, MovingAverages};
+use crate::units::{self, Units};
+use crate::write_queue::WriteQueue;
+use crate::{format_csv_with, format_data_with, Config, OutputFormat, SampleHook, SensorData, ServerConfig, ServerStats};
+
+/// Получатель отсчётов одного сервера. Каждый worker владеет своими приёмниками
+pub trait DataSink: Send {
+    fn write(&mut self, data: &SensorData) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Конец сбора: дописать накопленное и сбросить буферы
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Кодирование отсчётов в формат вывода; в режиме агрегации — сводка за окно
+struct Records {
+    format: OutputFormat,
+    aggregator: Option<Aggregator>,
+    units: Units,
+    averages: MovingAverages,
+    /// В CSV есть столбцы *_avg: их пишут все серверы, даже без своих средних
+    average_columns: bool,
+}
+
+impl Records {
+    fn new(config: &Config) -> Self {
+        Records {
+            format: config.format,
+            aggregator: config.aggregate_window().map(Aggregator::new),
+            units: Units::new(),
+            averages: MovingAverages::new(&AverageWindows::new()),
+            average_columns: config.format == OutputFormat::Csv && config.has_moving_averages(),
+        }
+    }
+
+    fn set_server(&mut self, server: &ServerConfig) {
+        self.units = server.units.clone();
+        self.averages = MovingAverages::new(&server.moving_average);
+    }
+
+    /// Текст и CSV со скользящими средними после сырых значений
+    fn encode_with_averages(&mut self, data: &SensorData) -> Vec<u8> {
+        let averages = self.averages.push(data);
+        let mut line = match self.format {
+            OutputFormat::Text => format_data_with(data, &self.units),
+            OutputFormat::Csv => format_csv_with(data, &self.units),
+            OutputFormat::Binary => return self.format.encode(data),
+        };
+        if self.average_columns {
+            moving_average::append_csv(&mut line, &averages, &self.units);
+        } else {
+            moving_average::append_text(&mut line, &averages, &self.units);
+        }
+        line.into_bytes()
+    }
+
+    /// None — отсчёт ушёл в незавершённое окно
+    fn encode(&mut self, data: &SensorData) -> Option<Vec<u8>> {
+        match &mut self.aggregator {
+            Some(aggregator) => aggregator
+                .push(data)
+                .map(|done| units::convert_aggregate(done, &self.units).encode(self.format)),
+            None if self.averages.is_empty() && !self.average_columns => Some(self.format.encode_with(data, &self.units)),
+            None => Some(self.encode_with_averages(data)),
+        }
+    }
+
+    /// Незавершённое окно агрегации
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        let done = self.aggregator.as_mut()?.flush()?;
+        Some(units::convert_aggregate(done, &self.units).encode(self.format))
+    }
+}
+
+fn lock_error<T>(_: T) -> io::Error {
+    io::Error::other("Writer lock poisoned")
+}
+
+enum Target {
+    /// Запись под общим Mutex прямо из worker
+    Shared(Arc<Mutex<BufWriter<File>>>),
+    /// Через поток записи; ожидание места и потери учитываются в статистике сервера
+    Queued(WriteQueue, Arc<ServerStats>),
+}
+
+/// Запись в общий для всех серверов выходной файл
+pub struct FileSink {
+    target: Target,
+    records: Records,
+}
+
+impl FileSink {
+    pub fn new(writer: Arc<Mutex<BufWriter<File>>>, config: &Config) -> Self {
+        FileSink { target: Target::Shared(writer), records: Records::new(config) }
+    }
+
+    /// Запись через поток записи файла: worker не ждёт диска, пока в очереди есть место
+    pub fn queued(queue: WriteQueue, stats: Arc<ServerStats>, config: &Config) -> Self {
+        FileSink { target: Target::Queued(queue, stats), records: Records::new(config) }
+    }
+
+    /// Пересчёт полей в физические единицы и скользящие средние сервера
+    pub fn with_server(mut self, server: &ServerConfig) -> Self {
+        self.records.set_server(server);
+        self
+    }
+
+    fn write_bytes(&self, record: Vec<u8>) -> io::Result<()> {
+        match &self.target {
+            Target::Shared(writer) => writer.lock().map_err(lock_error)?.write_all(&record),
+            Target::Queued(queue, stats) => queue.write(record, stats),
+        }
+    }
+}
+
+impl DataSink for FileSink {
+    fn write(&mut self, data: &SensorData) -> io::Result<()> {
+        match self.records.encode(data) {
+            Some(record) => self.write_bytes(record),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.target {
+            Target::Shared(writer) => writer.lock().map_err(lock_error)?.flush(),
+            Target::Queued(queue, _) => queue.flush(),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(record) = self.records.finish() {
+            self.write_bytes(record)?;
+        }
+        self.flush()
+    }
+}
+
+/// Вывод отсчётов в консоль в формате выходного файла (кроме двоичного)
+pub struct StdoutSink {
+    records: Records,
+}
+
+impl StdoutSink {
+    pub fn new(config: &Config) -> Self {
+        StdoutSink { records: Records::new(config) }
+    }
+
+    pub fn with_server(mut self, server: &ServerConfig) -> Self {
+        self.records.set_server(server);
+        self
+    }
+}
+
+impl DataSink for StdoutSink {
+    fn write(&mut self, data: &SensorData) -> io::Result<()> {
+        match self.records.encode(data) {
+            Some(record) => io::stdout().lock().write_all(&record),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(record) = self.records.finish() {
+            io::stdout().lock().write_all(&record)?;
+        }
+        self.flush()
+    }
+}
+
+/// Общий для всех серверов обработчик (SQLite, MQTT, история) в роли приёмника одного сервера
+pub struct HookSink {
+    server: String,
+    hook: SampleHook,
+}
+
+impl HookSink {
+    pub fn new(server: &str, hook: SampleHook) -> Self {
+        HookSink { server: server.to_string(), hook }
+    }
+}
+
+impl DataSink for HookSink {
+    fn write(&mut self, data: &SensorData) -> io::Result<()> {
+        (self.hook)(&self.server, data);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Передача отсчётов одного из параллельных соединений worker, который пишет их в свои приёмники
+pub(crate) struct ChannelSink {
+    sender: mpsc::UnboundedSender<SensorData>,
+}
+
+impl ChannelSink {
+    pub(crate) fn new(sender: mpsc::UnboundedSender<SensorData>) -> Self {
+        ChannelSink { sender }
+    }
+}
+
+impl DataSink for ChannelSink {
+    fn write(&mut self, data: &SensorData) -> io::Result<()> {
+        self.sender.send(data.clone()).map_err(|_| io::Error::other("Worker stopped"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Общий для всех worker предел числа записанных отсчётов (--max-samples)
+pub struct SampleLimit {
+    max: u64,
+    taken: AtomicU64,
+    shutdown: CancellationToken,
+}
+
+impl SampleLimit {
+    pub fn new(max: u64, shutdown: CancellationToken) -> Self {
+        SampleLimit { max, taken: AtomicU64::new(0), shutdown }
+    }
+
+    /// true — отсчёт укладывается в предел; последний разрешённый отменяет shutdown
+    pub fn take(&self) -> bool {
+        let taken = self.taken.fetch_add(1, Ordering::Relaxed) + 1;
+        if taken == self.max {
+            tracing::info!(samples = self.max, "Sample limit reached. Shutting down...");
+            self.shutdown.cancel();
+        }
+        taken <= self.max
+    }
+}
+
+/// Рассылка каждого отсчёта всем приёмникам по порядку. Ошибка одного приёмника
+/// не мешает остальным; возвращается первая из ошибок
+#[derive(Default)]
+pub struct FanOut {
+    sinks: Vec<Box<dyn DataSink>>,
+    limit: Option<Arc<SampleLimit>>,
+}
+
+impl FanOut {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Отсчёты сверх общего предела отбрасываются, не доходя до приёмников
+    pub fn set_limit(&mut self, limit: Arc<SampleLimit>) {
+        self.limit = Some(limit);
+    }
+
+    pub fn push(&mut self, sink: impl DataSink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    fn each(&mut self, mut f: impl FnMut(&mut dyn DataSink) -> io::Result<()>) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            let outcome = f(sink.as_mut());
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
+
+impl DataSink for FanOut {
+    fn write(&mut self, data: &SensorData) -> io::Result<()> {
+        if self.limit.as_ref().is_some_and(|limit| !limit.take()) {
+            return Ok(());
+        }
+        self.each(|sink| sink.write(data))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.each(|sink| sink.flush())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.each(|sink| sink.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SensorKind;
+    use chrono::DateTime;
+    use tempfile::NamedTempFile;
+
+    struct Failing;
+
+    impl DataSink for Failing {
+        fn write(&mut self, _: &SensorData) -> io::Result<()> {
+            Err(io::Error::other("broken sink"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn sample(millis: i64) -> SensorData {
+        let timestamp = DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap();
+        SensorData::TempPressure { timestamp, temperature: 20.0, pressure: 1000 }
+    }
+
+    #[test]
+    fn test_fan_out_reaches_every_sink() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_hook = seen.clone();
+        let hook: SampleHook = Arc::new(move |server, _| seen_by_hook.lock().unwrap().push(server.to_string()));
+
+        let mut sinks = FanOut::new();
+        sinks.push(Failing);
+        sinks.push(FileSink::new(writer, &Config { format: OutputFormat::Csv, ..Config::default() }));
+        sinks.push(HookSink::new("Server1", hook));
+        assert_eq!(sinks.len(), 3);
+
+        assert_eq!(sinks.write(&sample(0)).unwrap_err().to_string(), "broken sink");
+        sinks.finish().unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), ["Server1"]);
+        let content = std::fs::read_to_string(temp_file.path()).unwrap();
+        assert_eq!(content, "2023-11-14T22:13:20.000000Z,S1,20.00,1000,,,\n");
+    }
+
+    #[test]
+    fn test_file_sink_aggregates() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let config = Config { format: OutputFormat::Csv, aggregate_window_ms: Some(1000), ..Config::default() };
+        let mut sink = FileSink::new(writer, &config);
+
+        for millis in [0, 300, 600, 1200] {
+            sink.write(&sample(millis)).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let content = std::fs::read_to_string(temp_file.path()).unwrap();
+        assert_eq!(content.lines().count(), 4);
+        assert!(content.starts_with("2023-11-14T22:13:20.000000Z,S1,3,temperature,20,20,20\n"));
+        assert!(content.contains("2023-11-14T22:13:21.000000Z,S1,1,pressure,1000,1000,1000\n"));
+    }
+
+    #[test]
+    fn test_file_sink_moving_average_columns() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let mut averaged = ServerConfig::new("Server1", "127.0.0.1:5123", SensorKind::TempPressure);
+        averaged.moving_average.insert("pressure".into(), 2);
+        let plain = ServerConfig::new("Server3", "127.0.0.1:5125", SensorKind::TempPressure);
+        let config = Config { format: OutputFormat::Csv, servers: vec![averaged, plain], ..Config::default() };
+        let mut first = FileSink::new(writer.clone(), &config).with_server(&config.servers[0]);
+        let mut second = FileSink::new(writer, &config).with_server(&config.servers[1]);
+
+        first.write(&sample(0)).unwrap();
+        first.write(&SensorData::TempPressure { timestamp: sample(1).timestamp(), temperature: 20.0, pressure: 1003 }).unwrap();
+        second.write(&sample(2)).unwrap();
+        first.finish().unwrap();
+
+        let content = std::fs::read_to_string(temp_file.path()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "2023-11-14T22:13:20.000000Z,S1,20.00,1000,,,,,1000.00,,,");
+        assert_eq!(lines[1], "2023-11-14T22:13:20.001000Z,S1,20.00,1003,,,,,1001.50,,,");
+        // Сервер без средних пишет те же столбцы пустыми
+        assert_eq!(lines[2], "2023-11-14T22:13:20.002000Z,S1,20.00,1000,,,,,,,,");
+    }
+
+    #[test]
+    fn test_sample_limit_shared_between_workers() {
+        let shutdown = CancellationToken::new();
+        let limit = Arc::new(SampleLimit::new(3, shutdown.clone()));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut workers: Vec<FanOut> = ["Server1", "Server2"]
+            .iter()
+            .map(|name| {
+                let seen = seen.clone();
+                let mut sink = FanOut::new();
+                sink.push(HookSink::new(name, Arc::new(move |server, _| seen.lock().unwrap().push(server.to_string()))));
+                sink.set_limit(limit.clone());
+                sink
+            })
+            .collect();
+
+        for millis in 0..3 {
+            for sink in &mut workers {
+                sink.write(&sample(millis)).unwrap();
+            }
+            // Предел достигнут на третьем отсчёте
+            assert_eq!(shutdown.is_cancelled(), millis >= 1);
+        }
+        assert_eq!(*seen.lock().unwrap(), ["Server1", "Server2", "Server1"]);
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::ServerStats;
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    timestamp: DateTime<Utc>,
+    started_at: DateTime<Utc>,
+    uptime_secs: u64,
+    /// false в последнем снимке, записанном при остановке
+    running: bool,
+    servers: BTreeMap<&'a str, ServerSnapshot<'a>>,
+}
+
+#[derive(Serialize)]
+struct ServerSnapshot<'a> {
+    #[serde(flatten)]
+    counters: &'a ServerStats,
+    packets_per_sec: f64,
+    checksum_errors_per_sec: f64,
+}
+
+/// Пакеты и ошибки контрольной суммы каждого сервера
+fn counts(stats: &[(String, Arc<ServerStats>)]) -> Vec<(u64, u64)> {
+    stats
+        .iter()
+        .map(|(_, s)| (s.packets_received.load(Ordering::Relaxed), s.checksum_errors.load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// Файл со счётчиками в JSON для внешнего мониторинга. Скорости считаются
+/// с предыдущего снимка (для первого — с запуска)
+pub struct StatsFile {
+    path: PathBuf,
+    started: Instant,
+    started_at: DateTime<Utc>,
+    /// Время и счётчики (пакеты, ошибки контрольной суммы) предыдущего снимка
+    previous: (Instant, Vec<(u64, u64)>),
+}
+
+impl StatsFile {
+    pub fn new(path: &Path) -> Self {
+        let started = Instant::now();
+        StatsFile { path: path.to_path_buf(), started, started_at: Utc::now(), previous: (started, Vec::new()) }
+    }
+
+    /// Первая скорость считается от текущих значений, а не от нуля: счётчики могли начаться с итогов state_file
+    pub fn starting_from(mut self, stats: &[(String, Arc<ServerStats>)]) -> Self {
+        self.previous.1 = counts(stats);
+        self
+    }
+
+    /// Пишет снимок во временный файл и переименовывает его, чтобы читатель не увидел половину файла
+    pub fn write(&mut self, stats: &[(String, Arc<ServerStats>)], running: bool) -> io::Result<()> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.previous.0).as_secs_f64();
+        let rate = |current: u64, previous: u64| {
+            if elapsed > 0.0 { current.saturating_sub(previous) as f64 / elapsed } else { 0.0 }
+        };
+        let counts = counts(stats);
+        let servers = stats
+            .iter()
+            .zip(&counts)
+            .enumerate()
+            .map(|(index, ((name, s), &(packets, checksum_errors)))| {
+                let (previous_packets, previous_errors) = self.previous.1.get(index).copied().unwrap_or_default();
+                let server = ServerSnapshot {
+                    counters: s.as_ref(),
+                    packets_per_sec: rate(packets, previous_packets),
+                    checksum_errors_per_sec: rate(checksum_errors, previous_errors),
+                };
+                (name.as_str(), server)
+            })
+            .collect();
+        let snapshot = Snapshot {
+            timestamp: Utc::now(),
+            started_at: self.started_at,
+            uptime_secs: now.duration_since(self.started).as_secs(),
+            running,
+            servers,
+        };
+        let json = serde_json::to_vec_pretty(&snapshot).map_err(io::Error::other)?;
+
+        let mut temp = self.path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &self.path)?;
+        self.previous = (now, counts);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_stats_file_counters_and_rates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.json");
+        let stats = vec![("Server1".to_string(), Arc::new(ServerStats::new()))];
+        let mut file = StatsFile::new(&path);
+
+        stats[0].1.packets_received.store(50, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        file.write(&stats, true).unwrap();
+        stats[0].1.packets_received.store(60, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        file.write(&stats, false).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["running"], false);
+        let server = &json["servers"]["Server1"];
+        assert_eq!(server["packets_received"], 60);
+        assert_eq!(server["checksum_errors"], 0);
+        assert!(server["latency"]["p99_ms"].is_number());
+        // 10 пакетов примерно за 0.1 с
+        let rate = server["packets_per_sec"].as_f64().unwrap();
+        assert!(rate > 20.0 && rate <= 100.0, "{}", rate);
+        // Временный файл не остаётся
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,104 @@
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{SampleHook, SensorData};
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    CREATE TABLE IF NOT EXISTS temp_pressure (
+        timestamp_us INTEGER NOT NULL,
+        server TEXT NOT NULL,
+        temperature REAL NOT NULL,
+        pressure INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS temp_pressure_ts ON temp_pressure (timestamp_us);
+    CREATE TABLE IF NOT EXISTS accelerometer (
+        timestamp_us INTEGER NOT NULL,
+        server TEXT NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        z INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS accelerometer_ts ON accelerometer (timestamp_us);
+";
+
+/// Запись отсчётов в SQLite: по таблице на тип датчика, время в микросекундах UTC
+pub struct SqliteSink {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Cannot open SQLite database {}: {}", path.display(), e))?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteSink { conn: Mutex::new(conn) })
+    }
+
+    pub fn insert(&self, server: &str, data: &SensorData) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().map_err(|_| "SQLite connection lock poisoned")?;
+        match data {
+            SensorData::TempPressure { timestamp, temperature, pressure } => {
+                conn.prepare_cached(
+                    "INSERT INTO temp_pressure (timestamp_us, server, temperature, pressure) VALUES (?1, ?2, ?3, ?4)",
+                )?
+                .execute(params![timestamp.timestamp_micros(), server, temperature, pressure])?;
+            }
+            SensorData::Accelerometer { timestamp, x, y, z } => {
+                conn.prepare_cached(
+                    "INSERT INTO accelerometer (timestamp_us, server, x, y, z) VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?
+                .execute(params![timestamp.timestamp_micros(), server, x, y, z])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Обработчик для worker_task; ошибки вставки печатаются и не останавливают сбор
+    pub fn hook(self: Arc<Self>) -> SampleHook {
+        Arc::new(move |server, data| {
+            if let Err(e) = self.insert(server, data) {
+                tracing::error!(server, error = %e, "SQLite insert failed");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_sqlite_sink_inserts_by_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.db");
+        let sink = Arc::new(SqliteSink::open(&path).unwrap());
+        let hook = sink.clone().hook();
+
+        for i in 0..3 {
+            let timestamp = DateTime::from_timestamp_micros(1_000_000 * i).unwrap();
+            hook("Server1", &SensorData::TempPressure { timestamp, temperature: 20.5, pressure: 1000 });
+        }
+        let timestamp = DateTime::from_timestamp_micros(1_500_000).unwrap();
+        hook("Server2", &SensorData::Accelerometer { timestamp, x: 1, y: -2, z: 3 });
+
+        let conn = sink.conn.lock().unwrap();
+        let in_range: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM temp_pressure WHERE timestamp_us BETWEEN ?1 AND ?2",
+                params![1_000_000, 2_000_000],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(in_range, 2);
+        let accel: (String, i32, i32, i32) = conn
+            .query_row("SELECT server, x, y, z FROM accelerometer", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        assert_eq!(accel, ("Server2".to_string(), 1, -2, 3));
+    }
+}
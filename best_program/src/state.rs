@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::ServerStats;
+
+/// Итоги счётчиков по серверам, переживающие перезапуск логгера
+#[derive(Serialize, Deserialize)]
+struct State {
+    saved_at: DateTime<Utc>,
+    servers: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// Добавляет к счётчикам итоги серверов с теми же именами; неизвестные имена пропускаются.
+/// Без файла возвращает None, иначе время сохранения
+pub fn restore(path: &Path, stats: &[(String, Arc<ServerStats>)]) -> io::Result<Option<DateTime<Utc>>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let state: State = serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for (name, server) in stats {
+        let Some(totals) = state.servers.get(name) else {
+            continue;
+        };
+        for (counter, value) in server.counters() {
+            if let Some(total) = totals.get(counter) {
+                value.fetch_add(*total, Ordering::Relaxed);
+            }
+        }
+    }
+    Ok(Some(state.saved_at))
+}
+
+/// Пишет итоги во временный файл и переименовывает его: при сбое остаётся прежний файл целиком.
+/// Серверов, которых больше нет в конфигурации, в файле не остаётся
+pub fn save(path: &Path, stats: &[(String, Arc<ServerStats>)]) -> io::Result<()> {
+    let servers = stats
+        .iter()
+        .map(|(name, server)| {
+            let totals = server.counters().into_iter().map(|(counter, value)| (counter.to_string(), value.load(Ordering::Relaxed)));
+            (name.clone(), totals.collect())
+        })
+        .collect();
+    let json = serde_json::to_vec_pretty(&State { saved_at: Utc::now(), servers }).map_err(io::Error::other)?;
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, json)?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totals_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let first_run = vec![
+            ("Server1".to_string(), Arc::new(ServerStats::new())),
+            ("Old".to_string(), Arc::new(ServerStats::new())),
+        ];
+        assert!(restore(&path, &first_run).unwrap().is_none());
+        first_run[0].1.packets_received.store(1000, Ordering::Relaxed);
+        first_run[0].1.checksum_errors.store(7, Ordering::Relaxed);
+        first_run[0].1.clock_offset_ms.store(250, Ordering::Relaxed);
+        save(&path, &first_run).unwrap();
+
+        let second_run = vec![
+            ("Server1".to_string(), Arc::new(ServerStats::new())),
+            ("Server2".to_string(), Arc::new(ServerStats::new())),
+        ];
+        second_run[0].1.packets_received.store(5, Ordering::Relaxed);
+        assert!(restore(&path, &second_run).unwrap().is_some());
+        assert_eq!(second_run[0].1.packets_received.load(Ordering::Relaxed), 1005);
+        assert_eq!(second_run[0].1.checksum_errors.load(Ordering::Relaxed), 7);
+        // Смещение часов — мгновенное значение, оно не переносится
+        assert_eq!(second_run[0].1.clock_offset_ms.load(Ordering::Relaxed), 0);
+        assert_eq!(second_run[1].1.packets_received.load(Ordering::Relaxed), 0);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(restore(&path, &second_run).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{crypto, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+use crate::{authenticate, create_optimized_socket, Config, ServerConfig, ServerStats, TlsConfig};
+use crate::CONNECT_TIMEOUT_SECS;
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error + Send + Sync>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Cannot read certificates from {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates in {}", path).into());
+    }
+    Ok(certs)
+}
+
+/// Настройки клиента rustls по конфигурации сервера
+pub fn client_config(tls: &TlsConfig) -> Result<ClientConfig, Box<dyn std::error::Error + Send + Sync>> {
+    let mut roots = RootCertStore::empty();
+    match &tls.ca_file {
+        Some(path) => {
+            for cert in read_certs(path)? {
+                roots.add(cert)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let builder = ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let config = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            let key = PrivateKeyDer::from_pem_file(key)
+                .map_err(|e| format!("Cannot read private key from {}: {}", key, e))?;
+            builder.with_client_auth_cert(read_certs(cert)?, key)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    Ok(config)
+}
+
+fn server_name(server: &ServerConfig, tls: &TlsConfig) -> Result<ServerName<'static>, Box<dyn std::error::Error + Send + Sync>> {
+    match &tls.server_name {
+        Some(name) => Ok(ServerName::try_from(name.clone())?),
+        None => {
+            let addr: SocketAddr = server.address.parse()?;
+            Ok(ServerName::IpAddress(addr.ip().into()))
+        }
+    }
+}
+
+/// Подключение, TLS-рукопожатие и авторизация ключом внутри защищённого канала.
+/// Ошибки рукопожатия учитываются в stats.connection_errors
+pub async fn connect(
+    server: &ServerConfig,
+    tls: &TlsConfig,
+    stats: &ServerStats,
+    config: &Config,
+) -> Result<TlsStream<TcpStream>, Box<dyn std::error::Error + Send + Sync>> {
+    let connector = TlsConnector::from(Arc::new(client_config(tls)?));
+    let name = server_name(server, tls)?;
+    let tcp = create_optimized_socket(&server.address).await?;
+
+    let handshake = timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS), connector.connect(name, tcp)).await;
+    let mut stream = match handshake {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(format!("TLS handshake failed: {}", e).into());
+        }
+        Err(_) => {
+            stats.connection_errors.fetch_add(1, Ordering::Relaxed);
+            return Err("TLS handshake timeout".into());
+        }
+    };
+
+    authenticate(&mut stream, server, stats, config).await?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_checksum, Connection, SensorData, SensorKind, GET_CMD, KEY};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::ServerConfig as RustlsServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// Однократный TLS-сервер: авторизация и один кадр Server1
+    async fn spawn_tls_server(port: u16) {
+        let certs = read_certs(&testdata("server.pem")).unwrap();
+        let key = PrivateKeyDer::from_pem_file(testdata("server.key")).unwrap();
+        let config = RustlsServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await.unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let Ok(mut stream) = acceptor.accept(tcp).await else {
+                return;
+            };
+            let mut auth_buf = vec![0u8; KEY.len()];
+            stream.read_exact(&mut auth_buf).await.unwrap();
+            stream.write_all(b"AUTH_OK\n").await.unwrap();
+
+            let mut cmd_buf = vec![0u8; GET_CMD.len()];
+            stream.read_exact(&mut cmd_buf).await.unwrap();
+            let mut frame = Vec::new();
+            frame.extend_from_slice(&1700000000000000u64.to_be_bytes());
+            frame.extend_from_slice(&18.0f32.to_be_bytes());
+            frame.extend_from_slice(&1001i16.to_be_bytes());
+            frame.push(calculate_checksum(&frame));
+            stream.write_all(&frame).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        });
+    }
+
+    fn tls_server(port: u16, tls: TlsConfig) -> ServerConfig {
+        ServerConfig {
+            tls: Some(tls),
+            ..ServerConfig::new("Secure", &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_fetch_with_custom_ca() {
+        let port = 19024;
+        spawn_tls_server(port).await;
+
+        let server = tls_server(port, TlsConfig { ca_file: Some(testdata("ca.pem")), ..TlsConfig::default() });
+        let stats = ServerStats::new();
+        let config = Config::default();
+        let mut connection = Connection::open(&server, &stats, &config).await.unwrap();
+
+        match connection.fetch(&server, &stats, &config).await {
+            Ok(SensorData::TempPressure { temperature, pressure, .. }) => {
+                assert_eq!((temperature, pressure), (18.0, 1001));
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(other) => panic!("Unexpected data: {:?}", other),
+        }
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tls_untrusted_certificate_counts_connection_error() {
+        let port = 19025;
+        spawn_tls_server(port).await;
+
+        // Тестовый CA не входит в корни webpki
+        let server = tls_server(port, TlsConfig::default());
+        let stats = ServerStats::new();
+        let err = match Connection::open(&server, &stats, &Config::default()).await {
+            Ok(_) => panic!("Handshake with an untrusted certificate must fail"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("TLS handshake failed"));
+        assert_eq!(stats.connection_errors.load(Ordering::Relaxed), 1);
+
+        let missing = TlsConfig { ca_file: Some(testdata("missing.pem")), ..TlsConfig::default() };
+        assert!(client_config(&missing).is_err());
+    }
+}
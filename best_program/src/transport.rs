@@ -0,0 +1,205 @@
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+use crate::logging::EVENTS;
+use crate::{connect_and_auth, fetch_packet, parse_with_schema, probe, Config, KeepaliveSpec, SensorData, ServerConfig, ServerStats};
+
+/// Сколько раз повторить запрос по UDP, прежде чем вернуть ошибку
+const UDP_MAX_ATTEMPTS: u32 = 3;
+
+/// Способ доставки кадров от сервера
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Постоянное соединение с авторизацией ключом, при заданном tls — поверх TLS
+    #[default]
+    Tcp,
+    /// Запрос-ответ датаграммами: один кадр на датаграмму, без авторизации
+    Udp,
+}
+
+/// Открытое соединение с сервером независимо от транспорта
+pub enum Connection {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+    Udp(UdpFetcher),
+}
+
+impl Connection {
+    pub async fn open(
+        server: &ServerConfig,
+        stats: &ServerStats,
+        config: &Config,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match server.transport {
+            Transport::Tcp => match &server.tls {
+                #[cfg(feature = "tls")]
+                Some(tls) => Ok(Connection::Tls(Box::new(crate::tls::connect(server, tls, stats, config).await?))),
+                #[cfg(not(feature = "tls"))]
+                Some(_) => Err("TLS requested but the logger was built without the `tls` feature".into()),
+                None => Ok(Connection::Tcp(connect_and_auth(server, stats, config).await?)),
+            },
+            Transport::Udp => Ok(Connection::Udp(UdpFetcher::connect(&server.address).await?)),
+        }
+    }
+
+    pub async fn fetch(
+        &mut self,
+        server: &ServerConfig,
+        stats: &ServerStats,
+        config: &Config,
+    ) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Connection::Tcp(stream) => fetch_packet(stream, server, stats, config).await,
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => fetch_packet(&mut **stream, server, stats, config).await,
+            Connection::Udp(fetcher) => fetcher.fetch(server, stats, config).await,
+        }
+    }
+
+    /// Проверка keepalive; для UDP не применяется (соединения нет)
+    pub async fn probe(
+        &mut self,
+        server: &ServerConfig,
+        keepalive: &KeepaliveSpec,
+        config: &Config,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Connection::Tcp(stream) => probe(stream, server, keepalive, config).await,
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => probe(&mut **stream, server, keepalive, config).await,
+            Connection::Udp(_) => Ok(()),
+        }
+    }
+}
+
+/// UDP-сокет, привязанный к адресу сервера
+pub struct UdpFetcher {
+    socket: UdpSocket,
+}
+
+impl UdpFetcher {
+    pub async fn connect(address: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let addr: SocketAddr = address.parse()?;
+        let local = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(addr).await?;
+        tracing::info!(address, "UDP socket ready");
+        Ok(UdpFetcher { socket })
+    }
+
+    /// Отправляет команду запроса и ждёт датаграмму с кадром; при таймауте или битом кадре повторяет запрос
+    pub async fn fetch(
+        &self,
+        server: &ServerConfig,
+        stats: &ServerStats,
+        config: &Config,
+    ) -> Result<SensorData, Box<dyn std::error::Error + Send + Sync>> {
+        let packet_size = server.packet_size();
+        // Запас в один байт отличает слишком длинную датаграмму от кадра нужного размера
+        let mut buf = vec![0u8; packet_size + 1];
+        let mut last_error: Box<dyn std::error::Error + Send + Sync> = "no attempts made".into();
+
+        for attempt in 1..=UDP_MAX_ATTEMPTS {
+            let started = Instant::now();
+            self.socket.send(server.protocol.request()).await?;
+            let n = match timeout(config.read_timeout(), self.socket.recv(&mut buf)).await {
+                Ok(result) => {
+                    let n = result?;
+                    stats.latency.record(started.elapsed());
+                    n
+                }
+                Err(_) => {
+                    stats.timeout_errors.fetch_add(1, Ordering::Relaxed);
+                    last_error = format!("Read timeout: no datagram after {} attempts", attempt).into();
+                    continue;
+                }
+            };
+            if n != packet_size {
+                last_error = format!("Unexpected datagram size: expected {}, got {}", packet_size, n).into();
+                continue;
+            }
+            match parse_with_schema(server.kind, server.schema(), &buf[..n]) {
+                Ok(data) => {
+                    stats.packets_received.fetch_add(1, Ordering::Relaxed);
+                    return Ok(data);
+                }
+                Err(e) => {
+                    if e.to_string().contains("Checksum") {
+                        stats.checksum_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                    tracing::debug!(target: EVENTS, error = %e, attempt, "Bad datagram, retrying");
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_checksum, SensorKind};
+    use std::thread;
+
+    fn udp_server(name: &str, port: u16) -> ServerConfig {
+        ServerConfig {
+            transport: Transport::Udp,
+            ..ServerConfig::new(name, &format!("127.0.0.1:{}", port), SensorKind::TempPressure)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_udp_fetch_retries_lost_request() {
+        let port = 19022;
+        let mock = std::net::UdpSocket::bind(format!("127.0.0.1:{}", port)).unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 16];
+            // Первый запрос "теряется", на второй приходит кадр
+            let _ = mock.recv_from(&mut buf).unwrap();
+            let (_, peer) = mock.recv_from(&mut buf).unwrap();
+            let mut frame = Vec::new();
+            frame.extend_from_slice(&1700000000000000u64.to_be_bytes());
+            frame.extend_from_slice(&21.5f32.to_be_bytes());
+            frame.extend_from_slice(&1005i16.to_be_bytes());
+            frame.push(calculate_checksum(&frame));
+            mock.send_to(&frame, peer).unwrap();
+        });
+
+        let server = udp_server("Udp", port);
+        let config = Config { read_timeout_ms: 200, ..Config::default() };
+        let stats = ServerStats::new();
+        let mut connection = Connection::open(&server, &stats, &config).await.unwrap();
+
+        match connection.fetch(&server, &stats, &config).await {
+            Ok(SensorData::TempPressure { temperature, pressure, .. }) => {
+                assert_eq!((temperature, pressure), (21.5, 1005));
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(other) => panic!("Unexpected data: {:?}", other),
+        }
+        assert_eq!(stats.timeout_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_udp_fetch_gives_up_after_retries() {
+        let port = 19023;
+        let _silent = std::net::UdpSocket::bind(format!("127.0.0.1:{}", port)).unwrap();
+
+        let server = udp_server("Udp", port);
+        let config = Config { read_timeout_ms: 50, ..Config::default() };
+        let stats = ServerStats::new();
+        let fetcher = UdpFetcher::connect(&server.address).await.unwrap();
+
+        let err = fetcher.fetch(&server, &stats, &config).await.unwrap_err();
+        assert!(err.to_string().contains("Read timeout"));
+        assert_eq!(stats.timeout_errors.load(Ordering::Relaxed), UDP_MAX_ATTEMPTS as u64);
+    }
+}
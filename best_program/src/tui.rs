@@ -0,0 +1,236 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Sparkline};
+use ratatui::Frame;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{Config, SampleHistory, SensorData, SensorKind, ServerConfig, ServerStats};
+
+/// Период перерисовки и опроса клавиатуры
+const REFRESH_MS: u64 = 250;
+/// Ширина подписи слева от графика
+const LABEL_WIDTH: u16 = 24;
+
+/// Величины отсчёта по порядку вывода
+fn metrics(data: &SensorData) -> Vec<(&'static str, f64)> {
+    match *data {
+        SensorData::TempPressure { temperature, pressure, .. } => {
+            vec![("temperature", temperature as f64), ("pressure", pressure as f64)]
+        }
+        SensorData::Accelerometer { x, y, z, .. } => vec![("x", x as f64), ("y", y as f64), ("z", z as f64)],
+    }
+}
+
+/// Приводит ряд к 0..=100 от его минимума до максимума; постоянный ряд рисуется посередине
+fn scale(values: &[f64]) -> Vec<u64> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| if max > min { ((value - min) / (max - min) * 100.0).round() as u64 } else { 50 })
+        .collect()
+}
+
+/// Скорость прихода пакетов за последнюю секунду
+struct Rate {
+    count: u64,
+    at: Instant,
+    per_sec: f64,
+}
+
+impl Rate {
+    fn update(&mut self, count: u64) {
+        let elapsed = self.at.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.per_sec = count.saturating_sub(self.count) as f64 / elapsed.as_secs_f64();
+            self.count = count;
+            self.at = Instant::now();
+        }
+    }
+}
+
+/// Панель одного сервера
+struct ServerView<'a> {
+    server: &'a ServerConfig,
+    stats: &'a ServerStats,
+    rate: f64,
+}
+
+fn panel_height(view: &ServerView<'_>) -> u16 {
+    let rows = match view.server.kind {
+        SensorKind::TempPressure => 2,
+        SensorKind::Accelerometer => 3,
+    };
+    rows + 4
+}
+
+fn render(frame: &mut Frame, views: &[ServerView<'_>], history: &SampleHistory) {
+    let mut constraints: Vec<Constraint> = views.iter().map(|view| Constraint::Length(panel_height(view))).collect();
+    constraints.push(Constraint::Min(0));
+    constraints.push(Constraint::Length(1));
+    let areas = Layout::vertical(constraints).split(frame.area());
+
+    for (view, area) in views.iter().zip(areas.iter()) {
+        let block = Block::bordered().title(format!(" {} — {} ", view.server.name, view.server.address).bold());
+        let inner = block.inner(*area);
+        frame.render_widget(block, *area);
+
+        let samples = history.range(&view.server.name, None, None);
+        let latest = samples.last().map(|data| {
+            let values: Vec<String> = metrics(data)
+                .iter()
+                .map(|(name, value)| format!("{} {:.*}", name, if *name == "temperature" { 2 } else { 0 }, value))
+                .collect();
+            let age = (chrono::Utc::now() - data.timestamp()).num_milliseconds() as f64 / 1000.0;
+            format!("{}   ({:.1} s ago)", values.join("  "), age)
+        });
+        let s = view.stats;
+        let latency = s.latency.summary();
+        let counters = format!(
+            "packets {} ({:.0}/s)  checksum {}  reconnects {}  gaps {}  dup {}  rejected {}  latency p50 {:.2} p99 {:.2} ms",
+            s.packets_received.load(Ordering::Relaxed),
+            view.rate,
+            s.checksum_errors.load(Ordering::Relaxed),
+            s.reconnections.load(Ordering::Relaxed),
+            s.gaps.load(Ordering::Relaxed),
+            s.duplicates.load(Ordering::Relaxed),
+            s.rejected_samples.load(Ordering::Relaxed),
+            latency.p50_ms,
+            latency.p99_ms,
+        );
+
+        let rows = Layout::vertical([Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)]).split(inner);
+        let latest_line = match latest {
+            Some(text) => Line::from(text).fg(Color::Cyan),
+            None => Line::from("waiting for data").fg(Color::DarkGray),
+        };
+        frame.render_widget(Paragraph::new(latest_line), rows[0]);
+        frame.render_widget(Paragraph::new(counters), rows[1]);
+
+        let Some(last) = samples.last() else {
+            continue;
+        };
+        let width = rows[2].width.saturating_sub(LABEL_WIDTH) as usize;
+        let recent = &samples[samples.len().saturating_sub(width)..];
+        let names = metrics(last);
+        let lines = Layout::vertical(vec![Constraint::Length(1); names.len()]).split(rows[2]);
+        for (index, ((name, _), line)) in names.iter().zip(lines.iter()).enumerate() {
+            let values: Vec<f64> = recent.iter().filter_map(|data| metrics(data).get(index).map(|m| m.1)).collect();
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let [label, chart] = Layout::horizontal([Constraint::Length(LABEL_WIDTH), Constraint::Min(0)]).areas(*line);
+            frame.render_widget(Paragraph::new(format!("{:<11} {:.0}..{:.0}", name, min, max)), label);
+            let data = scale(&values);
+            frame.render_widget(Sparkline::default().data(&data).max(100).style(Style::new().fg(Color::Green)), chart);
+        }
+    }
+    let footer = Line::from("q, Esc or Ctrl+C — stop the logger").fg(Color::DarkGray);
+    frame.render_widget(Paragraph::new(footer), areas[areas.len() - 1]);
+}
+
+/// Запускает панель в отдельном потоке: перерисовка каждые REFRESH_MS, выход по q, Esc или Ctrl+C
+/// отменяет shutdown. Терминал восстанавливается при отмене shutdown любым способом
+pub fn spawn(
+    config: Arc<Config>,
+    stats: Vec<(String, Arc<ServerStats>)>,
+    history: Arc<SampleHistory>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut terminal = match ratatui::try_init() {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                tracing::error!(error = %e, "Cannot start the dashboard");
+                return;
+            }
+        };
+        let mut rates: Vec<Rate> = stats
+            .iter()
+            .map(|(_, s)| Rate { count: s.packets_received.load(Ordering::Relaxed), at: Instant::now(), per_sec: 0.0 })
+            .collect();
+
+        while !shutdown.is_cancelled() {
+            for (rate, (_, s)) in rates.iter_mut().zip(&stats) {
+                rate.update(s.packets_received.load(Ordering::Relaxed));
+            }
+            let views: Vec<ServerView<'_>> = config
+                .servers
+                .iter()
+                .zip(&stats)
+                .zip(&rates)
+                .map(|((server, (_, s)), rate)| ServerView { server, stats: s, rate: rate.per_sec })
+                .collect();
+            if let Err(e) = terminal.draw(|frame| render(frame, &views, &history)) {
+                tracing::error!(error = %e, "Dashboard draw failed");
+                break;
+            }
+
+            match event::poll(Duration::from_millis(REFRESH_MS)) {
+                Ok(true) => {
+                    if let Ok(Event::Key(key)) = event::read() {
+                        let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                        if key.kind == KeyEventKind::Press && (ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)) {
+                            tracing::info!("Dashboard closed. Shutting down...");
+                            shutdown.cancel();
+                        }
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!(error = %e, "Dashboard input failed");
+                    break;
+                }
+            }
+        }
+        ratatui::restore();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, Utc};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(&[10.0, 15.0, 20.0]), vec![0, 50, 100]);
+        assert_eq!(scale(&[3.0, 3.0]), vec![50, 50]);
+        assert!(scale(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_dashboard_shows_latest_values_and_counters() {
+        let server1 = ServerConfig::new("Server1", "127.0.0.1:5123", SensorKind::TempPressure);
+        let server2 = ServerConfig::new("Server2", "127.0.0.1:5124", SensorKind::Accelerometer);
+        let stats1 = ServerStats::new();
+        stats1.packets_received.store(42, Ordering::Relaxed);
+        let stats2 = ServerStats::new();
+        let history = SampleHistory::new(100);
+        for i in 0..10 {
+            let timestamp = Utc::now() - TimeDelta::milliseconds(100 * (10 - i));
+            history.push("Server1", &SensorData::TempPressure { timestamp, temperature: 20.0 + i as f32, pressure: 1013 });
+        }
+
+        let views = [
+            ServerView { server: &server1, stats: &stats1, rate: 12.0 },
+            ServerView { server: &server2, stats: &stats2, rate: 0.0 },
+        ];
+        let mut terminal = Terminal::new(TestBackend::new(140, 20)).unwrap();
+        terminal.draw(|frame| render(frame, &views, &history)).unwrap();
+
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Server1 — 127.0.0.1:5123"));
+        assert!(screen.contains("temperature 29.00  pressure 1013"));
+        assert!(screen.contains("packets 42 (12/s)"));
+        assert!(screen.contains("temperature 20..29"));
+        assert!(screen.contains("waiting for data"));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::aggregate::Aggregate;
+use crate::SensorKind;
+
+/// Знаков после запятой у пересчитанного значения по умолчанию
+const DEFAULT_DECIMALS: usize = 2;
+/// Больше знаков f64 всё равно не даст
+const MAX_DECIMALS: usize = 9;
+
+/// Пересчёт сырого значения поля в физическую величину: value * scale + offset
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Conversion {
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    /// Подпись после значения в текстовом формате, например "mmHg"
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Знаков после запятой; по умолчанию 2
+    #[serde(default)]
+    pub decimals: Option<usize>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Conversion {
+    pub fn new(scale: f64, offset: f64, unit: &str) -> Self {
+        Conversion { scale, offset, unit: Some(unit.to_string()), decimals: None }
+    }
+
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.scale + self.offset
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.scale.is_finite() || self.scale == 0.0 {
+            return Err("scale: must be a finite non-zero number".into());
+        }
+        if !self.offset.is_finite() {
+            return Err("offset: must be a finite number".into());
+        }
+        if self.unit.as_ref().is_some_and(|unit| unit.is_empty() || unit.contains(|c: char| c.is_whitespace() || c == ',')) {
+            return Err("unit: must be non-empty, without spaces or commas".into());
+        }
+        if self.decimals.is_some_and(|decimals| decimals > MAX_DECIMALS) {
+            return Err(format!("decimals: must not exceed {}", MAX_DECIMALS));
+        }
+        Ok(())
+    }
+}
+
+/// Пересчёты полей одного сервера по имени поля (temperature, pressure, x, y, z)
+pub type Units = BTreeMap<String, Conversion>;
+
+/// Поля без пересчёта выводятся как раньше
+pub fn validate(units: &Units, kind: SensorKind) -> Result<(), String> {
+    let fields = &kind.required_fields()[1..];
+    for (field, conversion) in units {
+        if !fields.contains(&field.as_str()) {
+            return Err(format!("unknown field '{}', expected one of: {}", field, fields.join(", ")));
+        }
+        conversion.validate().map_err(|e| format!("{}.{}", field, e))?;
+    }
+    Ok(())
+}
+
+fn converted(c: &Conversion, raw: f64) -> (String, &str) {
+    (format!("{:.*}", c.decimals.unwrap_or(DEFAULT_DECIMALS), c.apply(raw)), c.unit.as_deref().unwrap_or(""))
+}
+
+/// Значение поля для вывода и подпись единицы для текстового формата.
+/// Без пересчёта — сырое значение: температура с двумя знаками и единицей C, остальное целым
+pub fn field<'a>(units: &'a Units, name: &str, raw: f64) -> (String, &'a str) {
+    match units.get(name) {
+        Some(c) => converted(c, raw),
+        None if name == "temperature" => (format!("{:.2}", raw), "C"),
+        None => (raw.to_string(), ""),
+    }
+}
+
+/// Как field, но среднее без пересчёта всегда выводится с двумя знаками
+pub fn average<'a>(units: &'a Units, name: &str, value: f64) -> (String, &'a str) {
+    match units.get(name) {
+        Some(c) => converted(c, value),
+        None => (format!("{:.2}", value), if name == "temperature" { "C" } else { "" }),
+    }
+}
+
+/// Сводка за окно в пересчитанных величинах; при отрицательном scale min и max меняются местами
+pub fn convert_aggregate(mut aggregate: Aggregate, units: &Units) -> Aggregate {
+    for m in &mut aggregate.metrics {
+        if let Some(c) = units.get(m.name) {
+            let (min, max) = (c.apply(m.min), c.apply(m.max));
+            m.min = min.min(max);
+            m.max = min.max(max);
+            m.sum = m.sum * c.scale + c.offset * aggregate.count as f64;
+        }
+    }
+    aggregate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::MetricSummary;
+    use crate::{format_csv_with, format_data_with, SensorData};
+    use chrono::DateTime;
+
+    #[test]
+    fn test_converted_output() {
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let mut units = Units::new();
+        units.insert("pressure".into(), Conversion::new(0.750062, 0.0, "mmHg"));
+        units.insert("z".into(), Conversion { decimals: Some(3), ..Conversion::new(1.0 / 1000.0, 0.0, "g") });
+
+        let sample = SensorData::TempPressure { timestamp, temperature: 23.5, pressure: 1013 };
+        assert_eq!(format_data_with(&sample, &units), "2023-11-14 22:13:20 [S1] temperature=23.50C pressure=759.81mmHg\n");
+        assert_eq!(format_csv_with(&sample, &units), "2023-11-14T22:13:20.000000Z,S1,23.50,759.81,,,\n");
+        let sample = SensorData::Accelerometer { timestamp, x: 12, y: -40, z: 981 };
+        assert_eq!(format_data_with(&sample, &units), "2023-11-14 22:13:20 [S2] x=12 y=-40 z=0.981g\n");
+
+        // Отрицательный scale переворачивает границы окна
+        let window = Aggregate {
+            start: timestamp,
+            kind: SensorKind::Accelerometer,
+            count: 2,
+            metrics: vec![MetricSummary { name: "z", min: 1000.0, max: 3000.0, sum: 4000.0 }],
+        };
+        let mut inverted = Units::new();
+        inverted.insert("z".into(), Conversion::new(-0.5, 1000.0, "g"));
+        let converted = convert_aggregate(window, &inverted);
+        assert_eq!(converted.metrics[0], MetricSummary { name: "z", min: -500.0, max: 500.0, sum: 0.0 });
+
+        assert!(validate(&units, SensorKind::TempPressure).is_err());
+        units.remove("z");
+        assert!(validate(&units, SensorKind::TempPressure).is_ok());
+        units.insert("pressure".into(), Conversion::new(0.0, 0.0, "mmHg"));
+        assert_eq!(validate(&units, SensorKind::TempPressure).unwrap_err(), "pressure.scale: must be a finite non-zero number");
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+use crate::{SensorData, SensorKind};
+
+/// Сколько отклонённых подряд по скачку отсчётов считать новым уровнем сигнала
+const DELTA_RESET_AFTER: u32 = 3;
+
+/// Что делать с неправдоподобным отсчётом
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectAction {
+    /// Не записывать и не передавать дальше
+    #[default]
+    Drop,
+    /// Записать, но предупредить в логе
+    Flag,
+}
+
+/// Правила правдоподобия для одного сервера; границы диапазонов включительно
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    pub temperature: Option<[f32; 2]>,
+    pub pressure: Option<[i16; 2]>,
+    /// Наибольшее изменение любой оси между соседними отсчётами
+    pub max_accel_delta: Option<i32>,
+    pub action: RejectAction,
+}
+
+impl ValidationConfig {
+    pub fn validate(&self, kind: SensorKind) -> Result<(), String> {
+        let temp_pressure = kind == SensorKind::TempPressure;
+        if !temp_pressure && (self.temperature.is_some() || self.pressure.is_some()) {
+            return Err("temperature and pressure ranges apply only to temp_pressure servers".into());
+        }
+        if temp_pressure && self.max_accel_delta.is_some() {
+            return Err("max_accel_delta applies only to accelerometer servers".into());
+        }
+        if matches!(self.temperature, Some([min, max]) if min > max || min.is_nan() || max.is_nan()) {
+            return Err("temperature: min must not exceed max".into());
+        }
+        if matches!(self.pressure, Some([min, max]) if min > max) {
+            return Err("pressure: min must not exceed max".into());
+        }
+        if matches!(self.max_accel_delta, Some(delta) if delta <= 0) {
+            return Err("max_accel_delta: must be greater than 0".into());
+        }
+        Ok(())
+    }
+}
+
+/// Проверка отсчётов одного соединения. Скачок ускорения считается от последнего
+/// принятого отсчёта; после нескольких отказов подряд новый уровень принимается
+pub struct Validator<'a> {
+    rules: &'a ValidationConfig,
+    last_accel: Option<[i32; 3]>,
+    delta_rejections: u32,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(rules: &'a ValidationConfig) -> Self {
+        Validator { rules, last_accel: None, delta_rejections: 0 }
+    }
+
+    pub fn action(&self) -> RejectAction {
+        self.rules.action
+    }
+
+    /// Err с причиной, если отсчёт нарушает правила
+    pub fn check(&mut self, data: &SensorData) -> Result<(), String> {
+        match *data {
+            SensorData::TempPressure { temperature, pressure, .. } => {
+                if let Some([min, max]) = self.rules.temperature {
+                    if !(min..=max).contains(&temperature) {
+                        return Err(format!("temperature {:.2} outside [{}, {}]", temperature, min, max));
+                    }
+                }
+                if let Some([min, max]) = self.rules.pressure {
+                    if !(min..=max).contains(&pressure) {
+                        return Err(format!("pressure {} outside [{}, {}]", pressure, min, max));
+                    }
+                }
+                Ok(())
+            }
+            SensorData::Accelerometer { x, y, z, .. } => {
+                let current = [x, y, z];
+                if let (Some(limit), Some(last)) = (self.rules.max_accel_delta, self.last_accel) {
+                    let delta = (0..3).map(|i| (current[i] as i64 - last[i] as i64).abs()).max().unwrap_or(0);
+                    if delta > limit as i64 && self.delta_rejections + 1 < DELTA_RESET_AFTER {
+                        self.delta_rejections += 1;
+                        return Err(format!("acceleration changed by {} (limit {})", delta, limit));
+                    }
+                }
+                self.delta_rejections = 0;
+                self.last_accel = Some(current);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn accel(x: i32) -> SensorData {
+        SensorData::Accelerometer { timestamp: DateTime::from_timestamp(1700000000, 0).unwrap(), x, y: 0, z: 0 }
+    }
+
+    #[test]
+    fn test_temp_pressure_ranges() {
+        let rules = ValidationConfig { temperature: Some([-40.0, 85.0]), pressure: Some([300, 1100]), ..Default::default() };
+        let mut validator = Validator::new(&rules);
+        let timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+
+        assert!(validator.check(&SensorData::TempPressure { timestamp, temperature: 21.0, pressure: 1000 }).is_ok());
+        let err = validator.check(&SensorData::TempPressure { timestamp, temperature: 120.0, pressure: 1000 }).unwrap_err();
+        assert!(err.contains("temperature"));
+        let err = validator.check(&SensorData::TempPressure { timestamp, temperature: 21.0, pressure: 5 }).unwrap_err();
+        assert!(err.contains("pressure"));
+    }
+
+    #[test]
+    fn test_accel_delta_spike_and_new_level() {
+        let rules = ValidationConfig { max_accel_delta: Some(100), ..Default::default() };
+        let mut validator = Validator::new(&rules);
+
+        assert!(validator.check(&accel(0)).is_ok());
+        assert!(validator.check(&accel(50)).is_ok());
+        // Одиночный выброс отбрасывается, следующий нормальный отсчёт принимается
+        assert!(validator.check(&accel(5000)).is_err());
+        assert!(validator.check(&accel(60)).is_ok());
+        // Устойчивый сдвиг принимается на третьем отсчёте подряд
+        assert!(validator.check(&accel(1000)).is_err());
+        assert!(validator.check(&accel(1000)).is_err());
+        assert!(validator.check(&accel(1000)).is_ok());
+        assert!(validator.check(&accel(1010)).is_ok());
+    }
+
+    #[test]
+    fn test_rules_must_match_kind() {
+        let rules = ValidationConfig { max_accel_delta: Some(10), ..Default::default() };
+        assert!(rules.validate(SensorKind::Accelerometer).is_ok());
+        assert!(rules.validate(SensorKind::TempPressure).is_err());
+
+        let rules = ValidationConfig { pressure: Some([1100, 300]), ..Default::default() };
+        assert!(rules.validate(SensorKind::TempPressure).unwrap_err().contains("pressure"));
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::Utc;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::logging::EVENTS;
+use crate::{worker_task, Config, DataSink, ServerStats};
+
+/// Как часто за срок сторож проверяет отметки
+const CHECKS_PER_TIMEOUT: u32 = 4;
+
+fn spawn_worker<S: DataSink + 'static>(
+    index: usize,
+    sink: S,
+    stats: Arc<ServerStats>,
+    shutdown: CancellationToken,
+    config: Arc<Config>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        worker_task(&config.servers[index], sink, stats, shutdown, &config).await;
+    })
+}
+
+/// Запускает worker на каждый сервер и перезапускает тот, чья отметка ServerStats::touch
+/// старше config.watchdog_timeout_ms. make_sink создаёт приёмники заново для каждого запуска.
+/// Возвращает, когда после отмены shutdown завершатся все worker
+pub async fn supervise<S: DataSink + 'static>(
+    config: Arc<Config>,
+    stats: Vec<(String, Arc<ServerStats>)>,
+    make_sink: impl Fn(usize) -> S,
+    shutdown: CancellationToken,
+) {
+    let mut workers: Vec<JoinHandle<()>> = stats
+        .iter()
+        .enumerate()
+        .map(|(index, (_, s))| spawn_worker(index, make_sink(index), s.clone(), shutdown.clone(), config.clone()))
+        .collect();
+
+    if let Some(limit) = config.watchdog_timeout() {
+        let mut timer = tokio::time::interval((limit / CHECKS_PER_TIMEOUT).max(Duration::from_millis(10)));
+        let limit_ms = limit.as_millis() as i64;
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = timer.tick() => {}
+            }
+            let now = Utc::now().timestamp_millis();
+            for (index, (name, s)) in stats.iter().enumerate() {
+                let silent_ms = now - s.last_activity_ms.load(Ordering::Relaxed);
+                if silent_ms <= limit_ms || workers[index].is_finished() {
+                    continue;
+                }
+                warn!(target: EVENTS, server = %name, silent_ms, "Worker stalled, restarting");
+                workers[index].abort();
+                let _ = (&mut workers[index]).await;
+                s.watchdog_restarts.fetch_add(1, Ordering::Relaxed);
+                s.touch();
+                workers[index] = spawn_worker(index, make_sink(index), s.clone(), shutdown.clone(), config.clone());
+            }
+        }
+    }
+
+    for worker in workers {
+        let _ = worker.await;
+    }
+    info!("All workers stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Faults, MockConfig, MockServer};
+    use crate::{FileSink, SensorKind, ServerConfig};
+    use std::io::BufWriter;
+    use std::sync::Mutex;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_watchdog_restarts_wedged_worker() {
+        let mut mock = MockConfig::new(SensorKind::TempPressure);
+        mock.faults = Faults { stall_every: Some(1), stall: Duration::from_secs(60), ..Faults::default() };
+        let server = MockServer::start("127.0.0.1:19029", mock).unwrap();
+
+        // Чтение дольше срока сторожа имитирует сокет, который не отвечает и не рвётся
+        let config = Arc::new(Config {
+            servers: vec![ServerConfig::new("Server1", "127.0.0.1:19029", SensorKind::TempPressure)],
+            read_timeout_ms: 60_000,
+            watchdog_timeout_ms: 200,
+            ..Config::default()
+        });
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = Arc::new(Mutex::new(BufWriter::new(temp_file.reopen().unwrap())));
+        let stats = Arc::new(ServerStats::new());
+        let shutdown = CancellationToken::new();
+
+        let stopper = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            stopper.cancel();
+        });
+        let sink_config = config.clone();
+        supervise(
+            config,
+            vec![("Server1".to_string(), stats.clone())],
+            move |_| FileSink::new(writer.clone(), &sink_config),
+            shutdown,
+        )
+        .await;
+
+        assert!(stats.watchdog_restarts.load(Ordering::Relaxed) >= 1);
+        assert_eq!(stats.packets_received.load(Ordering::Relaxed), 0);
+        server.stop();
+    }
+}
@@ -0,0 +1,207 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::iter;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::{debug, error};
+
+use crate::output::OutputFile;
+use crate::{Config, ServerStats};
+
+/// Больше записей за один захват файла поток не берёт, чтобы не задерживать ротацию и fsync
+const BATCH_SIZE: usize = 256;
+
+/// Что делает worker, когда очередь к потоку записи заполнена
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueFullPolicy {
+    /// Ждать места: ничего не теряется, но опрос серверов замедляется вместе с диском
+    #[default]
+    Block,
+    /// Отбросить запись: опрос не ждёт диска
+    Drop,
+}
+
+enum Command {
+    Write(Vec<u8>),
+    /// Записать всё, что в очереди раньше, сбросить буфер и ответить
+    Flush(SyncSender<io::Result<()>>),
+}
+
+/// Очередь записей в выходной файл, который пишет отдельный поток. Worker только кодирует
+/// отсчёт и ставит его в очередь, не дожидаясь файла. Поток завершается, когда закрыты все копии очереди
+#[derive(Clone)]
+pub struct WriteQueue {
+    sender: SyncSender<Command>,
+    policy: QueueFullPolicy,
+}
+
+fn stopped<T>(_: T) -> io::Error {
+    io::Error::other("Writer thread stopped")
+}
+
+/// Ожидание потока записи из worker: в многопоточном runtime его задачи на время ожидания
+/// переходят к другим потокам, и опрос остальных серверов не стоит вместе с диском
+fn blocking<T>(wait: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(wait),
+        _ => wait(),
+    }
+}
+
+impl WriteQueue {
+    /// Очередь на config.write_queue_size записей и поток записи в output
+    pub fn start(output: &OutputFile, config: &Config) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(config.write_queue_size);
+        let output = output.clone();
+        thread::Builder::new().name("writer".to_string()).spawn(move || write_loop(&output, &receiver))?;
+        Ok(WriteQueue { sender, policy: config.write_queue_full })
+    }
+
+    /// Ожидание места и отброшенные записи учитываются в stats сервера
+    pub fn write(&self, record: Vec<u8>, stats: &ServerStats) -> io::Result<()> {
+        match self.sender.try_send(Command::Write(record)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(command)) => match self.policy {
+                QueueFullPolicy::Block => {
+                    stats.write_queue_waits.fetch_add(1, Ordering::Relaxed);
+                    blocking(|| self.sender.send(command)).map_err(stopped)
+                }
+                QueueFullPolicy::Drop => {
+                    stats.write_queue_drops.fetch_add(1, Ordering::Relaxed);
+                    debug!("Write queue is full, record dropped");
+                    Ok(())
+                }
+            },
+            Err(TrySendError::Disconnected(_)) => Err(stopped(())),
+        }
+    }
+
+    /// Ждёт, пока поток запишет всё поставленное до вызова, и сбрасывает буфер файла
+    pub fn flush(&self) -> io::Result<()> {
+        let (done, result) = mpsc::sync_channel(1);
+        self.sender.send(Command::Flush(done)).map_err(stopped)?;
+        blocking(|| result.recv()).map_err(stopped)?
+    }
+}
+
+fn execute(writer: &mut BufWriter<File>, command: Command, output: &OutputFile) {
+    match command {
+        Command::Write(record) => {
+            if let Err(e) = writer.write_all(&record) {
+                error!(file = %output.current().display(), error = %e, "Write error");
+            }
+        }
+        Command::Flush(done) => {
+            let _ = done.send(writer.flush());
+        }
+    }
+}
+
+fn write_loop(output: &OutputFile, receiver: &Receiver<Command>) {
+    while let Ok(first) = receiver.recv() {
+        let Ok(mut writer) = output.writer.lock() else {
+            error!(file = %output.current().display(), "Writer lock poisoned, writer thread stopped");
+            return;
+        };
+        for command in iter::once(first).chain(receiver.try_iter().take(BATCH_SIZE - 1)) {
+            execute(&mut writer, command, output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn queue(policy: QueueFullPolicy) -> (tempfile::TempDir, OutputFile, WriteQueue) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config { write_queue_size: 2, write_queue_full: policy, ..Config::default() };
+        let output = OutputFile::open(&dir.path().join("data.txt"), &config).unwrap();
+        let queue = WriteQueue::start(&output, &config).unwrap();
+        (dir, output, queue)
+    }
+
+    #[test]
+    fn test_full_queue_blocks() {
+        let (_dir, output, queue) = queue(QueueFullPolicy::Block);
+        let stats = Arc::new(ServerStats::new());
+        // Пока файл занят, поток записи стоит, и очередь заполняется
+        let busy = output.writer.lock().unwrap();
+        let worker = thread::spawn({
+            let (queue, stats) = (queue.clone(), stats.clone());
+            move || {
+                for n in 0..5 {
+                    queue.write(format!("{}\n", n).into_bytes(), &stats).unwrap();
+                }
+            }
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert!(!worker.is_finished());
+        assert!(stats.write_queue_waits.load(Ordering::Relaxed) >= 1);
+
+        drop(busy);
+        worker.join().unwrap();
+        queue.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(output.current()).unwrap(), "0\n1\n2\n3\n4\n");
+    }
+
+    #[test]
+    fn test_full_queue_drops() {
+        let (_dir, output, queue) = queue(QueueFullPolicy::Drop);
+        let stats = ServerStats::new();
+        let busy = output.writer.lock().unwrap();
+        for n in 0..5 {
+            queue.write(format!("{}\n", n).into_bytes(), &stats).unwrap();
+        }
+        drop(busy);
+        queue.flush().unwrap();
+
+        let written = std::fs::read_to_string(output.current()).unwrap();
+        let drops = stats.write_queue_drops.load(Ordering::Relaxed);
+        assert!(drops >= 2);
+        assert_eq!(written.lines().count() as u64 + drops, 5);
+        assert!(written.starts_with("0\n1\n"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_full_queue_does_not_stall_runtime() {
+        let (_dir, output, queue) = queue(QueueFullPolicy::Block);
+        let stats = Arc::new(ServerStats::new());
+        let (release, released) = mpsc::channel::<()>();
+        let busy = thread::spawn({
+            let output = output.clone();
+            move || {
+                let _writer = output.writer.lock().unwrap();
+                let _ = released.recv();
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+
+        // Единственный поток runtime ждёт места в очереди, а другие задачи всё равно выполняются
+        let writer = tokio::spawn({
+            let (queue, stats) = (queue.clone(), stats.clone());
+            async move {
+                for n in 0..5 {
+                    queue.write(format!("{}\n", n).into_bytes(), &stats).unwrap();
+                }
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(stats.write_queue_waits.load(Ordering::Relaxed) >= 1);
+        let other = tokio::spawn(async { tokio::time::sleep(Duration::from_millis(10)).await });
+        tokio::time::timeout(Duration::from_secs(2), other).await.unwrap().unwrap();
+        assert!(!writer.is_finished());
+
+        release.send(()).unwrap();
+        busy.join().unwrap();
+        writer.await.unwrap();
+        queue.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(output.current()).unwrap(), "0\n1\n2\n3\n4\n");
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::collections::VecDeque;
+use crate::interpreter::{jump_table, Instruction, TOKENS};
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramReport {
+    pub instruction_count: usize,
+    // MOO без парного moo и moo без парного MOO
+    pub unmatched_loops: Vec<usize>,
+    // Инструкции, до которых выполнение не может дойти
+    pub unreachable: Vec<usize>,
+    pub max_nesting: usize,
+    // Количество инструкций каждого вида, индекс - код инструкции.
+    // Пользовательские инструкции в гистограмму не попадают.
+    pub histogram: [usize; 12],
+}
+
+impl ProgramReport {
+    pub fn count(&self, instruction: Instruction) -> usize {
+        instruction.code().map_or(0, |code| self.histogram[code as usize])
+    }
+
+    // Гистограмма в виде пар "инструкция - количество" без нулевых строк
+    pub fn histogram_entries(&self) -> Vec<(&'static str, usize)> {
+        TOKENS
+            .iter()
+            .map(|(token, instruction)| (*token, self.count(*instruction)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.unmatched_loops.is_empty()
+    }
+}
+
+// Статический анализ программы без её выполнения.
+// Переходы циклов считаются по тем же правилам, что и в интерпретаторе.
+pub fn analyze(program: &[Instruction]) -> ProgramReport {
+    let jumps = jump_table(program);
+
+    let unmatched_loops = program
+        .iter()
+        .enumerate()
+        .filter(|(pos, instruction)| {
+            matches!(instruction, Instruction::MOO2 | Instruction::Moo) && jumps[*pos].is_none()
+        })
+        .map(|(pos, _)| pos)
+        .collect();
+
+    let mut histogram = [0; 12];
+    for code in program.iter().filter_map(Instruction::code) {
+        histogram[code as usize] += 1;
+    }
+
+    ProgramReport {
+        instruction_count: program.len(),
+        unmatched_loops,
+        unreachable: find_unreachable(program, &jumps),
+        max_nesting: max_nesting(program, &jumps),
+        histogram,
+    }
+}
+
+// Обход графа переходов от первой инструкции.
+// mOO выполняет чужую инструкцию на месте и не меняет порядок выполнения.
+// Пользовательская инструкция может перейти куда угодно: если до неё (или до mOO, который
+// может её выполнить) доходит выполнение, достижимо всё.
+fn find_unreachable(program: &[Instruction], jumps: &[Option<usize>]) -> Vec<usize> {
+    let has_custom = program.iter().any(|i| matches!(i, Instruction::Custom(_)));
+    let mut visited = vec![false; program.len()];
+    let mut queue = VecDeque::new();
+    if !program.is_empty() {
+        queue.push_back(0);
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        if pos >= program.len() || visited[pos] {
+            continue;
+        }
+        visited[pos] = true;
+
+        match program[pos] {
+            Instruction::Custom(_) => return Vec::new(),
+            Instruction::MOO if has_custom => return Vec::new(),
+            Instruction::Moo => {
+                if let Some(start) = jumps[pos] {
+                    queue.push_back(start);
+                }
+            }
+            Instruction::MOO2 => {
+                queue.push_back(pos + 1);
+                if let Some(end) = jumps[pos] {
+                    queue.push_back(end + 1);
+                }
+            }
+            _ => queue.push_back(pos + 1),
+        }
+    }
+
+    visited
+        .iter()
+        .enumerate()
+        .filter(|(_, seen)| !**seen)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+fn max_nesting(program: &[Instruction], jumps: &[Option<usize>]) -> usize {
+    let mut depth_change = vec![0i64; program.len() + 1];
+    for (pos, instruction) in program.iter().enumerate() {
+        if *instruction == Instruction::MOO2 {
+            if let Some(end) = jumps[pos] {
+                depth_change[pos] += 1;
+                depth_change[end + 1] -= 1;
+            }
+        }
+    }
+
+    let mut depth = 0;
+    let mut max = 0;
+    for change in depth_change {
+        depth += change;
+        max = max.max(depth);
+    }
+
+    max as usize
+}
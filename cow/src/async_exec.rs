@@ -0,0 +1,143 @@
+use crate::cell::CellValue;
+use crate::interpreter::{CowInterpreter, InputRequest};
+use crate::io::InputMode;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+// Как часто (в шагах) выполнение уступает планировщику
+const YIELD_INTERVAL: usize = 1024;
+
+// Источник ввода, который можно ждать, не блокируя поток
+trait AsyncSource {
+    async fn line(&mut self) -> Option<String>;
+    async fn char(&mut self) -> Option<char>;
+}
+
+struct ReaderSource<'a, R> {
+    reader: &'a mut R,
+    mode: InputMode,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncSource for ReaderSource<'_, R> {
+    async fn line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+
+    async fn char(&mut self) -> Option<char> {
+        match self.mode {
+            InputMode::Line => self.line().await.and_then(|line| line.chars().next()),
+            InputMode::Char => {
+                // Символ UTF-8 занимает от 1 до 4 байт
+                let mut bytes = vec![self.reader.read_u8().await.ok()?];
+                let len = match bytes[0] {
+                    0xF0.. => 4,
+                    0xE0.. => 3,
+                    0xC0.. => 2,
+                    _ => 1,
+                };
+                for _ in 1..len {
+                    bytes.push(self.reader.read_u8().await.ok()?);
+                }
+                Some(String::from_utf8(bytes).map_or(char::REPLACEMENT_CHARACTER, |s| {
+                    s.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER)
+                }))
+            }
+        }
+    }
+}
+
+// Каждое сообщение канала - одна строка ввода
+struct ChannelSource<'a> {
+    receiver: &'a mut mpsc::Receiver<String>,
+}
+
+impl AsyncSource for ChannelSource<'_> {
+    async fn line(&mut self) -> Option<String> {
+        self.receiver.recv().await
+    }
+
+    async fn char(&mut self) -> Option<char> {
+        self.line().await.and_then(|line| line.chars().next())
+    }
+}
+
+// Выполнение, при котором ввод ожидается из AsyncBufRead, а вывод пишется в AsyncWrite
+pub async fn execute_async<C, R, W>(
+    interpreter: &mut CowInterpreter<C>,
+    input: &mut R,
+    mode: InputMode,
+    output: &mut W,
+) -> Result<(), String>
+where
+    C: CellValue,
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut source = ReaderSource { reader: input, mode };
+    execute(interpreter, &mut source, output).await
+}
+
+// То же, но строки ввода приходят из канала
+pub async fn execute_with_channel<C, W>(
+    interpreter: &mut CowInterpreter<C>,
+    input: &mut mpsc::Receiver<String>,
+    output: &mut W,
+) -> Result<(), String>
+where
+    C: CellValue,
+    W: AsyncWrite + Unpin,
+{
+    let mut source = ChannelSource { receiver: input };
+    execute(interpreter, &mut source, output).await
+}
+
+async fn execute<C, S, W>(interpreter: &mut CowInterpreter<C>, source: &mut S, output: &mut W) -> Result<(), String>
+where
+    C: CellValue,
+    S: AsyncSource,
+    W: AsyncWrite + Unpin,
+{
+    let mut text = String::new();
+    // Ввод для следующего шага; интерпретатор читает его синхронно
+    let mut pending: Option<String> = None;
+
+    while !interpreter.is_finished() {
+        if let Some(request) = interpreter.next_input() {
+            // Перед ожиданием ввода пользователь должен увидеть уже напечатанное
+            write(output, &mut text).await?;
+            output.flush().await.map_err(|err| err.to_string())?;
+
+            let value = match request {
+                InputRequest::Char => source.char().await.map(String::from),
+                InputRequest::Line => source.line().await,
+            };
+            pending = value;
+        }
+
+        interpreter.step(&mut text, &mut pending.take().into_iter())?;
+
+        if !text.is_empty() {
+            write(output, &mut text).await?;
+        }
+        if interpreter.steps.is_multiple_of(YIELD_INTERVAL) {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    output.flush().await.map_err(|err| err.to_string())
+}
+
+async fn write<W: AsyncWrite + Unpin>(output: &mut W, text: &mut String) -> Result<(), String> {
+    if !text.is_empty() {
+        output
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|err| format!("Ошибка записи вывода: {}", err))?;
+        text.clear();
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use crate::interpreter::CowInterpreter;
+use crate::io::CowIo;
+use crate::RunResult;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// Без явного предела одна зациклившаяся программа задержала бы весь пакет
+pub const DEFAULT_BATCH_MAX_STEPS: usize = 10_000_000;
+
+// Ограничения, которые действуют на каждую программу пакета отдельно
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchLimits {
+    pub max_steps: Option<usize>,
+    pub max_memory: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        BatchLimits {
+            max_steps: Some(DEFAULT_BATCH_MAX_STEPS),
+            max_memory: None,
+            timeout: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchConfig {
+    pub limits: BatchLimits,
+    // Число рабочих потоков; 0 - по числу ядер
+    pub threads: usize,
+}
+
+// Выполнить программы параллельно с ограничениями по умолчанию.
+// io_factory получает номер программы и возвращает её источник ввода.
+pub fn run_batch<F>(programs: &[&str], io_factory: F) -> Vec<Result<RunResult, String>>
+where
+    F: Fn(usize) -> Box<dyn CowIo> + Sync,
+{
+    run_batch_with(programs, io_factory, &BatchConfig::default())
+}
+
+// Результаты возвращаются в порядке программ, независимо от порядка завершения
+pub fn run_batch_with<F>(programs: &[&str], io_factory: F, config: &BatchConfig) -> Vec<Result<RunResult, String>>
+where
+    F: Fn(usize) -> Box<dyn CowIo> + Sync,
+{
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(programs.len());
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; programs.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(source) = programs.get(index) else {
+                    break;
+                };
+                let result = run_one(source, io_factory(index).as_mut(), &config.limits);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("каждая программа пакета выполнена"))
+        .collect()
+}
+
+fn run_one(source: &str, input: &mut dyn CowIo, limits: &BatchLimits) -> Result<RunResult, String> {
+    let mut interpreter = CowInterpreter::new(source)?;
+    interpreter.max_steps = limits.max_steps;
+    interpreter.max_memory = limits.max_memory;
+
+    let output = match limits.timeout {
+        Some(timeout) => interpreter.execute_with_timeout(input, timeout)?,
+        None => interpreter.execute_with_input(input)?,
+    };
+    Ok(RunResult::new(interpreter, output))
+}
@@ -0,0 +1,75 @@
+use crate::bytecode::Bytecode;
+use crate::interpreter::CowInterpreter;
+use std::time::{Duration, Instant};
+
+// Программы, которые запускаются командой bench без каталога
+pub const EMBEDDED: [(&str, &str); 4] = [
+    ("hello", include_str!("../examples/hello.cow")),
+    ("fib", include_str!("../examples/fib.cow")),
+    ("loops", include_str!("../bench/loops.cow")),
+    ("text", include_str!("../bench/text.cow")),
+];
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: String,
+    pub steps: usize,
+    pub interpreter_time: Duration,
+    // None, если программа не переводится в байткод
+    pub bytecode_time: Option<Duration>,
+}
+
+// Выполнить программу интерпретатором и байткодом на пустом вводе.
+// Вывод обоих способов должен совпасть.
+pub fn run_benchmark(name: &str, source: &str, max_steps: Option<usize>) -> Result<BenchResult, String> {
+    let mut interpreter = CowInterpreter::new(source)?;
+    interpreter.max_steps = max_steps;
+
+    let mut input = std::iter::empty::<String>();
+    let started = Instant::now();
+    let output = interpreter.execute_with_input(&mut input)?;
+    let interpreter_time = started.elapsed();
+
+    let bytecode_time = match Bytecode::compile(&interpreter.program) {
+        Ok(bytecode) => {
+            let started = Instant::now();
+            let (bytecode_output, _) = bytecode.run(&mut input)?;
+            let elapsed = started.elapsed();
+            if bytecode_output != output {
+                return Err(format!("{}: вывод байткода отличается от интерпретатора", name));
+            }
+            Some(elapsed)
+        }
+        Err(_) => None,
+    };
+
+    Ok(BenchResult {
+        name: name.to_string(),
+        steps: interpreter.steps,
+        interpreter_time,
+        bytecode_time,
+    })
+}
+
+// Таблица результатов с ускорением байткода относительно интерпретатора
+pub fn format_table(results: &[BenchResult]) -> String {
+    let mut table = format!(
+        "{:<20} {:>12} {:>14} {:>14} {:>10}\n",
+        "программа", "шагов", "интерпр., мс", "байткод, мс", "ускорение"
+    );
+    for result in results {
+        let interpreter_ms = result.interpreter_time.as_secs_f64() * 1000.0;
+        let (bytecode_ms, speedup) = match result.bytecode_time {
+            Some(time) => {
+                let ms = time.as_secs_f64() * 1000.0;
+                (format!("{:.3}", ms), format!("{:.1}x", interpreter_ms / ms.max(1e-6)))
+            }
+            None => ("-".to_string(), "-".to_string()),
+        };
+        table.push_str(&format!(
+            "{:<20} {:>12} {:>14.3} {:>14} {:>10}\n",
+            result.name, result.steps, interpreter_ms, bytecode_ms, speedup
+        ));
+    }
+    table
+}
@@ -0,0 +1,198 @@
+use core::marker::PhantomData;
+use crate::cell::CellValue;
+use crate::heatmap::Heatmap;
+use crate::interpreter::{
+    CowInterpreter, Instruction, InstructionHandler, ParseMode, SourcePos, SpecMode,
+};
+use crate::io::{OutputEncoding, OutputPolicy};
+use crate::macros;
+use crate::prelude::*;
+use crate::state::CowState;
+
+// Настройка интерпретатора перед запуском.
+// CowInterpreter::new остаётся простым, а все дополнительные параметры собираются здесь.
+enum ProgramSource {
+    Text(String),
+    Parsed(Vec<Instruction>, Vec<SourcePos>),
+}
+
+pub struct CowInterpreterBuilder<C: CellValue = i32> {
+    source: ProgramSource,
+    memory_size: usize,
+    max_steps: Option<usize>,
+    max_memory: Option<usize>,
+    output_policy: OutputPolicy,
+    output_encoding: OutputEncoding,
+    trace: bool,
+    heatmap: bool,
+    spec_mode: SpecMode,
+    parse_mode: ParseMode,
+    macros: bool,
+    instructions: Vec<(&'static str, InstructionHandler<C>)>,
+    cells: PhantomData<C>,
+}
+
+impl CowInterpreterBuilder {
+    pub fn new(source: &str) -> Self {
+        Self::with_source(ProgramSource::Text(source.to_string()))
+    }
+
+    // Уже разобранная программа, например загруженная из .cowc
+    pub fn from_program(program: Vec<Instruction>, positions: Vec<SourcePos>) -> Self {
+        Self::with_source(ProgramSource::Parsed(program, positions))
+    }
+
+    fn with_source(source: ProgramSource) -> Self {
+        CowInterpreterBuilder {
+            source,
+            memory_size: 1,
+            max_steps: None,
+            max_memory: None,
+            output_policy: OutputPolicy::default(),
+            output_encoding: OutputEncoding::default(),
+            trace: false,
+            heatmap: false,
+            spec_mode: SpecMode::default(),
+            parse_mode: ParseMode::default(),
+            macros: false,
+            instructions: Vec::new(),
+            cells: PhantomData,
+        }
+    }
+}
+
+impl<C: CellValue> CowInterpreterBuilder<C> {
+    // Тип ячеек: .cell_type::<i64>() или .cell_type::<BigInt>().
+    // Обработчики пользовательских инструкций привязаны к типу ячеек и здесь сбрасываются,
+    // поэтому register_instruction вызывается после cell_type.
+    pub fn cell_type<D: CellValue>(self) -> CowInterpreterBuilder<D> {
+        CowInterpreterBuilder {
+            source: self.source,
+            memory_size: self.memory_size,
+            max_steps: self.max_steps,
+            max_memory: self.max_memory,
+            output_policy: self.output_policy,
+            output_encoding: self.output_encoding,
+            trace: self.trace,
+            heatmap: self.heatmap,
+            spec_mode: self.spec_mode,
+            parse_mode: self.parse_mode,
+            macros: self.macros,
+            instructions: Vec::new(),
+            cells: PhantomData,
+        }
+    }
+
+    // Начальный размер ленты, дальше она растёт по мере надобности
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    pub fn max_steps(mut self, limit: usize) -> Self {
+        self.max_steps = Some(limit);
+        self
+    }
+
+    // Предел длины ленты: moO за его границу - ошибка выполнения
+    pub fn max_memory(mut self, cells: usize) -> Self {
+        self.max_memory = Some(cells);
+        self
+    }
+
+    pub fn output_policy(mut self, policy: OutputPolicy) -> Self {
+        self.output_policy = policy;
+        self
+    }
+
+    pub fn output_encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.output_encoding = encoding;
+        self
+    }
+
+    // Записывать каждый шаг, см. CowInterpreter::take_trace
+    pub fn trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    // Считать чтения и записи ячеек, см. CowInterpreter::heatmap
+    pub fn heatmap(mut self, enabled: bool) -> Self {
+        self.heatmap = enabled;
+        self
+    }
+
+    pub fn spec_mode(mut self, mode: SpecMode) -> Self {
+        self.spec_mode = mode;
+        self
+    }
+
+    // Как разбирать исходный текст; на уже разобранную программу не влияет
+    pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+        self.parse_mode = mode;
+        self
+    }
+
+    // Раскрыть макросы (@имя = ...) перед разбором, см. macros::expand
+    pub fn macros(mut self, enabled: bool) -> Self {
+        self.macros = enabled;
+        self
+    }
+
+    // Инструкция диалекта COW: токен распознаётся при разборе, а при выполнении
+    // вызывается handler, см. CowInterpreter::register_instruction
+    pub fn register_instruction(
+        mut self,
+        token: &'static str,
+        handler: impl FnMut(&mut CowState<C>) -> Result<(), String> + Send + 'static,
+    ) -> Self {
+        self.instructions.push((token, Box::new(handler)));
+        self
+    }
+
+    pub fn build(self) -> Result<CowInterpreter<C>, String> {
+        let tokens: Vec<&'static str> = self.instructions.iter().map(|(token, _)| *token).collect();
+        let mut interpreter = match self.source {
+            ProgramSource::Text(source) => {
+                let source = if self.macros { macros::expand(&source)? } else { source };
+                let (program, positions) = match self.parse_mode {
+                    ParseMode::Lenient => CowInterpreter::parse_with_instructions(&source, &tokens)?,
+                    ParseMode::Strict => CowInterpreter::parse_strict_with_instructions(&source, &tokens)?,
+                };
+                CowInterpreter::from_program(program, positions)
+            }
+            ProgramSource::Parsed(program, positions) => CowInterpreter::from_program(program, positions),
+        };
+        if let Some(limit) = self.max_memory {
+            let initial = self.memory_size.max(1);
+            if initial > limit {
+                return Err(format!(
+                    "Начальный размер ленты {} не укладывается в предел {} ячеек",
+                    initial, limit
+                ));
+            }
+        }
+        interpreter.set_memory_size(self.memory_size);
+        interpreter.max_steps = self.max_steps;
+        interpreter.max_memory = self.max_memory;
+        interpreter.output_policy = self.output_policy;
+        interpreter.output_encoding = self.output_encoding;
+        interpreter.spec_mode = self.spec_mode;
+        for (token, handler) in self.instructions {
+            interpreter.set_instruction(token, handler)?;
+        }
+        if self.trace {
+            interpreter.trace = Some(Vec::new());
+        }
+        if self.heatmap {
+            interpreter.heatmap = Some(Heatmap::default());
+        }
+        Ok(interpreter)
+    }
+}
+
+impl CowInterpreter {
+    pub fn builder(source: &str) -> CowInterpreterBuilder {
+        CowInterpreterBuilder::new(source)
+    }
+}
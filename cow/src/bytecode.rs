@@ -0,0 +1,186 @@
+use crate::interpreter::{jump_table, Instruction};
+use crate::io::CowIo;
+use crate::prelude::*;
+
+// Байткод: серии MoO/MOo и moO/mOo свёрнуты в одну операцию, переходы циклов вычислены заранее.
+// Ячейки - i32, Moo пропускает значения, которые не являются символами.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add(i32),
+    // min - наименьшее смещение внутри серии: mOo на нулевой ячейке останавливает программу;
+    // max - наибольшее: до него лента должна дорасти, даже если серия потом вернулась назад
+    Move { delta: isize, min: isize, max: isize },
+    Clear,
+    Io,
+    PrintInt,
+    ReadInt,
+    Register,
+    // Куда перейти при нулевой ячейке; None - остановить программу
+    LoopStart(Option<usize>),
+    // Индекс операции MOO, к которой возвращается moo; None - остановить программу
+    LoopEnd(Option<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub ops: Vec<Op>,
+}
+
+impl Bytecode {
+    // mOO выполняет инструкции исходной программы по индексу, поэтому в байткод не переводится
+    pub fn compile(program: &[Instruction]) -> Result<Self, String> {
+        if let Some(pos) = program.iter().position(|i| *i == Instruction::MOO) {
+            return Err(format!("mOO (позиция {}) не поддерживается байткодом", pos));
+        }
+        if let Some(pos) = program.iter().position(|i| matches!(i, Instruction::Custom(_))) {
+            return Err(format!("{} (позиция {}) не поддерживается байткодом", program[pos].token(), pos));
+        }
+
+        let jumps = jump_table(program);
+
+        // На эти инструкции возможен переход, серию перед ними нужно прервать
+        let mut targets = vec![false; program.len() + 1];
+        for (pos, instruction) in program.iter().enumerate() {
+            match (instruction, jumps[pos]) {
+                (Instruction::MOO2, Some(end)) => targets[end + 1] = true,
+                (Instruction::Moo, Some(start)) => targets[start] = true,
+                _ => {}
+            }
+        }
+
+        let mut ops = Vec::new();
+        // Индекс операции для каждой инструкции программы (и для позиции за концом)
+        let mut op_index = vec![0; program.len() + 1];
+        let mut pos = 0;
+
+        while pos < program.len() {
+            op_index[pos] = ops.len();
+            let start = pos;
+            match program[pos] {
+                Instruction::MoO2 | Instruction::MOo2 => {
+                    let mut sum: i32 = 0;
+                    while pos < program.len() && (pos == start || !targets[pos]) {
+                        match program[pos] {
+                            Instruction::MoO2 => sum = sum.wrapping_add(1),
+                            Instruction::MOo2 => sum = sum.wrapping_sub(1),
+                            _ => break,
+                        }
+                        op_index[pos] = ops.len();
+                        pos += 1;
+                    }
+                    ops.push(Op::Add(sum));
+                    continue;
+                }
+                Instruction::MoO | Instruction::MOo => {
+                    let (mut delta, mut min, mut max) = (0isize, 0isize, 0isize);
+                    while pos < program.len() && (pos == start || !targets[pos]) {
+                        match program[pos] {
+                            Instruction::MoO => delta += 1,
+                            Instruction::MOo => delta -= 1,
+                            _ => break,
+                        }
+                        min = min.min(delta);
+                        max = max.max(delta);
+                        op_index[pos] = ops.len();
+                        pos += 1;
+                    }
+                    ops.push(Op::Move { delta, min, max });
+                    continue;
+                }
+                Instruction::OOO => ops.push(Op::Clear),
+                Instruction::Moo2 => ops.push(Op::Io),
+                Instruction::OOM => ops.push(Op::PrintInt),
+                Instruction::Oom => ops.push(Op::ReadInt),
+                Instruction::MMM => ops.push(Op::Register),
+                Instruction::MOO2 => ops.push(Op::LoopStart(None)),
+                Instruction::Moo => ops.push(Op::LoopEnd(None)),
+                Instruction::MOO | Instruction::Custom(_) => unreachable!(),
+            }
+            pos += 1;
+        }
+        op_index[program.len()] = ops.len();
+
+        // Переходы заполняются, когда известны индексы всех операций
+        for (pos, instruction) in program.iter().enumerate() {
+            let op = &mut ops[op_index[pos]];
+            match (instruction, jumps[pos]) {
+                (Instruction::MOO2, Some(end)) => *op = Op::LoopStart(Some(op_index[end + 1])),
+                // MOO последней инструкцией при нулевой ячейке просто завершает программу
+                (Instruction::MOO2, None) if pos + 1 == program.len() => *op = Op::LoopStart(Some(op_index[program.len()])),
+                (Instruction::Moo, Some(start)) => *op = Op::LoopEnd(Some(op_index[start])),
+                _ => {}
+            }
+        }
+
+        Ok(Bytecode { ops })
+    }
+
+    // Выполнение на чистой ленте. Возвращает вывод и количество выполненных операций.
+    pub fn run(&self, input: &mut dyn CowIo) -> Result<(String, usize), String> {
+        let mut memory = vec![0i32];
+        let mut mem_pos = 0usize;
+        let mut register: Option<i32> = None;
+        let mut output = String::new();
+        let mut pc = 0;
+        let mut executed = 0;
+
+        while pc < self.ops.len() {
+            executed += 1;
+            match self.ops[pc] {
+                Op::Add(value) => memory[mem_pos] = memory[mem_pos].wrapping_add(value),
+                Op::Move { delta, min, max } => {
+                    if (mem_pos as isize) + min < 0 {
+                        break;
+                    }
+                    let end = (mem_pos as isize + max) as usize;
+                    if end >= memory.len() {
+                        memory.resize(end + 1, 0);
+                    }
+                    mem_pos = (mem_pos as isize + delta) as usize;
+                }
+                Op::Clear => memory[mem_pos] = 0,
+                Op::Io => {
+                    if memory[mem_pos] != 0 {
+                        if let Some(ch) = char::from_u32(memory[mem_pos] as u32) {
+                            output.push(ch);
+                        }
+                    } else if let Some(ch) = input.read_char() {
+                        memory[mem_pos] = ch as i32;
+                    }
+                }
+                Op::PrintInt => {
+                    output.push_str(&memory[mem_pos].to_string());
+                    output.push('\n');
+                }
+                Op::ReadInt => {
+                    memory[mem_pos] = input.read_line().unwrap_or_default().trim().parse().unwrap_or(0);
+                }
+                Op::Register => match register.take() {
+                    None => register = Some(memory[mem_pos]),
+                    Some(value) => memory[mem_pos] = value,
+                },
+                Op::LoopStart(target) => {
+                    if memory[mem_pos] == 0 {
+                        match target {
+                            Some(target) => {
+                                pc = target;
+                                continue;
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                Op::LoopEnd(target) => match target {
+                    Some(target) => {
+                        pc = target;
+                        continue;
+                    }
+                    None => break,
+                },
+            }
+            pc += 1;
+        }
+
+        Ok((output, executed))
+    }
+}
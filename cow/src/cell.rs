@@ -0,0 +1,105 @@
+use crate::prelude::*;
+
+pub use num_bigint::BigInt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use core::fmt;
+use core::str::FromStr;
+
+// Тип значения ячейки ленты и регистра.
+// i32 - поведение по умолчанию, i64 и BigInt нужны программам с большими числами.
+// Фиксированные типы при переполнении заворачиваются, BigInt не переполняется.
+pub trait CellValue: Clone + Default + PartialEq + fmt::Debug + fmt::Display + FromStr + Serialize + DeserializeOwned {
+    fn from_i64(value: i64) -> Self;
+    // None, если значение не помещается в i64
+    fn to_i64(&self) -> Option<i64>;
+    fn increment(&mut self);
+    fn decrement(&mut self);
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Младший байт в дополнительном коде
+    fn low_byte(&self) -> u8 {
+        self.to_i64().map_or(0, |value| value as u8)
+    }
+}
+
+impl CellValue for i32 {
+    fn from_i64(value: i64) -> Self {
+        value as i32
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self as i64)
+    }
+
+    fn increment(&mut self) {
+        *self = self.wrapping_add(1);
+    }
+
+    fn decrement(&mut self) {
+        *self = self.wrapping_sub(1);
+    }
+}
+
+impl CellValue for i64 {
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn increment(&mut self) {
+        *self = self.wrapping_add(1);
+    }
+
+    fn decrement(&mut self) {
+        *self = self.wrapping_sub(1);
+    }
+}
+
+impl CellValue for BigInt {
+    fn from_i64(value: i64) -> Self {
+        BigInt::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self).ok()
+    }
+
+    fn increment(&mut self) {
+        *self += 1;
+    }
+
+    fn decrement(&mut self) {
+        *self -= 1;
+    }
+
+    fn low_byte(&self) -> u8 {
+        let byte: BigInt = self & BigInt::from(0xFF);
+        u8::try_from(&byte).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CellWidth {
+    #[default]
+    I32,
+    I64,
+    Big,
+}
+
+impl CellWidth {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "32" | "i32" => Ok(CellWidth::I32),
+            "64" | "i64" => Ok(CellWidth::I64),
+            "big" | "bigint" => Ok(CellWidth::Big),
+            _ => Err(format!("Неизвестная ширина ячейки {} (ожидается 32, 64 или big)", value)),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::cell::CellValue;
+use crate::interpreter::{CowInterpreter, ExecSummary, Instruction};
+use crate::io::CowIo;
+use crate::prelude::*;
+use crate::state::CowState;
+use crate::trace::RecordedInput;
+use serde::{Deserialize, Serialize};
+
+// Контрольная точка долгого вычисления: состояние машины, уже выведенный текст,
+// счётчики сводки и прочитанный ввод.
+// Отпечаток программы не даёт продолжить с точки, сохранённой для другой программы.
+// После возобновления ввод берётся из того же источника: прочитанное до точки пропускается.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Checkpoint<C: CellValue = i32> {
+    pub fingerprint: u64,
+    pub state: CowState<C>,
+    pub output: String,
+    // Точки, сохранённые до появления этих полей, восстанавливаются с нулевой сводкой и без пропуска ввода
+    #[serde(default)]
+    pub summary: ExecSummary,
+    #[serde(default)]
+    pub touched: Vec<usize>,
+    #[serde(default)]
+    pub inputs: Vec<RecordedInput>,
+}
+
+impl<C: CellValue> Checkpoint<C> {
+    // inputs - всё, что программа прочитала до точки, например из trace::InputRecorder
+    pub fn capture(interpreter: &CowInterpreter<C>, output: &str, inputs: &[RecordedInput]) -> Self {
+        Checkpoint {
+            fingerprint: fingerprint(&interpreter.program),
+            state: interpreter.state(),
+            output: output.to_string(),
+            summary: interpreter.summary(),
+            touched: interpreter.touched_cells(),
+            inputs: inputs.to_vec(),
+        }
+    }
+
+    // Вернуть интерпретатор в сохранённое состояние и пропустить в input столько же чтений,
+    // сколько было до точки. Уже выведенный текст остаётся в self.output
+    pub fn restore(&self, interpreter: &mut CowInterpreter<C>, input: &mut dyn CowIo) -> Result<(), String> {
+        if self.fingerprint != fingerprint(&interpreter.program) {
+            return Err("Контрольная точка сохранена для другой программы".to_string());
+        }
+        if self.state.mem_pos >= self.state.memory.len() {
+            return Err(format!(
+                "Некорректная контрольная точка: указатель {} за концом ленты из {} ячеек",
+                self.state.mem_pos,
+                self.state.memory.len()
+            ));
+        }
+
+        interpreter.memory = self.state.memory.clone();
+        interpreter.mem_pos = self.state.mem_pos;
+        interpreter.prog_pos = self.state.prog_pos;
+        interpreter.register = self.state.register.clone();
+        interpreter.steps = self.state.steps;
+        interpreter.halted = false;
+        interpreter.restore_summary(self.summary, &self.touched);
+        for read in &self.inputs {
+            match read {
+                RecordedInput::Char(_) => {
+                    input.read_char();
+                }
+                RecordedInput::Line(_) => {
+                    input.read_line();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("контрольная точка всегда сериализуется")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("Некорректная контрольная точка: {}", e))
+    }
+}
+
+// FNV-1a по токенам программы
+pub fn fingerprint(program: &[Instruction]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for instruction in program {
+        for byte in instruction.token().bytes().chain(core::iter::once(b' ')) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
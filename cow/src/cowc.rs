@@ -0,0 +1,164 @@
+use crate::interpreter::{jump_table, Instruction, SourcePos};
+use crate::prelude::*;
+
+// Двоичный формат разобранной программы (.cowc):
+//   "COWC", версия (1 байт), число инструкций (varint),
+//   инструкции по две в байте (младший полубайт - первая),
+//   переходы для каждого MOO/moo по порядку (varint, цель + 1, 0 - нет пары),
+//   признак карты исходника (1 байт) и для каждой инструкции
+//   приращение строки и столбец (varint).
+const MAGIC: &[u8; 4] = b"COWC";
+const VERSION: u8 = 1;
+// Пользовательские инструкции в .cowc не сохраняются: на их месте этот код,
+// и такой файл не загрузится
+const CUSTOM_CODE: u8 = 12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledProgram {
+    pub program: Vec<Instruction>,
+    pub jumps: Vec<Option<usize>>,
+    // Пусто, если карта исходника не сохранялась
+    pub positions: Vec<SourcePos>,
+}
+
+impl CompiledProgram {
+    pub fn new(program: Vec<Instruction>, positions: Vec<SourcePos>) -> Self {
+        CompiledProgram {
+            jumps: jump_table(&program),
+            program,
+            positions,
+        }
+    }
+}
+
+pub fn encode(compiled: &CompiledProgram) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    write_varint(&mut bytes, compiled.program.len());
+
+    for pair in compiled.program.chunks(2) {
+        let low = pair[0].code().unwrap_or(CUSTOM_CODE);
+        let high = pair.get(1).map_or(0, |instruction| instruction.code().unwrap_or(CUSTOM_CODE));
+        bytes.push(low | (high << 4));
+    }
+
+    for (instruction, jump) in compiled.program.iter().zip(&compiled.jumps) {
+        if is_jump(*instruction) {
+            write_varint(&mut bytes, jump.map_or(0, |target| target + 1));
+        }
+    }
+
+    let has_positions = compiled.positions.len() == compiled.program.len() && !compiled.program.is_empty();
+    bytes.push(has_positions as u8);
+    if has_positions {
+        let mut line = 0;
+        for pos in &compiled.positions {
+            write_varint(&mut bytes, pos.line - line);
+            write_varint(&mut bytes, pos.column);
+            line = pos.line;
+        }
+    }
+
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<CompiledProgram, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err("Файл не является программой .cowc".to_string());
+    }
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(format!("Неподдерживаемая версия .cowc: {}", version));
+    }
+
+    let count = reader.varint()?;
+    if count > bytes.len() * 2 {
+        return Err(format!("Некорректное число инструкций: {}", count));
+    }
+    let packed = reader.take(count.div_ceil(2))?;
+    let mut program = Vec::with_capacity(count);
+    for index in 0..count {
+        let byte = packed[index / 2];
+        let code = if index % 2 == 0 { byte & 0x0F } else { byte >> 4 };
+        if code == CUSTOM_CODE {
+            return Err(format!("Пользовательская инструкция на позиции {} не сохранена в .cowc", index));
+        }
+        let instruction = Instruction::from_code(code as i64)
+            .ok_or_else(|| format!("Неизвестный код инструкции {} на позиции {}", code, index))?;
+        program.push(instruction);
+    }
+
+    let mut jumps = vec![None; count];
+    for (index, instruction) in program.iter().enumerate() {
+        if is_jump(*instruction) {
+            let value = reader.varint()?;
+            if value > count {
+                return Err(format!("Переход за пределы программы на позиции {}", index));
+            }
+            jumps[index] = value.checked_sub(1);
+        }
+    }
+
+    let mut positions = Vec::new();
+    if reader.take(1)?[0] != 0 {
+        let mut line = 0;
+        for _ in 0..count {
+            line += reader.varint()?;
+            let column = reader.varint()?;
+            positions.push(SourcePos { line, column });
+        }
+    }
+
+    if reader.pos != bytes.len() {
+        return Err("Лишние данные в конце файла .cowc".to_string());
+    }
+
+    Ok(CompiledProgram {
+        program,
+        jumps,
+        positions,
+    })
+}
+
+fn is_jump(instruction: Instruction) -> bool {
+    matches!(instruction, Instruction::MOO2 | Instruction::Moo)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| "Файл .cowc обрезан".to_string())?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut value = 0usize;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Слишком длинное число в файле .cowc".to_string())
+    }
+}
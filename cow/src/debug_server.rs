@@ -0,0 +1,204 @@
+use crate::debugger::{Debugger, StopReason, WatchCondition, Watchpoint};
+use crate::interpreter::CowInterpreter;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::net::TcpListener;
+
+// Сколько шагов continue выполняет без явного maxSteps, чтобы бесконечная программа не подвесила сервер
+const DEFAULT_CONTINUE_STEPS: usize = 10_000_000;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EXECUTION_ERROR: i64 = -32000;
+
+// Отладчик, управляемый по JSON-RPC 2.0: один запрос или ответ на строку.
+// Методы: state, step, continue, setBreakpoint, clearBreakpoint,
+// setWatchpoint, clearWatchpoints, readMemory, input.
+pub struct DebugServer {
+    interpreter: CowInterpreter,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+    // Строки ввода, переданные методом input и ещё не прочитанные программой
+    input: VecDeque<String>,
+}
+
+impl DebugServer {
+    pub fn new(interpreter: CowInterpreter) -> Self {
+        DebugServer {
+            interpreter,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            input: VecDeque::new(),
+        }
+    }
+
+    // Обработать одну строку запроса и вернуть строку ответа
+    pub fn handle(&mut self, request: &str) -> String {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(err) => return error_response(Value::Null, PARSE_ERROR, &err.to_string()),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+        let params = request.get("params").cloned().unwrap_or(json!({}));
+
+        match self.dispatch(method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+            Err((code, message)) => error_response(id, code, &message),
+        }
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "state" => Ok(self.state()),
+            "step" => {
+                let count = optional_usize(params, "count")?.unwrap_or(1);
+                self.run(Some(count))
+            }
+            "continue" => {
+                let limit = optional_usize(params, "maxSteps")?.unwrap_or(DEFAULT_CONTINUE_STEPS);
+                self.run(Some(limit))
+            }
+            "setBreakpoint" => {
+                self.breakpoints.insert(required_usize(params, "position")?);
+                Ok(json!(self.breakpoints))
+            }
+            "clearBreakpoint" => {
+                let removed = self.breakpoints.remove(&required_usize(params, "position")?);
+                Ok(json!(removed))
+            }
+            "setWatchpoint" => {
+                let cell = required_usize(params, "cell")?;
+                let condition = match params.get("value") {
+                    Some(value) => WatchCondition::Equals(
+                        value
+                            .as_i64()
+                            .and_then(|value| i32::try_from(value).ok())
+                            .ok_or_else(|| invalid_params("value"))?,
+                    ),
+                    None => WatchCondition::Changed,
+                };
+                self.watchpoints.push(Watchpoint { cell, condition });
+                Ok(json!(self.watchpoints.len() - 1))
+            }
+            "clearWatchpoints" => {
+                self.watchpoints.clear();
+                Ok(Value::Null)
+            }
+            "readMemory" => {
+                let memory = self.interpreter.get_memory();
+                let start = optional_usize(params, "start")?.unwrap_or(0).min(memory.len());
+                let count = optional_usize(params, "count")?.unwrap_or(memory.len());
+                let end = start.saturating_add(count).min(memory.len());
+                Ok(json!(memory[start..end]))
+            }
+            "input" => {
+                let lines = params
+                    .get("lines")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid_params("lines"))?;
+                for line in lines {
+                    let line = line.as_str().ok_or_else(|| invalid_params("lines"))?;
+                    self.input.push_back(line.to_string());
+                }
+                Ok(json!(self.input.len()))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Неизвестный метод {}", method))),
+        }
+    }
+
+    fn run(&mut self, limit: Option<usize>) -> Result<Value, (i64, String)> {
+        let mut debugger = Debugger::new(&mut self.interpreter);
+        for watchpoint in &self.watchpoints {
+            debugger.add_watchpoint(watchpoint.clone());
+        }
+        for position in &self.breakpoints {
+            debugger.add_breakpoint(*position);
+        }
+
+        let queue = &mut self.input;
+        let mut input = std::iter::from_fn(|| queue.pop_front());
+        let mut output = String::new();
+        let stop = debugger
+            .resume(&mut output, &mut input, limit)
+            .map_err(|err| (EXECUTION_ERROR, err))?;
+
+        let stop = match stop {
+            StopReason::Finished => json!({ "reason": "finished" }),
+            StopReason::Breakpoint(position) => json!({ "reason": "breakpoint", "position": position }),
+            StopReason::Watchpoint(hit) => json!({
+                "reason": "watchpoint",
+                "watchpoint": hit.watchpoint,
+                "cell": hit.cell,
+                "old": hit.old,
+                "new": hit.new,
+                "position": hit.position,
+                "description": hit.description,
+            }),
+            StopReason::Limit => json!({ "reason": "limit" }),
+        };
+
+        Ok(json!({ "stop": stop, "output": output, "state": self.state() }))
+    }
+
+    fn state(&self) -> Value {
+        let interpreter = &self.interpreter;
+        json!({
+            "position": interpreter.prog_pos,
+            "instruction": interpreter.program.get(interpreter.prog_pos).map(|i| i.token()),
+            "location": interpreter.describe_position(interpreter.prog_pos),
+            "pointer": interpreter.mem_pos,
+            "register": interpreter.register,
+            "steps": interpreter.steps,
+            "finished": interpreter.is_finished(),
+        })
+    }
+
+    // Обслуживать запросы, пока reader не закроется
+    pub fn serve<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(writer, "{}", self.handle(&line))?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    // Принимать подключения по TCP по одному; состояние отладчика сохраняется между ними
+    pub fn serve_tcp(&mut self, address: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let reader = io::BufReader::new(stream.try_clone()?);
+            self.serve(reader, stream)?;
+        }
+        Ok(())
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
+
+fn invalid_params(name: &str) -> (i64, String) {
+    (INVALID_PARAMS, format!("Некорректный параметр {}", name))
+}
+
+fn optional_usize(params: &Value, name: &str) -> Result<Option<usize>, (i64, String)> {
+    match params.get(name) {
+        None => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|value| Some(value as usize))
+            .ok_or_else(|| invalid_params(name)),
+    }
+}
+
+fn required_usize(params: &Value, name: &str) -> Result<usize, (i64, String)> {
+    optional_usize(params, name)?.ok_or_else(|| invalid_params(name))
+}
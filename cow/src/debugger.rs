@@ -0,0 +1,326 @@
+use alloc::collections::{BTreeSet, VecDeque};
+use core::fmt;
+use crate::cell::CellValue;
+use crate::interpreter::CowInterpreter;
+use crate::io::CowIo;
+use crate::prelude::*;
+use crate::trace::RecordedInput;
+
+// Сколько последних шагов можно отменить по умолчанию
+pub const DEFAULT_HISTORY_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchCondition<C: CellValue = i32> {
+    // Любое изменение значения ячейки
+    Changed,
+    // Ячейка стала равна значению (срабатывает на переходе, а не пока значение держится)
+    Equals(C),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watchpoint<C: CellValue = i32> {
+    pub cell: usize,
+    pub condition: WatchCondition<C>,
+}
+
+impl<C: CellValue> Watchpoint<C> {
+    // Разбор записи вида "3" (любое изменение) или "3=65" (равенство значению)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (cell, value) = match text.split_once('=') {
+            Some((cell, value)) => (cell, Some(value)),
+            None => (text, None),
+        };
+        let cell = cell
+            .trim()
+            .parse()
+            .map_err(|_| format!("Некорректный номер ячейки {}", cell))?;
+        let condition = match value {
+            Some(value) => WatchCondition::Equals(
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Некорректное значение {}", value))?,
+            ),
+            None => WatchCondition::Changed,
+        };
+        Ok(Watchpoint { cell, condition })
+    }
+}
+
+// Срабатывание точки наблюдения
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchHit<C: CellValue = i32> {
+    // Номер точки наблюдения в порядке добавления
+    pub watchpoint: usize,
+    pub cell: usize,
+    pub old: C,
+    pub new: C,
+    // Инструкция, которая изменила ячейку
+    pub position: usize,
+    pub description: String,
+}
+
+impl<C: CellValue> fmt::Display for WatchHit<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ячейка {}: {} -> {} на {}",
+            self.cell, self.old, self.new, self.description
+        )
+    }
+}
+
+// Почему остановилось выполнение в Debugger::resume
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason<C: CellValue = i32> {
+    Finished,
+    // Точка останова перед выполнением инструкции на этой позиции
+    Breakpoint(usize),
+    Watchpoint(WatchHit<C>),
+    // Выполнено заданное число шагов
+    Limit,
+}
+
+// Всё, что нужно, чтобы отменить один шаг.
+// Инструкция меняет не больше одной ячейки - ту, на которую указатель смотрел до шага,
+// а moO может только дописать ячейки в конец ленты.
+struct Undo<C: CellValue> {
+    prog_pos: usize,
+    mem_pos: usize,
+    register: Option<C>,
+    steps: usize,
+    halted: bool,
+    memory_len: usize,
+    cell: C,
+    output_len: usize,
+    trace_len: Option<usize>,
+    // Прочитанный на шаге ввод: при повторном выполнении он выдаётся снова
+    inputs: Vec<RecordedInput>,
+}
+
+// Ввод для одного шага: сначала возвращённый отменой шагов, потом из источника
+struct StepInput<'a> {
+    inner: &'a mut dyn CowIo,
+    replay: &'a mut VecDeque<RecordedInput>,
+    consumed: Vec<RecordedInput>,
+}
+
+impl CowIo for StepInput<'_> {
+    fn read_char(&mut self) -> Option<char> {
+        let value = match self.replay.pop_front() {
+            Some(RecordedInput::Char(value)) => value,
+            Some(RecordedInput::Line(line)) => line.and_then(|line| line.chars().next()),
+            None => self.inner.read_char(),
+        };
+        self.consumed.push(RecordedInput::Char(value));
+        value
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let value = match self.replay.pop_front() {
+            Some(RecordedInput::Line(value)) => value,
+            Some(RecordedInput::Char(ch)) => ch.map(String::from),
+            None => self.inner.read_line(),
+        };
+        self.consumed.push(RecordedInput::Line(value.clone()));
+        value
+    }
+}
+
+// Пошаговое выполнение с точками останова и точками наблюдения за ячейками ленты.
+// Последние шаги запоминаются в ограниченном кольце, и их можно отменить (step_back).
+pub struct Debugger<'a, C: CellValue = i32> {
+    pub interpreter: &'a mut CowInterpreter<C>,
+    watchpoints: Vec<Watchpoint<C>>,
+    breakpoints: BTreeSet<usize>,
+    history: VecDeque<Undo<C>>,
+    history_limit: usize,
+    replay: VecDeque<RecordedInput>,
+}
+
+impl<'a, C: CellValue> Debugger<'a, C> {
+    pub fn new(interpreter: &'a mut CowInterpreter<C>) -> Self {
+        Debugger {
+            interpreter,
+            watchpoints: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            history: VecDeque::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            replay: VecDeque::new(),
+        }
+    }
+
+    // Сколько шагов хранить для отмены; 0 отключает историю
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    // Сколько шагов можно отменить прямо сейчас
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    // Отменить последний шаг: восстановить ленту, указатели и регистр,
+    // обрезать вывод и трассу. Прочитанный ввод будет выдан программе снова.
+    // Статистика (summary) и тепловая карта не откатываются.
+    pub fn step_back(&mut self, output: &mut String) -> bool {
+        let Some(undo) = self.history.pop_back() else {
+            return false;
+        };
+
+        let interpreter = &mut *self.interpreter;
+        interpreter.memory.truncate(undo.memory_len);
+        interpreter.memory[undo.mem_pos] = undo.cell;
+        interpreter.mem_pos = undo.mem_pos;
+        interpreter.prog_pos = undo.prog_pos;
+        interpreter.register = undo.register;
+        interpreter.steps = undo.steps;
+        interpreter.halted = undo.halted;
+        if let (Some(trace), Some(len)) = (interpreter.trace.as_mut(), undo.trace_len) {
+            trace.truncate(len);
+        }
+        output.truncate(undo.output_len.min(output.len()));
+        for input in undo.inputs.into_iter().rev() {
+            self.replay.push_front(input);
+        }
+        true
+    }
+
+    // Отменить до count шагов; возвращает, сколько удалось отменить
+    pub fn step_back_n(&mut self, count: usize, output: &mut String) -> usize {
+        (0..count).take_while(|_| self.step_back(output)).count()
+    }
+
+    pub fn add_breakpoint(&mut self, position: usize) {
+        self.breakpoints.insert(position);
+    }
+
+    pub fn remove_breakpoint(&mut self, position: usize) -> bool {
+        self.breakpoints.remove(&position)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    // Возвращает номер новой точки наблюдения
+    pub fn watch(&mut self, cell: usize, condition: WatchCondition<C>) -> usize {
+        self.watchpoints.push(Watchpoint { cell, condition });
+        self.watchpoints.len() - 1
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint<C>) -> usize {
+        self.watchpoints.push(watchpoint);
+        self.watchpoints.len() - 1
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint<C>] {
+        &self.watchpoints
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    // Один шаг; Some, если после него сработала точка наблюдения
+    pub fn step(&mut self, output: &mut String, input: &mut dyn CowIo) -> Result<Option<WatchHit<C>>, String> {
+        let position = self.interpreter.prog_pos;
+        let before: Vec<C> = self.watchpoints.iter().map(|w| self.cell(w.cell)).collect();
+
+        if self.interpreter.is_finished() || (self.history_limit == 0 && self.replay.is_empty()) {
+            self.interpreter.step(output, input)?;
+        } else {
+            let interpreter = &*self.interpreter;
+            let mut undo = Undo {
+                prog_pos: interpreter.prog_pos,
+                mem_pos: interpreter.mem_pos,
+                register: interpreter.register.clone(),
+                steps: interpreter.steps,
+                halted: interpreter.halted,
+                memory_len: interpreter.memory.len(),
+                cell: interpreter.memory[interpreter.mem_pos].clone(),
+                output_len: output.len(),
+                trace_len: interpreter.trace.as_ref().map(Vec::len),
+                inputs: Vec::new(),
+            };
+            let mut step_input = StepInput {
+                inner: input,
+                replay: &mut self.replay,
+                consumed: Vec::new(),
+            };
+            let result = self.interpreter.step(output, &mut step_input);
+            undo.inputs = step_input.consumed;
+
+            if self.history_limit > 0 {
+                if self.history.len() == self.history_limit {
+                    self.history.pop_front();
+                }
+                self.history.push_back(undo);
+            }
+            result?;
+        }
+
+        for (index, (watchpoint, old)) in self.watchpoints.iter().zip(before).enumerate() {
+            let new = self.cell(watchpoint.cell);
+            let triggered = match &watchpoint.condition {
+                WatchCondition::Changed => new != old,
+                WatchCondition::Equals(value) => new == *value && old != *value,
+            };
+            if triggered {
+                return Ok(Some(WatchHit {
+                    watchpoint: index,
+                    cell: watchpoint.cell,
+                    old,
+                    new,
+                    position,
+                    description: self.interpreter.describe_position(position),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Выполнять до срабатывания точки наблюдения или завершения программы
+    pub fn run(&mut self, output: &mut String, input: &mut dyn CowIo) -> Result<Option<WatchHit<C>>, String> {
+        while !self.interpreter.is_finished() {
+            if let Some(hit) = self.step(output, input)? {
+                return Ok(Some(hit));
+            }
+        }
+        Ok(None)
+    }
+
+    // Выполнять до точки останова, точки наблюдения, завершения программы или max_steps шагов.
+    // Точка останова на текущей инструкции не мешает продолжить с неё.
+    pub fn resume(
+        &mut self,
+        output: &mut String,
+        input: &mut dyn CowIo,
+        max_steps: Option<usize>,
+    ) -> Result<StopReason<C>, String> {
+        let mut executed = 0;
+        loop {
+            if self.interpreter.is_finished() {
+                return Ok(StopReason::Finished);
+            }
+            if executed > 0 && self.breakpoints.contains(&self.interpreter.prog_pos) {
+                return Ok(StopReason::Breakpoint(self.interpreter.prog_pos));
+            }
+            if max_steps.is_some_and(|limit| executed >= limit) {
+                return Ok(StopReason::Limit);
+            }
+            executed += 1;
+            if let Some(hit) = self.step(output, input)? {
+                return Ok(StopReason::Watchpoint(hit));
+            }
+        }
+    }
+
+    fn cell(&self, index: usize) -> C {
+        self.interpreter.memory.get(index).cloned().unwrap_or_default()
+    }
+}
@@ -0,0 +1,68 @@
+use crate::interpreter::{jump_table, CowInterpreter, Instruction};
+use crate::prelude::*;
+
+pub const DEFAULT_LINE_WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+// Каноническое оформление программы: только инструкции, разделённые пробелами,
+// тело каждого парного цикла MOO ... moo с дополнительным отступом,
+// строки переносятся по ширине line_width.
+pub fn format_program(program: &[Instruction], line_width: usize) -> String {
+    let jumps = jump_table(program);
+    let mut output = String::new();
+    let mut line = String::new();
+    let mut depth = 0;
+
+    for (pos, instruction) in program.iter().enumerate() {
+        let opens_loop = *instruction == Instruction::MOO2 && jumps[pos].is_some();
+        let closes_loop = *instruction == Instruction::Moo && jumps[pos].is_some() && depth > 0;
+
+        if opens_loop || closes_loop {
+            flush_line(&mut output, &mut line);
+        }
+        if closes_loop {
+            depth -= 1;
+        }
+
+        let indent = INDENT.repeat(depth);
+        let token = instruction.token();
+        if !line.is_empty() && line.len() + 1 + token.len() > line_width {
+            flush_line(&mut output, &mut line);
+        }
+        if line.is_empty() {
+            line.push_str(&indent);
+        } else {
+            line.push(' ');
+        }
+        line.push_str(token);
+
+        if opens_loop || closes_loop {
+            flush_line(&mut output, &mut line);
+        }
+        if opens_loop {
+            depth += 1;
+        }
+    }
+
+    flush_line(&mut output, &mut line);
+    output
+}
+
+// Самая плотная запись: инструкции подряд, без пробелов и комментариев.
+// Разбор после совпадения токена начинается заново, поэтому склейка читается так же.
+// Обратное преобразование - format_program.
+pub fn minify(source: &str) -> String {
+    minify_program(&CowInterpreter::parse(source).unwrap_or_default())
+}
+
+pub fn minify_program(program: &[Instruction]) -> String {
+    program.iter().map(Instruction::token).collect()
+}
+
+fn flush_line(output: &mut String, line: &mut String) {
+    if !line.is_empty() {
+        output.push_str(line);
+        output.push('\n');
+        line.clear();
+    }
+}
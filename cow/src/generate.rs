@@ -0,0 +1,81 @@
+use crate::format::{format_program, DEFAULT_LINE_WIDTH};
+use crate::interpreter::Instruction;
+use crate::prelude::*;
+use crate::program::ProgramBuilder;
+
+// Накладные расходы цикла умножения: moO MOO mOo moO MOo moo mOo
+const LOOP_OVERHEAD: usize = 7;
+
+// Программа COW, которая печатает заданный текст.
+// Ячейка 0 хранит код текущего символа и переиспользуется между символами,
+// большие изменения набираются циклом с ячейкой 1 в роли счётчика.
+// Символ с кодом 0 пропускается: Moo на нулевой ячейке читает ввод, а не печатает.
+pub fn print_string(text: &str) -> String {
+    format_program(&print_string_program(text), DEFAULT_LINE_WIDTH)
+}
+
+pub fn print_string_program(text: &str) -> Vec<Instruction> {
+    let mut program = ProgramBuilder::new();
+    let mut current: i64 = 0;
+
+    for ch in text.chars() {
+        let target = ch as i64;
+        if target == 0 {
+            continue;
+        }
+        emit_delta(&mut program, target - current);
+        program.push(Instruction::Moo2);
+        current = target;
+    }
+
+    program.build()
+}
+
+fn emit_delta(program: &mut ProgramBuilder, delta: i64) {
+    let step = if delta >= 0 { Instruction::MoO2 } else { Instruction::MOo2 };
+    let back = if delta >= 0 { Instruction::MOo2 } else { Instruction::MoO2 };
+    let amount = delta.unsigned_abs() as usize;
+
+    match best_loop(amount) {
+        Some((counter, body, rest)) => {
+            program.push(Instruction::MoO).push_n(Instruction::MoO2, counter);
+            program.loop_(|b| {
+                b.push(Instruction::MOo).push_n(step, body).push(Instruction::MoO).push(Instruction::MOo2);
+            });
+            program.push(Instruction::MOo);
+
+            // rest может быть отрицательным, если выгоднее перелететь и вернуться
+            let correction = if rest >= 0 { step } else { back };
+            program.push_n(correction, rest.unsigned_abs() as usize);
+        }
+        None => {
+            program.push_n(step, amount);
+        }
+    }
+}
+
+// Лучшее разложение amount = counter * body + rest, если цикл короче прямого набора
+fn best_loop(amount: usize) -> Option<(usize, usize, i64)> {
+    let mut best: Option<(usize, usize, i64)> = None;
+    let mut best_cost = amount;
+
+    for counter in 2..=amount {
+        let low = amount / counter;
+        for body in [low, low + 1] {
+            if body < 2 {
+                continue;
+            }
+            let rest = amount as i64 - (counter * body) as i64;
+            let cost = counter + body + LOOP_OVERHEAD + rest.unsigned_abs() as usize;
+            if cost < best_cost {
+                best_cost = cost;
+                best = Some((counter, body, rest));
+            }
+        }
+        if counter * 2 > amount + 2 {
+            break;
+        }
+    }
+
+    best
+}
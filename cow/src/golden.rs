@@ -0,0 +1,145 @@
+use crate::interpreter::CowInterpreter;
+use crate::io::{self, InputMode};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+// Защита от зависших программ в корпусе
+pub const DEFAULT_GOLDEN_MAX_STEPS: usize = 100_000_000;
+// Сколько отличающихся строк показывать в отчёте
+const MAX_DIFF_LINES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoldenOutcome {
+    Passed,
+    // Вывод отличается от ожидаемого, diff - построчное сравнение
+    Failed { diff: String },
+    // Программа не разобралась, завершилась с ошибкой или нет файла .out
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenResult {
+    pub name: String,
+    pub steps: usize,
+    pub outcome: GoldenOutcome,
+}
+
+impl GoldenResult {
+    pub fn passed(&self) -> bool {
+        self.outcome == GoldenOutcome::Passed
+    }
+}
+
+// Выполнить программу на вводе input (по строке на oom/Moo) и сравнить вывод с expected
+pub fn run_case(name: &str, source: &str, input: &str, expected: &str, max_steps: usize) -> GoldenResult {
+    let mut result = GoldenResult {
+        name: name.to_string(),
+        steps: 0,
+        outcome: GoldenOutcome::Passed,
+    };
+
+    let mut interpreter = match CowInterpreter::new(source) {
+        Ok(interpreter) => interpreter,
+        Err(err) => {
+            result.outcome = GoldenOutcome::Error(err);
+            return result;
+        }
+    };
+    interpreter.max_steps = Some(max_steps);
+
+    let mut input = io::reader_input(Cursor::new(input.to_string()), InputMode::Line);
+    let output = interpreter.execute_with_input(input.as_mut());
+    result.steps = interpreter.steps;
+    result.outcome = match output {
+        Ok(output) if output == expected => GoldenOutcome::Passed,
+        Ok(output) => GoldenOutcome::Failed {
+            diff: diff(expected, &output),
+        },
+        Err(err) => GoldenOutcome::Error(err),
+    };
+    result
+}
+
+// Все программы каталога: name.cow выполняется на name.in (если он есть)
+// и сравнивается с name.out. Результаты упорядочены по имени.
+pub fn run_dir(dir: &Path, max_steps: usize) -> Result<Vec<GoldenResult>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| format!("Ошибка при чтении каталога '{}': {}", dir.display(), err))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cow"))
+        .collect();
+    paths.sort();
+
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(|err| format!("Ошибка при чтении файла '{}': {}", path.display(), err))
+    };
+
+    let mut results = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let input_path = path.with_extension("in");
+        let prepared = read(&path).and_then(|source| {
+            let input = if input_path.exists() { read(&input_path)? } else { String::new() };
+            let expected = read(&path.with_extension("out"))?;
+            Ok((source, input, expected))
+        });
+        results.push(match prepared {
+            Ok((source, input, expected)) => run_case(&name, &source, &input, &expected, max_steps),
+            Err(err) => GoldenResult {
+                name,
+                steps: 0,
+                outcome: GoldenOutcome::Error(err),
+            },
+        });
+    }
+    Ok(results)
+}
+
+// Построчное сравнение: номер строки, ожидаемое (-) и полученное (+)
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.split('\n').collect();
+    let actual: Vec<&str> = actual.split('\n').collect();
+    let mut text = String::new();
+    let mut shown = 0;
+
+    for index in 0..expected.len().max(actual.len()) {
+        let (left, right) = (expected.get(index), actual.get(index));
+        if left == right {
+            continue;
+        }
+        if shown == MAX_DIFF_LINES {
+            text.push_str("  ...\n");
+            break;
+        }
+        text.push_str(&format!("  строка {}:\n", index + 1));
+        match left {
+            Some(line) => text.push_str(&format!("  - {:?}\n", line)),
+            None => text.push_str("  - (нет строки)\n"),
+        }
+        match right {
+            Some(line) => text.push_str(&format!("  + {:?}\n", line)),
+            None => text.push_str("  + (нет строки)\n"),
+        }
+        shown += 1;
+    }
+    text
+}
+
+// Отчёт: строка на программу с числом шагов, для проваленных - diff, в конце итог
+pub fn format_report(results: &[GoldenResult]) -> String {
+    let mut report = String::new();
+    for result in results {
+        let status = if result.passed() { "ok" } else { "FAIL" };
+        report.push_str(&format!("{:<5} {} ({} шагов)\n", status, result.name, result.steps));
+        match &result.outcome {
+            GoldenOutcome::Passed => {}
+            GoldenOutcome::Failed { diff } => report.push_str(diff),
+            GoldenOutcome::Error(err) => report.push_str(&format!("  ошибка: {}\n", err)),
+        }
+    }
+    let passed = results.iter().filter(|result| result.passed()).count();
+    report.push_str(&format!("Пройдено: {} из {}\n", passed, results.len()));
+    report
+}
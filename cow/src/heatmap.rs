@@ -0,0 +1,73 @@
+use crate::prelude::*;
+
+// Счётчики чтений и записей по ячейкам ленты
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Heatmap {
+    pub reads: Vec<u64>,
+    pub writes: Vec<u64>,
+}
+
+// Ширина картинки PPM в ячейках
+const PPM_WIDTH: usize = 32;
+
+impl Heatmap {
+    pub fn record_read(&mut self, cell: usize) {
+        Self::bump(&mut self.reads, cell);
+    }
+
+    pub fn record_write(&mut self, cell: usize) {
+        Self::bump(&mut self.writes, cell);
+    }
+
+    fn bump(counts: &mut Vec<u64>, cell: usize) {
+        if cell >= counts.len() {
+            counts.resize(cell + 1, 0);
+        }
+        counts[cell] += 1;
+    }
+
+    pub fn cells(&self) -> usize {
+        self.reads.len().max(self.writes.len())
+    }
+
+    pub fn reads_of(&self, cell: usize) -> u64 {
+        self.reads.get(cell).copied().unwrap_or(0)
+    }
+
+    pub fn writes_of(&self, cell: usize) -> u64 {
+        self.writes.get(cell).copied().unwrap_or(0)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("cell,reads,writes\n");
+        for cell in 0..self.cells() {
+            csv.push_str(&format!("{},{},{}\n", cell, self.reads_of(cell), self.writes_of(cell)));
+        }
+        csv
+    }
+
+    // Картинка PPM (P3): ячейка - пиксель, красный - записи, зелёный - чтения,
+    // яркость относительно самой нагруженной ячейки
+    pub fn to_ppm(&self) -> String {
+        let cells = self.cells().max(1);
+        let width = cells.min(PPM_WIDTH);
+        let height = cells.div_ceil(width);
+        let max_reads = self.reads.iter().copied().max().unwrap_or(0).max(1);
+        let max_writes = self.writes.iter().copied().max().unwrap_or(0).max(1);
+
+        let mut ppm = format!("P3\n{} {}\n255\n", width, height);
+        for row in 0..height {
+            let pixels: Vec<String> = (0..width)
+                .map(|column| {
+                    let cell = row * width + column;
+                    let red = self.writes_of(cell) * 255 / max_writes;
+                    let green = self.reads_of(cell) * 255 / max_reads;
+                    format!("{} {} 0", red, green)
+                })
+                .collect();
+            ppm.push_str(&pixels.join("  "));
+            ppm.push('\n');
+        }
+        ppm
+    }
+}
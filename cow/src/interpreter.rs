@@ -1,144 +1,652 @@
-use std::io::{self, Write};
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::cell::CellValue;
+use crate::heatmap::Heatmap;
+use crate::io::{CowIo, OutputEncoding, OutputPolicy};
+use crate::prelude::*;
+use crate::state::CowState;
+use crate::trace::TraceStep;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
-    Moo = 0,   // moo - начало цикла
-    MOo = 1,   // mOo - переместить указатель влево
-    MoO = 2,   // moO - переместить указатель вправо
-    MOO = 3,   // mOO - выполнить команду из текущей ячейки
-    Moo2 = 4,  // Moo - вывести/ввести символ
-    MOo2 = 5,  // MOo - декремент
-    MoO2 = 6,  // MoO - инкремент
-    MOO2 = 7,  // MOO - конец цикла
-    OOO = 8,   // OOO - обнулить ячейку
-    MMM = 9,   // MMM - работа с регистром
-    OOM = 10,  // OOM - вывести число
-    Oom = 11,  // oom - ввести число
+    Moo,      // moo - начало цикла
+    MOo,      // mOo - переместить указатель влево
+    MoO,      // moO - переместить указатель вправо
+    MOO,      // mOO - выполнить команду из текущей ячейки
+    Moo2,     // Moo - вывести/ввести символ
+    MOo2,     // MOo - декремент
+    MoO2,     // MoO - инкремент
+    MOO2,     // MOO - конец цикла
+    OOO,      // OOO - обнулить ячейку
+    MMM,      // MMM - работа с регистром
+    OOM,      // OOM - вывести число
+    Oom,      // oom - ввести число
+    // Инструкция, добавленная встраивающим кодом, см. CowInterpreter::register_instruction
+    Custom(&'static str),
 }
 
-pub struct CowInterpreter {
+// Правила выполнения mOO.
+// Legacy - прежнее поведение этого интерпретатора: значение ячейки - индекс инструкции программы.
+// Strict - как в эталонной реализации COW: значение ячейки - код инструкции,
+// которая выполняется на месте mOO.
+// Переходы MOO/moo в обоих режимах одинаковые: find_loop_end и find_loop_start повторяют поиск
+// эталонной реализации, включая пропуск соседней инструкции. Отличается только счёт шагов:
+// там moo сразу выполняет найденный MOO, здесь MOO выполняется следующим шагом.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpecMode {
+    #[default]
+    Legacy,
+    Strict,
+}
+
+impl SpecMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "legacy" => Ok(SpecMode::Legacy),
+            "strict" => Ok(SpecMode::Strict),
+            _ => Err(format!("Неизвестный режим {} (ожидается legacy или strict)", name)),
+        }
+    }
+}
+
+// Lenient пропускает всё, что не является инструкцией (так пишутся комментарии),
+// Strict допускает только инструкции и пробельные символы
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+// Какой ввод прочитает следующая инструкция
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputRequest {
+    // Moo на нулевой ячейке
+    Char,
+    // oom
+    Line,
+}
+
+// Как часто (в шагах) проверяются отмена и таймаут
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+pub const TOKENS: [(&str, Instruction); 12] = [
+    ("moo", Instruction::Moo),
+    ("mOo", Instruction::MOo),
+    ("moO", Instruction::MoO),
+    ("mOO", Instruction::MOO),
+    ("Moo", Instruction::Moo2),
+    ("MOo", Instruction::MOo2),
+    ("MoO", Instruction::MoO2),
+    ("MOO", Instruction::MOO2),
+    ("OOO", Instruction::OOO),
+    ("MMM", Instruction::MMM),
+    ("OOM", Instruction::OOM),
+    ("oom", Instruction::Oom),
+];
+
+impl Instruction {
+    // Инструкция по её коду 0..11
+    pub fn from_code(code: i64) -> Option<Instruction> {
+        let index = usize::try_from(code).ok()?;
+        TOKENS.get(index).map(|(_, instruction)| *instruction)
+    }
+
+    // Код инструкции 0..11; у пользовательских инструкций кода нет
+    pub fn code(&self) -> Option<u8> {
+        TOKENS
+            .iter()
+            .position(|(_, instruction)| instruction == self)
+            .map(|index| index as u8)
+    }
+
+    // Текстовое представление инструкции в исходном коде COW
+    pub fn token(&self) -> &'static str {
+        match self {
+            Instruction::Custom(token) => token,
+            _ => TOKENS.iter().find(|(_, instruction)| instruction == self).map_or("", |(token, _)| token),
+        }
+    }
+}
+
+// Поиск MOO, к которому возвращается moo на позиции pos.
+// Инструкция непосредственно перед moo при поиске пропускается.
+pub fn find_loop_start(program: &[Instruction], pos: usize) -> Option<usize> {
+    if pos == 0 {
+        return None;
+    }
+
+    let mut pos = pos - 1;
+    let mut level = 1;
+
+    while level > 0 {
+        if pos == 0 {
+            break;
+        }
+        pos -= 1;
+
+        if program[pos] == Instruction::Moo {
+            level += 1;
+        } else if program[pos] == Instruction::MOO2 {
+            level -= 1;
+        }
+    }
+
+    if level != 0 {
+        return None;
+    }
+
+    Some(pos)
+}
+
+// Поиск moo, за который переходит MOO на позиции pos при нулевой ячейке.
+// Инструкция сразу после MOO при поиске пропускается.
+pub fn find_loop_end(program: &[Instruction], pos: usize) -> Option<usize> {
+    let mut pos = pos + 1;
+    let mut level = 1;
+
+    if pos >= program.len() {
+        return None;
+    }
+
+    while level > 0 {
+        let prev = program[pos];
+        pos += 1;
+
+        if pos >= program.len() {
+            break;
+        }
+
+        if program[pos] == Instruction::MOO2 {
+            level += 1;
+        } else if program[pos] == Instruction::Moo {
+            level -= 1;
+            if prev == Instruction::MOO2 {
+                level -= 1;
+            }
+        }
+    }
+
+    if level != 0 {
+        return None;
+    }
+
+    Some(pos)
+}
+
+// Таблица переходов: для каждого MOO - парный moo, для каждого moo - парный MOO
+pub fn jump_table(program: &[Instruction]) -> Vec<Option<usize>> {
+    program
+        .iter()
+        .enumerate()
+        .map(|(pos, instruction)| match instruction {
+            Instruction::MOO2 => find_loop_end(program, pos),
+            Instruction::Moo => find_loop_start(program, pos),
+            _ => None,
+        })
+        .collect()
+}
+
+fn find_token(text: &str, custom: &[&'static str]) -> Option<Instruction> {
+    TOKENS
+        .iter()
+        .find(|(token, _)| *token == text)
+        .map(|(_, instruction)| *instruction)
+        .or_else(|| custom.iter().find(|token| **token == text).map(|token| Instruction::Custom(token)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourcePos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "строка {}, столбец {}", self.line, self.column)
+    }
+}
+
+// Обработчик пользовательской инструкции. Получает состояние машины и может менять
+// ленту, указатель и регистр; изменённый prog_pos означает переход.
+pub type InstructionHandler<C> = Box<dyn FnMut(&mut CowState<C>) -> Result<(), String> + Send>;
+
+pub struct CowInterpreter<C: CellValue = i32> {
     pub program: Vec<Instruction>,
-    pub memory: Vec<i32>,
+    pub positions: Vec<SourcePos>,
+    pub memory: Vec<C>,
     pub mem_pos: usize,
     pub prog_pos: usize,
-    pub register: Option<i32>,
+    pub register: Option<C>,
+    pub max_steps: Option<usize>,
+    // Максимальная длина ленты; None - лента растёт без ограничений
+    pub max_memory: Option<usize>,
+    pub steps: usize,
+    pub output_policy: OutputPolicy,
+    pub output_encoding: OutputEncoding,
+    pub spec_mode: SpecMode,
+    // Если Some, каждый шаг записывается в трассу
+    pub trace: Option<Vec<TraceStep<C>>>,
+    // Если Some, считаются чтения и записи каждой ячейки
+    pub heatmap: Option<Heatmap>,
+    pub halted: bool,
+    custom: Vec<(&'static str, InstructionHandler<C>)>,
+    counters: ExecSummary,
+    touched: Vec<bool>,
 }
 
 impl CowInterpreter {
     pub fn new(source: &str) -> Result<Self, String> {
-        let program = Self::parse(source)?;
-        Ok(CowInterpreter {
-            program,
-            memory: vec![0],
-            mem_pos: 0,
-            prog_pos: 0,
-            register: None,
-        })
+        Self::with_cells(source)
     }
 
-    fn parse(source: &str) -> Result<Vec<Instruction>, String> {
-        let tokens = [
-            ("moo", Instruction::Moo),
-            ("mOo", Instruction::MOo),
-            ("moO", Instruction::MoO),
-            ("mOO", Instruction::MOO),
-            ("Moo", Instruction::Moo2),
-            ("MOo", Instruction::MOo2),
-            ("MoO", Instruction::MoO2),
-            ("MOO", Instruction::MOO2),
-            ("OOO", Instruction::OOO),
-            ("MMM", Instruction::MMM),
-            ("OOM", Instruction::OOM),
-            ("oom", Instruction::Oom),
-        ];
+    pub fn parse(source: &str) -> Result<Vec<Instruction>, String> {
+        Ok(Self::parse_with_positions(source)?.0)
+    }
+
+    // Разбор с сохранением строки и столбца начала каждой инструкции
+    pub fn parse_with_positions(source: &str) -> Result<(Vec<Instruction>, Vec<SourcePos>), String> {
+        Self::parse_with_instructions(source, &[])
+    }
 
+    // Разбор, в котором кроме стандартных инструкций распознаются пользовательские токены
+    pub fn parse_with_instructions(
+        source: &str,
+        custom: &[&'static str],
+    ) -> Result<(Vec<Instruction>, Vec<SourcePos>), String> {
         let mut program = Vec::new();
+        let mut positions = Vec::new();
         let mut buffer = String::new();
+        let mut buffer_positions = VecDeque::new();
+        let mut current = SourcePos { line: 1, column: 1 };
 
         for ch in source.chars() {
             buffer.push(ch);
+            buffer_positions.push_back(current);
             if buffer.len() > 3 {
                 buffer.remove(0);
+                buffer_positions.pop_front();
+            }
+
+            if ch == '\n' {
+                current.line += 1;
+                current.column = 1;
+            } else {
+                current.column += 1;
             }
 
             if buffer.len() == 3 {
-                for (token, instruction) in &tokens {
-                    if buffer == *token {
-                        program.push(*instruction);
-                        buffer.clear();
-                        break;
+                if let Some(instruction) = find_token(&buffer, custom) {
+                    program.push(instruction);
+                    positions.push(buffer_positions[0]);
+                    buffer.clear();
+                    buffer_positions.clear();
+                }
+            }
+        }
+
+        Ok((program, positions))
+    }
+
+    // Строгий разбор: каждое слово должно целиком состоять из инструкций,
+    // поэтому опечатка вроде MoOO - ошибка, а не молча изменённая программа
+    pub fn parse_strict(source: &str) -> Result<(Vec<Instruction>, Vec<SourcePos>), String> {
+        Self::parse_strict_with_instructions(source, &[])
+    }
+
+    pub fn parse_strict_with_instructions(
+        source: &str,
+        custom: &[&'static str],
+    ) -> Result<(Vec<Instruction>, Vec<SourcePos>), String> {
+        let mut program = Vec::new();
+        let mut positions = Vec::new();
+        let mut word: Vec<(char, SourcePos)> = Vec::new();
+        let mut current = SourcePos { line: 1, column: 1 };
+
+        for ch in source.chars().chain(core::iter::once(' ')) {
+            if ch.is_whitespace() {
+                for (index, chunk) in word.chunks(3).enumerate() {
+                    let text: String = chunk.iter().map(|(ch, _)| ch).collect();
+                    match find_token(&text, custom) {
+                        Some(instruction) => {
+                            program.push(instruction);
+                            positions.push(chunk[0].1);
+                        }
+                        None => {
+                            let rest: String = word[index * 3..].iter().map(|(ch, _)| ch).collect();
+                            return Err(format!("Нераспознанный текст \"{}\" ({})", rest, chunk[0].1));
+                        }
                     }
                 }
+                word.clear();
+            } else {
+                word.push((ch, current));
             }
+
+            if ch == '\n' {
+                current.line += 1;
+                current.column = 1;
+            } else {
+                current.column += 1;
+            }
+        }
+
+        Ok((program, positions))
+    }
+}
+
+impl<C: CellValue> CowInterpreter<C> {
+    // Интерпретатор с ячейками другого типа: CowInterpreter::<i64>::with_cells(source)
+    pub fn with_cells(source: &str) -> Result<Self, String> {
+        let (program, positions) = CowInterpreter::parse_with_positions(source)?;
+        Ok(Self::from_program(program, positions))
+    }
+
+    // Интерпретатор для уже разобранной программы; positions может быть пустым
+    pub fn from_program(program: Vec<Instruction>, positions: Vec<SourcePos>) -> Self {
+        CowInterpreter {
+            program,
+            positions,
+            memory: vec![C::default()],
+            mem_pos: 0,
+            prog_pos: 0,
+            register: None,
+            max_steps: None,
+            max_memory: None,
+            steps: 0,
+            output_policy: OutputPolicy::default(),
+            output_encoding: OutputEncoding::default(),
+            spec_mode: SpecMode::default(),
+            trace: None,
+            heatmap: None,
+            halted: false,
+            custom: Vec::new(),
+            counters: ExecSummary::default(),
+            touched: Vec::new(),
         }
+    }
+
+    // Начать с подготовленной ленты вместо длинных серий MoO:
+    // CowInterpreter::new(source)?.with_memory(vec![72, 105], 1).
+    // Лента дополняется нулями, чтобы указатель start_pos на неё попадал.
+    pub fn with_memory(mut self, initial: Vec<C>, start_pos: usize) -> Self {
+        self.memory = initial;
+        if start_pos >= self.memory.len() {
+            self.memory.resize(start_pos + 1, C::default());
+        }
+        self.mem_pos = start_pos;
+        self.counters.max_pointer = start_pos;
+        self
+    }
 
-        Ok(program)
+    // Начальный размер ленты; уже записанные ячейки сохраняются
+    pub fn set_memory_size(&mut self, size: usize) {
+        if size > self.memory.len() {
+            self.memory.resize(size, C::default());
+        }
+    }
+
+    // Добавить инструкцию диалекта COW. Токен должен быть из трёх символов и не совпадать
+    // со стандартными; повторная регистрация заменяет обработчик. В программу такие
+    // инструкции попадают через parse_with_instructions или builder().register_instruction.
+    pub fn register_instruction(
+        &mut self,
+        token: &'static str,
+        handler: impl FnMut(&mut CowState<C>) -> Result<(), String> + Send + 'static,
+    ) -> Result<(), String> {
+        self.set_instruction(token, Box::new(handler))
     }
 
+    pub(crate) fn set_instruction(&mut self, token: &'static str, handler: InstructionHandler<C>) -> Result<(), String> {
+        if token.chars().count() != 3 || token.chars().any(char::is_whitespace) {
+            return Err(format!("Инструкция должна состоять из трёх непробельных символов: \"{}\"", token));
+        }
+        if TOKENS.iter().any(|(name, _)| *name == token) {
+            return Err(format!("{} - стандартная инструкция COW, её нельзя переопределить", token));
+        }
+
+        match self.custom.iter_mut().find(|(name, _)| *name == token) {
+            Some(entry) => entry.1 = handler,
+            None => self.custom.push((token, handler)),
+        }
+        Ok(())
+    }
+
+    // Описание инструкции для сообщений об ошибках: "mOo (строка 12, столбец 4)"
+    pub fn describe_position(&self, pos: usize) -> String {
+        let token = match self.program.get(pos) {
+            Some(instruction) => instruction.token(),
+            None => return format!("позиция {} за концом программы", pos),
+        };
+
+        match self.positions.get(pos) {
+            Some(source_pos) => format!("{} ({})", token, source_pos),
+            None => format!("{} (инструкция {})", token, pos),
+        }
+    }
+
+    #[cfg(feature = "std")]
     pub fn execute(&mut self) -> Result<String, String> {
+        let mut stdin_iter = std::io::stdin().lines().map(|l| l.unwrap_or_default());
+        self.execute_with_input(&mut stdin_iter)
+    }
+
+    pub fn execute_with_input(&mut self, input: &mut dyn CowIo) -> Result<String, String> {
         let mut output = String::new();
 
-        while self.prog_pos < self.program.len() {
-            if !self.exec_instruction(&mut output)? {
+        while !self.is_finished() {
+            self.step(&mut output, input)?;
+        }
+
+        Ok(output)
+    }
+
+    // Выполнение с выводом в байтах, см. OutputEncoding::encode
+    pub fn execute_bytes(&mut self, input: &mut dyn CowIo) -> Result<Vec<u8>, String> {
+        let output = self.execute_with_input(input)?;
+        Ok(self.output_encoding.encode(&output))
+    }
+
+    // Выполнение, которое другой поток может прервать, выставив флаг cancel
+    pub fn execute_with_cancel(&mut self, input: &mut dyn CowIo, cancel: &AtomicBool) -> Result<String, String> {
+        self.execute_until(input, || cancel.load(Ordering::Relaxed), "Выполнение отменено")
+    }
+
+    // Выполнить не больше fuel шагов. Если программа не успела завершиться,
+    // возвращается пауза, из которой выполнение продолжается вызовом resume -
+    // так хост может делить время между несколькими программами.
+    pub fn run_fuel(&mut self, input: &mut dyn CowIo, fuel: usize) -> Result<FuelRun<'_, C>, String> {
+        let mut output = String::new();
+        for _ in 0..fuel {
+            if self.is_finished() {
                 break;
             }
+            self.step(&mut output, input)?;
         }
 
-        Ok(output)
+        if self.is_finished() {
+            Ok(FuelRun::Finished(output))
+        } else {
+            Ok(FuelRun::Paused(Paused {
+                interpreter: self,
+                output,
+            }))
+        }
     }
 
-    pub fn execute_with_input(&mut self, input: &mut dyn Iterator<Item = String>) -> Result<String, String> {
+    // Выполнение с ограничением по реальному времени
+    #[cfg(feature = "std")]
+    pub fn execute_with_timeout(&mut self, input: &mut dyn CowIo, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        self.execute_until(
+            input,
+            || Instant::now() >= deadline,
+            &format!("Превышено время выполнения ({} мс)", timeout.as_millis()),
+        )
+    }
+
+    fn execute_until(
+        &mut self,
+        input: &mut dyn CowIo,
+        mut should_stop: impl FnMut() -> bool,
+        reason: &str,
+    ) -> Result<String, String> {
         let mut output = String::new();
 
-        while self.prog_pos < self.program.len() {
-            if !self.exec_instruction_with_input(&mut output, input)? {
-                break;
+        while !self.is_finished() {
+            if self.steps.is_multiple_of(CANCEL_CHECK_INTERVAL) && should_stop() {
+                return Err(format!("{} на {}", reason, self.describe_position(self.prog_pos)));
             }
+            self.step(&mut output, input)?;
         }
 
         Ok(output)
     }
 
-    fn exec_instruction(&mut self, output: &mut String) -> Result<bool, String> {
-        let mut stdin_iter = std::io::stdin().lines().map(|l| l.unwrap_or_default());
-        self.exec_instruction_with_input(output, &mut stdin_iter)
+    // Выполнить одну инструкцию с учётом лимита шагов.
+    // Возвращает false, если программа остановилась.
+    pub fn step(
+        &mut self,
+        output: &mut String,
+        input: &mut dyn CowIo,
+    ) -> Result<bool, String> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        if let Some(limit) = self.max_steps {
+            if self.steps >= limit {
+                return Err(format!(
+                    "Превышен лимит шагов ({}) на {}",
+                    limit,
+                    self.describe_position(self.prog_pos)
+                ));
+            }
+        }
+        self.steps += 1;
+
+        let record = self.trace.is_some().then(|| TraceStep {
+            step: self.steps,
+            position: self.prog_pos,
+            instruction: self.program[self.prog_pos].token(),
+            pointer: self.mem_pos,
+            before: self.memory[self.mem_pos].clone(),
+            after: C::default(),
+        });
+
+        let running = self.exec_instruction_with_input(output, input)?;
+
+        if let (Some(trace), Some(mut record)) = (self.trace.as_mut(), record) {
+            record.after = self.memory[record.pointer].clone();
+            trace.push(record);
+        }
+
+        if !running {
+            self.halted = true;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    // Снимок состояния, см. CowState::diff
+    pub fn state(&self) -> CowState<C> {
+        CowState::of(self)
+    }
+
+    // Забрать накопленную трассу; запись продолжается в новую
+    pub fn take_trace(&mut self) -> Vec<TraceStep<C>> {
+        self.trace.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    // Выполнять до ближайшего вывода; None - программа завершилась, ничего не выведя.
+    // Удобно для интерактивных оболочек, которые чередуют вывод программы с запросами ввода.
+    pub fn run_until_output(&mut self, input: &mut dyn CowIo) -> Result<Option<OutputChunk>, String> {
+        while !self.is_finished() {
+            let position = self.prog_pos;
+            let mut text = String::new();
+            self.step(&mut text, input)?;
+
+            if !text.is_empty() {
+                return Ok(Some(OutputChunk { text, position }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Ленивый поток вывода: инструкции выполняются только по мере чтения итератора
+    pub fn outputs<'a>(&'a mut self, io: &'a mut dyn CowIo) -> Outputs<'a, C> {
+        Outputs {
+            interpreter: self,
+            io,
+            failed: false,
+        }
+    }
+
+    // Поток событий: шаги, вывод по символам, запросы ввода и остановка
+    pub fn events<'a>(&'a mut self, io: &'a mut dyn CowIo) -> Events<'a, C> {
+        Events {
+            interpreter: self,
+            io,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    // Будет ли следующий шаг читать ввод - с учётом того, что выполнит mOO.
+    // Нужно, чтобы заранее получить ввод из асинхронного источника.
+    pub fn next_input(&self) -> Option<InputRequest> {
+        let mut instruction = *self.program.get(self.prog_pos)?;
+        if instruction == Instruction::MOO {
+            let value = self.memory[self.mem_pos].to_i64()?;
+            instruction = match self.spec_mode {
+                SpecMode::Strict => Instruction::from_code(value)?,
+                SpecMode::Legacy if value != 3 && (0..12).contains(&value) => *self.program.get(value as usize)?,
+                SpecMode::Legacy => return None,
+            };
+        }
+
+        match instruction {
+            Instruction::Moo2 if self.memory[self.mem_pos].is_zero() => Some(InputRequest::Char),
+            Instruction::Oom => Some(InputRequest::Line),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.halted || self.prog_pos >= self.program.len()
     }
 
     pub fn exec_instruction_with_input(
         &mut self,
         output: &mut String,
-        input: &mut dyn Iterator<Item = String>,
+        input: &mut dyn CowIo,
     ) -> Result<bool, String> {
-        let instruction = self.program[self.prog_pos];
+        self.exec(self.program[self.prog_pos], output, input)
+    }
 
+    // Выполнить instruction так, будто она стоит на позиции prog_pos
+    fn exec(&mut self, instruction: Instruction, output: &mut String, input: &mut dyn CowIo) -> Result<bool, String> {
         match instruction {
-            // moo - прыжок назад к предыдущему MOO
+            // moo - прыжок назад к предыдущему MOO. Сам MOO выполняется следующим шагом
+            // основного цикла, так что долгие циклы не наращивают стек вызовов.
             Instruction::Moo => {
-                if self.prog_pos == 0 {
-                    return Ok(false);
-                }
-
-                self.prog_pos -= 1;
-                let mut level = 1;
-
-                while level > 0 {
-                    if self.prog_pos == 0 {
-                        break;
+                match find_loop_start(&self.program, self.prog_pos) {
+                    Some(start) => {
+                        self.prog_pos = start;
+                        self.counters.loop_iterations += 1;
+                        return Ok(true);
                     }
-                    self.prog_pos -= 1;
-
-                    if self.program[self.prog_pos] == Instruction::Moo {
-                        level += 1;
-                    } else if self.program[self.prog_pos] == Instruction::MOO2 {
-                        level -= 1;
+                    None => {
+                        self.prog_pos = 0;
+                        return Ok(false);
                     }
                 }
-
-                if level != 0 {
-                    return Ok(false);
-                }
-
-                return self.exec_instruction_with_input(output, input);
             }
 
             // mOo - переместить указатель влево
@@ -151,25 +659,54 @@ impl CowInterpreter {
 
             // moO - переместить указатель вправо
             Instruction::MoO => {
+                if let Some(limit) = self.max_memory {
+                    if self.mem_pos + 1 >= limit {
+                        return Err(format!(
+                            "Превышен размер ленты ({} ячеек) на {}",
+                            limit,
+                            self.describe_position(self.prog_pos)
+                        ));
+                    }
+                }
                 self.mem_pos += 1;
                 if self.mem_pos >= self.memory.len() {
-                    self.memory.push(0);
+                    self.memory.push(C::default());
                 }
+                self.counters.max_pointer = self.counters.max_pointer.max(self.mem_pos);
             }
 
             // mOO - выполнить команду из текущей ячейки памяти
             Instruction::MOO => {
-                let value = self.memory[self.mem_pos];
+                self.note_read();
+                let value = self.memory[self.mem_pos].to_i64().unwrap_or(-1);
                 if value == 3 {
                     return Ok(false);
                 }
-                if value >= 0 && value < 12 {
-                    let saved_pos = self.prog_pos;
-                    self.prog_pos = value as usize;
-                    if self.prog_pos < self.program.len() {
-                        self.exec_instruction_with_input(output, input)?;
+                if self.spec_mode == SpecMode::Strict {
+                    return match Instruction::from_code(value) {
+                        Some(target) => self.exec(target, output, input),
+                        None => Ok(false),
+                    };
+                }
+                if (0..12).contains(&value) {
+                    let target = value as usize;
+                    if let Some(&instruction) = self.program.get(target) {
+                        // Целью не бывает mOO, поэтому mOO никогда не вкладывается глубже одного уровня
+                        if instruction == Instruction::MOO {
+                            return Err(format!(
+                                "{} выполняет {} из ячейки {} - бесконечная рекурсия",
+                                self.describe_position(self.prog_pos),
+                                self.describe_position(target),
+                                self.mem_pos
+                            ));
+                        }
+
+                        let saved_pos = self.prog_pos;
+                        self.prog_pos = target;
+                        let result = self.exec(instruction, output, input);
+                        self.prog_pos = saved_pos;
+                        result?;
                     }
-                    self.prog_pos = saved_pos;
                 } else {
                     return Ok(false);
                 }
@@ -177,89 +714,148 @@ impl CowInterpreter {
 
             // Moo - вывести символ или ввести
             Instruction::Moo2 => {
-                if self.memory[self.mem_pos] != 0 {
-                    if let Some(ch) = char::from_u32(self.memory[self.mem_pos] as u32) {
-                        output.push(ch);
+                self.note_read();
+                if !self.memory[self.mem_pos].is_zero() {
+                    let value = &self.memory[self.mem_pos];
+                    let code = value.to_i64().and_then(|value| u32::try_from(value).ok());
+                    let ch = match self.output_encoding {
+                        OutputEncoding::Unicode => code.and_then(char::from_u32),
+                        OutputEncoding::Latin1 => code.and_then(|code| u8::try_from(code).ok()).map(char::from),
+                        OutputEncoding::Bytes => Some(char::from(value.low_byte())),
+                    };
+                    match (ch, self.output_policy) {
+                        (Some(ch), _) => output.push(ch),
+                        (None, OutputPolicy::Skip) => {}
+                        (None, OutputPolicy::Replace) => output.push(char::REPLACEMENT_CHARACTER),
+                        (None, OutputPolicy::Raw) => {
+                            output.push(char::from(value.low_byte()));
+                        }
+                        (None, OutputPolicy::Error) => {
+                            return Err(format!(
+                                "Значение {} в ячейке {} не является символом на {}",
+                                value,
+                                self.mem_pos,
+                                self.describe_position(self.prog_pos)
+                            ));
+                        }
                     }
+                    self.counters.outputs += 1;
                 } else {
-                    let input_str = input.next().unwrap_or_default();
-                    if let Some(ch) = input_str.chars().next() {
-                        self.memory[self.mem_pos] = ch as i32;
+                    if let Some(ch) = input.read_char() {
+                        self.memory[self.mem_pos] = C::from_i64(ch as i64);
+                        self.touch();
                     }
+                    self.counters.inputs += 1;
                 }
             }
 
             // MOo - декремент
             Instruction::MOo2 => {
-                self.memory[self.mem_pos] -= 1;
+                self.memory[self.mem_pos].decrement();
+                self.touch();
             }
 
             // MoO - инкремент
             Instruction::MoO2 => {
-                self.memory[self.mem_pos] += 1;
+                self.memory[self.mem_pos].increment();
+                self.touch();
             }
 
             // MOO - конец цикла (если ячейка == 0, прыгаем вперед)
             Instruction::MOO2 => {
-                if self.memory[self.mem_pos] == 0 {
-                    let mut level = 1;
-                    self.prog_pos += 1;
-
-                    if self.prog_pos >= self.program.len() {
+                self.note_read();
+                if self.memory[self.mem_pos].is_zero() {
+                    if self.prog_pos + 1 >= self.program.len() {
+                        self.prog_pos += 1;
                         return Ok(true);
                     }
 
-                    let mut prev = self.program[self.prog_pos - 1];
-
-                    while level > 0 {
-                        prev = self.program[self.prog_pos];
-                        self.prog_pos += 1;
-
-                        if self.prog_pos >= self.program.len() {
-                            break;
+                    match find_loop_end(&self.program, self.prog_pos) {
+                        Some(end) => self.prog_pos = end,
+                        None => {
+                            self.prog_pos = self.program.len();
+                            return Ok(false);
                         }
-
-                        if self.program[self.prog_pos] == Instruction::MOO2 {
-                            level += 1;
-                        } else if self.program[self.prog_pos] == Instruction::Moo {
-                            level -= 1;
-                            if prev == Instruction::MOO2 {
-                                level -= 1;
-                            }
-                        }
-                    }
-
-                    if level != 0 {
-                        return Ok(false);
                     }
                 }
             }
 
             // OOO - обнулить ячейку
             Instruction::OOO => {
-                self.memory[self.mem_pos] = 0;
+                self.memory[self.mem_pos] = C::default();
+                self.touch();
             }
 
             // MMM - работа с регистром
             Instruction::MMM => {
-                if self.register.is_none() {
-                    self.register = Some(self.memory[self.mem_pos]);
-                } else {
-                    self.memory[self.mem_pos] = self.register.unwrap();
-                    self.register = None;
+                match self.register.take() {
+                    None => {
+                        self.note_read();
+                        self.register = Some(self.memory[self.mem_pos].clone());
+                    }
+                    Some(value) => {
+                        self.memory[self.mem_pos] = value;
+                        self.touch();
+                    }
                 }
             }
 
             // OOM - вывести число
             Instruction::OOM => {
+                self.note_read();
                 output.push_str(&self.memory[self.mem_pos].to_string());
                 output.push('\n');
+                self.counters.outputs += 1;
             }
 
             // oom - ввести число
             Instruction::Oom => {
-                let input_str = input.next().unwrap_or_default();
-                self.memory[self.mem_pos] = input_str.trim().parse().unwrap_or(0);
+                let input_str = input.read_line().unwrap_or_default();
+                self.memory[self.mem_pos] = input_str.trim().parse().unwrap_or_default();
+                self.touch();
+                self.counters.inputs += 1;
+            }
+
+            // Пользовательская инструкция: состояние передаётся обработчику без копирования ленты
+            Instruction::Custom(token) => {
+                let index = self.custom.iter().position(|(name, _)| *name == token).ok_or_else(|| {
+                    format!("Нет обработчика для {}", self.describe_position(self.prog_pos))
+                })?;
+                let mut state = CowState {
+                    memory: core::mem::take(&mut self.memory),
+                    mem_pos: self.mem_pos,
+                    prog_pos: self.prog_pos,
+                    register: self.register.take(),
+                    steps: self.steps,
+                };
+                let result = (self.custom[index].1)(&mut state);
+                self.memory = state.memory;
+                self.register = state.register;
+                if self.memory.is_empty() {
+                    self.memory.push(C::default());
+                }
+                result.map_err(|err| format!("{} на {}", err, self.describe_position(self.prog_pos)))?;
+
+                if let Some(limit) = self.max_memory {
+                    if state.mem_pos >= limit {
+                        return Err(format!(
+                            "Превышен размер ленты ({} ячеек) на {}",
+                            limit,
+                            self.describe_position(self.prog_pos)
+                        ));
+                    }
+                }
+                self.mem_pos = state.mem_pos;
+                if self.mem_pos >= self.memory.len() {
+                    self.memory.resize(self.mem_pos + 1, C::default());
+                }
+                self.counters.max_pointer = self.counters.max_pointer.max(self.mem_pos);
+                self.touch();
+
+                if state.prog_pos != self.prog_pos {
+                    self.prog_pos = state.prog_pos;
+                    return Ok(true);
+                }
             }
         }
 
@@ -267,7 +863,45 @@ impl CowInterpreter {
         Ok(true)
     }
 
-    pub fn get_memory(&self) -> &[i32] {
+    fn touch(&mut self) {
+        if let Some(heatmap) = self.heatmap.as_mut() {
+            heatmap.record_write(self.mem_pos);
+        }
+        if self.mem_pos >= self.touched.len() {
+            self.touched.resize(self.mem_pos + 1, false);
+        }
+        self.touched[self.mem_pos] = true;
+    }
+
+    fn note_read(&mut self) {
+        if let Some(heatmap) = self.heatmap.as_mut() {
+            heatmap.record_read(self.mem_pos);
+        }
+    }
+
+    // Сводка по выполнению: шаги, изменённые ячейки, указатель, циклы и ввод-вывод
+    pub fn summary(&self) -> ExecSummary {
+        ExecSummary {
+            steps: self.steps,
+            cells_touched: self.touched.iter().filter(|touched| **touched).count(),
+            ..self.counters
+        }
+    }
+
+    // Индексы изменённых ячеек: вместе со сводкой сохраняются в контрольной точке
+    pub(crate) fn touched_cells(&self) -> Vec<usize> {
+        (0..self.touched.len()).filter(|cell| self.touched[*cell]).collect()
+    }
+
+    pub(crate) fn restore_summary(&mut self, summary: ExecSummary, touched: &[usize]) {
+        self.counters = summary;
+        self.touched = vec![false; touched.iter().max().map_or(0, |cell| cell + 1)];
+        for cell in touched {
+            self.touched[*cell] = true;
+        }
+    }
+
+    pub fn get_memory(&self) -> &[C] {
         &self.memory
     }
 
@@ -275,7 +909,131 @@ impl CowInterpreter {
         self.mem_pos
     }
 
-    pub fn get_register(&self) -> Option<i32> {
-        self.register
+    pub fn get_register(&self) -> Option<C> {
+        self.register.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExecSummary {
+    pub steps: usize,
+    // Ячейки, значение которых хотя бы раз записывалось
+    pub cells_touched: usize,
+    pub max_pointer: usize,
+    // Переходы moo назад к началу цикла
+    pub loop_iterations: usize,
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl fmt::Display for ExecSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Шагов: {}", self.steps)?;
+        writeln!(f, "Изменено ячеек: {}", self.cells_touched)?;
+        writeln!(f, "Максимальный указатель: {}", self.max_pointer)?;
+        writeln!(f, "Итераций циклов: {}", self.loop_iterations)?;
+        writeln!(f, "Операций ввода: {}", self.inputs)?;
+        write!(f, "Операций вывода: {}", self.outputs)
+    }
+}
+
+// Фрагмент вывода и индекс инструкции, которая его выдала
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputChunk {
+    pub text: String,
+    pub position: usize,
+}
+
+pub struct Outputs<'a, C: CellValue = i32> {
+    interpreter: &'a mut CowInterpreter<C>,
+    io: &'a mut dyn CowIo,
+    failed: bool,
+}
+
+impl<C: CellValue> Iterator for Outputs<'_, C> {
+    type Item = Result<OutputChunk, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let result = self.interpreter.run_until_output(self.io);
+        self.failed = result.is_err();
+        result.transpose()
+    }
+}
+
+// Событие выполнения для встраивания в UI и асинхронный код без обратных вызовов
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecEvent {
+    InstructionExecuted { position: usize, instruction: Instruction },
+    Output(char),
+    // Инструкция на этом шаге прочитала ввод
+    InputRequested { position: usize },
+    Halted,
+}
+
+pub struct Events<'a, C: CellValue = i32> {
+    interpreter: &'a mut CowInterpreter<C>,
+    io: &'a mut dyn CowIo,
+    pending: VecDeque<ExecEvent>,
+    done: bool,
+}
+
+impl<C: CellValue> Iterator for Events<'_, C> {
+    type Item = Result<ExecEvent, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+        if self.done {
+            return None;
+        }
+
+        if self.interpreter.is_finished() {
+            self.done = true;
+            return Some(Ok(ExecEvent::Halted));
+        }
+
+        let position = self.interpreter.prog_pos;
+        let instruction = self.interpreter.program[position];
+        let inputs = self.interpreter.counters.inputs;
+        let mut text = String::new();
+
+        if let Err(err) = self.interpreter.step(&mut text, self.io) {
+            self.done = true;
+            return Some(Err(err));
+        }
+
+        if self.interpreter.counters.inputs != inputs {
+            self.pending.push_back(ExecEvent::InputRequested { position });
+        }
+        self.pending.push_back(ExecEvent::InstructionExecuted { position, instruction });
+        self.pending.extend(text.chars().map(ExecEvent::Output));
+
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+// Результат run_fuel; вывод - только то, что напечатано за этот отрезок
+pub enum FuelRun<'a, C: CellValue = i32> {
+    Finished(String),
+    Paused(Paused<'a, C>),
+}
+
+pub struct Paused<'a, C: CellValue = i32> {
+    interpreter: &'a mut CowInterpreter<C>,
+    pub output: String,
+}
+
+impl<'a, C: CellValue> Paused<'a, C> {
+    pub fn resume(self, input: &mut dyn CowIo, fuel: usize) -> Result<FuelRun<'a, C>, String> {
+        self.interpreter.run_fuel(input, fuel)
+    }
+
+    pub fn interpreter(&self) -> &CowInterpreter<C> {
+        self.interpreter
     }
 }
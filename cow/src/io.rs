@@ -0,0 +1,165 @@
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+
+// Источник ввода для инструкций Moo и oom
+pub trait CowIo {
+    // Символ для Moo
+    fn read_char(&mut self) -> Option<char>;
+
+    // Строка с числом для oom
+    fn read_line(&mut self) -> Option<String>;
+}
+
+// Итератор строк: Moo берёт первый символ очередной строки, oom - строку целиком
+impl<I: Iterator<Item = String> + ?Sized> CowIo for I {
+    fn read_char(&mut self) -> Option<char> {
+        self.next().and_then(|line| line.chars().next())
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.next()
+    }
+}
+
+// Посимвольный ввод: Moo забирает ровно один символ (включая перевод строки),
+// oom - остаток текущей строки
+#[cfg(feature = "std")]
+pub struct CharInput<R> {
+    reader: R,
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> CharInput<R> {
+    pub fn new(reader: R) -> Self {
+        CharInput { reader }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> CowIo for CharInput<R> {
+    fn read_char(&mut self) -> Option<char> {
+        let mut bytes = [0u8; 4];
+        self.reader.read_exact(&mut bytes[..1]).ok()?;
+
+        let len = match bytes[0] {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Some(char::REPLACEMENT_CHARACTER),
+        };
+        self.reader.read_exact(&mut bytes[1..len]).ok()?;
+
+        match core::str::from_utf8(&bytes[..len]) {
+            Ok(text) => text.chars().next(),
+            Err(_) => Some(char::REPLACEMENT_CHARACTER),
+        }
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputMode {
+    // Каждая команда ввода читает целую строку
+    #[default]
+    Line,
+    // Moo читает по одному символу
+    Char,
+}
+
+impl InputMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "line" => Ok(InputMode::Line),
+            "char" => Ok(InputMode::Char),
+            _ => Err(format!("Неизвестный режим ввода {} (ожидается line или char)", name)),
+        }
+    }
+}
+
+// Что делает Moo со значением, которое не является символом Unicode
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputPolicy {
+    // Пропустить значение
+    #[default]
+    Skip,
+    // Вывести U+FFFD
+    Replace,
+    // Вывести младший байт значения как символ U+0000..U+00FF
+    Raw,
+    // Остановить программу с ошибкой
+    Error,
+}
+
+impl OutputPolicy {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "skip" => Ok(OutputPolicy::Skip),
+            "replace" => Ok(OutputPolicy::Replace),
+            "raw" => Ok(OutputPolicy::Raw),
+            "error" => Ok(OutputPolicy::Error),
+            _ => Err(format!(
+                "Неизвестная политика вывода {} (ожидается skip, replace, raw или error)",
+                name
+            )),
+        }
+    }
+}
+
+// Как Moo превращает значение ячейки в вывод
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputEncoding {
+    // Значение - код символа Unicode
+    #[default]
+    Unicode,
+    // Значение 0..=255 - символ Latin-1 (U+0000..U+00FF), остальные по OutputPolicy
+    Latin1,
+    // Младший байт значения выводится как есть, см. encode
+    Bytes,
+}
+
+impl OutputEncoding {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "unicode" => Ok(OutputEncoding::Unicode),
+            "latin1" => Ok(OutputEncoding::Latin1),
+            "bytes" => Ok(OutputEncoding::Bytes),
+            _ => Err(format!(
+                "Неизвестная кодировка вывода {} (ожидается unicode, latin1 или bytes)",
+                name
+            )),
+        }
+    }
+
+    // Байты для записи в файл или stdout.
+    // В режиме Bytes каждый символ вывода U+0000..U+00FF - это один байт,
+    // в остальных режимах вывод кодируется в UTF-8.
+    pub fn encode(self, output: &str) -> Vec<u8> {
+        match self {
+            OutputEncoding::Bytes => output.chars().map(|ch| ch as u32 as u8).collect(),
+            OutputEncoding::Unicode | OutputEncoding::Latin1 => output.as_bytes().to_vec(),
+        }
+    }
+}
+
+// Ввод из произвольного источника в выбранном режиме
+#[cfg(feature = "std")]
+pub fn reader_input<R: BufRead + 'static>(reader: R, mode: InputMode) -> Box<dyn CowIo> {
+    match mode {
+        InputMode::Line => Box::new(reader.lines().map(|l| l.unwrap_or_default())),
+        InputMode::Char => Box::new(CharInput::new(reader)),
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn stdin_input(mode: InputMode) -> Box<dyn CowIo> {
+    reader_input(io::stdin().lock(), mode)
+}
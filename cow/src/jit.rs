@@ -0,0 +1,348 @@
+use crate::interpreter::{CowInterpreter, Instruction};
+use crate::io::CowIo;
+use crate::prelude::*;
+
+// Итог выполнения через JIT или через интерпретатор, если JIT недоступен
+#[derive(Debug, Clone, PartialEq)]
+pub struct JitRun {
+    pub output: String,
+    pub memory: Vec<i32>,
+    pub mem_pos: usize,
+    pub register: Option<i32>,
+}
+
+// Собран ли крейт с фичей jit
+pub const ENABLED: bool = cfg!(feature = "jit");
+
+// Выполнить программу как можно быстрее: машинный код из байткода, если включена
+// фича jit и в программе нет mOO, иначе обычный интерпретатор.
+// Лимиты шагов и трассировка здесь не действуют - это режим для долгих вычислений.
+pub fn execute(program: &[Instruction], input: &mut dyn CowIo) -> Result<JitRun, String> {
+    #[cfg(feature = "jit")]
+    if let Ok(bytecode) = crate::bytecode::Bytecode::compile(program) {
+        return Ok(native::JitProgram::compile(&bytecode)?.run(input));
+    }
+
+    let mut interpreter = CowInterpreter::<i32>::from_program(program.to_vec(), Vec::new());
+    let output = interpreter.execute_with_input(input)?;
+    Ok(JitRun {
+        output,
+        register: interpreter.get_register(),
+        mem_pos: interpreter.mem_pos,
+        memory: interpreter.memory,
+    })
+}
+
+#[cfg(feature = "jit")]
+pub use native::JitProgram;
+
+#[cfg(feature = "jit")]
+mod native {
+    use super::JitRun;
+    use crate::bytecode::{Bytecode, Op};
+    use crate::io::CowIo;
+    use cranelift_codegen::ir::condcodes::IntCC;
+    use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, MemFlags, Value};
+    use cranelift_codegen::settings::{self, Configurable};
+    use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+    use cranelift_jit::{JITBuilder, JITModule};
+    use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+    // Состояние, которое машинный код передаёт обратно в Rust при вводе-выводе.
+    // Лента живёт здесь же: при росте Vec переезжает, и код получает новый адрес.
+    struct Context<'a> {
+        input: &'a mut dyn CowIo,
+        output: String,
+        memory: Vec<i32>,
+        register: Option<i32>,
+    }
+
+    type Entry = unsafe extern "C" fn(*mut Context, *mut i32, i64) -> i64;
+
+    // Скомпилированная программа; код освобождается вместе с ней
+    pub struct JitProgram {
+        module: Option<JITModule>,
+        entry: Entry,
+    }
+
+    impl Drop for JitProgram {
+        fn drop(&mut self) {
+            if let Some(module) = self.module.take() {
+                // Safety: после drop указатель entry больше не используется
+                unsafe { module.free_memory() };
+            }
+        }
+    }
+
+    fn jit_error(err: impl core::fmt::Display) -> String {
+        format!("Ошибка JIT-компиляции: {}", err)
+    }
+
+    // Обработчики вызываются из машинного кода; ctx всегда указывает на живой Context
+    extern "C" fn cow_io(ctx: *mut Context, cell: *mut i32) {
+        let ctx = unsafe { &mut *ctx };
+        let cell = unsafe { &mut *cell };
+        if *cell != 0 {
+            if let Some(ch) = char::from_u32(*cell as u32) {
+                ctx.output.push(ch);
+            }
+        } else if let Some(ch) = ctx.input.read_char() {
+            *cell = ch as i32;
+        }
+    }
+
+    extern "C" fn cow_print_int(ctx: *mut Context, value: i32) {
+        let ctx = unsafe { &mut *ctx };
+        ctx.output.push_str(&value.to_string());
+        ctx.output.push('\n');
+    }
+
+    extern "C" fn cow_read_int(ctx: *mut Context) -> i32 {
+        let ctx = unsafe { &mut *ctx };
+        ctx.input.read_line().unwrap_or_default().trim().parse().unwrap_or(0)
+    }
+
+    extern "C" fn cow_register(ctx: *mut Context, cell: *mut i32) {
+        let ctx = unsafe { &mut *ctx };
+        let cell = unsafe { &mut *cell };
+        match ctx.register.take() {
+            None => ctx.register = Some(*cell),
+            Some(value) => *cell = value,
+        }
+    }
+
+    // Дорастить ленту до len ячеек и вернуть её новый адрес
+    extern "C" fn cow_grow(ctx: *mut Context, len: i64) -> *mut i32 {
+        let ctx = unsafe { &mut *ctx };
+        ctx.memory.resize(len as usize, 0);
+        ctx.memory.as_mut_ptr()
+    }
+
+    struct Callbacks {
+        io: FuncId,
+        print_int: FuncId,
+        read_int: FuncId,
+        register: FuncId,
+        grow: FuncId,
+    }
+
+    impl JitProgram {
+        pub fn compile(bytecode: &Bytecode) -> Result<Self, String> {
+            let mut flags = settings::builder();
+            flags.set("use_colocated_libcalls", "false").map_err(jit_error)?;
+            flags.set("is_pic", "false").map_err(jit_error)?;
+            flags.set("opt_level", "speed").map_err(jit_error)?;
+            let isa = cranelift_native::builder()
+                .map_err(jit_error)?
+                .finish(settings::Flags::new(flags))
+                .map_err(jit_error)?;
+
+            let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+            builder.symbol("cow_io", cow_io as *const u8);
+            builder.symbol("cow_print_int", cow_print_int as *const u8);
+            builder.symbol("cow_read_int", cow_read_int as *const u8);
+            builder.symbol("cow_register", cow_register as *const u8);
+            builder.symbol("cow_grow", cow_grow as *const u8);
+            let mut module = JITModule::new(builder);
+
+            let ptr = module.target_config().pointer_type();
+            let mut declare = |name: &str, params: &[types::Type], ret: Option<types::Type>| {
+                let mut signature = module.make_signature();
+                signature.params.extend(params.iter().map(|ty| AbiParam::new(*ty)));
+                signature.returns.extend(ret.map(AbiParam::new));
+                module.declare_function(name, Linkage::Import, &signature).map_err(jit_error)
+            };
+            let callbacks = Callbacks {
+                io: declare("cow_io", &[ptr, ptr], None)?,
+                print_int: declare("cow_print_int", &[ptr, types::I32], None)?,
+                read_int: declare("cow_read_int", &[ptr], Some(types::I32))?,
+                register: declare("cow_register", &[ptr, ptr], None)?,
+                grow: declare("cow_grow", &[ptr, types::I64], Some(ptr))?,
+            };
+
+            let mut signature = module.make_signature();
+            signature.params.extend([AbiParam::new(ptr), AbiParam::new(ptr), AbiParam::new(types::I64)]);
+            signature.returns.push(AbiParam::new(types::I64));
+            let id = module
+                .declare_function("cow_main", Linkage::Local, &signature)
+                .map_err(jit_error)?;
+
+            let mut context = module.make_context();
+            context.func.signature = signature;
+            let mut builder_context = FunctionBuilderContext::new();
+            translate(
+                &mut module,
+                &callbacks,
+                FunctionBuilder::new(&mut context.func, &mut builder_context),
+                &bytecode.ops,
+                ptr,
+            );
+
+            module.define_function(id, &mut context).map_err(jit_error)?;
+            module.clear_context(&mut context);
+            module.finalize_definitions().map_err(jit_error)?;
+
+            // Safety: сигнатура cow_main совпадает с Entry
+            let entry = unsafe { core::mem::transmute::<*const u8, Entry>(module.get_finalized_function(id)) };
+            Ok(JitProgram {
+                module: Some(module),
+                entry,
+            })
+        }
+
+        // Выполнение на чистой ленте
+        pub fn run(&self, input: &mut dyn CowIo) -> JitRun {
+            let mut ctx = Context {
+                input,
+                output: String::new(),
+                memory: vec![0],
+                register: None,
+            };
+            let base = ctx.memory.as_mut_ptr();
+            // Safety: код обращается к ленте только в пределах переданной длины
+            // и получает новый адрес через cow_grow
+            let mem_pos = unsafe { (self.entry)(&mut ctx, base, 1) };
+            JitRun {
+                output: ctx.output,
+                memory: ctx.memory,
+                mem_pos: mem_pos as usize,
+                register: ctx.register,
+            }
+        }
+    }
+
+    // Каждая операция байткода - отдельный блок, переходы циклов - переходы между блоками
+    fn translate(module: &mut JITModule, callbacks: &Callbacks, mut builder: FunctionBuilder, ops: &[Op], ptr: types::Type) {
+        let mem_pos = Variable::from_u32(0);
+        let base = Variable::from_u32(1);
+        let len = Variable::from_u32(2);
+        builder.declare_var(mem_pos, types::I64);
+        builder.declare_var(base, ptr);
+        builder.declare_var(len, types::I64);
+
+        let io = module.declare_func_in_func(callbacks.io, builder.func);
+        let print_int = module.declare_func_in_func(callbacks.print_int, builder.func);
+        let read_int = module.declare_func_in_func(callbacks.read_int, builder.func);
+        let register = module.declare_func_in_func(callbacks.register, builder.func);
+        let grow = module.declare_func_in_func(callbacks.grow, builder.func);
+
+        let entry = builder.create_block();
+        let blocks: Vec<Block> = (0..ops.len()).map(|_| builder.create_block()).collect();
+        let exit = builder.create_block();
+        let block_at = |index: usize| blocks.get(index).copied().unwrap_or(exit);
+
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let params = builder.block_params(entry).to_vec();
+        let ctx = params[0];
+        builder.def_var(base, params[1]);
+        builder.def_var(len, params[2]);
+        let zero = builder.ins().iconst(types::I64, 0);
+        builder.def_var(mem_pos, zero);
+        builder.ins().jump(block_at(0), &[]);
+
+        let cell_address = |builder: &mut FunctionBuilder| -> Value {
+            let offset = builder.use_var(mem_pos);
+            let offset = builder.ins().ishl_imm(offset, 2);
+            let base = builder.use_var(base);
+            builder.ins().iadd(base, offset)
+        };
+        let flags = MemFlags::trusted();
+
+        for (index, op) in ops.iter().enumerate() {
+            builder.switch_to_block(blocks[index]);
+            let next = block_at(index + 1);
+            match *op {
+                Op::Add(value) => {
+                    let address = cell_address(&mut builder);
+                    let cell = builder.ins().load(types::I32, flags, address, 0);
+                    let cell = builder.ins().iadd_imm(cell, value as i64);
+                    builder.ins().store(flags, cell, address, 0);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::Move { delta, min, max } => {
+                    let position = builder.use_var(mem_pos);
+                    if min < 0 {
+                        // mOo на нулевой ячейке останавливает программу, указатель остаётся на ней
+                        let lowest = builder.ins().iadd_imm(position, min as i64);
+                        let underflow = builder.ins().icmp_imm(IntCC::SignedLessThan, lowest, 0);
+                        let halt = builder.create_block();
+                        let proceed = builder.create_block();
+                        builder.ins().brif(underflow, halt, &[], proceed, &[]);
+
+                        builder.switch_to_block(halt);
+                        let zero = builder.ins().iconst(types::I64, 0);
+                        builder.def_var(mem_pos, zero);
+                        builder.ins().jump(exit, &[]);
+                        builder.switch_to_block(proceed);
+                    }
+                    if max > 0 {
+                        let end = builder.ins().iadd_imm(position, max as i64);
+                        let current = builder.use_var(len);
+                        let outside = builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, end, current);
+                        let grow_block = builder.create_block();
+                        let moved = builder.create_block();
+                        builder.ins().brif(outside, grow_block, &[], moved, &[]);
+
+                        builder.switch_to_block(grow_block);
+                        let needed = builder.ins().iadd_imm(end, 1);
+                        let call = builder.ins().call(grow, &[ctx, needed]);
+                        let new_base = builder.inst_results(call)[0];
+                        builder.def_var(base, new_base);
+                        builder.def_var(len, needed);
+                        builder.ins().jump(moved, &[]);
+                        builder.switch_to_block(moved);
+                    }
+                    let position = builder.ins().iadd_imm(position, delta as i64);
+                    builder.def_var(mem_pos, position);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::Clear => {
+                    let address = cell_address(&mut builder);
+                    let zero = builder.ins().iconst(types::I32, 0);
+                    builder.ins().store(flags, zero, address, 0);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::Io => {
+                    let address = cell_address(&mut builder);
+                    builder.ins().call(io, &[ctx, address]);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::PrintInt => {
+                    let address = cell_address(&mut builder);
+                    let cell = builder.ins().load(types::I32, flags, address, 0);
+                    builder.ins().call(print_int, &[ctx, cell]);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::ReadInt => {
+                    let call = builder.ins().call(read_int, &[ctx]);
+                    let value = builder.inst_results(call)[0];
+                    let address = cell_address(&mut builder);
+                    builder.ins().store(flags, value, address, 0);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::Register => {
+                    let address = cell_address(&mut builder);
+                    builder.ins().call(register, &[ctx, address]);
+                    builder.ins().jump(next, &[]);
+                }
+                Op::LoopStart(target) => {
+                    let address = cell_address(&mut builder);
+                    let cell = builder.ins().load(types::I32, flags, address, 0);
+                    let target = target.map_or(exit, block_at);
+                    builder.ins().brif(cell, next, &[], target, &[]);
+                }
+                Op::LoopEnd(target) => {
+                    builder.ins().jump(target.map_or(exit, block_at), &[]);
+                }
+            }
+        }
+
+        builder.switch_to_block(exit);
+        let position = builder.use_var(mem_pos);
+        builder.ins().return_(&[position]);
+
+        builder.seal_all_blocks();
+        builder.finalize();
+    }
+}
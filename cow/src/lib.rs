@@ -1 +1,79 @@
-pub mod interpreter;
\ No newline at end of file
+// Без фичи std крейт собирается как no_std + alloc: остаются интерпретатор,
+// CowIo и всё, что не обращается к ОС
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
+#[cfg(feature = "tokio")]
+pub mod async_exec;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+pub mod builder;
+pub mod bytecode;
+pub mod cell;
+pub mod checkpoint;
+pub mod cowc;
+#[cfg(feature = "std")]
+pub mod debug_server;
+pub mod debugger;
+pub mod format;
+pub mod generate;
+#[cfg(feature = "std")]
+pub mod golden;
+pub mod heatmap;
+pub mod interpreter;
+pub mod jit;
+pub mod io;
+pub mod macros;
+pub mod optimize;
+pub mod program;
+pub mod state;
+pub mod trace;
+pub mod transpile;
+#[cfg(feature = "std")]
+pub mod visualizer;
+pub mod wasm;
+
+// То, что в std приходит из прелюдии, а в no_std - из alloc
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+    pub use alloc::{format, vec};
+}
+
+use interpreter::CowInterpreter;
+use prelude::*;
+
+// Итог выполнения программы целиком
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunResult {
+    pub output: String,
+    pub memory: Vec<i32>,
+    pub register: Option<i32>,
+    pub steps: usize,
+}
+
+// Разобрать и выполнить программу за один вызов.
+// Ввод читается построчно из input, как из stdin.
+pub fn run(source: &str, input: &str) -> Result<RunResult, String> {
+    let mut interpreter = CowInterpreter::new(source)?;
+    let mut input = input.lines().map(String::from);
+    let output = interpreter.execute_with_input(&mut input)?;
+
+    Ok(RunResult::new(interpreter, output))
+}
+
+impl RunResult {
+    fn new(interpreter: CowInterpreter, output: String) -> Self {
+        RunResult {
+            output,
+            register: interpreter.get_register(),
+            steps: interpreter.steps,
+            memory: interpreter.memory,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use alloc::collections::BTreeMap;
+use crate::prelude::*;
+
+// Препроцессор макросов.
+// Строка "@имя = тело" определяет макрос, "@имя" в любом месте программы заменяется телом.
+// Тела могут ссылаться на другие макросы; рекурсия - ошибка.
+// Строки определений заменяются пустыми, чтобы номера строк в сообщениях об ошибках не сдвигались.
+pub fn expand(source: &str) -> Result<String, String> {
+    let mut definitions = BTreeMap::new();
+    let mut lines = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        match parse_definition(line) {
+            Some((name, body)) => {
+                if definitions.insert(name.to_string(), body.to_string()).is_some() {
+                    return Err(format!("Макрос @{} определён повторно (строка {})", name, index + 1));
+                }
+                lines.push(String::new());
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    let mut result = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        let mut stack = Vec::new();
+        let expanded = expand_text(line, &definitions, &mut stack)
+            .map_err(|err| format!("{} (строка {})", err, index + 1))?;
+        result.push(expanded);
+    }
+
+    let mut text = result.join("\n");
+    if source.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn parse_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('@')?;
+    let (name, body) = rest.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return None;
+    }
+    Some((name, body.trim()))
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+fn expand_text(text: &str, definitions: &BTreeMap<String, String>, stack: &mut Vec<String>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '@' {
+            result.push(ch);
+            continue;
+        }
+
+        let mut name = String::new();
+        while let Some(&next) = chars.peek() {
+            if !is_name_char(next) {
+                break;
+            }
+            name.push(next);
+            chars.next();
+        }
+        if name.is_empty() {
+            result.push('@');
+            continue;
+        }
+
+        let body = definitions
+            .get(&name)
+            .ok_or_else(|| format!("Неизвестный макрос @{}", name))?;
+        if stack.contains(&name) {
+            let chain: Vec<String> = stack.iter().chain([&name]).map(|n| format!("@{}", n)).collect();
+            return Err(format!("Рекурсивный макрос: {}", chain.join(" -> ")));
+        }
+
+        stack.push(name);
+        result.push_str(&expand_text(body, definitions, stack)?);
+        stack.pop();
+    }
+
+    Ok(result)
+}
@@ -1,39 +1,832 @@
-use cow_interpreter::interpreter::CowInterpreter;
+use cow_interpreter::analysis;
+use cow_interpreter::bench;
+use cow_interpreter::builder::CowInterpreterBuilder;
+use cow_interpreter::cell::{BigInt, CellValue, CellWidth};
+use cow_interpreter::checkpoint::Checkpoint;
+use cow_interpreter::cowc::{self, CompiledProgram};
+use cow_interpreter::debug_server::DebugServer;
+use cow_interpreter::debugger::{Debugger, Watchpoint};
+use cow_interpreter::format;
+use cow_interpreter::generate;
+use cow_interpreter::golden;
+use cow_interpreter::interpreter::{CowInterpreter, Instruction, ParseMode, SpecMode};
+use cow_interpreter::jit;
+use cow_interpreter::io::{self as cow_io, InputMode, OutputEncoding, OutputPolicy};
+use cow_interpreter::trace;
+use cow_interpreter::transpile;
+use cow_interpreter::visualizer;
+use cow_interpreter::wasm;
 use std::env;
 use std::fs;
+use std::io::{self, Cursor, IsTerminal, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 use std::process;
 
+// Защита от бесконечных программ в каталоге бенчмарков
+const BENCH_MAX_STEPS: usize = 100_000_000;
+// Период контрольных точек по умолчанию, в миллионах шагов
+const DEFAULT_CHECKPOINT_EVERY: usize = 10;
+
+#[derive(Default)]
+struct Options {
+    file: Option<String>,
+    max_steps: Option<usize>,
+    timeout_ms: Option<usize>,
+    memory_size: Option<usize>,
+    max_memory: Option<usize>,
+    input: Option<String>,
+    input_mode: InputMode,
+    cell_width: CellWidth,
+    output_policy: OutputPolicy,
+    output_encoding: OutputEncoding,
+    watch: Vec<String>,
+    spec_mode: SpecMode,
+    trace: bool,
+    trace_color: bool,
+    trace_delay_ms: Option<usize>,
+    trace_json: Option<String>,
+    trace_ndjson: Option<String>,
+    heatmap: Option<String>,
+    record: Option<String>,
+    replay: Option<String>,
+    checkpoint: Option<String>,
+    checkpoint_every: Option<usize>,
+    resume: Option<String>,
+    dump_memory: bool,
+    visualize: bool,
+    check: bool,
+    fmt: bool,
+    stats: bool,
+    optimize: bool,
+    jit: bool,
+    parse_mode: ParseMode,
+    macros: bool,
+    line_width: Option<usize>,
+}
+
+fn print_usage(program: &str) {
+    eprintln!("Использование: {} [опции] <файл>", program);
+    eprintln!("             ... | {} [опции] [-]", program);
+    eprintln!("             {} from-bf <файл.bf>", program);
+    eprintln!("             {} to-rust <файл>", program);
+    eprintln!("             {} minify <файл>", program);
+    eprintln!("             {} fmt <файл> [ширина]", program);
+    eprintln!("             {} gen <текст>", program);
+    eprintln!("             {} bench [каталог]", program);
+    eprintln!("             {} test <каталог>", program);
+    eprintln!("             {} debug-server <файл> [--tcp адрес]", program);
+    eprintln!("             {} compile <файл.cow> [файл.cowc]", program);
+    eprintln!("             {} wasm <файл.cow> [файл.wasm]", program);
+    eprintln!("             {} [run] [опции] <файл.cowc>", program);
+    eprintln!();
+    eprintln!("Опции:");
+    eprintln!("  --strict-parse            считать ошибкой любой текст, кроме инструкций");
+    eprintln!("  --macros                  раскрыть макросы @имя = ... перед разбором");
+    eprintln!("  --max-steps <N>           остановить программу после N шагов");
+    eprintln!("  --timeout <мс>            прервать программу по истечении времени");
+    eprintln!("  --memory-size <N>         начальный размер ленты");
+    eprintln!("  --max-memory <N>          ошибка, если лента станет длиннее N ячеек");
+    eprintln!("  --input <файл>            читать ввод Moo/oom из файла вместо stdin");
+    eprintln!("  --input-mode <line|char>  Moo читает строку целиком или один символ");
+    eprintln!("  --cell-width <32|64|big>  тип ячеек: i32 (по умолчанию), i64 или BigInt");
+    eprintln!("  --non-printable <режим>   Moo для не-символа: skip, replace, raw или error");
+    eprintln!("  --output-encoding <вид>   вывод Moo: unicode, latin1 или bytes (сырые байты)");
+    eprintln!("  --spec <legacy|strict>    правила mOO: прежние или эталонные");
+    eprintln!("  --watch <ячейка[=знач]>   остановиться, когда ячейка изменится (или станет равна знач)");
+    eprintln!("  --trace                   печатать в stderr каждый выполненный шаг");
+    eprintln!("  --trace-color             выделять --trace цветом");
+    eprintln!("  --trace-delay <мс>        пауза после каждого шага --trace");
+    eprintln!("  --trace-json <файл>       записать трассу выполнения в JSON");
+    eprintln!("  --trace-ndjson <файл>     записать трассу выполнения в NDJSON (объект на строку)");
+    eprintln!("  --heatmap <файл>          записать чтения/записи ячеек в CSV или PPM (.ppm)");
+    eprintln!("  --record <файл>           записать ввод, трассу и итоговое состояние в JSON");
+    eprintln!("  --replay <файл>           повторить запись и проверить, что выполнение совпало");
+    eprintln!("  --checkpoint <файл>       сохранять состояние в файл каждые --checkpoint-every шагов");
+    eprintln!("  --checkpoint-every <N>    период контрольных точек в миллионах шагов (по умолчанию 10)");
+    eprintln!("  --resume <файл>           продолжить выполнение с контрольной точки");
+    eprintln!("  --dump-memory             вывести память после выполнения");
+    eprintln!("  --stats                   вывести статистику выполнения");
+    eprintln!("  --visualize               пошаговый просмотр выполнения в терминале");
+    eprintln!("  --optimize                удалить мёртвый код перед выполнением или --fmt");
+    eprintln!("  --jit                     выполнить машинный код (фича jit) без лимитов и трассировки");
+    eprintln!("  --check                   проверить программу без выполнения");
+    eprintln!("  --fmt                     вывести программу в каноническом виде");
+    eprintln!("  --line-width <N>          ширина строки для --fmt (по умолчанию 80)");
+}
+
+// "-" - прочитать исходник из stdin, например из генератора: cow gen Привет | cow -
+fn read_source(filename: &str) -> String {
+    if filename == "-" {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("Ошибка при чтении программы из stdin: {}", err);
+            process::exit(1);
+        }
+        return source;
+    }
+
+    fs::read_to_string(filename).unwrap_or_else(|err| {
+        eprintln!("Ошибка при чтении файла '{}': {}", filename, err);
+        process::exit(1);
+    })
+}
+
+// Исходник .cow разбирается, .cowc загружается без разбора
+fn load_program(filename: &str, options: &Options) -> CowInterpreter {
+    if filename.ends_with(".cowc") {
+        let bytes = fs::read(filename).unwrap_or_else(|err| {
+            eprintln!("Ошибка при чтении файла '{}': {}", filename, err);
+            process::exit(1);
+        });
+        let compiled = cowc::decode(&bytes).unwrap_or_else(|err| {
+            eprintln!("Ошибка при загрузке программы: {}", err);
+            process::exit(1);
+        });
+        return CowInterpreter::from_program(compiled.program, compiled.positions);
+    }
+
+    CowInterpreter::builder(&read_source(filename))
+        .parse_mode(options.parse_mode)
+        .macros(options.macros)
+        .build()
+        .unwrap_or_else(|err| {
+            eprintln!("Ошибка при разборе программы: {}", err);
+            process::exit(1);
+        })
+}
+
+fn parse_number(name: &str, value: Option<&String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("Опция {} требует значение", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("Некорректное значение {} для {}", value, name))
+}
+
+// При нулевой ширине format_program вывел бы каждую инструкцию на отдельной строке
+fn parse_line_width(name: &str, value: Option<&String>) -> Result<usize, String> {
+    match parse_number(name, value)? {
+        0 => Err("ширина строки должна быть больше 0".to_string()),
+        width => Ok(width),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--max-steps" => options.max_steps = Some(parse_number(arg, iter.next())?),
+            "--timeout" => options.timeout_ms = Some(parse_number(arg, iter.next())?),
+            "--memory-size" => options.memory_size = Some(parse_number(arg, iter.next())?),
+            "--max-memory" => options.max_memory = Some(parse_number(arg, iter.next())?),
+            "--input" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.input = Some(path.clone());
+            }
+            "--input-mode" => {
+                let mode = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.input_mode = InputMode::parse(mode)?;
+            }
+            "--cell-width" => {
+                let width = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.cell_width = CellWidth::parse(width)?;
+            }
+            "--non-printable" => {
+                let policy = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.output_policy = OutputPolicy::parse(policy)?;
+            }
+            "--output-encoding" => {
+                let encoding = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.output_encoding = OutputEncoding::parse(encoding)?;
+            }
+            "--spec" => {
+                let mode = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.spec_mode = SpecMode::parse(mode)?;
+            }
+            "--watch" => {
+                let spec = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.watch.push(spec.clone());
+            }
+            "--trace" => options.trace = true,
+            "--trace-color" => options.trace_color = true,
+            "--trace-delay" => options.trace_delay_ms = Some(parse_number(arg, iter.next())?),
+            "--trace-json" | "--trace-ndjson" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                if arg == "--trace-json" {
+                    options.trace_json = Some(path.clone());
+                } else {
+                    options.trace_ndjson = Some(path.clone());
+                }
+            }
+            "--checkpoint" | "--resume" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                if arg == "--checkpoint" {
+                    options.checkpoint = Some(path.clone());
+                } else {
+                    options.resume = Some(path.clone());
+                }
+            }
+            "--checkpoint-every" => options.checkpoint_every = Some(parse_number(arg, iter.next())?),
+            "--heatmap" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                options.heatmap = Some(path.clone());
+            }
+            "--record" | "--replay" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| format!("Опция {} требует значение", arg))?;
+                if arg == "--record" {
+                    options.record = Some(path.clone());
+                } else {
+                    options.replay = Some(path.clone());
+                }
+            }
+            "--dump-memory" => options.dump_memory = true,
+            "--stats" => options.stats = true,
+            "--visualize" => options.visualize = true,
+            "--check" => options.check = true,
+            "--strict-parse" => options.parse_mode = ParseMode::Strict,
+            "--macros" => options.macros = true,
+            "--optimize" => options.optimize = true,
+            "--jit" => options.jit = true,
+            "--fmt" => options.fmt = true,
+            "--line-width" => options.line_width = Some(parse_line_width(arg, iter.next())?),
+            _ if arg.starts_with("--") => return Err(format!("Неизвестная опция {}", arg)),
+            _ => {
+                if options.file.is_some() {
+                    return Err(format!("Лишний аргумент {}", arg));
+                }
+                options.file = Some(arg.clone());
+            }
+        }
+    }
+
+    // Отладчик выполняет программу сам, и наблюдатели в его цикл не встраиваются
+    let debugger = options.visualize || !options.watch.is_empty();
+    if debugger && (options.trace || options.record.is_some() || options.checkpoint.is_some()) {
+        return Err("--visualize и --watch не совмещаются с --trace, --record и --checkpoint".to_string());
+    }
+    if debugger && options.timeout_ms.is_some() {
+        return Err("--timeout не совмещается с --visualize и --watch".to_string());
+    }
+
+    Ok(options)
+}
+
+fn dump_memory<C: CellValue>(interpreter: &CowInterpreter<C>) {
+    println!("Память: {:?}", interpreter.get_memory());
+    println!("Указатель: {}", interpreter.get_memory_pos());
+    match interpreter.get_register() {
+        Some(value) => println!("Регистр: {}", value),
+        None => println!("Регистр: пуст"),
+    }
+}
+
+fn write_trace<C: CellValue>(interpreter: &mut CowInterpreter<C>, options: &Options) {
+    let steps = interpreter.take_trace();
+    let outputs = [
+        (&options.trace_json, trace::to_json(&steps) + "\n"),
+        (&options.trace_ndjson, trace::to_ndjson(&steps)),
+    ];
+    for (path, text) in outputs {
+        if let Some(path) = path {
+            if let Err(err) = fs::write(path, text) {
+                eprintln!("Ошибка при записи трассы в '{}': {}", path, err);
+            }
+        }
+    }
+}
+
+// Все .cow из каталога или встроенные программы, если каталог не задан
+fn run_benchmarks(dir: Option<&String>) {
+    let programs: Vec<(String, String)> = match dir {
+        Some(dir) => {
+            let mut paths: Vec<_> = fs::read_dir(dir)
+                .unwrap_or_else(|err| {
+                    eprintln!("Ошибка при чтении каталога '{}': {}", dir, err);
+                    process::exit(1);
+                })
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "cow"))
+                .collect();
+            paths.sort();
+            paths
+                .iter()
+                .map(|path| {
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                    (name, read_source(&path.to_string_lossy()))
+                })
+                .collect()
+        }
+        None => bench::EMBEDDED
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect(),
+    };
+
+    let mut results = Vec::new();
+    for (name, source) in &programs {
+        match bench::run_benchmark(name, source, Some(BENCH_MAX_STEPS)) {
+            Ok(result) => results.push(result),
+            Err(err) => eprintln!("{}: {}", name, err),
+        }
+    }
+    print!("{}", bench::format_table(&results));
+}
+
+fn write_heatmap<C: CellValue>(interpreter: &CowInterpreter<C>, options: &Options) {
+    if let (Some(path), Some(heatmap)) = (&options.heatmap, &interpreter.heatmap) {
+        let text = if path.ends_with(".ppm") { heatmap.to_ppm() } else { heatmap.to_csv() };
+        if let Err(err) = fs::write(path, text) {
+            eprintln!("Ошибка при записи тепловой карты в '{}': {}", path, err);
+        }
+    }
+}
+
+fn print_report(interpreter: &CowInterpreter) -> bool {
+    let report = analysis::analyze(&interpreter.program);
+
+    println!("Инструкций: {}", report.instruction_count);
+    println!("Максимальная вложенность циклов: {}", report.max_nesting);
+
+    if !report.unmatched_loops.is_empty() {
+        println!("Непарные циклы:");
+        for pos in &report.unmatched_loops {
+            println!("  {}", interpreter.describe_position(*pos));
+        }
+    }
+
+    if !report.unreachable.is_empty() {
+        println!("Недостижимые инструкции:");
+        for pos in &report.unreachable {
+            println!("  {}", interpreter.describe_position(*pos));
+        }
+    }
+
+    println!("Гистограмма:");
+    for (token, count) in report.histogram_entries() {
+        println!("  {}: {}", token, count);
+    }
+
+    report.is_ok()
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 {
-        eprintln!("Использование: {} <файл>", args[0]);
+    // Без аргументов программа читается из stdin, если туда что-то перенаправлено
+    if args.len() < 2 && io::stdin().is_terminal() {
+        print_usage(&args[0]);
         process::exit(1);
     }
+    let command = args.get(1).map_or("", String::as_str);
+
+    if command == "from-bf" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        println!("{}", transpile::from_brainfuck(&read_source(&args[2])));
+        return;
+    }
 
-    let filename = &args[1];
+    if command == "minify" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let interpreter = load_program(&args[2], &Options::default());
+        println!("{}", format::minify_program(&interpreter.program));
+        return;
+    }
 
-    let source = fs::read_to_string(filename).unwrap_or_else(|err| {
-        eprintln!("Ошибка при чтении файла '{}': {}", filename, err);
+    // То же, что --fmt, но подкомандой: обратное к minify
+    if command == "fmt" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let width = args
+            .get(3)
+            .map_or(Ok(format::DEFAULT_LINE_WIDTH), |value| parse_line_width("ширины", Some(value)))
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            });
+        let interpreter = load_program(&args[2], &Options::default());
+        print!("{}", format::format_program(&interpreter.program, width));
+        return;
+    }
+
+    if command == "to-rust" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let interpreter = load_program(&args[2], &Options::default());
+        print!("{}", transpile::to_rust(&interpreter.program));
+        return;
+    }
+
+    if command == "debug-server" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let mut server = DebugServer::new(load_program(&args[2], &Options::default()));
+        let result = match (args.get(3).map(String::as_str), args.get(4)) {
+            (Some("--tcp"), Some(address)) => server.serve_tcp(address),
+            (None, _) => server.serve(io::stdin().lock(), io::stdout().lock()),
+            _ => {
+                print_usage(&args[0]);
+                process::exit(1);
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("Ошибка отладочного сервера: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if command == "compile" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let interpreter = load_program(&args[2], &Options::default());
+        let output = match args.get(3) {
+            Some(path) => path.clone(),
+            None => format!("{}.cowc", args[2].strip_suffix(".cow").unwrap_or(&args[2])),
+        };
+        let compiled = CompiledProgram::new(interpreter.program, interpreter.positions);
+        if let Err(err) = fs::write(&output, cowc::encode(&compiled)) {
+            eprintln!("Ошибка при записи файла '{}': {}", output, err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if command == "wasm" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let interpreter = load_program(&args[2], &Options::default());
+        let output = match args.get(3) {
+            Some(path) => path.clone(),
+            None => format!("{}.wasm", args[2].strip_suffix(".cow").unwrap_or(&args[2])),
+        };
+        let module = wasm::compile(&interpreter.program).unwrap_or_else(|err| {
+            eprintln!("Ошибка при компиляции в WebAssembly: {}", err);
+            process::exit(1);
+        });
+        if let Err(err) = fs::write(&output, module) {
+            eprintln!("Ошибка при записи файла '{}': {}", output, err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if command == "bench" {
+        run_benchmarks(args.get(2));
+        return;
+    }
+
+    // Регрессионный корпус: каждый name.cow сравнивается с name.out на вводе name.in
+    if command == "test" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        let results = golden::run_dir(Path::new(&args[2]), golden::DEFAULT_GOLDEN_MAX_STEPS).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        print!("{}", golden::format_report(&results));
+        if !results.iter().all(golden::GoldenResult::passed) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if command == "gen" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            process::exit(1);
+        }
+        print!("{}", generate::print_string(&args[2..].join(" ")));
+        return;
+    }
+
+    // "run" перед опциями необязателен: cow run file.cowc == cow file.cowc
+    let rest = if command == "run" { &args[2..] } else { &args[1..] };
+    let options = parse_options(rest).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        print_usage(&args[0]);
+        process::exit(1);
+    });
+
+    let stdin_program = options.file.is_none() && !io::stdin().is_terminal();
+    let filename = options.file.as_deref().or(stdin_program.then_some("-")).unwrap_or_else(|| {
+        print_usage(&args[0]);
         process::exit(1);
     });
 
-    let mut interpreter = CowInterpreter::new(&source).unwrap_or_else(|err| {
+    let mut interpreter = load_program(filename, &options);
+
+    if options.fmt {
+        if options.optimize {
+            interpreter.eliminate_dead_code();
+        }
+        let width = options.line_width.unwrap_or(format::DEFAULT_LINE_WIDTH);
+        print!("{}", format::format_program(&interpreter.program, width));
+        return;
+    }
+
+    if options.check {
+        if !print_report(&interpreter) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if options.jit {
+        run_jit(&interpreter.program, &options);
+        return;
+    }
+
+    let mut builder = CowInterpreterBuilder::from_program(interpreter.program, interpreter.positions)
+        .output_policy(options.output_policy)
+        .output_encoding(options.output_encoding)
+        .spec_mode(options.spec_mode)
+        .trace(options.trace || options.trace_json.is_some() || options.trace_ndjson.is_some())
+        .heatmap(options.heatmap.is_some());
+    if let Some(limit) = options.max_steps {
+        builder = builder.max_steps(limit);
+    }
+    if let Some(size) = options.memory_size {
+        builder = builder.memory_size(size);
+    }
+    if let Some(limit) = options.max_memory {
+        builder = builder.max_memory(limit);
+    }
+
+    match options.cell_width {
+        CellWidth::I32 => run(builder, &options),
+        CellWidth::I64 => run(builder.cell_type::<i64>(), &options),
+        CellWidth::Big => run(builder.cell_type::<BigInt>(), &options),
+    }
+}
+
+// Запись через временный файл, чтобы прерывание во время записи не испортило прошлую точку.
+// В точку попадают и вывод, и ввод, сделанные до возобновления.
+fn save_checkpoint<C: CellValue>(
+    path: &str,
+    resumed: Option<&Checkpoint<C>>,
+    checkpoint: &Checkpoint<C>,
+) -> Result<(), String> {
+    let mut checkpoint = checkpoint.clone();
+    if let Some(resumed) = resumed {
+        checkpoint.output.insert_str(0, &resumed.output);
+        checkpoint.inputs.splice(0..0, resumed.inputs.iter().cloned());
+    }
+    let temp = format!("{}.tmp", path);
+    fs::write(&temp, checkpoint.to_json())
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|err| format!("Ошибка при записи контрольной точки в '{}': {}", path, err))
+}
+
+// Без фичи jit программа выполняется интерпретатором, результат тот же
+fn run_jit(program: &[Instruction], options: &Options) {
+    let mut input = match &options.input {
+        Some(path) => cow_io::reader_input(Cursor::new(read_source(path)), options.input_mode),
+        None => cow_io::stdin_input(options.input_mode),
+    };
+    if !jit::ENABLED {
+        eprintln!("JIT недоступен (сборка без фичи jit), программа выполняется интерпретатором");
+    }
+    match jit::execute(program, input.as_mut()) {
+        Ok(run) => {
+            print!("{}", run.output);
+            if options.dump_memory {
+                println!("Память: {:?}", run.memory);
+                println!("Указатель: {}", run.mem_pos);
+                match run.register {
+                    Some(value) => println!("Регистр: {}", value),
+                    None => println!("Регистр: пуст"),
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("Ошибка при выполнении программы: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+// Выполнение под наблюдением: --trace печатает каждый шаг в stderr, --record запоминает
+// прочитанный ввод, --checkpoint сохраняет состояние каждые --checkpoint-every шагов, --timeout прерывает.
+// Шаги остаются в трассе интерпретатора, чтобы --trace-json тоже их получил.
+fn observed_run<C: CellValue>(
+    interpreter: &mut CowInterpreter<C>,
+    input: &mut dyn cow_io::CowIo,
+    options: &Options,
+    resumed: Option<&Checkpoint<C>>,
+) -> Result<String, String> {
+    let every = options
+        .checkpoint_every
+        .unwrap_or(DEFAULT_CHECKPOINT_EVERY)
+        .saturating_mul(1_000_000)
+        .max(1);
+    if options.record.is_some() && interpreter.trace.is_none() {
+        interpreter.trace = Some(Vec::new());
+    }
+    let mut input = trace::InputRecorder::new(input);
+    let mut output = String::new();
+    let mut steps = Vec::new();
+    let mut stderr = io::stderr().lock();
+    let deadline = options.timeout_ms.map(|ms| (ms, Instant::now() + Duration::from_millis(ms as u64)));
+
+    let result = loop {
+        if interpreter.is_finished() {
+            break Ok(());
+        }
+        if let Some((ms, deadline)) = deadline {
+            if Instant::now() >= deadline {
+                break Err(format!(
+                    "Превышено время выполнения ({} мс) на {}",
+                    ms,
+                    interpreter.describe_position(interpreter.prog_pos)
+                ));
+            }
+        }
+        let result = interpreter.step(&mut output, &mut input);
+        for step in interpreter.take_trace() {
+            if options.trace {
+                let description = interpreter.describe_position(step.position);
+                let _ = writeln!(stderr, "{}", trace::format_step(&step, &description, options.trace_color));
+            }
+            steps.push(step);
+        }
+        if let Err(err) = result {
+            break Err(err);
+        }
+        if let Some(path) = &options.checkpoint {
+            if interpreter.steps.is_multiple_of(every) && !interpreter.is_finished() {
+                let checkpoint = Checkpoint::capture(interpreter, &output, &input.inputs);
+                if let Err(err) = save_checkpoint(path, resumed, &checkpoint) {
+                    break Err(err);
+                }
+            }
+        }
+        if let (true, Some(ms)) = (options.trace, options.trace_delay_ms) {
+            std::thread::sleep(Duration::from_millis(ms as u64));
+        }
+    };
+
+    if let (Ok(()), Some(path)) = (&result, &options.record) {
+        let recording = trace::Recording {
+            inputs: input.inputs,
+            trace: steps.clone(),
+            output: output.clone(),
+            memory: interpreter.memory.clone(),
+            mem_pos: interpreter.mem_pos,
+            register: interpreter.register.clone(),
+        };
+        if let Err(err) = fs::write(path, recording.to_json() + "\n") {
+            eprintln!("Ошибка при записи в '{}': {}", path, err);
+        }
+    }
+    interpreter.trace = Some(steps);
+    result.map(|()| output)
+}
+
+fn run<C: CellValue>(builder: CowInterpreterBuilder<C>, options: &Options) {
+    let mut interpreter = builder.build().unwrap_or_else(|err| {
         eprintln!("Ошибка при разборе программы: {}", err);
         process::exit(1);
     });
+    if options.optimize {
+        interpreter.eliminate_dead_code();
+    }
+
+    if let Some(path) = &options.replay {
+        let recording = trace::Recording::<C>::from_json(&read_source(path)).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        if let Err(err) = trace::replay(&mut interpreter, &recording) {
+            eprintln!("Ошибка воспроизведения: {}", err);
+            process::exit(1);
+        }
+        println!("Воспроизведение совпало с записью, шагов: {}", recording.trace.len());
+        return;
+    }
+
+    let watchpoints: Vec<Watchpoint<C>> = options
+        .watch
+        .iter()
+        .map(|spec| Watchpoint::parse(spec))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+
+    let mut input = match &options.input {
+        Some(path) => cow_io::reader_input(Cursor::new(read_source(path)), options.input_mode),
+        // Терминал занят визуализатором, поэтому ввод берётся только из --input
+        None if options.visualize => cow_io::reader_input(Cursor::new(String::new()), options.input_mode),
+        None => cow_io::stdin_input(options.input_mode),
+    };
+
+    // Вывод, сделанный до прерывания, печатается вместе с остальным
+    let resumed = options.resume.as_ref().map(|path| {
+        Checkpoint::<C>::from_json(&read_source(path))
+            .and_then(|checkpoint| checkpoint.restore(&mut interpreter, input.as_mut()).map(|()| checkpoint))
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            })
+    });
+
+    let result = if options.visualize || !watchpoints.is_empty() {
+        let mut debugger = Debugger::new(&mut interpreter);
+        for watchpoint in watchpoints {
+            debugger.add_watchpoint(watchpoint);
+        }
+        if options.visualize {
+            visualizer::run(&mut debugger, input.as_mut()).map_err(|err| err.to_string())
+        } else {
+            let mut output = String::new();
+            debugger.run(&mut output, input.as_mut()).map(|hit| {
+                if let Some(hit) = hit {
+                    eprintln!("Остановка на точке наблюдения: {}", hit);
+                }
+                output
+            })
+        }
+    } else if options.trace || options.record.is_some() || options.checkpoint.is_some() {
+        observed_run(&mut interpreter, input.as_mut(), options, resumed.as_ref())
+    } else if let Some(ms) = options.timeout_ms {
+        interpreter.execute_with_timeout(input.as_mut(), Duration::from_millis(ms as u64))
+    } else {
+        interpreter.execute_with_input(input.as_mut())
+    };
+    let result = result.map(|output| resumed.map_or(String::new(), |checkpoint| checkpoint.output) + &output);
 
-    match interpreter.execute() {
+    write_trace(&mut interpreter, options);
+    write_heatmap(&interpreter, options);
+
+    match result {
         Ok(output) => {
             if output.is_empty() {
                 println!("Программа выполнена, но вывода нет.");
             } else {
-                print!("{}", output);
+                let mut stdout = io::stdout().lock();
+                if let Err(err) = stdout.write_all(&options.output_encoding.encode(&output)) {
+                    eprintln!("Ошибка при записи вывода: {}", err);
+                }
+            }
+            if options.dump_memory {
+                dump_memory(&interpreter);
+            }
+            if options.stats {
+                println!("{}", interpreter.summary());
             }
         }
         Err(err) => {
             eprintln!("Ошибка при выполнении программы: {}", err);
+            if options.dump_memory {
+                dump_memory(&interpreter);
+            }
+            if options.stats {
+                eprintln!("{}", interpreter.summary());
+            }
             process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,109 @@
+use crate::analysis::analyze;
+use crate::cell::CellValue;
+use crate::interpreter::{jump_table, CowInterpreter, Instruction};
+use crate::prelude::*;
+
+// Индексы инструкций, которые никогда не выполняются:
+// недостижимые по результатам анализа и циклы MOO...moo, в которые программа входит с заведомо нулевой ячейкой.
+// Считается, что выполнение начинается с нулевой ленты.
+pub fn dead_code(program: &[Instruction]) -> Vec<usize> {
+    // mOO выполняет инструкции по индексу или коду из ячейки, а пользовательская инструкция
+    // может сменить prog_pos - удалять что-либо небезопасно
+    if program.iter().any(|i| matches!(i, Instruction::MOO | Instruction::Custom(_))) {
+        return Vec::new();
+    }
+
+    let jumps = jump_table(program);
+    let mut dead = vec![false; program.len()];
+    for pos in analyze(program).unreachable {
+        dead[pos] = true;
+    }
+
+    // Ни одна ячейка ещё не записывалась
+    let mut tape_zero = true;
+    // Текущая ячейка заведомо равна нулю
+    let mut cell_zero = true;
+    let mut pos = 0;
+
+    while pos < program.len() {
+        match program[pos] {
+            Instruction::MOO2 => match jumps[pos] {
+                Some(end) if cell_zero => {
+                    dead[pos..=end].iter_mut().for_each(|d| *d = true);
+                    pos = end + 1;
+                    continue;
+                }
+                // В тело цикла можно попасть с разным состоянием ленты
+                _ => {
+                    tape_zero = false;
+                    cell_zero = false;
+                }
+            },
+            // За moo выполнение попадает только прыжком от MOO с нулевой ячейкой
+            Instruction::Moo => cell_zero = true,
+            Instruction::MOo | Instruction::MoO => cell_zero = tape_zero,
+            Instruction::OOO => {
+                tape_zero = false;
+                cell_zero = true;
+            }
+            Instruction::OOM => {}
+            _ => {
+                tape_zero = false;
+                cell_zero = false;
+            }
+        }
+        pos += 1;
+    }
+
+    (0..program.len()).filter(|pos| dead[*pos]).collect()
+}
+
+// Программа без мёртвого кода. Если удаление изменило бы парность циклов
+// (правила сопоставления зависят от соседних инструкций), возвращается исходная программа.
+pub fn eliminate_dead_code(program: &[Instruction]) -> Vec<Instruction> {
+    let kept = kept_positions(program);
+    kept.iter().map(|pos| program[*pos]).collect()
+}
+
+fn kept_positions(program: &[Instruction]) -> Vec<usize> {
+    let dead = dead_code(program);
+    let kept: Vec<usize> = (0..program.len()).filter(|pos| dead.binary_search(pos).is_err()).collect();
+    if dead.is_empty() {
+        return kept;
+    }
+
+    let mut new_index = vec![None; program.len()];
+    for (index, pos) in kept.iter().enumerate() {
+        new_index[*pos] = Some(index);
+    }
+
+    let old_jumps = jump_table(program);
+    let optimized: Vec<Instruction> = kept.iter().map(|pos| program[*pos]).collect();
+    let new_jumps = jump_table(&optimized);
+
+    // Каждый оставшийся переход должен вести туда же, куда и раньше
+    let consistent = kept.iter().zip(&new_jumps).all(|(pos, jump)| match old_jumps[*pos] {
+        Some(target) => new_index[target].is_some() && new_index[target] == *jump,
+        None => jump.is_none(),
+    });
+
+    if consistent {
+        kept
+    } else {
+        (0..program.len()).collect()
+    }
+}
+
+impl<C: CellValue> CowInterpreter<C> {
+    // Удалить мёртвый код до начала выполнения, сохранив позиции в исходнике.
+    // Возвращает количество удалённых инструкций.
+    pub fn eliminate_dead_code(&mut self) -> usize {
+        let kept = kept_positions(&self.program);
+        let removed = self.program.len() - kept.len();
+        if removed > 0 {
+            self.program = kept.iter().map(|pos| self.program[*pos]).collect();
+            self.positions = kept.iter().filter_map(|pos| self.positions.get(*pos).copied()).collect();
+        }
+        removed
+    }
+}
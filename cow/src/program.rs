@@ -0,0 +1,58 @@
+use crate::interpreter::Instruction;
+use crate::prelude::*;
+
+// Построение программы из Rust-кода без склейки строк.
+// Циклы сопоставляются автоматически: если тело цикла пустое, начинается или заканчивается
+// инструкцией цикла, добавляется нейтральная пара moO mOo - иначе MOO/moo сопоставились бы
+// не так, как задумано (соседняя с ними инструкция при поиске пары пропускается).
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+    program: Vec<Instruction>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        ProgramBuilder::default()
+    }
+
+    pub fn push(&mut self, instruction: Instruction) -> &mut Self {
+        self.program.push(instruction);
+        self
+    }
+
+    pub fn push_n(&mut self, instruction: Instruction, count: usize) -> &mut Self {
+        self.program.extend(core::iter::repeat_n(instruction, count));
+        self
+    }
+
+    pub fn extend(&mut self, instructions: &[Instruction]) -> &mut Self {
+        self.program.extend_from_slice(instructions);
+        self
+    }
+
+    // MOO, тело из body, moo
+    pub fn loop_(&mut self, body: impl FnOnce(&mut ProgramBuilder)) -> &mut Self {
+        let mut inner = ProgramBuilder::new();
+        body(&mut inner);
+        let inner = inner.program;
+
+        self.program.push(Instruction::MOO2);
+        if inner.first().is_none_or(|first| is_loop(*first)) {
+            self.program.extend([Instruction::MoO, Instruction::MOo]);
+        }
+        self.program.extend_from_slice(&inner);
+        if inner.last().is_some_and(|last| is_loop(*last)) {
+            self.program.extend([Instruction::MoO, Instruction::MOo]);
+        }
+        self.program.push(Instruction::Moo);
+        self
+    }
+
+    pub fn build(&self) -> Vec<Instruction> {
+        self.program.clone()
+    }
+}
+
+fn is_loop(instruction: Instruction) -> bool {
+    matches!(instruction, Instruction::MOO2 | Instruction::Moo)
+}
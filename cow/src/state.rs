@@ -0,0 +1,94 @@
+use crate::cell::CellValue;
+use crate::interpreter::CowInterpreter;
+use crate::prelude::*;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+// Снимок состояния машины: лента, указатели, регистр и число шагов
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CowState<C: CellValue = i32> {
+    pub memory: Vec<C>,
+    pub mem_pos: usize,
+    pub prog_pos: usize,
+    pub register: Option<C>,
+    pub steps: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellChange<C: CellValue = i32> {
+    pub cell: usize,
+    pub old: C,
+    pub new: C,
+}
+
+// Разница между двумя снимками. Ячейки за концом более короткой ленты считаются нулевыми,
+// поэтому рост ленты без записи изменением не считается.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDiff<C: CellValue = i32> {
+    pub cells: Vec<CellChange<C>>,
+    // (было, стало), если указатель на ленту сдвинулся
+    pub pointer: Option<(usize, usize)>,
+    pub register: Option<(Option<C>, Option<C>)>,
+}
+
+impl<C: CellValue> CowState<C> {
+    pub fn of(interpreter: &CowInterpreter<C>) -> Self {
+        CowState {
+            memory: interpreter.memory.clone(),
+            mem_pos: interpreter.mem_pos,
+            prog_pos: interpreter.prog_pos,
+            register: interpreter.register.clone(),
+            steps: interpreter.steps,
+        }
+    }
+
+    // Что изменилось по пути от self к other
+    pub fn diff(&self, other: &CowState<C>) -> StateDiff<C> {
+        let len = self.memory.len().max(other.memory.len());
+        let cell = |memory: &[C], index: usize| memory.get(index).cloned().unwrap_or_default();
+        let cells = (0..len)
+            .filter_map(|index| {
+                let (old, new) = (cell(&self.memory, index), cell(&other.memory, index));
+                (old != new).then_some(CellChange { cell: index, old, new })
+            })
+            .collect();
+
+        StateDiff {
+            cells,
+            pointer: (self.mem_pos != other.mem_pos).then_some((self.mem_pos, other.mem_pos)),
+            register: (self.register != other.register).then(|| (self.register.clone(), other.register.clone())),
+        }
+    }
+}
+
+impl<C: CellValue> StateDiff<C> {
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.pointer.is_none() && self.register.is_none()
+    }
+}
+
+// Кратко, для отладчика: "ячейка 1: 0 -> 1; указатель 0 -> 1; регистр пуст -> 5"
+impl<C: CellValue> fmt::Display for StateDiff<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let register = |value: &Option<C>| value.as_ref().map_or("пуст".to_string(), ToString::to_string);
+
+        let mut parts: Vec<String> = self
+            .cells
+            .iter()
+            .map(|change| format!("ячейка {}: {} -> {}", change.cell, change.old, change.new))
+            .collect();
+        if let Some((from, to)) = self.pointer {
+            parts.push(format!("указатель {} -> {}", from, to));
+        }
+        if let Some((old, new)) = &self.register {
+            parts.push(format!("регистр {} -> {}", register(old), register(new)));
+        }
+
+        if parts.is_empty() {
+            write!(f, "без изменений")
+        } else {
+            write!(f, "{}", parts.join("; "))
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::cell::CellValue;
+use crate::interpreter::{CowInterpreter, TOKENS};
+use crate::io::CowIo;
+use crate::prelude::*;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// Запись об одном шаге выполнения.
+// before/after - значение ячейки, на которую указатель смотрел перед шагом.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceStep<C: CellValue = i32> {
+    pub step: usize,
+    pub position: usize,
+    pub instruction: &'static str,
+    pub pointer: usize,
+    pub before: C,
+    pub after: C,
+}
+
+// Вся трасса одним JSON-массивом
+pub fn to_json<C: CellValue>(trace: &[TraceStep<C>]) -> String {
+    serde_json::to_string_pretty(trace).expect("трасса всегда сериализуется")
+}
+
+// Один JSON-объект на строку, удобно для потоковой обработки и diff
+pub fn to_ndjson<C: CellValue>(trace: &[TraceStep<C>]) -> String {
+    let mut result = String::new();
+    for step in trace {
+        result.push_str(&serde_json::to_string(step).expect("шаг трассы всегда сериализуется"));
+        result.push('\n');
+    }
+    result
+}
+
+// Строка живой трассы: номер шага, инструкция с позицией в исходнике,
+// указатель и значение ячейки до и после шага.
+// description - результат CowInterpreter::describe_position для step.position.
+// color выделяет инструкцию и изменённое значение цветами ANSI.
+pub fn format_step<C: CellValue>(step: &TraceStep<C>, description: &str, color: bool) -> String {
+    let changed = step.before != step.after;
+    let values = if changed {
+        format!("{} -> {}", step.before, step.after)
+    } else {
+        step.before.to_string()
+    };
+
+    if color {
+        let values = if changed { format!("\x1b[33m{}\x1b[0m", values) } else { values };
+        format!(
+            "\x1b[2m{:>6}\x1b[0m  \x1b[1;36m{:<30}\x1b[0m ptr={:<4} {}",
+            step.step, description, step.pointer, values
+        )
+    } else {
+        format!("{:>6}  {:<30} ptr={:<4} {}", step.step, description, step.pointer, values)
+    }
+}
+
+// Шаг трассы в том виде, как он лежит в JSON: имя инструкции ещё не
+// сопоставлено с TOKENS
+#[derive(Deserialize)]
+#[serde(bound = "")]
+struct RawTraceStep<C: CellValue> {
+    step: usize,
+    position: usize,
+    instruction: String,
+    pointer: usize,
+    before: C,
+    after: C,
+}
+
+impl<'de, C: CellValue> Deserialize<'de> for TraceStep<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawTraceStep::<C>::deserialize(deserializer)?;
+        let instruction = TOKENS
+            .iter()
+            .map(|(token, _)| *token)
+            .find(|token| *token == raw.instruction)
+            .ok_or_else(|| D::Error::custom(format!("неизвестная инструкция {}", raw.instruction)))?;
+
+        Ok(TraceStep {
+            step: raw.step,
+            position: raw.position,
+            instruction,
+            pointer: raw.pointer,
+            before: raw.before,
+            after: raw.after,
+        })
+    }
+}
+
+// Один прочитанный программой ввод; None - ввод закончился
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedInput {
+    Char(Option<char>),
+    Line(Option<String>),
+}
+
+// Обёртка над источником ввода, запоминающая всё, что прочитала программа
+pub struct InputRecorder<'a> {
+    inner: &'a mut dyn CowIo,
+    pub inputs: Vec<RecordedInput>,
+}
+
+impl<'a> InputRecorder<'a> {
+    pub fn new(inner: &'a mut dyn CowIo) -> Self {
+        InputRecorder { inner, inputs: Vec::new() }
+    }
+}
+
+impl CowIo for InputRecorder<'_> {
+    fn read_char(&mut self) -> Option<char> {
+        let value = self.inner.read_char();
+        self.inputs.push(RecordedInput::Char(value));
+        value
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let value = self.inner.read_line();
+        self.inputs.push(RecordedInput::Line(value.clone()));
+        value
+    }
+}
+
+// Запись выполнения: трасса, весь прочитанный ввод и итоговое состояние.
+// По ней replay повторяет запуск без исходного источника ввода.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Recording<C: CellValue = i32> {
+    pub inputs: Vec<RecordedInput>,
+    pub trace: Vec<TraceStep<C>>,
+    pub output: String,
+    pub memory: Vec<C>,
+    pub mem_pos: usize,
+    pub register: Option<C>,
+}
+
+impl<C: CellValue> Recording<C> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("запись всегда сериализуется")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("Некорректная запись выполнения: {}", e))
+    }
+}
+
+// Выполнить программу до конца, записывая трассу и прочитанный ввод
+pub fn record<C: CellValue>(
+    interpreter: &mut CowInterpreter<C>,
+    input: &mut dyn CowIo,
+) -> Result<Recording<C>, String> {
+    if interpreter.trace.is_none() {
+        interpreter.trace = Some(Vec::new());
+    }
+
+    let mut recorder = InputRecorder::new(input);
+    let output = interpreter.execute_with_input(&mut recorder)?;
+
+    Ok(Recording {
+        inputs: recorder.inputs,
+        trace: interpreter.take_trace(),
+        output,
+        memory: interpreter.memory.clone(),
+        mem_pos: interpreter.mem_pos,
+        register: interpreter.register.clone(),
+    })
+}
+
+// Ввод, воспроизводящий записанные значения в том же порядке.
+// Запрос другого вида или сверх записанного считается расхождением.
+struct ReplayInput<'a> {
+    inputs: core::slice::Iter<'a, RecordedInput>,
+    mismatch: Option<String>,
+}
+
+impl ReplayInput<'_> {
+    fn fail(&mut self, message: String) {
+        if self.mismatch.is_none() {
+            self.mismatch = Some(message);
+        }
+    }
+}
+
+impl CowIo for ReplayInput<'_> {
+    fn read_char(&mut self) -> Option<char> {
+        match self.inputs.next() {
+            Some(RecordedInput::Char(value)) => *value,
+            Some(RecordedInput::Line(_)) => {
+                self.fail("программа запросила символ, а в записи строка".to_string());
+                None
+            }
+            None => {
+                self.fail("программа запросила больше ввода, чем записано".to_string());
+                None
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        match self.inputs.next() {
+            Some(RecordedInput::Line(value)) => value.clone(),
+            Some(RecordedInput::Char(_)) => {
+                self.fail("программа запросила строку, а в записи символ".to_string());
+                None
+            }
+            None => {
+                self.fail("программа запросила больше ввода, чем записано".to_string());
+                None
+            }
+        }
+    }
+}
+
+// Повторить выполнение на записанном вводе и сверить трассу, вывод и итоговое
+// состояние. Интерпретатор должен быть свежим и содержать ту же программу.
+pub fn replay<C: CellValue>(interpreter: &mut CowInterpreter<C>, recording: &Recording<C>) -> Result<(), String> {
+    interpreter.trace = Some(Vec::new());
+
+    let mut input = ReplayInput {
+        inputs: recording.inputs.iter(),
+        mismatch: None,
+    };
+    let output = interpreter.execute_with_input(&mut input)?;
+    let trace = interpreter.take_trace();
+
+    if let Some(mismatch) = input.mismatch {
+        return Err(format!("Расхождение при воспроизведении: {}", mismatch));
+    }
+    if input.inputs.next().is_some() {
+        return Err("Расхождение при воспроизведении: программа прочитала не весь записанный ввод".to_string());
+    }
+
+    if let Some((actual, expected)) = trace.iter().zip(&recording.trace).find(|(a, e)| a != e) {
+        return Err(format!(
+            "Расхождение на шаге {}: записано {} на позиции {}, выполнено {} на позиции {}",
+            expected.step, expected.instruction, expected.position, actual.instruction, actual.position
+        ));
+    }
+    if trace.len() != recording.trace.len() {
+        return Err(format!(
+            "Расхождение в числе шагов: записано {}, выполнено {}",
+            recording.trace.len(),
+            trace.len()
+        ));
+    }
+
+    if output != recording.output {
+        return Err(format!("Вывод отличается: записано {:?}, получено {:?}", recording.output, output));
+    }
+    if interpreter.memory != recording.memory
+        || interpreter.mem_pos != recording.mem_pos
+        || interpreter.register != recording.register
+    {
+        return Err("Итоговое состояние памяти отличается от записанного".to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use alloc::collections::BTreeMap;
+use crate::interpreter::{jump_table, Instruction};
+use crate::prelude::*;
+
+// Что известно о значении ячейки при переводе между COW и Brainfuck
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fact {
+    Known(i32),
+    NonZero,
+    Unknown,
+}
+
+impl Fact {
+    fn is_non_zero(self) -> bool {
+        match self {
+            Fact::Known(value) => value != 0,
+            Fact::NonZero => true,
+            Fact::Unknown => false,
+        }
+    }
+
+    fn add(self, delta: i32) -> Fact {
+        match self {
+            Fact::Known(value) => Fact::Known(value.wrapping_add(delta)),
+            _ => Fact::Unknown,
+        }
+    }
+}
+
+// Известные значения ячеек по смещению от начального положения указателя.
+// Тело цикла выполняется неизвестное число раз, поэтому на границах цикла
+// всё забывается, кроме текущей ячейки, которую только что проверил MOO.
+struct Tape {
+    cells: BTreeMap<i64, Fact>,
+    rest: Fact,
+    pos: i64,
+}
+
+impl Tape {
+    fn new() -> Self {
+        Tape { cells: BTreeMap::new(), rest: Fact::Known(0), pos: 0 }
+    }
+
+    fn get(&self) -> Fact {
+        self.cells.get(&self.pos).copied().unwrap_or(self.rest)
+    }
+
+    fn set(&mut self, fact: Fact) {
+        self.cells.insert(self.pos, fact);
+    }
+
+    fn forget(&mut self, current: Fact) {
+        self.cells.clear();
+        self.rest = Fact::Unknown;
+        self.set(current);
+    }
+}
+
+// Пары скобок по вложенности Brainfuck. COW ищет парные MOO/moo иначе (пропускает
+// соседнюю со скобкой инструкцию), поэтому каждая пара сверяется с jump_table.
+fn check_loops(program: &[Instruction], problems: &mut Vec<String>) {
+    let jumps = jump_table(program);
+    let mut open = Vec::new();
+
+    for (pos, instruction) in program.iter().enumerate() {
+        match instruction {
+            Instruction::MOO2 => open.push(pos),
+            Instruction::Moo => match open.pop() {
+                Some(start) if jumps[start] == Some(pos) && jumps[pos] == Some(start) => {}
+                Some(start) => problems.push(format!(
+                    "MOO (позиция {}) и moo (позиция {}) в COW не парные",
+                    start, pos
+                )),
+                None => problems.push(format!("moo (позиция {}) без парного MOO", pos)),
+            },
+            _ => {}
+        }
+    }
+
+    for pos in open {
+        problems.push(format!("MOO (позиция {}) без парного moo", pos));
+    }
+}
+
+// Перевод программы COW в Brainfuck.
+// Перемещение указателя, инкремент/декремент и циклы переводятся напрямую, OOO раскрывается в `[-]`.
+// Moo становится выводом символа, только если ячейка заведомо не нулевая: на нулевой он читал бы ввод.
+// Циклы переводятся, если пары MOO/moo в COW совпадают с вложенностью скобок Brainfuck.
+// Инструкции mOO, MMM, OOM и oom в Brainfuck не выражаются - они перечисляются в ошибке.
+pub fn to_brainfuck(program: &[Instruction]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut unsupported = Vec::new();
+    let mut tape = Tape::new();
+
+    for (pos, instruction) in program.iter().enumerate() {
+        match instruction {
+            Instruction::Moo => {
+                output.push(']');
+                tape.forget(Fact::Known(0));
+            }
+            Instruction::MOo => {
+                output.push('<');
+                tape.pos -= 1;
+            }
+            Instruction::MoO => {
+                output.push('>');
+                tape.pos += 1;
+            }
+            Instruction::Moo2 => {
+                if !tape.get().is_non_zero() {
+                    unsupported.push(format!("Moo (позиция {}, ячейка может быть нулевой)", pos));
+                }
+                output.push('.');
+            }
+            Instruction::MOo2 => {
+                output.push('-');
+                tape.set(tape.get().add(-1));
+            }
+            Instruction::MoO2 => {
+                output.push('+');
+                tape.set(tape.get().add(1));
+            }
+            Instruction::MOO2 => {
+                output.push('[');
+                tape.forget(Fact::NonZero);
+            }
+            Instruction::OOO => {
+                output.push_str("[-]");
+                tape.set(Fact::Known(0));
+            }
+            Instruction::MOO | Instruction::MMM | Instruction::OOM | Instruction::Oom | Instruction::Custom(_) => {
+                unsupported.push(format!("{} (позиция {})", instruction.token(), pos));
+                tape.forget(Fact::Unknown);
+            }
+        }
+    }
+
+    check_loops(program, &mut unsupported);
+
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Инструкции не представимы в Brainfuck: {}",
+            unsupported.join(", ")
+        ));
+    }
+
+    Ok(output)
+}
+
+// Перевод программы Brainfuck в программу COW.
+// Все символы, кроме восьми команд Brainfuck, считаются комментариями.
+// `.` становится Moo, только если ячейка заведомо не нулевая: на нулевой Moo читает символ.
+// Иначе вывод обходит нулевую ячейку через регистр (в остальное время он пуст):
+// MMM MOO Moo OOO moo MMM - запомнить ячейку, вывести и обнулить её, если она не нулевая,
+// вернуть значение. Нулевой байт COW вывести не может, поэтому он пропускается - в этом
+// единственное отличие результата от Brainfuck.
+// `,` становится OOO Moo, чтобы ввод выполнялся независимо от значения ячейки.
+// Поиск парных MOO/moo пропускает соседнюю с ними инструкцию, поэтому
+// между стоящими рядом скобками вставляется пустая пара moO mOo.
+pub fn from_brainfuck(source: &str) -> String {
+    let mut program = Vec::new();
+    let mut tape = Tape::new();
+
+    for ch in source.chars() {
+        match ch {
+            '+' => {
+                program.push(Instruction::MoO2);
+                tape.set(tape.get().add(1));
+            }
+            '-' => {
+                program.push(Instruction::MOo2);
+                tape.set(tape.get().add(-1));
+            }
+            '>' => {
+                program.push(Instruction::MoO);
+                tape.pos += 1;
+            }
+            '<' => {
+                program.push(Instruction::MOo);
+                tape.pos -= 1;
+            }
+            '.' if tape.get().is_non_zero() => program.push(Instruction::Moo2),
+            '.' => program.extend([
+                Instruction::MMM,
+                Instruction::MOO2,
+                Instruction::Moo2,
+                Instruction::OOO,
+                Instruction::Moo,
+                Instruction::MMM,
+            ]),
+            ',' => {
+                program.push(Instruction::OOO);
+                program.push(Instruction::Moo2);
+                tape.set(Fact::Unknown);
+            }
+            '[' | ']' => {
+                if matches!(program.last(), Some(Instruction::MOO2 | Instruction::Moo)) {
+                    program.push(Instruction::MoO);
+                    program.push(Instruction::MOo);
+                }
+                program.push(if ch == '[' { Instruction::MOO2 } else { Instruction::Moo });
+                tape.forget(if ch == '[' { Fact::NonZero } else { Fact::Known(0) });
+            }
+            _ => {}
+        }
+    }
+
+    program
+        .iter()
+        .map(|instruction| instruction.token())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const RUST_PRELUDE: &str = r#"// Программа сгенерирована из исходного кода COW
+#![allow(dead_code)]
+
+use std::io::{self, BufRead, Write};
+
+struct Machine {
+    memory: Vec<i32>,
+    mem_pos: usize,
+    register: Option<i32>,
+    output: io::BufWriter<io::Stdout>,
+}
+
+impl Machine {
+    fn read_line(&mut self) -> String {
+        let _ = self.output.flush();
+        let mut line = String::new();
+        let _ = io::stdin().lock().read_line(&mut line);
+        line.trim_end_matches(['\r', '\n']).to_string()
+    }
+}
+"#;
+
+const RUST_MAIN: &str = r#"fn main() {
+    let mut m = Machine {
+        memory: vec![0],
+        mem_pos: 0,
+        register: None,
+        output: io::BufWriter::new(io::stdout()),
+    };
+
+    let mut pc = 0;
+    while pc < PROGRAM_LEN {
+        match step(&mut m, pc) {
+            Some(next) => pc = next,
+            None => break,
+        }
+    }
+
+    let _ = m.output.flush();
+}
+"#;
+
+// Генерация самостоятельной программы на Rust, эквивалентной программе COW.
+// Переходы циклов берутся из таблицы переходов и подставляются в код константами,
+// а каждая инструкция становится отдельной ветвью match, чтобы mOO мог
+// выполнить инструкцию по её индексу.
+pub fn to_rust(program: &[Instruction]) -> String {
+    let jumps = jump_table(program);
+    let mut code = String::from(RUST_PRELUDE);
+
+    // Индексы mOO, на которые может указать ячейка: их выполнение через mOO зациклилось бы
+    let recursive_targets = program
+        .iter()
+        .take(12)
+        .enumerate()
+        .filter(|(_, instruction)| **instruction == Instruction::MOO)
+        .map(|(pos, _)| pos.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    code.push_str(&format!("\nconst PROGRAM_LEN: usize = {};\n\n", program.len()));
+    code.push_str("fn step(m: &mut Machine, pc: usize) -> Option<usize> {\n");
+    code.push_str("    match pc {\n");
+
+    for (pos, instruction) in program.iter().enumerate() {
+        let next = pos + 1;
+        let body = match instruction {
+            Instruction::Moo => match jumps[pos] {
+                Some(start) => format!("Some({})", start),
+                None => "None".to_string(),
+            },
+            Instruction::MOo => format!(
+                "if m.mem_pos == 0 {{ return None; }} m.mem_pos -= 1; Some({})",
+                next
+            ),
+            Instruction::MoO => format!(
+                "m.mem_pos += 1; if m.mem_pos >= m.memory.len() {{ m.memory.push(0); }} Some({})",
+                next
+            ),
+            Instruction::MOO => format!(
+                "let value = m.memory[m.mem_pos]; \
+                 if value == 3 || !(0..12).contains(&value) {{ return None; }} \
+                 if [{}].contains(&value) {{ eprintln!(\"mOO: бесконечная рекурсия\"); std::process::exit(1); }} \
+                 if (value as usize) < PROGRAM_LEN {{ step(m, value as usize); }} Some({})",
+                recursive_targets, next
+            ),
+            Instruction::Moo2 => format!(
+                "if m.memory[m.mem_pos] != 0 {{ \
+                 if let Some(ch) = char::from_u32(m.memory[m.mem_pos] as u32) {{ let _ = write!(m.output, \"{{}}\", ch); }} \
+                 }} else if let Some(ch) = m.read_line().chars().next() {{ m.memory[m.mem_pos] = ch as i32; }} Some({})",
+                next
+            ),
+            Instruction::MOo2 => format!("m.memory[m.mem_pos] = m.memory[m.mem_pos].wrapping_sub(1); Some({})", next),
+            Instruction::MoO2 => format!("m.memory[m.mem_pos] = m.memory[m.mem_pos].wrapping_add(1); Some({})", next),
+            Instruction::MOO2 => {
+                let skip = if next >= program.len() {
+                    "return None;".to_string()
+                } else {
+                    match jumps[pos] {
+                        Some(end) => format!("return Some({});", end + 1),
+                        None => "return None;".to_string(),
+                    }
+                };
+                format!("if m.memory[m.mem_pos] == 0 {{ {} }} Some({})", skip, next)
+            }
+            Instruction::OOO => format!("m.memory[m.mem_pos] = 0; Some({})", next),
+            Instruction::MMM => format!(
+                "match m.register.take() {{ \
+                 None => m.register = Some(m.memory[m.mem_pos]), \
+                 Some(value) => m.memory[m.mem_pos] = value, \
+                 }} Some({})",
+                next
+            ),
+            Instruction::OOM => format!(
+                "let _ = writeln!(m.output, \"{{}}\", m.memory[m.mem_pos]); Some({})",
+                next
+            ),
+            Instruction::Oom => format!(
+                "m.memory[m.mem_pos] = m.read_line().trim().parse().unwrap_or(0); Some({})",
+                next
+            ),
+            Instruction::Custom(token) => format!(
+                "eprintln!(\"{}: пользовательская инструкция не переводится в Rust\"); std::process::exit(1)",
+                token
+            ),
+        };
+        code.push_str(&format!(
+            "        {} => {{ {} }} // {}\n",
+            pos,
+            body,
+            instruction.token()
+        ));
+    }
+
+    code.push_str("        _ => None,\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+    code.push_str(RUST_MAIN);
+
+    code
+}
@@ -0,0 +1,209 @@
+use crate::cell::CellValue;
+use crate::debugger::Debugger;
+use crate::interpreter::CowInterpreter;
+use crate::io::CowIo;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+use std::time::Duration;
+
+const TAPE_CELLS: usize = 10;
+const PROGRAM_WINDOW: usize = 8;
+const OUTPUT_LINES: usize = 6;
+
+// Возвращает терминал в обычный режим даже при ошибке
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct ViewState {
+    output: String,
+    running: bool,
+    delay_ms: u64,
+    error: Option<String>,
+    // Последнее срабатывание точки наблюдения
+    watch: Option<String>,
+    // Что изменил последний шаг
+    change: Option<String>,
+}
+
+// Интерактивный просмотр выполнения: лента, указатель, регистр и текущая инструкция.
+// Пробел/s - шаг, b - шаг назад, r - запуск, p - пауза, +/- - скорость, q - выход.
+// Срабатывание точки наблюдения ставит выполнение на паузу.
+pub fn run<C: CellValue>(debugger: &mut Debugger<C>, input: &mut dyn CowIo) -> io::Result<String> {
+    let _guard = TerminalGuard::enter()?;
+    let mut stdout = io::stdout();
+    let mut state = ViewState {
+        output: String::new(),
+        running: false,
+        delay_ms: 100,
+        error: None,
+        watch: None,
+        change: None,
+    };
+
+    loop {
+        draw(&mut stdout, debugger.interpreter, &state)?;
+
+        let timeout = if state.running && !debugger.interpreter.is_finished() {
+            Duration::from_millis(state.delay_ms)
+        } else {
+            Duration::from_secs(3600)
+        };
+
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char(' ') | KeyCode::Char('s') => {
+                        state.running = false;
+                        step(debugger, input, &mut state);
+                    }
+                    KeyCode::Char('b') => {
+                        state.running = false;
+                        state.watch = None;
+                        state.error = None;
+                        state.change = None;
+                        debugger.step_back(&mut state.output);
+                    }
+                    KeyCode::Char('r') => state.running = true,
+                    KeyCode::Char('p') => state.running = false,
+                    KeyCode::Char('+') => state.delay_ms = (state.delay_ms / 2).max(1),
+                    KeyCode::Char('-') => state.delay_ms = (state.delay_ms * 2).min(2000),
+                    _ => {}
+                }
+            }
+        } else if state.running {
+            step(debugger, input, &mut state);
+        }
+    }
+
+    Ok(state.output)
+}
+
+fn step<C: CellValue>(debugger: &mut Debugger<C>, input: &mut dyn CowIo, state: &mut ViewState) {
+    if debugger.interpreter.is_finished() || state.error.is_some() {
+        state.running = false;
+        return;
+    }
+    state.watch = None;
+    let before = debugger.interpreter.state();
+    let result = debugger.step(&mut state.output, input);
+    state.change = Some(before.diff(&debugger.interpreter.state()).to_string());
+    match result {
+        Ok(Some(hit)) => {
+            state.watch = Some(hit.to_string());
+            state.running = false;
+        }
+        Ok(None) => {}
+        Err(err) => {
+            state.error = Some(err);
+            state.running = false;
+        }
+    }
+}
+
+fn draw<C: CellValue>(stdout: &mut io::Stdout, interpreter: &CowInterpreter<C>, state: &ViewState) -> io::Result<()> {
+    queue!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+    let status = if let Some(err) = &state.error {
+        format!("ошибка: {}", err)
+    } else if interpreter.is_finished() {
+        "завершена".to_string()
+    } else if state.running {
+        format!("выполняется, задержка {} мс", state.delay_ms)
+    } else {
+        "пауза".to_string()
+    };
+    line(stdout, &format!("COW - шаг {} - {}", interpreter.steps, status))?;
+    if let Some(watch) = &state.watch {
+        line(stdout, &format!("Точка наблюдения: {}", watch))?;
+    }
+    if let Some(change) = &state.change {
+        line(stdout, &format!("Последний шаг: {}", change))?;
+    }
+    line(stdout, "")?;
+
+    // Окно программы вокруг текущей инструкции
+    line(stdout, "Программа:")?;
+    queue!(stdout, Print("  "))?;
+    let start = interpreter.prog_pos.saturating_sub(PROGRAM_WINDOW);
+    let end = (interpreter.prog_pos + PROGRAM_WINDOW + 1).min(interpreter.program.len());
+    for pos in start..end {
+        let token = interpreter.program[pos].token();
+        if pos == interpreter.prog_pos {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Reverse),
+                Print(token),
+                SetAttribute(Attribute::Reset),
+                Print(" ")
+            )?;
+        } else {
+            queue!(stdout, Print(token), Print(" "))?;
+        }
+    }
+    line(stdout, "")?;
+    if interpreter.prog_pos < interpreter.program.len() {
+        line(stdout, &format!("  {}", interpreter.describe_position(interpreter.prog_pos)))?;
+    } else {
+        line(stdout, "  конец программы")?;
+    }
+    line(stdout, "")?;
+
+    // Лента памяти с указателем
+    line(stdout, "Лента:")?;
+    let memory = interpreter.get_memory();
+    let first = interpreter.mem_pos.saturating_sub(TAPE_CELLS / 2);
+    let last = (first + TAPE_CELLS).min(memory.len());
+    let mut indexes = String::from("  ");
+    let mut values = String::from("  ");
+    let mut pointer = String::from("  ");
+    for (idx, value) in memory.iter().enumerate().take(last).skip(first) {
+        indexes.push_str(&format!("{:>7}", idx));
+        values.push_str(&format!("{:>7}", value));
+        pointer.push_str(if idx == interpreter.mem_pos { "      ^" } else { "       " });
+    }
+    line(stdout, &indexes)?;
+    line(stdout, &values)?;
+    line(stdout, &pointer)?;
+
+    match interpreter.get_register() {
+        Some(value) => line(stdout, &format!("Регистр: {}", value))?,
+        None => line(stdout, "Регистр: пуст")?,
+    }
+    line(stdout, "")?;
+
+    line(stdout, "Вывод:")?;
+    let lines: Vec<&str> = state.output.lines().collect();
+    for text in lines.iter().skip(lines.len().saturating_sub(OUTPUT_LINES)) {
+        line(stdout, &format!("  {}", text))?;
+    }
+    line(stdout, "")?;
+
+    line(stdout, "пробел/s - шаг, r - запуск, p - пауза, +/- - скорость, q - выход")?;
+    stdout.flush()
+}
+
+// В raw-режиме перевод строки не возвращает каретку
+fn line(stdout: &mut io::Stdout, text: &str) -> io::Result<()> {
+    queue!(stdout, Print(text), Print("\r\n"))
+}
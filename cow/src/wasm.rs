@@ -0,0 +1,381 @@
+use crate::bytecode::{Bytecode, Op};
+use crate::interpreter::Instruction;
+use crate::prelude::*;
+
+// Компиляция в модуль WebAssembly.
+// Лента - массив i32 в линейной памяти (экспорт "memory"), точка входа - экспорт "run".
+// Ввод и вывод - импорты модуля "cow":
+//   read_char() -> i32   код символа для Moo или -1, если ввод закончился
+//   read_int() -> i32    число для oom
+//   write_char(i32)      вывод Moo; как печатать не-символы, решает хост
+//   write_int(i32)       вывод OOM
+// Переходы циклов могут пересекаться, поэтому код разбит на блоки,
+// а переход между ними - br_table по номеру блока внутри одного loop.
+
+const PAGE_SIZE_LOG2: i32 = 16;
+
+// Номера импортированных функций; run идёт следом
+const READ_CHAR: u32 = 0;
+const READ_INT: u32 = 1;
+const WRITE_CHAR: u32 = 2;
+const WRITE_INT: u32 = 3;
+const RUN: u32 = 4;
+
+// Локальные переменные run
+const PC: u32 = 0;
+const PTR: u32 = 1;
+const REG: u32 = 2;
+const HAS_REG: u32 = 3;
+const TMP: u32 = 4;
+const LOCALS: u32 = 5;
+
+const I32: u8 = 0x7F;
+const EMPTY: u8 = 0x40;
+
+mod op {
+    pub const UNREACHABLE: u8 = 0x00;
+    pub const BLOCK: u8 = 0x02;
+    pub const LOOP: u8 = 0x03;
+    pub const IF: u8 = 0x04;
+    pub const ELSE: u8 = 0x05;
+    pub const END: u8 = 0x0B;
+    pub const BR: u8 = 0x0C;
+    pub const BR_TABLE: u8 = 0x0E;
+    pub const RETURN: u8 = 0x0F;
+    pub const CALL: u8 = 0x10;
+    pub const LOCAL_GET: u8 = 0x20;
+    pub const LOCAL_SET: u8 = 0x21;
+    pub const LOCAL_TEE: u8 = 0x22;
+    pub const I32_LOAD: u8 = 0x28;
+    pub const I32_STORE: u8 = 0x36;
+    pub const MEMORY_SIZE: u8 = 0x3F;
+    pub const MEMORY_GROW: u8 = 0x40;
+    pub const I32_CONST: u8 = 0x41;
+    pub const I32_EQZ: u8 = 0x45;
+    pub const I32_EQ: u8 = 0x46;
+    pub const I32_LT_S: u8 = 0x48;
+    pub const I32_GT_S: u8 = 0x4A;
+    pub const I32_GE_S: u8 = 0x4E;
+    pub const I32_ADD: u8 = 0x6A;
+    pub const I32_SUB: u8 = 0x6B;
+    pub const I32_SHL: u8 = 0x74;
+    pub const I32_SHR_U: u8 = 0x76;
+}
+
+// Модуль для программы; mOO не поддерживается, как и в байткоде
+pub fn compile(program: &[Instruction]) -> Result<Vec<u8>, String> {
+    let bytecode = Bytecode::compile(program)?;
+    Ok(compile_bytecode(&bytecode))
+}
+
+pub fn compile_bytecode(bytecode: &Bytecode) -> Vec<u8> {
+    let mut module = b"\0asm".to_vec();
+    module.extend_from_slice(&1u32.to_le_bytes());
+
+    // Типы: 0 - () -> i32, 1 - (i32) -> (), 2 - () -> ()
+    let mut types = Vec::new();
+    uleb(&mut types, 3);
+    types.extend_from_slice(&[0x60, 0, 1, I32]);
+    types.extend_from_slice(&[0x60, 1, I32, 0]);
+    types.extend_from_slice(&[0x60, 0, 0]);
+    section(&mut module, 1, &types);
+
+    let mut imports = Vec::new();
+    let functions: [(&str, u32); 4] = [("read_char", 0), ("read_int", 0), ("write_char", 1), ("write_int", 1)];
+    uleb(&mut imports, functions.len() as u64);
+    for (name, type_index) in functions {
+        name_bytes(&mut imports, "cow");
+        name_bytes(&mut imports, name);
+        imports.push(0x00);
+        uleb(&mut imports, type_index as u64);
+    }
+    section(&mut module, 2, &imports);
+
+    section(&mut module, 3, &[1, 2]);
+
+    // Одна страница в начале, дальше память растёт вместе с лентой
+    section(&mut module, 5, &[1, 0x00, 1]);
+
+    let mut exports = Vec::new();
+    uleb(&mut exports, 2);
+    name_bytes(&mut exports, "run");
+    exports.push(0x00);
+    uleb(&mut exports, RUN as u64);
+    name_bytes(&mut exports, "memory");
+    exports.push(0x02);
+    uleb(&mut exports, 0);
+    section(&mut module, 7, &exports);
+
+    let body = function_body(&bytecode.ops);
+    let mut code = Vec::new();
+    uleb(&mut code, 1);
+    uleb(&mut code, body.len() as u64);
+    code.extend_from_slice(&body);
+    section(&mut module, 10, &code);
+
+    module
+}
+
+fn function_body(ops: &[Op]) -> Vec<u8> {
+    // Блок начинается с точки входа, с цели перехода и после каждой операции перехода
+    let mut leaders = vec![false; ops.len() + 1];
+    leaders[0] = true;
+    leaders[ops.len()] = true;
+    for (index, op) in ops.iter().enumerate() {
+        match *op {
+            Op::LoopStart(target) | Op::LoopEnd(target) => {
+                leaders[index + 1] = true;
+                if let Some(target) = target {
+                    leaders[target] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    let starts: Vec<usize> = (0..=ops.len()).filter(|&index| leaders[index]).collect();
+    let block_of = |index: usize| starts.binary_search(&index).expect("переход ведёт на начало блока") as i32;
+    // Последний блок - выход из программы
+    let count = starts.len();
+
+    let mut body = Vec::new();
+    uleb(&mut body, 1);
+    uleb(&mut body, LOCALS as u64);
+    body.push(I32);
+
+    body.extend_from_slice(&[op::LOOP, EMPTY]);
+    for _ in 0..count {
+        body.extend_from_slice(&[op::BLOCK, EMPTY]);
+    }
+    local_get(&mut body, PC);
+    body.push(op::BR_TABLE);
+    uleb(&mut body, count as u64);
+    for depth in 0..count {
+        uleb(&mut body, depth as u64);
+    }
+    uleb(&mut body, (count - 1) as u64);
+    body.push(op::END);
+
+    for (block, window) in starts.windows(2).enumerate() {
+        // До loop остались открытыми блоки с большими номерами
+        let dispatch = (count - 1 - block) as u32;
+        let mut emit = Emitter { body: &mut body, dispatch, depth: 0 };
+        for &op in &ops[window[0]..window[1]] {
+            match op {
+                Op::LoopStart(target) => {
+                    emit.load_cell();
+                    emit.body.extend_from_slice(&[op::I32_EQZ, op::IF, EMPTY]);
+                    emit.depth += 1;
+                    emit.jump(target.map(block_of));
+                    emit.depth -= 1;
+                    emit.body.push(op::END);
+                }
+                Op::LoopEnd(target) => emit.jump(target.map(block_of)),
+                _ => emit.op(op),
+            }
+        }
+        emit.jump(Some(block as i32 + 1));
+        emit.body.push(op::END);
+    }
+
+    // Выход
+    body.push(op::RETURN);
+    body.push(op::END);
+    body.push(op::END);
+    body
+}
+
+struct Emitter<'a> {
+    body: &'a mut Vec<u8>,
+    // Глубина метки loop относительно текущего места
+    dispatch: u32,
+    depth: u32,
+}
+
+impl Emitter<'_> {
+    // Перейти к блоку; None - остановить программу
+    fn jump(&mut self, block: Option<i32>) {
+        match block {
+            Some(block) => {
+                i32_const(self.body, block);
+                local_set(self.body, PC);
+                self.body.push(op::BR);
+                uleb(self.body, (self.dispatch + self.depth) as u64);
+            }
+            None => self.body.push(op::RETURN),
+        }
+    }
+
+    // Адрес текущей ячейки: ptr * 4
+    fn address(&mut self) {
+        local_get(self.body, PTR);
+        i32_const(self.body, 2);
+        self.body.push(op::I32_SHL);
+    }
+
+    fn load_cell(&mut self) {
+        self.address();
+        self.body.extend_from_slice(&[op::I32_LOAD, 2, 0]);
+    }
+
+    // Значение для записи должно лежать в TMP
+    fn store_tmp(&mut self) {
+        self.address();
+        local_get(self.body, TMP);
+        self.body.extend_from_slice(&[op::I32_STORE, 2, 0]);
+    }
+
+    fn op(&mut self, op: Op) {
+        match op {
+            Op::Add(value) => {
+                self.load_cell();
+                i32_const(self.body, value);
+                self.body.push(op::I32_ADD);
+                local_set(self.body, TMP);
+                self.store_tmp();
+            }
+            Op::Move { delta, min, .. } => {
+                if min < 0 {
+                    // mOo на нулевой ячейке останавливает программу
+                    local_get(self.body, PTR);
+                    i32_const(self.body, min as i32);
+                    self.body.extend_from_slice(&[op::I32_ADD]);
+                    i32_const(self.body, 0);
+                    self.body.extend_from_slice(&[op::I32_LT_S, op::IF, EMPTY, op::RETURN, op::END]);
+                }
+                local_get(self.body, PTR);
+                i32_const(self.body, delta as i32);
+                self.body.push(op::I32_ADD);
+                local_set(self.body, PTR);
+                if delta > 0 {
+                    self.grow_memory();
+                }
+            }
+            Op::Clear => {
+                i32_const(self.body, 0);
+                local_set(self.body, TMP);
+                self.store_tmp();
+            }
+            Op::Io => {
+                self.load_cell();
+                local_tee(self.body, TMP);
+                self.body.extend_from_slice(&[op::IF, EMPTY]);
+                local_get(self.body, TMP);
+                call(self.body, WRITE_CHAR);
+                self.body.push(op::ELSE);
+                call(self.body, READ_CHAR);
+                local_tee(self.body, TMP);
+                i32_const(self.body, 0);
+                self.body.extend_from_slice(&[op::I32_GE_S, op::IF, EMPTY]);
+                self.store_tmp();
+                self.body.extend_from_slice(&[op::END, op::END]);
+            }
+            Op::PrintInt => {
+                self.load_cell();
+                call(self.body, WRITE_INT);
+            }
+            Op::ReadInt => {
+                call(self.body, READ_INT);
+                local_set(self.body, TMP);
+                self.store_tmp();
+            }
+            Op::Register => {
+                local_get(self.body, HAS_REG);
+                self.body.extend_from_slice(&[op::IF, EMPTY]);
+                local_get(self.body, REG);
+                local_set(self.body, TMP);
+                self.store_tmp();
+                i32_const(self.body, 0);
+                local_set(self.body, HAS_REG);
+                self.body.push(op::ELSE);
+                self.load_cell();
+                local_set(self.body, REG);
+                i32_const(self.body, 1);
+                local_set(self.body, HAS_REG);
+                self.body.push(op::END);
+            }
+            Op::LoopStart(_) | Op::LoopEnd(_) => unreachable!("переходы обрабатывает function_body"),
+        }
+    }
+
+    // Досчитать страницы, если ячейка ptr вышла за конец памяти
+    fn grow_memory(&mut self) {
+        local_get(self.body, PTR);
+        i32_const(self.body, 1);
+        self.body.push(op::I32_ADD);
+        i32_const(self.body, 2);
+        self.body.push(op::I32_SHL);
+        i32_const(self.body, (1 << PAGE_SIZE_LOG2) - 1);
+        self.body.push(op::I32_ADD);
+        i32_const(self.body, PAGE_SIZE_LOG2);
+        self.body.push(op::I32_SHR_U);
+        self.body.extend_from_slice(&[op::MEMORY_SIZE, 0, op::I32_SUB]);
+        local_tee(self.body, TMP);
+        i32_const(self.body, 0);
+        self.body.extend_from_slice(&[op::I32_GT_S, op::IF, EMPTY]);
+        local_get(self.body, TMP);
+        self.body.extend_from_slice(&[op::MEMORY_GROW, 0]);
+        i32_const(self.body, -1);
+        self.body.extend_from_slice(&[op::I32_EQ, op::IF, EMPTY, op::UNREACHABLE, op::END, op::END]);
+    }
+}
+
+fn section(module: &mut Vec<u8>, id: u8, content: &[u8]) {
+    module.push(id);
+    uleb(module, content.len() as u64);
+    module.extend_from_slice(content);
+}
+
+fn name_bytes(buffer: &mut Vec<u8>, name: &str) {
+    uleb(buffer, name.len() as u64);
+    buffer.extend_from_slice(name.as_bytes());
+}
+
+fn local_get(body: &mut Vec<u8>, local: u32) {
+    body.push(op::LOCAL_GET);
+    uleb(body, local as u64);
+}
+
+fn local_set(body: &mut Vec<u8>, local: u32) {
+    body.push(op::LOCAL_SET);
+    uleb(body, local as u64);
+}
+
+fn local_tee(body: &mut Vec<u8>, local: u32) {
+    body.push(op::LOCAL_TEE);
+    uleb(body, local as u64);
+}
+
+fn call(body: &mut Vec<u8>, function: u32) {
+    body.push(op::CALL);
+    uleb(body, function as u64);
+}
+
+fn i32_const(body: &mut Vec<u8>, value: i32) {
+    body.push(op::I32_CONST);
+    sleb(body, value as i64);
+}
+
+fn uleb(buffer: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
+
+fn sleb(buffer: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
@@ -1,5 +1,25 @@
+use cow_interpreter::analysis;
+use cow_interpreter::bench;
+use cow_interpreter::bytecode::{Bytecode, Op};
+use cow_interpreter::cell::{BigInt, CellWidth};
+use cow_interpreter::checkpoint::Checkpoint;
+use cow_interpreter::cowc::{self, CompiledProgram};
+use cow_interpreter::debug_server::DebugServer;
+use cow_interpreter::debugger::{Debugger, StopReason, WatchCondition, Watchpoint};
+use cow_interpreter::format;
+use cow_interpreter::generate;
+use cow_interpreter::golden;
 use cow_interpreter::interpreter::CowInterpreter;
-use cow_interpreter::interpreter::Instruction;
+use cow_interpreter::interpreter::{
+    jump_table, ExecEvent, ExecSummary, FuelRun, InputRequest, Instruction, OutputChunk, ParseMode,
+    SourcePos, SpecMode,
+};
+use cow_interpreter::optimize;
+use cow_interpreter::program::ProgramBuilder;
+use cow_interpreter::macros;
+use cow_interpreter::io::{reader_input, CharInput, CowIo, InputMode, OutputPolicy};
+use cow_interpreter::trace::{self, TraceStep};
+use cow_interpreter::transpile;
 
 #[cfg(test)]
 mod tests {
@@ -120,7 +140,7 @@ mod tests {
         let mut interpreter = CowInterpreter::new("MoOmoOMoOmoOMoO").unwrap();
         interpreter.execute().unwrap();
         assert!(interpreter.memory.len() >= 2);
-        assert!(interpreter.mem_pos >= 0);
+        assert!(interpreter.mem_pos < interpreter.memory.len());
     }
 
     #[test]
@@ -203,4 +223,1478 @@ mod tests {
         assert_eq!(interp.prog_pos, 0);
     }
 
+    #[test]
+    fn test_to_brainfuck_compatible_subset() {
+        let interpreter = CowInterpreter::new("MoOMoOMOOmoOMoOmOoMOomooMoOMooOOO").unwrap();
+        let bf = transpile::to_brainfuck(&interpreter.program).unwrap();
+        assert_eq!(bf, "++[>+<-]+.[-]");
+    }
+
+    fn run_brainfuck(code: &str) -> (String, Vec<i32>) {
+        let code = code.as_bytes();
+        let mut pairs = vec![0; code.len()];
+        let mut open = Vec::new();
+        for (pos, byte) in code.iter().enumerate() {
+            match byte {
+                b'[' => open.push(pos),
+                b']' => {
+                    let start = open.pop().unwrap();
+                    pairs[start] = pos;
+                    pairs[pos] = start;
+                }
+                _ => {}
+            }
+        }
+
+        let (mut memory, mut ptr, mut pc, mut output) = (vec![0i32], 0, 0, String::new());
+        while pc < code.len() {
+            match code[pc] {
+                b'+' => memory[ptr] = memory[ptr].wrapping_add(1),
+                b'-' => memory[ptr] = memory[ptr].wrapping_sub(1),
+                b'>' => {
+                    ptr += 1;
+                    if ptr == memory.len() {
+                        memory.push(0);
+                    }
+                }
+                b'<' => ptr -= 1,
+                b'.' => output.extend(char::from_u32(memory[ptr] as u32)),
+                b'[' if memory[ptr] == 0 => pc = pairs[pc],
+                b']' if memory[ptr] != 0 => pc = pairs[pc],
+                _ => {}
+            }
+            pc += 1;
+        }
+        (output, memory)
+    }
+
+    #[test]
+    fn test_to_brainfuck_matches_interpreter() {
+        let programs = [
+            "MoO MoO MOO moO MoO MoO MoO mOo MOo moo moO OOO MoO MoO MoO Moo mOo OOO MoO MoO Moo",
+            "MoO MoO MoO MOO moO MoO MoO moO MoO mOo mOo MOo moo moO moO MOO MOo moo OOO MoO Moo",
+            &transpile::from_brainfuck("+++[[-]>++[>+<-]<]>>[-]+."),
+        ];
+        for source in programs {
+            let mut interpreter = CowInterpreter::new(source).unwrap();
+            let bf = transpile::to_brainfuck(&interpreter.program).unwrap();
+            let output = interpreter.execute().unwrap();
+            let (bf_output, bf_memory) = run_brainfuck(&bf);
+            assert_eq!(bf_output, output, "{}", source);
+            assert_eq!(bf_memory, interpreter.memory, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_to_brainfuck_rejects_moo_input() {
+        // На нулевой ячейке Moo читает символ, а не выводит его
+        let interpreter = CowInterpreter::new("Moo").unwrap();
+        let err = transpile::to_brainfuck(&interpreter.program).unwrap_err();
+        assert!(err.contains("Moo (позиция 0, ячейка может быть нулевой)"));
+
+        let interpreter = CowInterpreter::new("MoO MOO MOo moo Moo").unwrap();
+        assert!(transpile::to_brainfuck(&interpreter.program).is_err());
+
+        let interpreter = CowInterpreter::new("MoO MOO moO MoO mOo MOo moo moO Moo").unwrap();
+        assert!(transpile::to_brainfuck(&interpreter.program).is_err());
+    }
+
+    #[test]
+    fn test_to_brainfuck_rejects_cow_loop_pairing() {
+        // Рядом стоящие MOO MOO в COW не образуют вложенный цикл
+        let interpreter = CowInterpreter::new("MOO MOO moo moo").unwrap();
+        let err = transpile::to_brainfuck(&interpreter.program).unwrap_err();
+        assert!(err.contains("в COW не парные"));
+
+        let interpreter = CowInterpreter::new("MoO MOO MOo").unwrap();
+        let err = transpile::to_brainfuck(&interpreter.program).unwrap_err();
+        assert!(err.contains("MOO (позиция 1) без парного moo"));
+    }
+
+    #[test]
+    fn test_to_brainfuck_reports_unsupported() {
+        let interpreter = CowInterpreter::new("MoOmOOMMMOOMoom").unwrap();
+        let err = transpile::to_brainfuck(&interpreter.program).unwrap_err();
+        assert!(err.contains("mOO (позиция 1)"));
+        assert!(err.contains("MMM (позиция 2)"));
+        assert!(err.contains("OOM (позиция 3)"));
+        assert!(err.contains("oom (позиция 4)"));
+    }
+
+    #[test]
+    fn test_from_brainfuck_mapping() {
+        let cow = transpile::from_brainfuck("+-><+.,[-] comment");
+        assert_eq!(cow, "MoO MOo moO mOo MoO Moo OOO Moo MOO MOo moo");
+    }
+
+    #[test]
+    fn test_from_brainfuck_output_of_zero_cell() {
+        // Ячейка может быть нулевой: вывод через регистр не читает ввод, а нулевой байт пропускается
+        let cow = transpile::from_brainfuck(".");
+        assert_eq!(cow, "MMM MOO Moo OOO moo MMM");
+
+        let bf = "+.-.,.>,[.[-]],.";
+        let mut interpreter = CowInterpreter::new(&transpile::from_brainfuck(bf)).unwrap();
+        let mut input = ["A", "B", "C"].iter().map(|s| s.to_string());
+        let output = interpreter.execute_with_input(&mut input).unwrap();
+        // Brainfuck вывел бы "\u{1}\0ABC"
+        assert_eq!(output, "\u{1}ABC");
+        assert_eq!(interpreter.memory[..2], [65, 67]);
+        assert_eq!(interpreter.register, None);
+    }
+
+    #[test]
+    fn test_from_brainfuck_pads_adjacent_brackets() {
+        let cow = transpile::from_brainfuck("[[]]");
+        assert_eq!(cow, "MOO moO mOo MOO moO mOo moo moO mOo moo");
+    }
+
+    #[test]
+    fn test_from_brainfuck_hello_world_runs() {
+        let bf = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let mut interpreter = CowInterpreter::new(&transpile::from_brainfuck(bf)).unwrap();
+        let output = interpreter.execute().unwrap();
+        assert_eq!(output, "Hello World!\n");
+    }
+
+    #[test]
+    fn test_jump_table_matches_loops() {
+        let interpreter = CowInterpreter::new("MOO MoO MOO MOo moo moO moo").unwrap();
+        let jumps = jump_table(&interpreter.program);
+        assert_eq!(jumps[0], Some(6));
+        assert_eq!(jumps[6], Some(0));
+        assert_eq!(jumps[2], Some(4));
+        assert_eq!(jumps[4], Some(2));
+        assert_eq!(jumps[1], None);
+    }
+
+    #[test]
+    fn test_to_rust_inlines_jump_targets() {
+        let interpreter = CowInterpreter::new("MoO MOO MOo MOo moo OOM").unwrap();
+        let code = transpile::to_rust(&interpreter.program);
+        assert!(code.contains("const PROGRAM_LEN: usize = 6;"));
+        assert!(code.contains("1 => { if m.memory[m.mem_pos] == 0 { return Some(5); } Some(2) } // MOO"));
+        assert!(code.contains("4 => { Some(1) } // moo"));
+        assert!(code.contains("fn main()"));
+    }
+
+    // Собирает программу, сгенерированную to_rust, и запускает её с данным stdin
+    fn run_generated_rust(name: &str, program: &[Instruction], stdin: &str) -> String {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("cow_to_rust_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join(format!("{}.rs", name));
+        let bin = dir.join(name);
+        std::fs::write(&src, transpile::to_rust(program)).unwrap();
+
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = std::process::Command::new(rustc)
+            .arg(&src)
+            .arg("-o")
+            .arg(&bin)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut child = std::process::Command::new(&bin)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let _ = std::fs::remove_dir_all(&dir);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_to_rust_compiles_and_matches_interpreter() {
+        let source = std::fs::read_to_string("examples/hello.cow").unwrap();
+        let mut interpreter = CowInterpreter::new(&source).unwrap();
+        let expected = interpreter.execute().unwrap();
+        assert_eq!(run_generated_rust("hello", &interpreter.program, ""), expected);
+    }
+
+    #[test]
+    fn test_to_rust_wraps_like_interpreter() {
+        // Переход через i32::MAX и i32::MIN: без wrapping_* отладочная сборка паникует
+        let mut interpreter = CowInterpreter::new("oom MoO OOM MOo OOM moO oom MOo OOM").unwrap();
+        let mut input = vec!["2147483647".to_string(), "-2147483648".to_string()].into_iter();
+        let expected = interpreter.execute_with_input(&mut input).unwrap();
+        assert_eq!(expected, "-2147483648\n2147483647\n2147483647\n");
+
+        let output = run_generated_rust("wrap", &interpreter.program, "2147483647\n-2147483648\n");
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_max_steps_limit() {
+        let mut interpreter = CowInterpreter::new("MoOMOOMoOmoo").unwrap();
+        interpreter.max_steps = Some(100);
+        let mut input = vec![].into_iter();
+        let err = interpreter.execute_with_input(&mut input).unwrap_err();
+        assert!(err.contains("100"));
+        assert_eq!(interpreter.steps, 100);
+    }
+
+    #[test]
+    fn test_steps_counted_without_limit() {
+        let mut interpreter = CowInterpreter::new("MoOMoOOOM").unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.steps, 3);
+    }
+
+    #[test]
+    fn test_set_memory_size() {
+        let mut interpreter = CowInterpreter::new("moOmoOMoO").unwrap();
+        interpreter.set_memory_size(8);
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.memory.len(), 8);
+        assert_eq!(interpreter.memory[2], 1);
+
+        interpreter.set_memory_size(2);
+        assert_eq!(interpreter.memory.len(), 8);
+    }
+
+    #[test]
+    fn test_parse_records_positions() {
+        let interpreter = CowInterpreter::new("MoO x moO\n  мычание OOM").unwrap();
+        assert_eq!(interpreter.positions.len(), 3);
+        assert_eq!(interpreter.positions[0], SourcePos { line: 1, column: 1 });
+        assert_eq!(interpreter.positions[1], SourcePos { line: 1, column: 7 });
+        assert_eq!(interpreter.positions[2], SourcePos { line: 2, column: 11 });
+    }
+
+    #[test]
+    fn test_describe_position() {
+        let interpreter = CowInterpreter::new("MoO\n   mOo").unwrap();
+        assert_eq!(interpreter.describe_position(1), "mOo (строка 2, столбец 4)");
+        assert!(interpreter.describe_position(5).contains("за концом"));
+    }
+
+    #[test]
+    fn test_step_limit_error_mentions_position() {
+        let mut interpreter = CowInterpreter::new("MoO\nMOO\n  MoO\nmoo").unwrap();
+        interpreter.max_steps = Some(3);
+        let err = interpreter.execute().unwrap_err();
+        assert!(err.contains("moo (строка 4, столбец 1)"));
+    }
+
+    #[test]
+    fn test_moo_self_reference_is_error() {
+        let mut interpreter = CowInterpreter::new("mOO").unwrap();
+        let err = interpreter.execute().unwrap_err();
+        assert!(err.contains("бесконечная рекурсия"));
+        assert!(err.contains("mOO (строка 1, столбец 1)"));
+    }
+
+    #[test]
+    fn test_moo_executes_target_instruction() {
+        // ячейка = 1 -> mOO выполняет инструкцию 1 (MoO) ещё раз
+        let mut interpreter = CowInterpreter::new("MoOMoOMOommOOOOM").unwrap();
+        let output = interpreter.execute().unwrap();
+        assert_eq!(output, "2\n");
+    }
+
+    #[test]
+    fn test_moo_chain_is_error() {
+        // mOO на позиции 0 указывает на другой mOO: тот прочитал бы ту же ячейку и выполнил себя же
+        let mut interpreter = CowInterpreter::new("mOO OOM mOO").unwrap().with_memory(vec![2], 0);
+        let err = interpreter.execute().unwrap_err();
+        assert!(err.contains("mOO (строка 1, столбец 1) выполняет mOO (строка 1, столбец 9)"));
+    }
+
+    #[test]
+    fn test_outputs_yields_chunks_lazily() {
+        let mut interpreter = CowInterpreter::new("MoOOOMMoOOOMMoOMoOMoO").unwrap();
+        let mut input = vec![].into_iter();
+        let first = interpreter.outputs(&mut input).next().unwrap().unwrap();
+        assert_eq!(first, OutputChunk { text: "1\n".to_string(), position: 1 });
+        assert_eq!(interpreter.prog_pos, 2);
+
+        let mut input = vec![].into_iter();
+        let rest: Vec<_> = interpreter.outputs(&mut input).collect::<Result<_, _>>().unwrap();
+        assert_eq!(rest, vec![OutputChunk { text: "2\n".to_string(), position: 3 }]);
+        assert!(interpreter.is_finished());
+        assert_eq!(interpreter.memory[0], 5);
+    }
+
+    #[test]
+    fn test_outputs_stops_after_error() {
+        let mut interpreter = CowInterpreter::new("OOMmOO").unwrap();
+        interpreter.memory[0] = 1;
+        let mut input = vec![].into_iter();
+        let items: Vec<_> = interpreter.outputs(&mut input).collect();
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_ok());
+        assert!(items[1].is_err());
+    }
+
+    #[test]
+    fn test_char_input_reads_single_characters() {
+        let mut input = CharInput::new("Hж\n42\n".as_bytes());
+        assert_eq!(input.read_char(), Some('H'));
+        assert_eq!(input.read_char(), Some('ж'));
+        assert_eq!(input.read_char(), Some('\n'));
+        assert_eq!(input.read_line(), Some("42".to_string()));
+        assert_eq!(input.read_char(), None);
+        assert_eq!(input.read_line(), None);
+    }
+
+    #[test]
+    fn test_char_mode_program_echo() {
+        // Moo с нулевой ячейкой читает символ, затем выводит его
+        let program = "Moo Moo moO Moo Moo";
+        let mut interpreter = CowInterpreter::new(program).unwrap();
+        let mut input = reader_input("ab".as_bytes(), InputMode::Char);
+        let output = interpreter.execute_with_input(input.as_mut()).unwrap();
+        assert_eq!(output, "ab");
+
+        let mut interpreter = CowInterpreter::new(program).unwrap();
+        let mut input = reader_input("ab\ncd".as_bytes(), InputMode::Line);
+        let output = interpreter.execute_with_input(input.as_mut()).unwrap();
+        assert_eq!(output, "ac");
+    }
+
+    #[test]
+    fn test_input_mode_parse() {
+        assert_eq!(InputMode::parse("char"), Ok(InputMode::Char));
+        assert_eq!(InputMode::parse("line"), Ok(InputMode::Line));
+        assert!(InputMode::parse("byte").is_err());
+    }
+
+    #[test]
+    fn test_analyze_histogram_and_nesting() {
+        let interpreter = CowInterpreter::new("MoO MOO moO MOO MOo moo mOo MOo moo OOM").unwrap();
+        let report = analysis::analyze(&interpreter.program);
+        assert_eq!(report.instruction_count, 10);
+        assert_eq!(report.max_nesting, 2);
+        assert_eq!(report.count(Instruction::MOO2), 2);
+        assert_eq!(report.count(Instruction::Moo), 2);
+        assert_eq!(report.count(Instruction::OOO), 0);
+        assert!(report.unmatched_loops.is_empty());
+        assert!(report.unreachable.is_empty());
+        assert!(report.is_ok());
+        assert_eq!(report.histogram_entries()[0], ("moo", 2));
+    }
+
+    #[test]
+    fn test_analyze_unmatched_and_unreachable() {
+        // moo без пары останавливает программу, всё после него недостижимо
+        let interpreter = CowInterpreter::new("MoO MoO moo OOM MOO MoO").unwrap();
+        let report = analysis::analyze(&interpreter.program);
+        assert_eq!(report.unmatched_loops, vec![2, 4]);
+        assert_eq!(report.unreachable, vec![3, 4, 5]);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn test_format_indents_loops_and_strips_noise() {
+        let interpreter = CowInterpreter::new("MoO c1 MOO moO MOO MOo moo mOo MOo moo OOM").unwrap();
+        let formatted = format::format_program(&interpreter.program, 80);
+        assert_eq!(
+            formatted,
+            "MoO\nMOO\n    moO\n    MOO\n        MOo\n    moo\n    mOo MOo\nmoo\nOOM\n"
+        );
+    }
+
+    #[test]
+    fn test_cli_rejects_zero_line_width() {
+        let dir = std::env::temp_dir().join(format!("cow_line_width_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("program.cow");
+        std::fs::write(&path, "MoO MoO OOM").unwrap();
+        let cli = |args: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_cow_interpreter")).args(args).output().unwrap();
+        let path = path.to_str().unwrap();
+
+        for args in [vec!["fmt", path, "0"], vec![path, "--fmt", "--line-width", "0"]] {
+            let output = cli(&args);
+            assert!(!output.status.success(), "{:?}", args);
+            assert!(String::from_utf8_lossy(&output.stderr).contains("ширина строки должна быть больше 0"), "{:?}", args);
+        }
+        let output = cli(&["fmt", path, "20"]);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "MoO MoO OOM\n");
+    }
+
+    #[test]
+    fn test_format_wraps_lines_and_preserves_program() {
+        let source = std::fs::read_to_string("examples/fib.cow").unwrap();
+        let interpreter = CowInterpreter::new(&source).unwrap();
+        let formatted = format::format_program(&interpreter.program, 20);
+        assert!(formatted.lines().all(|line| line.len() <= 20));
+
+        let reparsed = CowInterpreter::new(&formatted).unwrap();
+        assert_eq!(reparsed.program, interpreter.program);
+    }
+
+    #[test]
+    fn test_exec_summary() {
+        // ячейка 0 = 3, цикл переносит её в ячейку 1
+        let mut interpreter = CowInterpreter::new("MoOMoOMoO MOO moOMoOmOoMOo moo moO OOM Moo").unwrap();
+        let mut input = vec!["x".to_string()].into_iter();
+        interpreter.execute_with_input(&mut input).unwrap();
+
+        let summary = interpreter.summary();
+        assert_eq!(
+            summary,
+            ExecSummary {
+                steps: interpreter.steps,
+                cells_touched: 2,
+                max_pointer: 1,
+                loop_iterations: 3,
+                inputs: 0,
+                outputs: 2,
+            }
+        );
+        assert!(summary.to_string().contains("Итераций циклов: 3"));
+    }
+
+    #[test]
+    fn test_exec_summary_counts_input() {
+        let mut interpreter = CowInterpreter::new("Moo moO oom").unwrap();
+        let mut input = vec!["A".to_string(), "7".to_string()].into_iter();
+        interpreter.execute_with_input(&mut input).unwrap();
+        let summary = interpreter.summary();
+        assert_eq!(summary.inputs, 2);
+        assert_eq!(summary.outputs, 0);
+        assert_eq!(summary.cells_touched, 2);
+    }
+
+    #[test]
+    fn test_execute_with_cancel_from_other_thread() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let mut interpreter = CowInterpreter::new("MoO MOO MoO moo").unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            flag.store(true, Ordering::Relaxed);
+        });
+
+        let mut input = vec![].into_iter();
+        let err = interpreter.execute_with_cancel(&mut input, &cancel).unwrap_err();
+        assert!(err.contains("отменено"));
+        assert!(interpreter.steps > 0);
+    }
+
+    #[test]
+    fn test_execute_with_cancel_finishes_normally() {
+        let mut interpreter = CowInterpreter::new("MoO OOM").unwrap();
+        let cancel = std::sync::atomic::AtomicBool::new(false);
+        let mut input = vec![].into_iter();
+        assert_eq!(interpreter.execute_with_cancel(&mut input, &cancel).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn test_execute_with_timeout() {
+        let mut interpreter = CowInterpreter::new("MoO MOO MoO moo").unwrap();
+        let mut input = vec![].into_iter();
+        let err = interpreter
+            .execute_with_timeout(&mut input, std::time::Duration::from_millis(30))
+            .unwrap_err();
+        assert!(err.contains("30 мс"));
+    }
+
+    #[test]
+    fn test_generate_print_string_runs() {
+        for text in ["Hello, World!\n", "aaa", "Zz ~!", "Привет", ""] {
+            let source = generate::print_string(text);
+            let mut interpreter = CowInterpreter::new(&source).unwrap();
+            let mut input = vec![].into_iter();
+            assert_eq!(interpreter.execute_with_input(&mut input).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_generate_uses_loops_for_large_increments() {
+        let program = generate::print_string_program("x");
+        let naive = 'x' as usize + 1;
+        assert!(program.len() < naive / 2);
+        assert!(program.contains(&Instruction::MOO2));
+
+        // Небольшое изменение набирается напрямую от предыдущего символа
+        let program = generate::print_string_program("xy");
+        assert_eq!(&program[program.len() - 2..], &[Instruction::MoO2, Instruction::Moo2]);
+    }
+
+    #[test]
+    fn test_builder_applies_options() {
+        let interpreter = CowInterpreter::builder("MoO")
+            .memory_size(8)
+            .max_steps(100)
+            .build()
+            .unwrap();
+        assert_eq!(interpreter.get_memory().len(), 8);
+        assert_eq!(interpreter.max_steps, Some(100));
+
+        let mut interpreter = CowInterpreter::builder("MoO MoO MoO").max_steps(2).build().unwrap();
+        let mut input = vec![].into_iter();
+        assert!(interpreter.execute_with_input(&mut input).is_err());
+    }
+
+    #[test]
+    fn test_wide_cells_do_not_overflow() {
+        let mut interpreter = CowInterpreter::<i64>::with_cells("MoO OOM").unwrap();
+        interpreter.memory[0] = i32::MAX as i64;
+        let mut input = vec![].into_iter();
+        assert_eq!(interpreter.execute_with_input(&mut input).unwrap(), "2147483648\n");
+
+        let mut interpreter = CowInterpreter::builder("MoO OOM").cell_type::<BigInt>().build().unwrap();
+        interpreter.memory[0] = BigInt::from(i64::MAX);
+        let mut input = vec![].into_iter();
+        assert_eq!(interpreter.execute_with_input(&mut input).unwrap(), "9223372036854775808\n");
+
+        let mut interpreter = CowInterpreter::<BigInt>::with_cells("oom MoO OOM").unwrap();
+        let mut input = vec!["123456789012345678901234567890".to_string()].into_iter();
+        assert_eq!(
+            interpreter.execute_with_input(&mut input).unwrap(),
+            "123456789012345678901234567891\n"
+        );
+    }
+
+    #[test]
+    fn test_cell_width_parse() {
+        assert_eq!(CellWidth::parse("64").unwrap(), CellWidth::I64);
+        assert_eq!(CellWidth::parse("big").unwrap(), CellWidth::Big);
+        assert!(CellWidth::parse("16").is_err());
+    }
+
+    #[test]
+    fn test_non_printable_output_policy() {
+        let run = |policy: OutputPolicy, value: i32| {
+            let mut interpreter = CowInterpreter::builder("Moo").output_policy(policy).build().unwrap();
+            interpreter.memory[0] = value;
+            let mut input = vec![].into_iter();
+            interpreter.execute_with_input(&mut input)
+        };
+
+        assert_eq!(run(OutputPolicy::Skip, -1).unwrap(), "");
+        assert_eq!(run(OutputPolicy::Replace, 0xD800).unwrap(), "\u{FFFD}");
+        assert_eq!(run(OutputPolicy::Raw, -191).unwrap(), "A");
+        let err = run(OutputPolicy::Error, -5).unwrap_err();
+        assert!(err.contains("-5") && err.contains("ячейке 0"));
+        assert!(err.contains("Moo (строка 1, столбец 1)"));
+
+        assert_eq!(run(OutputPolicy::Error, 65).unwrap(), "A");
+        assert_eq!(OutputPolicy::parse("raw").unwrap(), OutputPolicy::Raw);
+        assert!(OutputPolicy::parse("drop").is_err());
+    }
+
+    #[test]
+    fn test_run_convenience() {
+        let result = cow_interpreter::run("oom MoO OOM MMM moO", "41\n").unwrap();
+        assert_eq!(result.output, "42\n");
+        assert_eq!(result.memory, vec![42, 0]);
+        assert_eq!(result.register, Some(42));
+        assert_eq!(result.steps, 5);
+
+        assert!(cow_interpreter::run("MoO MoO MoO mOO", "").unwrap().output.is_empty());
+    }
+
+    #[test]
+    fn test_watchpoint_changed_reports_instruction() {
+        let mut interpreter = CowInterpreter::new("MoO moO MoO\nMoO").unwrap();
+        let mut debugger = Debugger::new(&mut interpreter);
+        debugger.watch(1, WatchCondition::Changed);
+
+        let mut output = String::new();
+        let mut input = vec![].into_iter();
+        let hit = debugger.run(&mut output, &mut input).unwrap().unwrap();
+        assert_eq!((hit.cell, hit.old, hit.new, hit.position), (1, 0, 1, 2));
+        assert_eq!(hit.to_string(), "ячейка 1: 0 -> 1 на MoO (строка 1, столбец 9)");
+
+        // Выполнение продолжается с того же места
+        let hit = debugger.run(&mut output, &mut input).unwrap().unwrap();
+        assert_eq!((hit.new, hit.position), (2, 3));
+        assert_eq!(debugger.run(&mut output, &mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_watchpoint_equals_value() {
+        let mut interpreter = CowInterpreter::new("MoO MoO MoO MoO MOo MoO").unwrap();
+        let mut debugger = Debugger::new(&mut interpreter);
+        debugger.add_watchpoint(Watchpoint::parse("0=3").unwrap());
+
+        let mut output = String::new();
+        let mut input = vec![].into_iter();
+        let hit = debugger.run(&mut output, &mut input).unwrap().unwrap();
+        assert_eq!((hit.position, hit.new), (2, 3));
+        // 4 -> 3 тоже переход к значению
+        let hit = debugger.run(&mut output, &mut input).unwrap().unwrap();
+        assert_eq!((hit.position, hit.old), (4, 4));
+        assert_eq!(debugger.run(&mut output, &mut input).unwrap(), None);
+
+        assert!(Watchpoint::<i32>::parse("x=1").is_err());
+        assert_eq!(Watchpoint::<i32>::parse("2").unwrap().condition, WatchCondition::Changed);
+    }
+
+    #[test]
+    fn test_trace_records_each_step() {
+        let mut interpreter = CowInterpreter::builder("MoO moO MOo").trace(true).build().unwrap();
+        let mut input = vec![].into_iter();
+        interpreter.execute_with_input(&mut input).unwrap();
+
+        let steps = interpreter.take_trace();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            steps[2],
+            TraceStep { step: 3, position: 2, instruction: "MOo", pointer: 1, before: 0, after: -1 }
+        );
+
+        let ndjson = trace::to_ndjson(&steps[..1]);
+        assert_eq!(
+            ndjson,
+            "{\"step\":1,\"position\":0,\"instruction\":\"MoO\",\"pointer\":0,\"before\":0,\"after\":1}\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&trace::to_json(&steps)).unwrap();
+        assert_eq!(json[1]["instruction"], "moO");
+
+        // Без включённой трассы ничего не записывается
+        let mut interpreter = CowInterpreter::new("MoO").unwrap();
+        interpreter.execute_with_input(&mut input).unwrap();
+        assert!(interpreter.take_trace().is_empty());
+    }
+
+    fn run_in_mode(source: &str, mode: SpecMode) -> Result<String, String> {
+        let mut interpreter = CowInterpreter::builder(source).spec_mode(mode).build().unwrap();
+        let mut input = vec![].into_iter();
+        interpreter.execute_with_input(&mut input)
+    }
+
+    #[test]
+    fn test_strict_moo_executes_instruction_code() {
+        // Ячейка = 6 - код MoO: в strict инкремент, в legacy - шестая инструкция программы (сам mOO)
+        let program = "MoO MoO MoO MoO MoO MoO mOO OOM";
+        assert_eq!(run_in_mode(program, SpecMode::Strict).unwrap(), "7\n");
+        assert!(run_in_mode(program, SpecMode::Legacy).is_err());
+
+        // Код 10 - OOM
+        assert_eq!(run_in_mode("OOO MoO MoO MoO MoO MoO MoO MoO MoO MoO MoO mOO", SpecMode::Strict).unwrap(), "10\n");
+        // Код 3 и неизвестные коды останавливают программу
+        assert_eq!(run_in_mode("MoO MoO MoO mOO OOM", SpecMode::Strict).unwrap(), "");
+        assert_eq!(run_in_mode("MOo mOO OOM", SpecMode::Strict).unwrap(), "");
+    }
+
+    #[test]
+    fn test_spec_modes_agree_on_loops() {
+        // Пример из описания языка: MOO пропускает следующую инструкцию и находит второй moo
+        for mode in [SpecMode::Legacy, SpecMode::Strict] {
+            assert_eq!(run_in_mode("OOO MOO moo moo OOM", mode).unwrap(), "0\n");
+        }
+        let hello = std::fs::read_to_string("examples/hello.cow").unwrap();
+        let legacy = run_in_mode(&hello, SpecMode::Legacy).unwrap();
+        assert!(!legacy.is_empty());
+        assert_eq!(run_in_mode(&hello, SpecMode::Strict).unwrap(), legacy);
+        assert_eq!(SpecMode::parse("strict").unwrap(), SpecMode::Strict);
+        assert!(SpecMode::parse("loose").is_err());
+    }
+
+    #[test]
+    fn test_spec_modes_follow_reference_jumps() {
+        // Ожидаемый вывод - как у эталонного cow.cpp: MOO при нулевой ячейке пропускает следующую
+        // инструкцию и уменьшает уровень дважды на паре "MOO moo", moo пропускает предыдущую
+        let cases = [
+            // Обычный цикл: после выхода выполняется инструкция за moo
+            ("MoO MOO MOo moo OOM", "0\n"),
+            // moo сразу после MOO пропускает его и не находит начала цикла - программа останавливается
+            ("MoO MOO moo OOM", ""),
+            // MOO сразу перед moo при нулевой ячейке тоже не находит конца цикла
+            ("MOO moo MoO OOM", ""),
+            // Пара "MOO moo" внутри цикла закрывает и его: переход идёт на первый moo,
+            // а тот возвращает к вложенному MOO
+            ("MOO MoO MOO moo moo OOM", "0\n"),
+            // moo без MOO останавливает программу
+            ("MoO moo OOM", ""),
+        ];
+        for (program, expected) in cases {
+            for mode in [SpecMode::Legacy, SpecMode::Strict] {
+                assert_eq!(run_in_mode(program, mode).unwrap(), expected, "{} ({:?})", program, mode);
+            }
+        }
+
+        let fib = std::fs::read_to_string("examples/fib.cow").unwrap();
+        for mode in [SpecMode::Legacy, SpecMode::Strict] {
+            assert!(run_in_mode(&fib, mode).unwrap().starts_with("1\n, 1\n, 2\n, 3\n, 5\n, 8\n"));
+        }
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let mut interpreter = CowInterpreter::new("Moo MoO Moo").unwrap();
+        let mut input = vec!["A".to_string()].into_iter();
+        let events: Vec<ExecEvent> = interpreter.events(&mut input).map(|e| e.unwrap()).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                ExecEvent::InputRequested { position: 0 },
+                ExecEvent::InstructionExecuted { position: 0, instruction: Instruction::Moo2 },
+                ExecEvent::InstructionExecuted { position: 1, instruction: Instruction::MoO2 },
+                ExecEvent::InstructionExecuted { position: 2, instruction: Instruction::Moo2 },
+                ExecEvent::Output('B'),
+                ExecEvent::Halted,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_stream_stops_after_error() {
+        let mut interpreter = CowInterpreter::builder("MoO MoO MoO").max_steps(1).build().unwrap();
+        let mut input = vec![].into_iter();
+        let events: Vec<_> = interpreter.events(&mut input).collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_ok());
+        assert!(events[1].is_err());
+    }
+
+    #[test]
+    fn test_dead_code_zero_cell_loops() {
+        // Лента нулевая: первый цикл не выполняется; после OOO второй тоже
+        let program = CowInterpreter::parse("MOO MoO OOM moo MoO OOM OOO MOO OOM MOo moo OOM").unwrap();
+        assert_eq!(optimize::dead_code(&program), vec![0, 1, 2, 3, 7, 8, 9, 10]);
+        let optimized = optimize::eliminate_dead_code(&program);
+        assert_eq!(optimized.len(), 4);
+
+        // Цикл с ненулевой ячейкой остаётся
+        let program = CowInterpreter::parse("MoO MOO MOo OOM moo").unwrap();
+        assert!(optimize::dead_code(&program).is_empty());
+
+        // С mOO ничего не удаляется
+        let program = CowInterpreter::parse("MOO MoO OOM moo mOO").unwrap();
+        assert!(optimize::dead_code(&program).is_empty());
+    }
+
+    #[test]
+    fn test_dead_code_keeps_custom_jump_targets() {
+        // JMP переходит в тело цикла, в который программа с нулевой лентой не входит
+        let source = "JMP MOO OOM moo MoO OOM";
+        let (program, _) = CowInterpreter::parse_with_instructions(source, &["JMP"]).unwrap();
+        assert!(optimize::dead_code(&program).is_empty());
+        let build = || {
+            CowInterpreter::builder(source)
+                .register_instruction("JMP", |state| {
+                    state.prog_pos = 2;
+                    Ok(())
+                })
+                .build()
+                .unwrap()
+        };
+        let mut plain = build();
+        assert_eq!(plain.execute_with_input(&mut std::iter::empty()).unwrap(), "0\n1\n");
+        let mut optimized = build();
+        assert_eq!(optimized.eliminate_dead_code(), 0);
+        assert_eq!(optimized.execute_with_input(&mut std::iter::empty()).unwrap(), "0\n1\n");
+
+        // Переход через moo без пары: анализ не считает код за ним недостижимым
+        let (program, _) = CowInterpreter::parse_with_instructions("JMP moo OOM", &["JMP"]).unwrap();
+        assert!(analysis::analyze(&program).unreachable.is_empty());
+        let (program, _) = CowInterpreter::parse_with_instructions("moo JMP OOM", &["JMP"]).unwrap();
+        assert_eq!(analysis::analyze(&program).unreachable, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_eliminate_dead_code_keeps_behaviour() {
+        let source = "MOO MoO moo\nMoO MoO OOM moo MoO";
+        let mut input = vec![].into_iter();
+        let mut plain = CowInterpreter::new(source).unwrap();
+        let expected = plain.execute_with_input(&mut input).unwrap();
+
+        let mut optimized = CowInterpreter::new(source).unwrap();
+        assert_eq!(optimized.eliminate_dead_code(), 4);
+        assert_eq!(optimized.positions[0], SourcePos { line: 2, column: 1 });
+        assert_eq!(optimized.execute_with_input(&mut input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_bytecode_matches_interpreter() {
+        for (name, source) in bench::EMBEDDED {
+            let mut interpreter = CowInterpreter::new(source).unwrap();
+            let mut input = vec![].into_iter();
+            let expected = interpreter.execute_with_input(&mut input).unwrap();
+            let bytecode = Bytecode::compile(&interpreter.program).unwrap();
+            assert!(bytecode.ops.len() < interpreter.program.len(), "{}", name);
+            assert_eq!(bytecode.run(&mut input).unwrap().0, expected, "{}", name);
+        }
+
+        // Серии свёрнуты, mOo на нулевой ячейке останавливает программу
+        let program = CowInterpreter::parse("MoO MoO MOo moO mOo mOo OOM").unwrap();
+        let bytecode = Bytecode::compile(&program).unwrap();
+        assert_eq!(bytecode.ops, vec![Op::Add(1), Op::Move { delta: -1, min: -1, max: 1 }, Op::PrintInt]);
+        assert_eq!(bytecode.run(&mut vec![].into_iter()).unwrap().0, "");
+
+        assert!(Bytecode::compile(&CowInterpreter::parse("mOO").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_bench_table() {
+        let result = bench::run_benchmark("hello", bench::EMBEDDED[0].1, None).unwrap();
+        assert!(result.steps > 0 && result.bytecode_time.is_some());
+        let table = bench::format_table(&[result]);
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.lines().nth(1).unwrap().starts_with("hello"));
+
+        let result = bench::run_benchmark("moo", "OOO mOO", None).unwrap();
+        assert!(result.bytecode_time.is_none());
+    }
+
+    #[test]
+    fn test_run_fuel_pause_and_resume() {
+        let mut interpreter = CowInterpreter::new("MoO OOM MoO OOM MoO OOM").unwrap();
+        let mut input = vec![].into_iter();
+
+        let paused = match interpreter.run_fuel(&mut input, 4).unwrap() {
+            FuelRun::Paused(paused) => paused,
+            FuelRun::Finished(_) => panic!("программа не должна успеть завершиться"),
+        };
+        assert_eq!(paused.output, "1\n2\n");
+        assert_eq!(paused.interpreter().steps, 4);
+
+        match paused.resume(&mut input, 10).unwrap() {
+            FuelRun::Finished(output) => assert_eq!(output, "3\n"),
+            FuelRun::Paused(_) => panic!("программа должна завершиться"),
+        }
+        assert!(interpreter.is_finished());
+        assert!(matches!(interpreter.run_fuel(&mut input, 1).unwrap(), FuelRun::Finished(output) if output.is_empty()));
+    }
+
+    #[test]
+    fn test_cowc_roundtrip() {
+        let source = std::fs::read_to_string("examples/fib.cow").unwrap();
+        let (program, positions) = CowInterpreter::parse_with_positions(&source).unwrap();
+        let compiled = CompiledProgram::new(program.clone(), positions.clone());
+
+        let bytes = cowc::encode(&compiled);
+        assert!(bytes.len() < source.len());
+        let loaded = cowc::decode(&bytes).unwrap();
+        assert_eq!(loaded, compiled);
+        assert_eq!(loaded.jumps, jump_table(&program));
+
+        let mut input = vec![].into_iter();
+        let mut from_source = CowInterpreter::new(&source).unwrap();
+        let mut from_binary = CowInterpreter::<i32>::from_program(loaded.program, loaded.positions);
+        assert_eq!(
+            from_binary.execute_with_input(&mut input).unwrap(),
+            from_source.execute_with_input(&mut input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cowc_rejects_bad_input() {
+        let compiled = CompiledProgram::new(CowInterpreter::parse("MOO MoO moo").unwrap(), Vec::new());
+        let bytes = cowc::encode(&compiled);
+        assert_eq!(cowc::decode(&bytes).unwrap().positions, Vec::new());
+
+        assert!(cowc::decode(b"MOOC\x01\x00\x00").is_err());
+        assert!(cowc::decode(&bytes[..bytes.len() - 1]).unwrap_err().contains("обрезан"));
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 9;
+        assert!(cowc::decode(&bad_version).unwrap_err().contains("версия"));
+        let mut bad_code = bytes;
+        bad_code[6] = 0xFF;
+        assert!(cowc::decode(&bad_code).unwrap_err().contains("код инструкции"));
+    }
+
+    #[test]
+    fn test_next_input() {
+        let mut interpreter = CowInterpreter::new("Moo MoO Moo oom OOO mOO").unwrap();
+        let mut input = vec!["A".to_string()].into_iter();
+        assert_eq!(interpreter.next_input(), Some(InputRequest::Char));
+        interpreter.step(&mut String::new(), &mut input).unwrap();
+        interpreter.step(&mut String::new(), &mut input).unwrap();
+        // Ячейка не нулевая - Moo печатает
+        assert_eq!(interpreter.next_input(), None);
+        interpreter.step(&mut String::new(), &mut input).unwrap();
+        assert_eq!(interpreter.next_input(), Some(InputRequest::Line));
+        interpreter.step(&mut String::new(), &mut input).unwrap();
+        interpreter.step(&mut String::new(), &mut input).unwrap();
+        // mOO с нулевой ячейкой выполнит инструкцию 0 - Moo на нулевой ячейке
+        assert_eq!(interpreter.next_input(), Some(InputRequest::Char));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_async_execution() {
+        use cow_interpreter::async_exec;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let mut interpreter = CowInterpreter::new("oom MoO OOM Moo MoO Moo").unwrap();
+            let mut input: &[u8] = b"41\n";
+            let mut output = Vec::new();
+            async_exec::execute_async(&mut interpreter, &mut input, InputMode::Line, &mut output)
+                .await
+                .unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "42\n*+");
+
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+            sender.send("7".to_string()).await.unwrap();
+            drop(sender);
+            let mut interpreter = CowInterpreter::new("oom OOM oom OOM").unwrap();
+            let mut output = Vec::new();
+            async_exec::execute_with_channel(&mut interpreter, &mut receiver, &mut output)
+                .await
+                .unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "7\n0\n");
+        });
+    }
+
+    #[test]
+    fn test_strict_parse() {
+        let (program, positions) = CowInterpreter::parse_strict("mooMoO\n  MOo").unwrap();
+        assert_eq!(program, vec![Instruction::Moo, Instruction::MoO2, Instruction::MOo2]);
+        assert_eq!(positions[2], SourcePos { line: 2, column: 3 });
+
+        let err = CowInterpreter::parse_strict("MoO MoOO").unwrap_err();
+        assert_eq!(err, "Нераспознанный текст \"O\" (строка 1, столбец 8)");
+        let err = CowInterpreter::parse_strict("MoO\nкомментарий MoO").unwrap_err();
+        assert!(err.contains("(строка 2, столбец 1)"));
+
+        // Обычный разбор то же молча принимает
+        assert!(CowInterpreter::new("MoO MoOO").is_ok());
+        assert!(CowInterpreter::builder("MoO MoOO").parse_mode(ParseMode::Strict).build().is_err());
+    }
+
+    #[test]
+    fn test_heatmap_counts_reads_and_writes() {
+        let mut interpreter = CowInterpreter::builder("MoO MoO OOM moO MMM MoO").heatmap(true).build().unwrap();
+        let mut input = vec![].into_iter();
+        interpreter.execute_with_input(&mut input).unwrap();
+
+        let heatmap = interpreter.heatmap.as_ref().unwrap();
+        assert_eq!((heatmap.reads_of(0), heatmap.writes_of(0)), (1, 2));
+        assert_eq!((heatmap.reads_of(1), heatmap.writes_of(1)), (1, 1));
+        assert_eq!(heatmap.to_csv(), "cell,reads,writes\n0,1,2\n1,1,1\n");
+        assert_eq!(heatmap.to_ppm(), "P3\n2 1\n255\n255 255 0  127 255 0\n");
+
+        assert!(CowInterpreter::new("MoO").unwrap().heatmap.is_none());
+    }
+
+    #[test]
+    fn test_debugger_breakpoints() {
+        let mut interpreter = CowInterpreter::new("MoO MoO OOM MoO OOM").unwrap();
+        let mut debugger = Debugger::new(&mut interpreter);
+        debugger.add_breakpoint(2);
+
+        let mut output = String::new();
+        let mut input = vec![].into_iter();
+        assert_eq!(debugger.resume(&mut output, &mut input, None).unwrap(), StopReason::Breakpoint(2));
+        assert_eq!(output, "");
+        // С точки останова можно продолжить
+        assert_eq!(debugger.resume(&mut output, &mut input, Some(1)).unwrap(), StopReason::Limit);
+        assert_eq!(output, "2\n");
+        assert_eq!(debugger.resume(&mut output, &mut input, None).unwrap(), StopReason::Finished);
+        assert_eq!(output, "2\n3\n");
+    }
+
+    #[test]
+    fn test_debug_server_protocol() {
+        let mut server = DebugServer::new(CowInterpreter::new("oom MoO OOM moO MoO").unwrap());
+        let call = |server: &mut DebugServer, request: &str| -> serde_json::Value {
+            serde_json::from_str(&server.handle(request)).unwrap()
+        };
+
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":1,"method":"setBreakpoint","params":{"position":3}}"#);
+        assert_eq!(response["result"], serde_json::json!([3]));
+        call(&mut server, r#"{"jsonrpc":"2.0","id":2,"method":"input","params":{"lines":["41"]}}"#);
+
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":3,"method":"continue"}"#);
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["result"]["stop"]["reason"], "breakpoint");
+        assert_eq!(response["result"]["output"], "42\n");
+        assert_eq!(response["result"]["state"]["instruction"], "moO");
+
+        call(&mut server, r#"{"jsonrpc":"2.0","id":4,"method":"setWatchpoint","params":{"cell":1,"value":1}}"#);
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":5,"method":"continue"}"#);
+        assert_eq!(response["result"]["stop"]["reason"], "watchpoint");
+        assert_eq!(response["result"]["stop"]["position"], 4);
+
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":6,"method":"readMemory"}"#);
+        assert_eq!(response["result"], serde_json::json!([42, 1]));
+
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":7,"method":"fly"}"#);
+        assert_eq!(response["error"]["code"], -32601);
+        let response = call(&mut server, "not json");
+        assert_eq!(response["error"]["code"], -32700);
+        let response = call(&mut server, r#"{"jsonrpc":"2.0","id":8,"method":"setBreakpoint","params":{}}"#);
+        assert_eq!(response["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn test_macro_expansion() {
+        let source = "@add5 = MoO MoO MoO MoO MoO\n@add10 = @add5 @add5\n@add10 @add5 OOM\n";
+        let expanded = macros::expand(source).unwrap();
+        assert_eq!(expanded.lines().count(), 3);
+        assert_eq!(expanded.matches("MoO").count(), 15);
+
+        let mut interpreter = CowInterpreter::builder(source).macros(true).build().unwrap();
+        let mut input = vec![].into_iter();
+        assert_eq!(interpreter.execute_with_input(&mut input).unwrap(), "15\n");
+        // Строки определений остаются пустыми - позиции не сдвигаются
+        assert_eq!(interpreter.positions[0].line, 3);
+    }
+
+    #[test]
+    fn test_macro_errors() {
+        let err = macros::expand("@a = MoO @b\n@b = @a\n@a").unwrap_err();
+        assert_eq!(err, "Рекурсивный макрос: @a -> @b -> @a (строка 3)");
+        assert_eq!(macros::expand("MoO @nope").unwrap_err(), "Неизвестный макрос @nope (строка 1)");
+        assert!(macros::expand("@a = MoO\n@a = MOo").unwrap_err().contains("повторно"));
+        // Одиночный @ - обычный текст
+        assert_eq!(macros::expand("MoO @ OOM").unwrap(), "MoO @ OOM");
+    }
+
+    #[test]
+    fn test_program_builder_matches_loops() {
+        // Тело внешнего цикла начинается и заканчивается циклом, затем пустой цикл
+        let mut builder = ProgramBuilder::new();
+        builder.push_n(Instruction::MoO2, 3);
+        builder.loop_(|b| {
+            b.loop_(|b| {
+                b.push(Instruction::MOo2);
+            });
+        });
+        builder.loop_(|_| {});
+        builder.push(Instruction::OOM);
+        let program = builder.build();
+
+        let jumps = jump_table(&program);
+        let mut depth = Vec::new();
+        for (pos, instruction) in program.iter().enumerate() {
+            match instruction {
+                Instruction::MOO2 => depth.push(pos),
+                Instruction::Moo => {
+                    let start = depth.pop().unwrap();
+                    assert_eq!(jumps[start], Some(pos));
+                    assert_eq!(jumps[pos], Some(start));
+                }
+                _ => {}
+            }
+        }
+
+        let mut interpreter = CowInterpreter::<i32>::from_program(program, Vec::new());
+        let mut input = vec![].into_iter();
+        assert_eq!(interpreter.execute_with_input(&mut input).unwrap(), "0\n");
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        use cow_interpreter::trace::{self, RecordedInput, Recording};
+
+        let source = "oom OOM OOO Moo OOM";
+        let mut interpreter = CowInterpreter::new(source).unwrap();
+        let mut input = vec!["41".to_string(), "x".to_string()].into_iter();
+        let recording = trace::record(&mut interpreter, &mut input).unwrap();
+
+        assert_eq!(recording.inputs, vec![RecordedInput::Line(Some("41".to_string())), RecordedInput::Char(Some('x'))]);
+        assert_eq!(recording.output, "41\n120\n");
+        assert_eq!(recording.trace.len(), 5);
+
+        let restored = Recording::<i32>::from_json(&recording.to_json()).unwrap();
+        assert_eq!(restored, recording);
+        trace::replay(&mut CowInterpreter::new(source).unwrap(), &restored).unwrap();
+
+        let mut tampered = recording.clone();
+        tampered.output = "другой".to_string();
+        assert!(trace::replay(&mut CowInterpreter::new(source).unwrap(), &tampered).unwrap_err().contains("Вывод"));
+
+        let err = trace::replay(&mut CowInterpreter::new("oom OOM oom OOM").unwrap(), &recording).unwrap_err();
+        assert!(err.contains("строку, а в записи символ"), "{}", err);
+    }
+
+    #[test]
+    fn test_max_memory_guard() {
+        let mut interpreter = CowInterpreter::builder("MoO MOO moO MoO moo").max_memory(4).build().unwrap();
+        let err = interpreter.execute_with_input(&mut std::iter::empty()).unwrap_err();
+        assert!(err.contains("Превышен размер ленты (4 ячеек)"), "{}", err);
+        assert_eq!(interpreter.get_memory().len(), 4);
+
+        let mut interpreter = CowInterpreter::builder("moO moO moO").max_memory(4).build().unwrap();
+        assert!(interpreter.execute_with_input(&mut std::iter::empty()).is_ok());
+
+        assert!(CowInterpreter::builder("moO").memory_size(8).max_memory(4).build().is_err());
+    }
+
+    #[test]
+    fn test_run_batch() {
+        use cow_interpreter::batch::{self, BatchConfig};
+        use cow_interpreter::io::CowIo;
+
+        let programs = ["oom MoO OOM", "MoO MOO moO MoO moo", "oom", "bad"];
+        let inputs = |index: usize| -> Box<dyn CowIo> { Box::new(vec![(index * 10).to_string()].into_iter()) };
+
+        let results = batch::run_batch(&programs, inputs);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().output, "1\n");
+        assert!(results[1].as_ref().unwrap_err().contains("Превышен лимит шагов"));
+        assert_eq!(results[2].as_ref().unwrap().memory, vec![20]);
+        assert_eq!(results[3].as_ref().unwrap().steps, 0);
+
+        let mut config = BatchConfig { threads: 2, ..BatchConfig::default() };
+        config.limits.max_steps = None;
+        config.limits.max_memory = Some(3);
+        let results = batch::run_batch_with(&programs[..2], inputs, &config);
+        assert!(results[1].as_ref().unwrap_err().contains("Превышен размер ленты"));
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        use cow_interpreter::io::OutputEncoding;
+
+        // 233 (é) и 300, который не помещается в байт
+        let source = format!("{} Moo {} Moo", "MoO ".repeat(233), "MoO ".repeat(67));
+        let run = |encoding| {
+            let mut interpreter = CowInterpreter::builder(&source).output_encoding(encoding).build().unwrap();
+            interpreter.execute_bytes(&mut std::iter::empty()).unwrap()
+        };
+
+        assert_eq!(run(OutputEncoding::Unicode), "éĬ".as_bytes());
+        assert_eq!(run(OutputEncoding::Latin1), "é".as_bytes());
+        assert_eq!(run(OutputEncoding::Bytes), vec![233, 44]);
+        assert_eq!(OutputEncoding::parse("bytes"), Ok(OutputEncoding::Bytes));
+        assert!(OutputEncoding::parse("utf16").is_err());
+    }
+
+    #[test]
+    fn test_debugger_step_back() {
+        use cow_interpreter::debugger::Debugger;
+
+        let mut interpreter = CowInterpreter::new("oom OOM moO MoO MoO mOo MMM").unwrap();
+        interpreter.trace = Some(Vec::new());
+        let mut debugger = Debugger::new(&mut interpreter);
+        let mut output = String::new();
+        let mut input = vec!["7".to_string()].into_iter();
+
+        assert!(debugger.run(&mut output, &mut input).unwrap().is_none());
+        assert_eq!(output, "7\n");
+        assert_eq!(debugger.history_len(), 7);
+        assert_eq!(debugger.interpreter.register, Some(7));
+
+        assert_eq!(debugger.step_back_n(5, &mut output), 5);
+        assert_eq!(debugger.interpreter.prog_pos, 2);
+        assert_eq!(debugger.interpreter.memory, vec![7]);
+        assert_eq!(debugger.interpreter.register, None);
+        assert_eq!(debugger.interpreter.steps, 2);
+
+        assert_eq!(debugger.step_back_n(10, &mut output), 2);
+        assert!(!debugger.step_back(&mut output));
+        assert_eq!(output, "");
+        assert_eq!(debugger.interpreter.memory, vec![0]);
+
+        // Ввод уже прочитан из источника, но после отмены выдаётся снова
+        assert!(debugger.run(&mut output, &mut input).unwrap().is_none());
+        assert_eq!(output, "7\n");
+        assert_eq!(debugger.interpreter.memory, vec![7, 2]);
+        assert_eq!(debugger.interpreter.take_trace().len(), 7);
+
+        debugger.set_history_limit(3);
+        assert_eq!(debugger.history_len(), 3);
+    }
+
+    // Запуск модуля из wasm::compile во встроенном рантайме wasmi
+    fn run_wasm(source: &str, input: &[&str]) -> (String, Vec<i32>) {
+        use wasmi::{Caller, Engine, Linker, Module, Store};
+
+        struct Host {
+            input: std::collections::VecDeque<String>,
+            output: String,
+        }
+
+        let program = CowInterpreter::parse(source).unwrap();
+        let bytes = cow_interpreter::wasm::compile(&program).unwrap();
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, &bytes[..]).unwrap();
+        let host = Host {
+            input: input.iter().map(|line| line.to_string()).collect(),
+            output: String::new(),
+        };
+        let mut store = Store::new(&engine, host);
+        let mut linker = <Linker<Host>>::new(&engine);
+        linker
+            .func_wrap("cow", "read_char", |mut caller: Caller<Host>| -> i32 {
+                let line = caller.data_mut().input.pop_front();
+                line.and_then(|line| line.chars().next()).map_or(-1, |ch| ch as i32)
+            })
+            .unwrap();
+        linker
+            .func_wrap("cow", "read_int", |mut caller: Caller<Host>| -> i32 {
+                let line = caller.data_mut().input.pop_front().unwrap_or_default();
+                line.trim().parse().unwrap_or(0)
+            })
+            .unwrap();
+        linker
+            .func_wrap("cow", "write_char", |mut caller: Caller<Host>, value: i32| {
+                if let Some(ch) = char::from_u32(value as u32) {
+                    caller.data_mut().output.push(ch);
+                }
+            })
+            .unwrap();
+        linker
+            .func_wrap("cow", "write_int", |mut caller: Caller<Host>, value: i32| {
+                caller.data_mut().output.push_str(&format!("{}\n", value));
+            })
+            .unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap().start(&mut store).unwrap();
+        let run = instance.get_typed_func::<(), ()>(&store, "run").unwrap();
+        run.call(&mut store, ()).unwrap();
+
+        let memory = instance.get_memory(&store, "memory").unwrap();
+        let tape = memory.data(&store)[..16]
+            .chunks(4)
+            .map(|cell| i32::from_le_bytes(cell.try_into().unwrap()))
+            .collect();
+        (store.into_data().output, tape)
+    }
+
+    #[test]
+    fn test_wasm_matches_interpreter() {
+        let hello = std::fs::read_to_string("examples/hello.cow").unwrap();
+        let fib = std::fs::read_to_string("examples/fib.cow").unwrap();
+        let programs = [
+            (hello.as_str(), vec![]),
+            (fib.as_str(), vec![]),
+            ("oom MMM moO MMM MoO MoO OOM mOo OOM", vec!["41"]),
+            ("Moo MoO Moo moO oom MOO MOo mOo MoO moO moo mOo OOM", vec!["A", "5"]),
+            ("MoO moO mOo mOo MoO OOM", vec![]),
+        ];
+
+        for (source, input) in programs {
+            let expected = cow_interpreter::run(source, &input.join("\n")).unwrap();
+            let (output, tape) = run_wasm(source, &input);
+            assert_eq!(output, expected.output, "{}", source);
+            let cells = expected.memory.len().min(tape.len());
+            assert_eq!(tape[..cells], expected.memory[..cells], "{}", source);
+        }
+
+        assert!(cow_interpreter::wasm::compile(&CowInterpreter::parse("mOO").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_jit_matches_interpreter() {
+        use cow_interpreter::jit;
+
+        let hello = std::fs::read_to_string("examples/hello.cow").unwrap();
+        let fib = std::fs::read_to_string("examples/fib.cow").unwrap();
+        let programs = [
+            (hello.as_str(), vec![]),
+            (fib.as_str(), vec![]),
+            ("oom MMM moO MMM MoO MoO OOM mOo OOM", vec!["41"]),
+            ("Moo MoO Moo moO oom MOO MOo mOo MoO moO moo mOo OOM", vec!["A", "5"]),
+            ("moO moO moO MoO mOo mOo mOo mOo MoO", vec![]),
+            ("moO moO mOo MoO moO moO moO mOo mOo OOM", vec![]),
+            // mOO в JIT не переводится и выполняется интерпретатором
+            ("OOM MoO MoO MoO MoO mOO OOM", vec![]),
+        ];
+
+        for (source, input) in programs {
+            let program = CowInterpreter::parse(source).unwrap();
+            let mut interpreter = CowInterpreter::new(source).unwrap();
+            let expected = interpreter.execute_with_input(&mut input.iter().map(|s| s.to_string())).unwrap();
+
+            let run = jit::execute(&program, &mut input.iter().map(|s| s.to_string())).unwrap();
+            assert_eq!(run.output, expected, "{}", source);
+            assert_eq!(run.memory, interpreter.memory, "{}", source);
+            assert_eq!(run.mem_pos, interpreter.mem_pos, "{}", source);
+            assert_eq!(run.register, interpreter.register, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_format_trace_step() {
+        use cow_interpreter::trace;
+
+        let mut interpreter = CowInterpreter::builder("MoO moO").trace(true).build().unwrap();
+        interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        let steps = interpreter.take_trace();
+
+        let line = trace::format_step(&steps[0], &interpreter.describe_position(0), false);
+        assert_eq!(line, "     1  MoO (строка 1, столбец 1)      ptr=0    0 -> 1");
+        let line = trace::format_step(&steps[1], &interpreter.describe_position(1), false);
+        assert!(line.ends_with("ptr=0    1"), "{}", line);
+
+        let colored = trace::format_step(&steps[0], "MoO", true);
+        assert!(colored.contains("\x1b[33m0 -> 1\x1b[0m"), "{}", colored);
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let mut interpreter = CowInterpreter::new("MoO MMM moO MoO MoO MMM").unwrap();
+        let start = interpreter.state();
+        interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        let end = interpreter.state();
+
+        let diff = start.diff(&end);
+        assert_eq!(diff.cells.len(), 2);
+        assert_eq!((diff.cells[1].cell, diff.cells[1].old, diff.cells[1].new), (1, 0, 1));
+        assert_eq!(diff.pointer, Some((0, 1)));
+        assert_eq!(diff.register, None);
+        assert_eq!(diff.to_string(), "ячейка 0: 0 -> 1; ячейка 1: 0 -> 1; указатель 0 -> 1");
+
+        let reverse = end.diff(&start);
+        assert_eq!(reverse.cells[0].new, 0);
+        assert!(end.diff(&end).is_empty());
+        assert_eq!(end.diff(&end).to_string(), "без изменений");
+
+        let mut interpreter = CowInterpreter::new("MoO MMM").unwrap();
+        let before = interpreter.state();
+        interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        assert_eq!(before.diff(&interpreter.state()).to_string(), "ячейка 0: 0 -> 1; регистр пуст -> 1");
+    }
+
+    #[test]
+    fn test_backward_jump_is_separate_step() {
+        let mut interpreter = CowInterpreter::builder("MoO MoO MOO MOo moo").trace(true).build().unwrap();
+        interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        let positions: Vec<usize> = interpreter.take_trace().iter().map(|step| step.position).collect();
+        assert_eq!(positions, vec![0, 1, 2, 3, 4, 2, 3, 4, 2]);
+        assert_eq!(interpreter.summary().loop_iterations, 2);
+
+        // Миллион итераций не расходует стек
+        let mut interpreter = CowInterpreter::new("MOO MOo moo").unwrap();
+        interpreter.memory[0] = 1_000_000;
+        interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        assert_eq!(interpreter.memory[0], 0);
+    }
+
+    #[test]
+    fn test_run_until_output() {
+        let mut interpreter = CowInterpreter::new("oom OOM MoO MoO OOM OOO").unwrap();
+        let mut input = vec!["5".to_string()].into_iter();
+
+        let chunk = interpreter.run_until_output(&mut input).unwrap().unwrap();
+        assert_eq!((chunk.text.as_str(), chunk.position), ("5\n", 1));
+        assert_eq!(interpreter.prog_pos, 2);
+
+        let chunk = interpreter.run_until_output(&mut input).unwrap().unwrap();
+        assert_eq!((chunk.text.as_str(), chunk.position), ("7\n", 4));
+
+        assert_eq!(interpreter.run_until_output(&mut input).unwrap(), None);
+        assert!(interpreter.is_finished());
+        assert_eq!(interpreter.memory[0], 0);
+    }
+
+    #[test]
+    fn test_custom_instruction() {
+        let mut interpreter = CowInterpreter::builder("MoO MoO MoO MoM OOM MoM OOM OmO OOM")
+            .register_instruction("MoM", |state| {
+                state.memory[state.mem_pos] *= 2;
+                Ok(())
+            })
+            .register_instruction("OmO", |state| {
+                state.mem_pos += 5;
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        let output = interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        assert_eq!(output, "6\n12\n0\n");
+        assert_eq!((interpreter.mem_pos, interpreter.memory.len()), (5, 6));
+        assert_eq!(interpreter.program[3].token(), "MoM");
+
+        // Стандартные инструкции не переопределяются
+        let result = CowInterpreter::builder("MoO").register_instruction("MOO", |_| Ok(())).build();
+        assert!(result.is_err());
+
+        // Ошибка обработчика останавливает выполнение с позицией инструкции
+        let mut interpreter = CowInterpreter::builder("MoO XXX")
+            .register_instruction("XXX", |_| Err("сбой".to_string()))
+            .build()
+            .unwrap();
+        let err = interpreter.execute_with_input(&mut std::iter::empty()).unwrap_err();
+        assert!(err.contains("сбой") && err.contains("XXX"), "{}", err);
+
+        // Без обработчика токен разбирается, но выполнить его нельзя
+        let (program, positions) = CowInterpreter::parse_with_instructions("MoO MoM", &["MoM"]).unwrap();
+        assert!(Bytecode::compile(&program).is_err());
+        let mut interpreter = CowInterpreter::<i32>::from_program(program, positions);
+        assert!(interpreter.execute_with_input(&mut std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn test_with_memory() {
+        let mut interpreter = CowInterpreter::new("Moo mOo Moo MoO MoO OOM").unwrap().with_memory(vec![72, 105], 1);
+        let output = interpreter.execute_with_input(&mut std::iter::empty()).unwrap();
+        assert_eq!(output, "iH74\n");
+        assert_eq!(interpreter.memory, vec![74, 105]);
+
+        // Указатель за концом данных дополняет ленту нулями
+        let interpreter = CowInterpreter::new("").unwrap().with_memory(Vec::new(), 3);
+        assert_eq!((interpreter.memory, interpreter.mem_pos), (vec![0, 0, 0, 0], 3));
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        // Первое число задаёт длину цикла, второе читается после него
+        let source = "oom MOO MOo moo OOM oom OOM";
+        let lines = || vec!["60".to_string(), "7".to_string()].into_iter();
+        let mut interpreter = CowInterpreter::new(source).unwrap();
+        let mut source_input = lines();
+        let mut input = trace::InputRecorder::new(&mut source_input);
+        let mut output = String::new();
+        let mut saved = Vec::new();
+        while !interpreter.is_finished() {
+            interpreter.step(&mut output, &mut input).unwrap();
+            if interpreter.steps.is_multiple_of(50) && !interpreter.is_finished() {
+                saved.push(Checkpoint::capture(&interpreter, &output, &input.inputs).to_json());
+            }
+        }
+        assert_eq!(output, "0\n7\n");
+        assert_eq!(saved.len(), interpreter.steps / 50);
+
+        // Точка внутри цикла: первое число уже прочитано, второе ещё нет
+        let checkpoint = Checkpoint::<i32>::from_json(&saved[1]).unwrap();
+        assert_eq!((checkpoint.state.steps, checkpoint.inputs.len()), (100, 1));
+        let mut resumed = CowInterpreter::new(source).unwrap();
+        let mut input = lines();
+        checkpoint.restore(&mut resumed, &mut input).unwrap();
+        let output = checkpoint.output + &resumed.execute_with_input(&mut input).unwrap();
+        assert_eq!(output, "0\n7\n");
+        assert_eq!((resumed.steps, resumed.memory.clone()), (interpreter.steps, interpreter.memory.clone()));
+        assert_eq!(resumed.summary(), interpreter.summary());
+
+        // Точку другой программы восстановить нельзя
+        let checkpoint = Checkpoint::<i32>::from_json(&saved[0]).unwrap();
+        assert!(checkpoint.restore(&mut CowInterpreter::new("MoO").unwrap(), &mut lines()).is_err());
+    }
+
+    #[test]
+    fn test_minify() {
+        let source = "Привет: MoO MoO\n  MOO MOo moO MoO mOo moo\n  moO OOM // конец";
+        let minified = format::minify(source);
+        assert_eq!(minified, "MoOMoOMOOMOomoOMoOmOomoomoOOOM");
+
+        let program = CowInterpreter::parse(source).unwrap();
+        assert_eq!(CowInterpreter::parse(&minified).unwrap(), program);
+        assert_eq!(CowInterpreter::parse_strict(&minified).unwrap().0, program);
+
+        // Форматирование минифицированной программы возвращает читаемый вид
+        let formatted = format::format_program(&CowInterpreter::parse(&minified).unwrap(), 80);
+        assert_eq!(format::minify(&formatted), minified);
+        let mut interpreter = CowInterpreter::new(&minified).unwrap();
+        assert_eq!(interpreter.execute_with_input(&mut std::iter::empty()).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn test_golden_corpus() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let results = golden::run_dir(&dir, golden::DEFAULT_GOLDEN_MAX_STEPS).unwrap();
+        assert!(results.len() >= 2);
+        assert!(results.iter().all(golden::GoldenResult::passed), "{}", golden::format_report(&results));
+
+        let result = golden::run_case("sum", "oom moO oom MOO MOo mOo MoO moO moo mOo OOM", "2\n3\n", "6\n", 1000);
+        assert_eq!(result.steps, 24);
+        assert_eq!(
+            result.outcome,
+            golden::GoldenOutcome::Failed {
+                diff: "  строка 1:\n  - \"6\"\n  + \"5\"\n".to_string()
+            }
+        );
+        assert!(golden::format_report(&[result]).ends_with("Пройдено: 0 из 1\n"));
+    }
 }